@@ -0,0 +1,178 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+
+use crate::path::get_home_directory;
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::text::sanitize;
+use crate::theme::Role;
+
+// Just the parts of what the CLI leaves behind after assuming a role that are needed
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CachedCredentials {
+    credentials: Option<Expiry>,
+    assumed_role_user: Option<AssumedRoleUser>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Expiry {
+    expiration: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AssumedRoleUser {
+    arn: String,
+}
+
+// Both arn:aws:sts::123456789012:assumed-role/AdminRole/session and
+// arn:aws:iam::123456789012:role/path/AdminRole give the account and AdminRole
+pub fn parse_role_arn(arn: &str) -> Option<(String, String)> {
+    let mut parts = arn.splitn(6, ':');
+    let (Some("arn"), Some(_), Some(_), Some(_), Some(account), Some(resource)) = (parts.next(), parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+
+    let role = match resource.split_once('/')? {
+        ("assumed-role", rest) => rest.split('/').next()?,
+        ("role", rest) => rest.rsplit('/').next()?,
+        _ => return None
+    };
+
+    Some((account.to_owned(), role.to_owned())).filter(|(account, role)| !account.is_empty() && !role.is_empty())
+}
+
+// Days since 1970 for a day in the proleptic Gregorian calendar, from Howard Hinnant's
+// days_from_civil
+fn get_days(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// The CLI writes 2026-10-14T12:34:56Z, sometimes with fractions of a second or +00:00 instead
+pub fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| s.get(range).filter(|x| x.bytes().all(|b| b.is_ascii_digit())).and_then(|x| x.parse::<i64>().ok());
+    if s.len() < 19 || s.as_bytes()[4] != b'-' || s.as_bytes()[7] != b'-' || !matches!(s.as_bytes()[10], b'T' | b' ') {
+        return None;
+    }
+
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let rest = s[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match rest {
+        "" | "Z" | "z" | "UTC" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None
+            };
+            let (hours, minutes) = rest[1..].split_once(':')?;
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+        }
+    };
+
+    let seconds = get_days(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+pub fn get_credentials_cache_dir() -> Option<PathBuf> {
+    get_home_directory().map(|x| x.join(".aws").join("cli").join("cache"))
+}
+
+pub fn get_config_path() -> Option<PathBuf> {
+    env::var_os("AWS_CONFIG_FILE").filter(|x| !x.is_empty()).map(PathBuf::from).or_else(|| get_home_directory().map(|x| x.join(".aws").join("config")))
+}
+
+// The role a profile assumes from ~/.aws/config, where every profile but the default is written
+// as [profile name]
+pub fn get_profile_role_arn(config: &str, profile: &str) -> Option<String> {
+    let mut in_profile = false;
+    for line in config.lines().map(str::trim).filter(|x| !x.is_empty() && !x.starts_with(['#', ';'])) {
+        if let Some(section) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            let section = section.trim();
+            in_profile = section.strip_prefix("profile ").map(str::trim) == Some(profile) || (profile == "default" && section == "default");
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) if in_profile && key.trim() == "role_arn" => return Some(value.trim().to_owned()),
+            _ => ()
+        }
+    }
+
+    None
+}
+
+// There's no telling which profile each file's for without working out the CLI's own hash of it,
+// so it's whichever credentials for the profile's role were cached last and haven't run out yet
+pub fn find_cached_role(dir: &Path, role_arn: &str, now: SystemTime) -> Option<String> {
+    let role = parse_role_arn(role_arn)?;
+    let entries = fs::read_dir(dir).ok()?.flatten().filter(|x| x.path().extension().is_some_and(|e| e == "json"));
+
+    let roles = entries.filter_map(|entry| {
+        let modified = entry.metadata().and_then(|x| x.modified()).ok()?;
+        let credentials: CachedCredentials = serde_json::from_str(&fs::read_to_string(entry.path()).ok()?).ok()?;
+
+        let expiration = credentials.credentials.and_then(|x| x.expiration).and_then(|x| parse_timestamp(&x));
+        if expiration.is_some_and(|x| x <= now) {
+            tracing::debug!(path = ?entry.path(), "the cached credentials have run out");
+            return None;
+        }
+
+        let arn = credentials.assumed_role_user?.arn;
+        Some((modified, arn)).filter(|(_, arn)| parse_role_arn(arn).as_ref() == Some(&role))
+    });
+
+    roles.max_by_key(|(modified, _)| *modified).map(|(_, arn)| arn)
+}
+
+// Nothing's ever asked of STS, it's only ever what's already in the environment or on disk
+pub struct AwsAccountSegment;
+
+impl AwsAccountSegment {
+    fn get_role(&self) -> Option<(String, String)> {
+        if let Some(role) = env::var("AWS_ROLE_ARN").ok().and_then(|x| parse_role_arn(&x)) {
+            return Some(role);
+        }
+
+        // Credentials cached for some other profile aren't what the next command will use
+        let profile = env::var("AWS_PROFILE").ok().filter(|x| !x.is_empty()).unwrap_or_else(|| "default".to_owned());
+        let role_arn = get_profile_role_arn(&fs::read_to_string(get_config_path()?).ok()?, &profile)?;
+        parse_role_arn(&find_cached_role(&get_credentials_cache_dir()?, &role_arn, SystemTime::now())?)
+    }
+}
+
+impl Segment for AwsAccountSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::AwsAccount
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match self.get_role() {
+            Some((account, role)) => {
+                let name = context.config.aws_accounts.get(&account).unwrap_or(&account);
+                let spans = vec![context.theme.paint(Role::Aws, &sanitize(&format!("{}/{}", name, role)))];
+                SegmentResult::Present(Rendered::Aliased(spans, format!("{}/{}", account, role)))
+            },
+            None => SegmentResult::Absent(Reason::Nothing("AWS_ROLE_ARN isn't set and there are no cached credentials for the profile's role"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
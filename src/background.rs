@@ -0,0 +1,120 @@
+use std::env;
+use std::io::{stdout, IsTerminal};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// Terminals that don't answer leave the prompt waiting, so it's never for long
+pub const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Background {
+    Dark,
+    Light
+}
+
+// Set by rxvt, Konsole and friends to something like 15;0 or 0;default;15, with the background
+// last. The colours are the usual ANSI ones so white and the bright ones other than black are light
+pub fn parse_colorfgbg(s: &str) -> Option<Background> {
+    match s.rsplit(';').next()?.parse::<u8>().ok()? {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None
+    }
+}
+
+// Each channel can have anything from one to four hex digits
+fn parse_channel(s: &str) -> Option<f64> {
+    if s.is_empty() || s.len() > 4 {
+        return None;
+    }
+
+    let value = u32::from_str_radix(s, 16).ok()?;
+    Some(f64::from(value) / f64::from((1u32 << (s.len() * 4)) - 1))
+}
+
+// The answer to OSC 11 looks like ESC ] 11 ; rgb:RRRR/GGGG/BBBB followed by BEL or ESC \
+pub fn parse_colour_reply(s: &str) -> Option<Background> {
+    let start = s.find("rgb:")? + 4;
+    let rgb = s[start..].trim_end_matches(['\x07', '\\']).trim_end_matches('\x1b');
+
+    let mut channels = rgb.split('/').map(parse_channel);
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+
+    let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+    Some(if luma > 0.5 { Background::Light } else { Background::Dark })
+}
+
+// Asks the terminal what colour its background is. Input is read straight off the tty so it has to
+// stop echoing and waiting for a newline while it does, and anything that goes wrong just gives up
+#[cfg(unix)]
+fn query_terminal(timeout: Duration) -> Option<Background> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::Instant;
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let fd = tty.as_raw_fd();
+
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return None;
+    }
+
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 0;
+    raw.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None;
+    }
+
+    let mut reply = vec![];
+    if tty.write_all(b"\x1b]11;?\x1b\\").and_then(|_| tty.flush()).is_ok() {
+        let deadline = Instant::now() + timeout;
+
+        while !reply.ends_with(b"\x07") && !reply.ends_with(b"\x1b\\") {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            if remaining.is_zero() || unsafe { libc::poll(&mut poll, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+                break;
+            }
+
+            let mut buffer = [0; 64];
+            match tty.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => reply.extend_from_slice(&buffer[..n])
+            }
+        }
+    }
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+
+    parse_colour_reply(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn query_terminal(_timeout: Duration) -> Option<Background> {
+    None
+}
+
+// COLORFGBG is free to look at so it wins, then the terminal's asked if that's allowed. Most
+// terminals are dark so that's what's assumed otherwise
+pub fn detect_background(query: bool) -> Background {
+    if let Some(background) = env::var("COLORFGBG").ok().and_then(|x| parse_colorfgbg(&x)) {
+        return background;
+    }
+
+    // The prompt's usually captured by the shell, and then there's no terminal to ask
+    if query && stdout().is_terminal() {
+        if let Some(background) = query_terminal(QUERY_TIMEOUT) {
+            tracing::debug!(?background, "asked the terminal for its background");
+            return background;
+        }
+    }
+
+    Background::Dark
+}
@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Instant;
+
+// Somewhere for a detection to leave what it found as soon as it's done, so whatever has finished
+// can still be shown once the budget runs out
+pub struct Slot<T>(RefCell<Option<T>>);
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Slot(RefCell::new(None))
+    }
+}
+
+impl<T> Slot<T> {
+    pub async fn fill(&self, future: impl Future<Output = T>) {
+        let value = future.await;
+        *self.0.borrow_mut() = Some(value);
+    }
+
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+}
+
+// Says whether everything finished before the deadline. Whatever hadn't is dropped, which kills any
+// commands it was waiting on. Everything gets polled at least once first so nothing that's ready
+// straight away is lost
+pub async fn run_until(deadline: Option<Instant>, future: impl Future<Output = ()>) -> bool {
+    let Some(deadline) = deadline else {
+        future.await;
+        return true;
+    };
+
+    tokio::select! {
+        biased;
+        _ = future => true,
+        _ = tokio::time::sleep_until(deadline.into()) => false
+    }
+}
@@ -0,0 +1,605 @@
+#[cfg(feature = "aws")]
+use std::env;
+use std::path::Path;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+#[cfg(feature = "git")]
+use crate::chevron::{Position, State};
+use crate::ci::get_ci_system;
+use crate::command::{check_output, get_output, to_segment_result};
+#[cfg(feature = "git")]
+use crate::git::{
+    get_best_git_name, get_git_errors, get_git_remote_url, get_git_state, get_git_unmerged, get_push_branch, get_uncommitted_changes, get_unpushed_changes, GitState,
+    UncommittedChanges, UnpushedChanges
+};
+use crate::git::Fsmonitor;
+#[cfg(feature = "kubernetes")]
+use crate::helm::HelmChartSegment;
+use crate::message::Message;
+use crate::path::{format_path, PathAlias, PathStyle};
+use crate::plugin::find_repo_root;
+use crate::project::find_project;
+#[cfg(feature = "git")]
+use crate::remote::{get_remote_label, parse_remote_url, ForgeHost, RemoteLabel};
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::styled::{Span, Stylize};
+use crate::system::get_battery_level;
+use crate::text::{format_duration, format_idle, sanitize};
+use crate::theme::{Look, Role, Theme};
+
+pub struct PathSegment {
+    pub style: PathStyle,
+    pub keep: Option<usize>,
+    pub aliases: Vec<PathAlias>,
+}
+
+impl PathSegment {
+    pub fn get_spans(&self, path: &Path, theme: &Theme) -> Vec<Span> {
+        // Directory names can have anything in them
+        let (alias, rest) = format_path(path, self.style, self.keep, &self.aliases);
+        let rest = sanitize(&rest);
+
+        let alias = alias.map(|a| match a.colour {
+            Some(colour) => Look { colour: Some(colour), ..theme.get_look(Role::Path) }.paint(&a.replacement),
+            None => theme.paint(Role::Path, &a.replacement)
+        });
+
+        alias.into_iter().chain(Some(rest).filter(|r| !r.is_empty()).map(|r| theme.paint(Role::Path, &r))).collect()
+    }
+}
+
+impl Segment for PathSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Path
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let spans = context.current_dir.path.as_ref().map_or(vec![], |x| self.get_spans(x, context.theme));
+        let text = spans.iter().map(|s| &**s).collect::<String>();
+
+        // The title only wants the path itself
+        let result = match spans.is_empty() {
+            _ if context.current_dir.deleted => {
+                let deleted = Some(context.theme.paint(Role::Error, "(deleted)"));
+                let spans = spans.into_iter().chain(Some(" ".normal()).filter(|_| !text.is_empty())).chain(deleted).collect();
+                SegmentResult::Present(Rendered::Aliased(spans, text))
+            },
+            true => SegmentResult::Absent(Reason::Nothing("the current directory couldn't be worked out")),
+            false => SegmentResult::Present(Rendered::Spans(spans))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+pub struct MessageSegment {
+    pub message: Message,
+}
+
+impl Segment for MessageSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Message
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let look = context.theme.get_look(Role::Message);
+        let look = Look { colour: self.message.colour.or(look.colour), dimmed: self.message.dimmed || look.dimmed, ..look };
+
+        async move { SegmentResult::Present(Rendered::Spans(vec![look.paint(&self.message.text)])) }.boxed_local()
+    }
+}
+
+// Which forge or host origin is on, going by the labels given before anything that's known
+#[cfg(feature = "git")]
+pub struct GitRemoteSegment {
+    pub labels: Vec<RemoteLabel>,
+    pub hosts: Vec<ForgeHost>,
+}
+
+#[cfg(feature = "git")]
+impl Segment for GitRemoteSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitRemote
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            let remote_url = get_git_remote_url(context.runner).await;
+            match remote_url.as_deref().and_then(parse_remote_url).and_then(|x| get_remote_label(x.host, &self.labels, &self.hosts)) {
+                Some(label) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Git, &sanitize(&label))])),
+                None => SegmentResult::Absent(Reason::Nothing("there's no origin or its host isn't known"))
+            }
+        }.boxed_local()
+    }
+}
+
+#[cfg(feature = "git")]
+pub struct GitNameSegment;
+
+#[cfg(feature = "git")]
+impl Segment for GitNameSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitBranch
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            get_best_git_name(context.runner).await.map(|x| Rendered::Spans(vec![context.theme.paint(Role::Git, &x)]))
+        }.boxed_local()
+    }
+}
+
+// Conflicts are counted separately so they're left to whoever draws the chevron
+#[cfg(feature = "git")]
+pub struct ChangesSegment {
+    pub fsmonitor: Fsmonitor,
+}
+
+#[cfg(feature = "git")]
+impl Segment for ChangesSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitStatus
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            let state = match get_uncommitted_changes(context.runner, self.fsmonitor).await {
+                UncommittedChanges::None => State::Clean,
+                UncommittedChanges::FilesChanged => State::Dirty,
+                UncommittedChanges::FilesNotAdded => State::Untracked
+            };
+            SegmentResult::Present(Rendered::State(Position::Changes, state))
+        }.boxed_local()
+    }
+}
+
+#[cfg(feature = "git")]
+pub struct UpstreamSegment;
+
+#[cfg(feature = "git")]
+impl Segment for UpstreamSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitStatus
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        Some("git-upstream".to_owned())
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            let state = match get_unpushed_changes(context.runner).await {
+                UnpushedChanges::None => State::Pushed,
+                UnpushedChanges::UnpushedChanges => State::Unpushed,
+                UnpushedChanges::UnpulledChanges => State::Unpulled,
+                UnpushedChanges::DivergedChanges => State::Diverged,
+                UnpushedChanges::NoUpstreamBranch => State::NoUpstream
+            };
+            SegmentResult::Present(Rendered::State(Position::Upstream, state))
+        }.boxed_local()
+    }
+}
+
+// A mark for triangular workflows, where the unpushed chevron is about where git push goes rather
+// than the upstream
+#[cfg(feature = "git")]
+pub struct PushSegment;
+
+#[cfg(feature = "git")]
+impl Segment for PushSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitPush
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match get_push_branch(context.runner).await {
+                Some(_) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Git, "△")])),
+                None => SegmentResult::Absent(Reason::Nothing("git push goes to the upstream"))
+            }
+        }.boxed_local()
+    }
+}
+
+// Only the files in .git are looked at, so there's nothing to time
+#[cfg(feature = "git")]
+pub struct GitStateSegment;
+
+#[cfg(feature = "git")]
+impl Segment for GitStateSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitState
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        // Not really sure what some of these git states are but they seem important
+        let state = get_git_state(context.dir).map(|x| match x {
+            GitState::RebaseInteractive => "(interactive rebase)",
+            GitState::RebaseMerge => "(rebase merge)",
+            GitState::Rebase => "(rebase)",
+            GitState::ApplyMailbox => "(AM)",
+            GitState::ApplyMailboxOrRebase => "(AM/rebase)",
+            GitState::Merge => "(merge)",
+            GitState::Revert => "(revert)",
+            GitState::CherryPick => "(cherry-pick)",
+            GitState::Bisect => "(bisect)"
+        });
+        let result = match state {
+            Some(state) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Git, state)])),
+            None => SegmentResult::Absent(Reason::Nothing("no rebase, merge or anything like it is in progress"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+#[cfg(feature = "git")]
+pub struct GitErrorsSegment;
+
+#[cfg(feature = "git")]
+impl Segment for GitErrorsSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitErrors
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match get_git_errors(context.runner).await {
+                true => SegmentResult::Present(Rendered::Spans(vec!["\u{26A0}\u{FE0F}".bold()])),
+                false => SegmentResult::Absent(Reason::Nothing("git diff --check found nothing"))
+            }
+        }.boxed_local()
+    }
+}
+
+// Conflicts show up in the chevron as well, which goes by whether this is there
+#[cfg(feature = "git")]
+pub struct GitUnmergedSegment;
+
+#[cfg(feature = "git")]
+impl Segment for GitUnmergedSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitUnmerged
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match get_git_unmerged(context.runner).await {
+                0 => SegmentResult::Absent(Reason::Nothing("there are no conflicts")),
+                count => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Git, &format!("({})", count))]))
+            }
+        }.boxed_local()
+    }
+}
+
+#[cfg(feature = "kubernetes")]
+pub struct K8sContextSegment;
+
+#[cfg(feature = "kubernetes")]
+impl Segment for K8sContextSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::K8sContext
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            let output_res = get_output(context.runner, "kubectl", &["config", "current-context"], context.timeout_ms).await;
+
+            to_segment_result(check_output(output_res), "kubectl").map(|x| Rendered::Spans(vec![context.theme.paint(Role::K8s, &x)]))
+        }.boxed_local()
+    }
+}
+
+#[cfg(feature = "kubernetes")]
+pub struct K8sNamespaceSegment;
+
+#[cfg(feature = "kubernetes")]
+impl Segment for K8sNamespaceSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::K8sNamespace
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            let output_res = get_output(context.runner, "kubectl", &["config", "view", "--minify", "--output", "jsonpath={..namespace}"], context.timeout_ms).await;
+
+            to_segment_result(check_output(output_res), "kubectl").map(|x| Rendered::Spans(vec![context.theme.paint(Role::K8s, &x)]))
+        }.boxed_local()
+    }
+}
+
+#[cfg(feature = "aws")]
+pub struct AwsProfileSegment;
+
+#[cfg(feature = "aws")]
+impl Segment for AwsProfileSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::AwsProfile
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match env::var("AWS_PROFILE") {
+            Ok(profile) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Aws, &sanitize(&profile))])),
+            Err(_) => SegmentResult::Absent(Reason::NotSet(&["AWS_PROFILE"]))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+#[cfg(feature = "aws")]
+pub struct AwsRegionSegment;
+
+#[cfg(feature = "aws")]
+impl Segment for AwsRegionSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::AwsRegion
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match env::var("AWS_REGION").ok().or(env::var("AWS_DEFAULT_REGION").ok()).or(env::var("AWS_PROFILE_REGION").ok()) {
+            Some(region) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Aws, &sanitize(&region))])),
+            None => SegmentResult::Absent(Reason::NotSet(&["AWS_REGION", "AWS_DEFAULT_REGION", "AWS_PROFILE_REGION"]))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+// Only asked for inside tmux, where there's a session to name
+pub struct TmuxSessionSegment {
+    pub in_tmux: bool,
+}
+
+impl Segment for TmuxSessionSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::TmuxSession
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        Some("tmux-session".to_owned()).filter(|_| self.in_tmux)
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            if !self.in_tmux {
+                return SegmentResult::Absent(Reason::Nothing("not in tmux"));
+            }
+
+            let output_res = get_output(context.runner, "tmux", &["display-message", "-p", "#S"], context.timeout_ms).await;
+            to_segment_result(check_output(output_res), "tmux").map(|x| Rendered::Spans(vec![context.theme.paint(Role::Tmux, &x)]))
+        }.boxed_local()
+    }
+}
+
+pub struct SudoSegment;
+
+impl Segment for SudoSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Sudo
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            // -n means sudo fails rather than asking for a password, so this can never hang on a prompt
+            match get_output(context.runner, "sudo", &["-n", "true"], Some(context.timeout_ms.unwrap_or(100))).await {
+                Ok(x) if x.status.success() => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Sudo, "⚡sudo")])),
+                Ok(_) => SegmentResult::Absent(Reason::Nothing("sudo would ask for a password")),
+                Err(e) => SegmentResult::Absent(Reason::Failed(format!("sudo: {}", e)))
+            }
+        }.boxed_local()
+    }
+}
+
+pub struct BatterySegment {
+    pub threshold: u8,
+}
+
+impl Segment for BatterySegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Battery
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match get_battery_level().filter(|x| *x < self.threshold) {
+            Some(level) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Battery, &format!("🔋{}%", level))])),
+            None => SegmentResult::Absent(Reason::Nothing("no battery is draining below the threshold"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+pub struct CiSegment;
+
+impl Segment for CiSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Ci
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match get_ci_system() {
+            Some(system) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Info, &system)])),
+            None => SegmentResult::Absent(Reason::Nothing("not running in CI"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+pub struct ProjectSegment {
+    pub manifests: Vec<String>,
+}
+
+impl Segment for ProjectSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Project
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match context.current_dir.full.as_deref().filter(|_| !context.current_dir.deleted) {
+            Some(dir) => match find_project(dir, &self.manifests) {
+                Some(project) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Info, &project)])),
+                None if find_repo_root(dir).is_none() => SegmentResult::Absent(Reason::NotInRepository),
+                None => SegmentResult::Absent(Reason::Nothing("there's no manifest between here and the top of the repository"))
+            },
+            None => SegmentResult::Absent(Reason::Deleted)
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+// How long the previous prompt sat there is worked out before anything else, as it's gone once read
+pub struct IdleSegment {
+    pub idle_ms: Option<u64>,
+    pub threshold_ms: u64,
+}
+
+impl Segment for IdleSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Idle
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match self.idle_ms.filter(|x| *x >= self.threshold_ms) {
+            Some(idle_ms) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Info, &format!("⏾ {}", format_idle(idle_ms)))])),
+            None => SegmentResult::Absent(Reason::Nothing("the previous prompt was drawn less than --idle-threshold-mins ago"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+pub struct DurationSegment {
+    pub duration_ms: u64,
+    pub threshold_ms: u64,
+    pub always: bool,
+}
+
+impl Segment for DurationSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Duration
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        // Anything quicker isn't worth cluttering the prompt with
+        let result = match self.always || self.duration_ms >= self.threshold_ms {
+            true => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Duration, &format_duration(self.duration_ms))])),
+            false => SegmentResult::Absent(Reason::Nothing("the last command was quicker than --duration-threshold-ms"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+pub struct JobsSegment {
+    pub jobs: usize,
+}
+
+impl Segment for JobsSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Jobs
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match self.jobs {
+            0 => SegmentResult::Absent(Reason::Nothing("there are no background jobs")),
+            jobs => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Jobs, &format!("✦{}", jobs))]))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+// The exit code of the pipeline as a whole comes from the context like the chevrons' does
+pub struct PipestatusSegment {
+    pub codes: Vec<i32>,
+    pub last_command: Option<String>,
+}
+
+impl Segment for PipestatusSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Pipestatus
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let codes = self.codes.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("|");
+
+        // Knowing what failed is worth a lone code on top of the first chevron, otherwise that's
+        // already covered
+        let text = match &self.last_command {
+            Some(name) if context.exit_code != 0 && self.codes.len() > 1 => Some(format!("✗ {} {}", codes, name)),
+            Some(name) if context.exit_code != 0 => Some(format!("✗ {} {}", context.exit_code, name)),
+            _ if self.codes.len() > 1 && self.codes.iter().any(|x| *x != 0) => Some(codes),
+            _ => None
+        };
+        let result = match text {
+            Some(text) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Pipestatus, &text)])),
+            None if self.codes.len() > 1 => SegmentResult::Absent(Reason::Nothing("every command in the pipeline succeeded")),
+            None => SegmentResult::Absent(Reason::Nothing("the last command succeeded"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+// The segments that are always there, in the order they go on the top line, with each message
+// getting a segment of its own and only the families that were built in
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+pub fn get_registry(path: PathSegment, messages: Vec<Message>, fsmonitor: Fsmonitor) -> Vec<Box<dyn Segment>> {
+    let mut registry: Vec<Box<dyn Segment>> = vec![Box::new(path)];
+    registry.extend(messages.into_iter().map(|message| Box::new(MessageSegment { message }) as Box<dyn Segment>));
+    #[cfg(feature = "git")]
+    registry.extend([
+        Box::new(GitNameSegment) as Box<dyn Segment>, Box::new(ChangesSegment { fsmonitor }), Box::new(UpstreamSegment), Box::new(PushSegment),
+        Box::new(GitStateSegment), Box::new(GitErrorsSegment), Box::new(GitUnmergedSegment)
+    ]);
+    #[cfg(feature = "kubernetes")]
+    registry.extend([Box::new(K8sContextSegment) as Box<dyn Segment>, Box::new(K8sNamespaceSegment), Box::new(HelmChartSegment)]);
+    #[cfg(feature = "aws")]
+    registry.extend([Box::new(AwsProfileSegment) as Box<dyn Segment>, Box::new(AwsRegionSegment)]);
+    registry
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::Duration;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+use crate::command::{get_exit_status, CommandRunner};
+use crate::doctor::get_kubeconfig_paths;
+use crate::plugin::find_repo_root;
+use crate::store::{CacheKey, Store};
+
+// The files a command was worked out from are part of its key, so this only keeps the cache from
+// filling up with entries for files that have long since changed
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    code: i32,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+// Only commands whose output comes from a few files are kept. kubectl's config is the same wherever
+// it's run from, while the git ones only depend on a file in the repository. Worktrees have their
+// .git somewhere else so they're left alone
+fn get_sources(dir: &Path, program: &str, args: &[&str]) -> Option<Vec<PathBuf>> {
+    let git_dir = || find_repo_root(dir).map(|x| x.join(".git")).filter(|x| x.is_dir());
+
+    match (program, args) {
+        ("kubectl", ["config", ..]) => Some(get_kubeconfig_paths()),
+        ("git", ["config", "--get", ..]) => Some(vec![git_dir()?.join("config")]),
+        ("git", ["branch", "--show-current"]) => Some(vec![git_dir()?.join("HEAD")]),
+        ("git", ["rev-parse", "--show-toplevel"]) => Some(vec![git_dir()?]),
+        _ => None
+    }
+}
+
+// Answers what it can from the store and runs everything else. Commands that were killed by a
+// signal or given up on aren't kept, and nor is anything given the whole environment, as what it
+// prints could then depend on any of it
+pub struct CachedRunner<'a> {
+    pub dir: &'a Path,
+    pub inherit_env: bool,
+    pub runner: &'a dyn CommandRunner,
+    pub store: &'a Store,
+}
+
+impl CommandRunner for CachedRunner<'_> {
+    fn run<'a>(&'a self, program: &'a str, args: &'a [&'a str]) -> LocalBoxFuture<'a, io::Result<Output>> {
+        let Some(paths) = get_sources(self.dir, program, args).filter(|_| !self.inherit_env) else {
+            return self.runner.run(program, args);
+        };
+
+        // The files are looked at before running anything so a change part way through isn't missed.
+        // PATH is what decides which program is run
+        let key = paths.iter().fold(CacheKey::new(program).with(args).with_env("PATH"), |key, x| key.with_modified(x));
+
+        let entry = self.store.load(&key, TTL).and_then(|x| serde_json::from_str::<Entry>(&x).ok());
+        if let Some(entry) = entry {
+            tracing::debug!(program, ?args, "answered from the cache");
+            return async move { Ok(Output { status: get_exit_status(entry.code), stdout: entry.stdout, stderr: entry.stderr }) }.boxed_local();
+        }
+
+        let output_future = self.runner.run(program, args);
+        async move {
+            let output = output_future.await;
+            if let Ok(x) = &output {
+                if let Some(code) = x.status.code() {
+                    let entry = Entry { code, stdout: x.stdout.clone(), stderr: x.stderr.clone() };
+                    if let Ok(text) = serde_json::to_string(&entry) {
+                        self.store.save(&key, &text);
+                    }
+                }
+            }
+            output
+        }.boxed_local()
+    }
+}
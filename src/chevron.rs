@@ -0,0 +1,185 @@
+use clap::ValueEnum;
+use colored::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::colour::parse_colour;
+use crate::styled::{Span, Stylize};
+use crate::theme::{Look, Role, Theme};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptStyle {
+    Chevrons,
+    Single
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Position {
+    Exit,
+    Changes,
+    Upstream
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    ExitOk,
+    ExitFail,
+    Clean,
+    Dirty,
+    Untracked,
+    Conflict,
+    Pushed,
+    Unpushed,
+    Unpulled,
+    Diverged,
+    NoUpstream
+}
+
+impl Position {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Position::Exit => "the last command",
+            Position::Changes => "uncommitted changes",
+            Position::Upstream => "the upstream branch"
+        }
+    }
+
+    // Every state a chevron can be in, for prompt explain
+    pub fn states(&self) -> &'static [State] {
+        match self {
+            Position::Exit => &[State::ExitOk, State::ExitFail],
+            Position::Changes => &[State::Clean, State::Dirty, State::Untracked, State::Conflict],
+            Position::Upstream => &[State::Pushed, State::Unpushed, State::Unpulled, State::Diverged, State::NoUpstream]
+        }
+    }
+}
+
+impl State {
+    pub fn description(&self) -> &'static str {
+        match self {
+            State::ExitOk => "succeeded",
+            State::ExitFail => "failed",
+            State::Clean => "nothing to commit",
+            State::Dirty => "uncommitted changes",
+            State::Untracked => "untracked files",
+            State::Conflict => "conflicts",
+            State::Pushed => "up to date",
+            State::Unpushed => "unpushed commits",
+            State::Unpulled => "unpulled commits",
+            State::Diverged => "unpushed and unpulled commits",
+            State::NoUpstream => "no upstream branch"
+        }
+    }
+
+    fn role(&self) -> Role {
+        match self {
+            State::ExitOk | State::Clean | State::Pushed => Role::Ok,
+            State::Dirty | State::Conflict | State::Unpushed | State::Diverged => Role::Warning,
+            State::Untracked | State::Unpulled => Role::Info,
+            State::ExitFail => Role::Error,
+            State::NoUpstream => Role::NoUpstream
+        }
+    }
+
+    // Stands in for the colour when there isn't any
+    pub fn marker(&self) -> &'static str {
+        match self {
+            State::ExitOk | State::Clean | State::Pushed => "",
+            State::ExitFail => "!",
+            State::Dirty => "*",
+            State::Untracked => "?",
+            State::Conflict => "=",
+            State::Unpushed => "↑",
+            State::Unpulled => "↓",
+            State::Diverged => "↕",
+            State::NoUpstream => "~"
+        }
+    }
+
+    // Shown after the branch when there's only the one chevron. Not having an upstream is normal
+    // enough for new branches that it's left out
+    pub fn status_glyph(&self) -> &'static str {
+        match self {
+            State::Dirty => "✚",
+            State::Untracked => "?",
+            State::Conflict => "=",
+            State::Unpushed => "⇡",
+            State::Unpulled => "⇣",
+            State::Diverged => "⇕",
+            _ => ""
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PositionGlyph {
+    pub position: Position,
+    pub glyph: String,
+}
+
+pub fn parse_position_glyph(s: &str) -> Result<PositionGlyph, String> {
+    let (position, glyph) = s.split_once('=').ok_or("expected POSITION=GLYPH")?;
+
+    Ok(PositionGlyph { position: Position::from_str(position, false)?, glyph: glyph.to_owned() })
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StateColour {
+    pub state: State,
+    pub colour: Color,
+}
+
+pub fn parse_state_colour(s: &str) -> Result<StateColour, String> {
+    let (state, colour) = s.split_once('=').ok_or("expected STATE=COLOUR")?;
+
+    Ok(StateColour { state: State::from_str(state, false)?, colour: parse_colour(colour)? })
+}
+
+pub struct Chevrons<'a> {
+    pub theme: &'a Theme,
+    pub glyphs: &'a [PositionGlyph],
+    pub colours: &'a [StateColour],
+    pub colour: bool,
+}
+
+impl Chevrons<'_> {
+    pub fn get_glyph(&self, position: Position) -> &str {
+        self.glyphs.iter().rev().find(|g| g.position == position).map_or("❯", |g| &g.glyph)
+    }
+
+    // Chevrons with nothing to show, like outside of git, are just left plain
+    pub fn get_look(&self, state: Option<State>) -> Look {
+        let Some(state) = state else {
+            return Look::plain();
+        };
+
+        let look = self.theme.get_look(state.role());
+
+        match self.colours.iter().rev().find(|c| c.state == state) {
+            Some(c) => Look { colour: Some(c.colour), ..look },
+            None => look
+        }
+    }
+
+    // Without colour the chevrons can't show anything, so what they would have shown goes after them
+    pub fn add_marker(&self, chevron: Span, state: Option<State>) -> Span {
+        match state {
+            Some(state) if !self.colour => format!("{}{}", &*chevron, state.marker()).normal(),
+            _ => chevron
+        }
+    }
+
+    pub fn draw(&self, position: Position, state: Option<State>) -> Span {
+        self.add_marker(self.get_look(state).paint(self.get_glyph(position)), state)
+    }
+
+    pub fn draw_status(&self, states: &[Option<State>]) -> Vec<Span> {
+        states.iter()
+            .flatten()
+            .filter(|s| !s.status_glyph().is_empty())
+            .map(|s| self.get_look(Some(*s)).paint(s.status_glyph()))
+            .collect()
+    }
+}
@@ -0,0 +1,32 @@
+use std::env;
+
+use crate::text::sanitize;
+
+// Each system's own variable, what to call it and where it keeps the number of the run. CI on its
+// own is set by nearly all of them so it's only used when nothing more specific is
+const SYSTEMS: [(&str, &str, &str); 5] = [
+    ("GITHUB_ACTIONS", "github", "GITHUB_RUN_ID"),
+    ("GITLAB_CI", "gitlab", "CI_PIPELINE_ID"),
+    ("BUILDKITE", "buildkite", "BUILDKITE_BUILD_NUMBER"),
+    ("JENKINS_URL", "jenkins", "BUILD_NUMBER"),
+    ("CIRCLECI", "circleci", "CIRCLE_BUILD_NUM"),
+];
+
+fn is_set(name: &str) -> bool {
+    env::var_os(name).is_some_and(|x| !x.is_empty() && x != "false" && x != "0")
+}
+
+pub fn is_ci() -> bool {
+    is_set("CI") || SYSTEMS.iter().any(|(name, _, _)| is_set(name))
+}
+
+// Like ci:github#1234, or just ci when it's not known which system it is
+pub fn get_ci_system() -> Option<String> {
+    match SYSTEMS.iter().find(|(name, _, _)| is_set(name)) {
+        Some((_, system, run)) => Some(match env::var(run).ok().map(|x| sanitize(&x)).filter(|x| !x.is_empty()) {
+            Some(run) => format!("ci:{}#{}", system, run),
+            None => format!("ci:{}", system)
+        }),
+        None => Some("ci".to_owned()).filter(|_| is_set("CI"))
+    }
+}
@@ -0,0 +1,108 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use crate::command::{check_output, get_output, to_segment_result};
+use crate::custom::matches_pattern;
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::store::CacheKey;
+use crate::theme::Role;
+
+// Long enough for a cluster that's up to answer and short enough not to be noticed when it isn't
+pub const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+pub const PROBE_TTL: Duration = Duration::from_secs(180);
+
+// The host and port of https://host:port/path, where the port's left out for the scheme's own and
+// IPv6 addresses are in brackets
+pub fn parse_server(server: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = server.split_once("://")?;
+    let default_port = match scheme {
+        "https" => 443,
+        "http" => 80,
+        _ => return None
+    };
+
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, x)| x);
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, rest) = rest.split_once(']')?;
+            (host, rest.strip_prefix(':'))
+        },
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None)
+        }
+    };
+
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port
+    };
+    Some((host.to_owned(), port)).filter(|(host, _)| !host.is_empty())
+}
+
+// Only a connection is made, nothing's ever sent down it. Looking the host up can take much longer
+// than connecting, so it's done on a thread of its own that's left behind if it doesn't finish
+pub async fn is_reachable(host: &str, port: u16, timeout: Duration) -> bool {
+    let (sender, receiver) = oneshot::channel();
+    let host = host.to_owned();
+    thread::spawn(move || {
+        let connected = (host.as_str(), port).to_socket_addrs()
+            .is_ok_and(|mut addrs| addrs.any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok()));
+        let _ = sender.send(connected);
+    });
+
+    matches!(tokio::time::timeout(timeout, receiver).await, Ok(Ok(true)))
+}
+
+pub struct K8sReachableSegment {
+    // Clusters behind a VPN that's only sometimes up would always look unreachable
+    pub unprobed: Vec<String>,
+}
+
+impl K8sReachableSegment {
+    async fn get_reachable(&self, context: &Context<'_>) -> SegmentResult<bool> {
+        let output_res = get_output(context.runner, "kubectl", &["config", "view", "--minify", "--output", "jsonpath={.clusters[0].name}|{.clusters[0].cluster.server}"], context.timeout_ms).await;
+        let output = match to_segment_result(check_output(output_res), "kubectl") {
+            SegmentResult::Present(output) => output,
+            SegmentResult::Absent(reason) => return SegmentResult::Absent(reason)
+        };
+
+        let Some((cluster, server)) = output.rsplit_once('|') else { return SegmentResult::Absent(Reason::Nothing("the current context has no cluster")) };
+        let Some((host, port)) = parse_server(server) else { return SegmentResult::Absent(Reason::Nothing("the cluster's server isn't a URL")) };
+        if self.unprobed.iter().any(|x| matches_pattern(x, cluster) || matches_pattern(x, &host)) {
+            return SegmentResult::Absent(Reason::Nothing("the cluster is never probed"));
+        }
+
+        let key = CacheKey::new("k8s-reachable").with((&host, port));
+        if let Some(cached) = context.store.load(&key, PROBE_TTL) {
+            return SegmentResult::Present(cached == "true");
+        }
+
+        let reachable = is_reachable(&host, port, PROBE_TIMEOUT).await;
+        tracing::debug!(%host, port, reachable, "probed the cluster's API server");
+        context.store.save(&key, &reachable.to_string());
+        SegmentResult::Present(reachable)
+    }
+}
+
+impl Segment for K8sReachableSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::K8sReachable
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match self.get_reachable(context).await {
+                SegmentResult::Present(false) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Warning, "◦")])),
+                SegmentResult::Present(true) => SegmentResult::Absent(Reason::Nothing("the API server answered")),
+                SegmentResult::Absent(reason) => SegmentResult::Absent(reason)
+            }
+        }.boxed_local()
+    }
+}
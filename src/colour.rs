@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+use std::env;
+
+use colored::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColourDepth {
+    TrueColour,
+    Palette,
+    Basic
+}
+
+pub fn get_colour_depth() -> ColourDepth {
+    let colour_term = env::var("COLORTERM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+
+    if colour_term == "truecolor" || colour_term == "24bit" {
+        ColourDepth::TrueColour
+    } else if term.contains("256color") {
+        ColourDepth::Palette
+    } else {
+        ColourDepth::Basic
+    }
+}
+
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+    (Color::BrightBlack, (127, 127, 127)),
+    (Color::BrightRed, (255, 0, 0)),
+    (Color::BrightGreen, (0, 255, 0)),
+    (Color::BrightYellow, (255, 255, 0)),
+    (Color::BrightBlue, (92, 92, 255)),
+    (Color::BrightMagenta, (255, 0, 255)),
+    (Color::BrightCyan, (0, 255, 255)),
+    (Color::BrightWhite, (255, 255, 255)),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn get_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn get_palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            (CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize])
+        },
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+// The first 16 are left out as every terminal theme changes them
+pub fn to_palette(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255).min_by_key(|i| get_distance(rgb, get_palette_rgb(*i))).unwrap()
+}
+
+pub fn to_basic(rgb: (u8, u8, u8)) -> Color {
+    let rgb = get_palette_rgb(to_palette(rgb));
+    BASIC.iter().min_by_key(|(_, basic)| get_distance(rgb, *basic)).unwrap().0
+}
+
+// Picked to be told apart from each other at a glance, with nothing that's near the red and yellow
+// the chevrons use for warnings. They're all in the 256 colour palette so they look the same there
+pub const ACCENTS: [(u8, u8, u8); 8] = [
+    (0x5f, 0xaf, 0xff),
+    (0x5f, 0xd7, 0xaf),
+    (0x00, 0xd7, 0x5f),
+    (0xaf, 0x87, 0xff),
+    (0xd7, 0x5f, 0xd7),
+    (0x00, 0xaf, 0xaf),
+    (0x87, 0x87, 0xff),
+    (0xff, 0x87, 0xd7),
+];
+
+// FNV-1a rather than the standard library's hasher, which is free to change between releases, so
+// a repository keeps its colour across upgrades and machines
+pub fn get_accent(identity: &[u8]) -> Color {
+    let hash = identity.iter().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3));
+    let (r, g, b) = ACCENTS[(hash % ACCENTS.len() as u64) as usize];
+    Color::TrueColor { r, g, b }
+}
+
+// Hex and palette colours are kept as RGB until the terminal's known, palette ones come back out
+// as themselves when they're downsampled again
+pub fn parse_colour(s: &str) -> Result<Color, String> {
+    if let Some(hex) = s.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::TrueColor { r, g, b }),
+            _ => Err(format!("invalid hex colour '{}', expected #rrggbb", s))
+        }
+    } else if let Ok(index) = s.parse::<u8>() {
+        match index {
+            0..=15 => Ok(BASIC[index as usize].0),
+            _ => {
+                let (r, g, b) = get_palette_rgb(index);
+                Ok(Color::TrueColor { r, g, b })
+            }
+        }
+    } else {
+        s.parse().map_err(|_| format!("unknown colour '{}'", s))
+    }
+}
+
+fn get_code(colour: Color, depth: ColourDepth, background: bool) -> Cow<'static, str> {
+    let colour = match (colour, depth) {
+        (Color::TrueColor { r, g, b }, ColourDepth::Palette) => {
+            return format!("{};5;{}", if background { 48 } else { 38 }, to_palette((r, g, b))).into();
+        },
+        (Color::TrueColor { r, g, b }, ColourDepth::Basic) => to_basic((r, g, b)),
+        _ => colour
+    };
+
+    if background { colour.to_bg_str() } else { colour.to_fg_str() }
+}
+
+pub fn get_fg_code(colour: Color, depth: ColourDepth) -> Cow<'static, str> {
+    get_code(colour, depth, false)
+}
+
+pub fn get_bg_code(colour: Color, depth: ColourDepth) -> Cow<'static, str> {
+    get_code(colour, depth, true)
+}
+
+// The names tmux uses, which are also plain enough for JSON. tmux works out the nearest colour
+// itself for anything the terminal can't draw
+pub fn get_colour_name(colour: Color) -> Cow<'static, str> {
+    match colour {
+        Color::Black => "black".into(),
+        Color::Red => "red".into(),
+        Color::Green => "green".into(),
+        Color::Yellow => "yellow".into(),
+        Color::Blue => "blue".into(),
+        Color::Magenta => "magenta".into(),
+        Color::Cyan => "cyan".into(),
+        Color::White => "white".into(),
+        Color::BrightBlack => "brightblack".into(),
+        Color::BrightRed => "brightred".into(),
+        Color::BrightGreen => "brightgreen".into(),
+        Color::BrightYellow => "brightyellow".into(),
+        Color::BrightBlue => "brightblue".into(),
+        Color::BrightMagenta => "brightmagenta".into(),
+        Color::BrightCyan => "brightcyan".into(),
+        Color::BrightWhite => "brightwhite".into(),
+        Color::TrueColor { r, g, b } => format!("#{:02x}{:02x}{:02x}", r, g, b).into()
+    }
+}
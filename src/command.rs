@@ -0,0 +1,172 @@
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use tokio::process::Command;
+
+use crate::logging::run_command;
+use crate::segment::{Reason, SegmentResult};
+use crate::text::sanitize;
+
+pub trait CommandRunner {
+    fn run<'a>(&'a self, program: &'a str, args: &'a [&'a str]) -> LocalBoxFuture<'a, io::Result<Output>>;
+}
+
+// Everything else in the environment is left out. git and kubectl need to find their config and
+// credentials, as do the forges' tools, tmux its server, and Windows won't start much at all without its own
+const KEPT_VARIABLES: [&str; 20] = [
+    "HOME", "USER", "LOGNAME", "PATH", "TMPDIR", "XDG_CONFIG_HOME", "SSH_AUTH_SOCK", "TMUX", "TMUX_TMPDIR", "KUBECONFIG",
+    "SYSTEMROOT", "WINDIR", "USERPROFILE", "HOMEDRIVE", "HOMEPATH", "APPDATA", "LOCALAPPDATA", "PATHEXT", "TEMP", "TMP",
+];
+const KEPT_PREFIXES: [&str; 8] = ["GIT_", "GITLAB_", "GITEA_", "KUBE", "AWS_", "GOOGLE_", "CLOUDSDK_", "AZURE_"];
+
+// Where each program was found, for as long as PATH stays the same
+static RESOLVED: Mutex<Vec<(OsString, String, Option<PathBuf>)>> = Mutex::new(vec![]);
+
+#[cfg(unix)]
+fn get_candidate(dir: &Path, program: &str) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(program);
+    path.metadata().is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0).then_some(path)
+}
+
+#[cfg(windows)]
+fn get_candidate(dir: &Path, program: &str) -> Option<PathBuf> {
+    let path = dir.join(program).with_extension("exe");
+    path.is_file().then_some(path)
+}
+
+// Relative entries in PATH are skipped, as they'd pick up whatever wrapper the repository happens to
+// have lying around
+fn find_program(program: &str, path: &OsString) -> Option<PathBuf> {
+    env::split_paths(path).filter(|x| x.is_absolute()).find_map(|x| get_candidate(&x, program))
+}
+
+fn resolve_program(program: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    let mut resolved = RESOLVED.lock().ok()?;
+
+    if let Some((_, _, found)) = resolved.iter().find(|(p, name, _)| *p == path && name == program) {
+        return found.clone();
+    }
+
+    let found = find_program(program, &path);
+    tracing::debug!(program, found = ?found, "looked for a program");
+    resolved.push((path, program.to_owned(), found.clone()));
+    found
+}
+
+fn is_kept(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    KEPT_VARIABLES.contains(&name.as_str()) || KEPT_PREFIXES.iter().any(|x| name.starts_with(x))
+}
+
+// Everything's run in the directory the prompt is for, which isn't always where we are, and never
+// gets any input. Commands are started straight away rather than when they're first waited on.
+// Unless the environment's inherited, programs are found once up front and only get the variables
+// they need, in the C locale so what they print doesn't depend on the language
+pub struct ProcessRunner<'a> {
+    pub dir: &'a Path,
+    pub inherit_env: bool,
+}
+
+impl CommandRunner for ProcessRunner<'_> {
+    fn run<'a>(&'a self, program: &'a str, args: &'a [&'a str]) -> LocalBoxFuture<'a, io::Result<Output>> {
+        let mut command = match self.inherit_env {
+            true => Command::new(program),
+            false => {
+                let mut command = Command::new(resolve_program(program).map_or_else(|| program.into(), |x| x.into_os_string()));
+                command.env_clear()
+                    .envs(env::vars_os().filter(|(name, _)| name.to_str().is_some_and(is_kept)))
+                    .env("LC_ALL", "C");
+                command
+            }
+        };
+
+        run_command(command.args(args).current_dir(self.dir).stdin(Stdio::null()).kill_on_drop(true)).boxed_local()
+    }
+}
+
+pub enum Canned {
+    Exit(i32, &'static str),
+    Hang
+}
+
+// Answers each command line with whatever it was given for it so what's made of the output can be
+// tried without a repository. Anything it wasn't given fails as if there was no such program
+pub struct CannedRunner {
+    pub outputs: Vec<(&'static str, Canned)>,
+}
+
+#[cfg(unix)]
+pub fn get_exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+pub fn get_exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+impl CommandRunner for CannedRunner {
+    fn run<'a>(&'a self, program: &'a str, args: &'a [&'a str]) -> LocalBoxFuture<'a, io::Result<Output>> {
+        let command = std::iter::once(program).chain(args.iter().copied()).collect::<Vec<_>>().join(" ");
+
+        match self.outputs.iter().find(|(x, _)| *x == command) {
+            Some((_, Canned::Exit(code, stdout))) => {
+                let output = Output { status: get_exit_status(*code), stdout: stdout.as_bytes().to_vec(), stderr: vec![] };
+                async move { Ok(output) }.boxed_local()
+            },
+            Some((_, Canned::Hang)) => futures::future::pending().boxed_local(),
+            None => async move { Err(io::Error::new(io::ErrorKind::NotFound, format!("nothing canned for {}", command))) }.boxed_local()
+        }
+    }
+}
+
+pub fn parse_output(output_res: Result<Output, io::Error>) -> Option<String> {
+    check_output(output_res).ok().flatten()
+}
+
+// The same as parse_output but keeps hold of why the command failed
+// Only kubectl, tmux and sudo are given up on as git is needed for most of the prompt anyway
+pub async fn get_output(runner: &dyn CommandRunner, program: &str, args: &[&str], timeout_ms: Option<u64>) -> Result<Output, io::Error> {
+    let output_future = runner.run(program, args);
+
+    match timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), output_future).await
+            .unwrap_or_else(|_| {
+                tracing::debug!(program, ?args, timeout_ms = ms, "command timed out");
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("timed out after {}ms", ms)))
+            }),
+        None => output_future.await
+    }
+}
+
+pub fn to_segment_result(output: Result<Option<String>, String>, command: &'static str) -> SegmentResult<String> {
+    match output {
+        Ok(Some(x)) => SegmentResult::Present(x),
+        Ok(None) => SegmentResult::Absent(Reason::Empty(command)),
+        Err(e) => SegmentResult::Absent(Reason::Failed(format!("{}: {}", command, e)))
+    }
+}
+
+pub fn check_output(output_res: Result<Output, io::Error>) -> Result<Option<String>, String> {
+    let output = output_res.map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().map_or_else(|| output.status.to_string(), |x| x.trim().to_owned()));
+    }
+
+    Ok(String::from_utf8(output.stdout).ok().map(|mut x| {
+        x.retain(|c| !c.is_whitespace());
+        sanitize(&x)
+    }).filter(|x| !x.is_empty()))
+}
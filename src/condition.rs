@@ -0,0 +1,239 @@
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::custom::{file_exists, matches_pattern};
+use crate::path::get_home_directory;
+use crate::plugin::find_repo_root;
+use crate::segment::{get_conditions, SegmentCondition, SegmentKind};
+
+// The things a segment can be shown or hidden by, and !, & and | to put them together
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Multiplexer,
+    Tmux,
+    Screen,
+    Ssh,
+    Container,
+    EnvSet(String),
+    // The variable's value has to match the glob, which without any wildcards is just equal to it
+    EnvMatches(String, String),
+    File(String),
+    RepoFile(String),
+    // The current directory or any above it, with ~ for the home directory
+    Dir(String),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>)
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Anything with more than one part is bracketed wherever it'd be ambiguous
+        let bracketed = |condition: &Condition| match condition {
+            Condition::All(_) | Condition::Any(_) => format!("({})", condition),
+            _ => condition.to_string()
+        };
+
+        match self {
+            Condition::Multiplexer => f.write_str("multiplexer"),
+            Condition::Tmux => f.write_str("tmux"),
+            Condition::Screen => f.write_str("screen"),
+            Condition::Ssh => f.write_str("ssh"),
+            Condition::Container => f.write_str("container"),
+            Condition::EnvSet(name) => write!(f, "env:{}", name),
+            Condition::EnvMatches(name, pattern) => write!(f, "env:{}={}", name, pattern),
+            Condition::File(pattern) => write!(f, "file:{}", pattern),
+            Condition::RepoFile(pattern) => write!(f, "repo-file:{}", pattern),
+            Condition::Dir(pattern) => write!(f, "dir:{}", pattern),
+            Condition::Not(condition) => write!(f, "!{}", bracketed(condition)),
+            Condition::All(conditions) => f.write_str(&conditions.iter().map(|x| match x {
+                Condition::Any(_) => bracketed(x),
+                _ => x.to_string()
+            }).collect::<Vec<_>>().join(" & ")),
+            Condition::Any(conditions) => f.write_str(&conditions.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" | "))
+        }
+    }
+}
+
+fn parse_atom(s: &str) -> Result<Condition, String> {
+    let condition = match s.split_once(':') {
+        None if s == "multiplexer" => Condition::Multiplexer,
+        None if s == "tmux" => Condition::Tmux,
+        None if s == "screen" => Condition::Screen,
+        None if s == "ssh" => Condition::Ssh,
+        None if s == "container" => Condition::Container,
+        Some(("env", variable)) => match variable.split_once('=') {
+            Some((name, pattern)) if !name.is_empty() => Condition::EnvMatches(name.to_owned(), pattern.to_owned()),
+            None if !variable.is_empty() => Condition::EnvSet(variable.to_owned()),
+            _ => return Err(format!("'{}' has no variable in it", s))
+        },
+        Some(("file", pattern)) if !pattern.is_empty() => Condition::File(pattern.to_owned()),
+        Some(("repo-file", pattern)) if !pattern.is_empty() => Condition::RepoFile(pattern.to_owned()),
+        Some(("dir", pattern)) if !pattern.is_empty() => Condition::Dir(pattern.to_owned()),
+        _ => return Err(format!(
+            "unknown condition '{}', expected one of multiplexer, tmux, screen, ssh, container, env:VARIABLE[=GLOB], file:GLOB, repo-file:GLOB or dir:GLOB",
+            s
+        ))
+    };
+
+    Ok(condition)
+}
+
+// ! binds tightest, then &, then |, with brackets for anything else
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn parse_any(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.parse_all()?];
+        while self.eat('|') {
+            conditions.push(self.parse_all()?);
+        }
+
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { Condition::Any(conditions) })
+    }
+
+    fn parse_all(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.parse_not()?];
+        while self.eat('&') {
+            conditions.push(self.parse_not()?);
+        }
+
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { Condition::All(conditions) })
+    }
+
+    fn parse_not(&mut self) -> Result<Condition, String> {
+        if self.eat('!') {
+            return Ok(Condition::Not(Box::new(self.parse_not()?)));
+        }
+
+        if self.eat('(') {
+            let condition = self.parse_any()?;
+            if !self.eat(')') {
+                return Err("a bracket is never closed".to_owned());
+            }
+            return Ok(condition);
+        }
+
+        let end = self.rest.find(['&', '|', '(', ')']).unwrap_or(self.rest.len());
+        let (atom, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        match atom.trim() {
+            "" => Err("expected a condition".to_owned()),
+            atom => parse_atom(atom)
+        }
+    }
+}
+
+pub fn parse_condition(s: &str) -> Result<Condition, String> {
+    let mut parser = Parser { rest: s };
+    let condition = parser.parse_any()?;
+
+    match parser.rest.trim() {
+        "" => Ok(condition),
+        rest => Err(format!("unexpected '{}'", rest))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen
+}
+
+pub fn get_multiplexer() -> Option<Multiplexer> {
+    if env::var_os("TMUX").is_some() {
+        Some(Multiplexer::Tmux)
+    } else if env::var_os("STY").is_some() {
+        Some(Multiplexer::Screen)
+    } else {
+        None
+    }
+}
+
+pub fn is_ssh_session() -> bool {
+    env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CLIENT").is_some()
+}
+
+// Docker and Podman leave a file at the top of the container, and systemd-nspawn, Podman and
+// toolbox set container
+pub fn is_in_container(root: &Path) -> bool {
+    env::var_os("container").is_some_and(|x| !x.is_empty())
+        || root.join(".dockerenv").exists()
+        || root.join("run").join(".containerenv").exists()
+}
+
+pub fn is_in_dir(dir: &Path, pattern: &str) -> bool {
+    let pattern = match (pattern.strip_prefix("~/"), get_home_directory()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => pattern.to_owned()
+    };
+
+    dir.ancestors().any(|x| matches_pattern(&pattern, &x.to_string_lossy()))
+}
+
+// Anything else that marks out where a segment is worth showing, which is files like the given ones
+// here or at the top of the repository, or being somewhere under one of the given directories
+pub fn get_marker_condition(markers: &[String], dirs: &[String]) -> Option<Condition> {
+    let files = markers.iter().flat_map(|x| [Condition::File(x.clone()), Condition::RepoFile(x.clone())]);
+    let conditions: Vec<_> = files.chain(dirs.iter().map(|x| Condition::Dir(x.clone()))).collect();
+
+    Some(Condition::Any(conditions)).filter(|x| x != &Condition::Any(vec![]))
+}
+
+// Everything a condition can depend on. The environment's looked at once up front, and files only
+// when a condition asks about them
+pub struct Surroundings {
+    pub multiplexer: Option<Multiplexer>,
+    pub ssh: bool,
+    pub container: bool,
+    pub dir: PathBuf,
+}
+
+impl Surroundings {
+    pub fn detect(dir: &Path) -> Surroundings {
+        Surroundings {
+            multiplexer: get_multiplexer(),
+            ssh: is_ssh_session(),
+            container: is_in_container(Path::new("/")),
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    pub fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Multiplexer => self.multiplexer.is_some(),
+            Condition::Tmux => self.multiplexer == Some(Multiplexer::Tmux),
+            Condition::Screen => self.multiplexer == Some(Multiplexer::Screen),
+            Condition::Ssh => self.ssh,
+            Condition::Container => self.container,
+            Condition::EnvSet(name) => env::var_os(name).is_some_and(|x| !x.is_empty()),
+            Condition::EnvMatches(name, pattern) => env::var(name).is_ok_and(|x| matches_pattern(pattern, &x)),
+            Condition::File(pattern) => file_exists(&self.dir, pattern),
+            Condition::RepoFile(pattern) => find_repo_root(&self.dir).is_some_and(|x| file_exists(x, pattern)),
+            Condition::Dir(pattern) => is_in_dir(&self.dir, pattern),
+            Condition::Not(condition) => !self.holds(condition),
+            Condition::All(conditions) => conditions.iter().all(|x| self.holds(x)),
+            Condition::Any(conditions) => conditions.iter().any(|x| self.holds(x))
+        }
+    }
+
+    pub fn is_visible(&self, segment: SegmentKind, conditions: &[SegmentCondition]) -> bool {
+        get_conditions(segment, conditions).into_iter().all(|x| self.holds(x))
+    }
+}
+
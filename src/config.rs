@@ -0,0 +1,738 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::background::Background;
+use crate::chevron::{Position, PromptStyle, State};
+use crate::colour::parse_colour;
+use crate::custom::parse_custom_condition;
+use crate::danger::parse_danger_style;
+use crate::git::Fsmonitor;
+use crate::network::DEFAULT_IP_PROBE;
+use crate::overrides::apply_env_overrides;
+use crate::path::{get_home_directory, parse_path_alias, PathStyle};
+use crate::layout::Dedupe;
+use crate::plugin::parse_plugin_name;
+use crate::project::DEFAULT_MANIFESTS;
+use crate::remote::Forge;
+use crate::render::{ColourMode, Style};
+use crate::segment::{parse_segment_condition, parse_segment_group, IconStyle, SegmentKind, Side};
+use crate::template::parse_template;
+use crate::text::EllipsisPosition;
+use crate::theme::{Role, ThemeName};
+
+// Strings that are checked as they're read in so mistakes are reported with where they are in the
+// file, but are otherwise kept exactly as they were written
+macro_rules! checked_string {
+    ($name:ident, $check:expr) => {
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl TryFrom<String> for $name {
+            type Error = String;
+
+            fn try_from(s: String) -> Result<Self, String> {
+                let check: fn(&str) -> Result<(), String> = $check;
+                check(&s)?;
+                Ok($name(s))
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+checked_string!(Colour, |s| parse_colour(s).map(|_| ()));
+checked_string!(Condition, |s| parse_segment_condition(&format!("path={}", s)).map(|_| ()));
+checked_string!(TemplateText, |s| parse_template(s).map(|_| ()));
+checked_string!(Replacement, |s| parse_path_alias(&format!("~={}", s)).map(|_| ()));
+checked_string!(CustomWhen, |s| parse_custom_condition(s).map(|_| ()));
+checked_string!(SegmentGroup, |s| parse_segment_group(s).map(|_| ()));
+checked_string!(PluginName, parse_plugin_name);
+checked_string!(StyleText, |s| parse_danger_style(s).map(|_| ()));
+checked_string!(KeymapGlyph, |s| s.split_once(':').map_or(Ok(()), |(_, colour)| parse_colour(colour).map(|_| ())));
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PathConfig {
+    pub style: PathStyle,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+    pub physical: bool,
+    // Prefixes and what to show instead, optionally with :COLOUR on the end
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Replacement>,
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        PathConfig { style: PathStyle::Full, keep: None, physical: false, aliases: BTreeMap::new() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SegmentConfig {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<Side>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Colour>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<usize>,
+    // Only used with max-width, and otherwise depends on the segment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ellipsis: Option<EllipsisPosition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<Condition>,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        SegmentConfig { enabled: true, side: None, color: None, label: None, icon: None, max_width: None, ellipsis: None, when: vec![] }
+    }
+}
+
+// Segments of your own, showing the first line printed by a command. These have no flags as they
+// can only come from config that's trusted to run things
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CustomSegment {
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    #[serde(default = "get_custom_side")]
+    pub side: Side,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Colour>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<CustomWhen>,
+    // Seconds to keep reusing the last output for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+}
+
+// Segments showing an environment variable, or whatever its value is aliased to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct EnvSegment {
+    pub variable: String,
+    #[serde(default = "get_custom_side")]
+    pub side: Side,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Colour>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    // Globs the value has to match one of to be shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<String>,
+    // Values and what to show instead, optionally with :COLOUR on the end
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Replacement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<usize>,
+}
+
+// Values that look like production and how to make them stand out, for any segment that shows
+// something like a context or a profile
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DangerPattern {
+    // A glob matched against the whole value, before it's aliased or cut short
+    pub pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<StyleText>,
+}
+
+fn get_custom_side() -> Side {
+    Side::Left
+}
+
+// Everything else here has a flag of its own, the config file just saves typing them all out
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub theme: ThemeName,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Background>,
+    pub query_background: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColourMode>,
+    pub style: Style,
+    pub dedupe: Dedupe,
+    pub prompt_style: PromptStyle,
+    pub icons: IconStyle,
+    pub icon_width: usize,
+    pub ascii: bool,
+    pub single_line: bool,
+    pub no_blank_line: bool,
+    pub no_trailing_space: bool,
+    pub trailing: String,
+    pub no_slow_warning: bool,
+    pub separator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<TemplateText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill: Option<char>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<SegmentKind>,
+    // Segments to hide, where a name like k8s covers every k8s- segment
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disable: Vec<SegmentGroup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_ms: Option<u64>,
+    pub battery: bool,
+    pub battery_threshold: u8,
+    pub load: bool,
+    // How many times the number of cores the load has to be over to be shown
+    pub load_threshold: f64,
+    pub memory: bool,
+    // The percentage of memory available that it has to drop below to be shown
+    pub memory_threshold: u8,
+    pub local_ip: bool,
+    // Where the local address is worked out as going to, which is never actually sent anything
+    pub ip_probe: Ipv4Addr,
+    // Interfaces to show a label for while they're there, like wg0 = "vpn↑"
+    pub interface_markers: BTreeMap<String, String>,
+    // How much of a failed command's name to show next to its exit code
+    pub last_command_length: usize,
+    pub duration_threshold_ms: u64,
+    pub always_show_duration: bool,
+    // How long a terminal has to be left at a prompt for the next one to say so
+    pub idle_threshold_mins: u64,
+    pub tmux_session: bool,
+    pub ci: bool,
+    pub sudo: bool,
+    pub aws_account: bool,
+    pub k8s_reachability: bool,
+    // Clusters that are never probed, by name or by the host of their server
+    pub unprobed_clusters: Vec<String>,
+    pub git_lfs: bool,
+    pub merge_requests: bool,
+    // Seconds before the forge is asked about the branch again
+    pub merge_request_ttl: u64,
+    // Self hosted GitLab and Gitea instances, which can't be told apart by their names
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub forge_hosts: BTreeMap<String, Forge>,
+    pub git_remote: bool,
+    // What's shown for hosts that aren't public forges, like a company's own server
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub remote_labels: BTreeMap<String, String>,
+    pub fsmonitor: Fsmonitor,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub osc7: bool,
+    pub links: bool,
+    pub inherit_env: bool,
+    pub semantic_prompt: bool,
+    pub path: PathConfig,
+    // Files that mark the top of a project inside a repository
+    pub project_manifests: Vec<String>,
+    // Without any the AWS segments are shown everywhere
+    pub aws_markers: Vec<String>,
+    pub aws_dirs: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub segments: BTreeMap<SegmentKind, SegmentConfig>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<Role, Colour>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub chevrons: BTreeMap<Position, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub chevron_colors: BTreeMap<State, Colour>,
+    pub repo_accent: bool,
+    pub repo_accent_path: bool,
+    // For a repository's own .prompt.toml to pick its colour instead of the one it's given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_accent_color: Option<Colour>,
+    // Keymaps and the glyph to use for them, optionally with :COLOUR on the end
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keymaps: BTreeMap<String, KeymapGlyph>,
+    // Directories whose .prompt.toml files are allowed to run commands, only read from the global
+    // config
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trusted: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, CustomSegment>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, EnvSegment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub danger_patterns: Vec<DangerPattern>,
+    // Names for AWS account IDs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aws_accounts: BTreeMap<String, String>,
+    // prompt-segment-<name> executables to run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PluginName>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: ThemeName::Default,
+            background: None,
+            query_background: false,
+            color: None,
+            style: Style::Plain,
+            dedupe: Dedupe::Off,
+            prompt_style: PromptStyle::Chevrons,
+            icons: IconStyle::None,
+            icon_width: 1,
+            ascii: false,
+            single_line: false,
+            no_blank_line: false,
+            no_trailing_space: false,
+            trailing: " ".to_owned(),
+            no_slow_warning: false,
+            separator: " ".to_owned(),
+            template: None,
+            fill: None,
+            order: vec![],
+            disable: vec![],
+            timeout_ms: None,
+            budget_ms: None,
+            battery: false,
+            battery_threshold: 20,
+            load: false,
+            load_threshold: 1.0,
+            memory: false,
+            memory_threshold: 10,
+            local_ip: false,
+            ip_probe: DEFAULT_IP_PROBE,
+            interface_markers: BTreeMap::new(),
+            last_command_length: 16,
+            duration_threshold_ms: 2000,
+            always_show_duration: false,
+            idle_threshold_mins: 60,
+            tmux_session: false,
+            ci: false,
+            sudo: false,
+            aws_account: false,
+            k8s_reachability: false,
+            unprobed_clusters: vec![],
+            git_lfs: false,
+            merge_requests: false,
+            merge_request_ttl: 60,
+            forge_hosts: BTreeMap::new(),
+            git_remote: false,
+            remote_labels: BTreeMap::new(),
+            fsmonitor: Fsmonitor::Auto,
+            title: None,
+            osc7: false,
+            links: false,
+            inherit_env: false,
+            semantic_prompt: false,
+            path: PathConfig::default(),
+            project_manifests: DEFAULT_MANIFESTS.map(String::from).to_vec(),
+            aws_markers: vec![],
+            aws_dirs: vec![],
+            segments: BTreeMap::new(),
+            colors: BTreeMap::new(),
+            chevrons: BTreeMap::new(),
+            chevron_colors: BTreeMap::new(),
+            repo_accent: false,
+            repo_accent_path: false,
+            repo_accent_color: None,
+            keymaps: BTreeMap::new(),
+            trusted: vec![],
+            custom: BTreeMap::new(),
+            env: BTreeMap::new(),
+            danger_patterns: vec![],
+            aws_accounts: BTreeMap::new(),
+            plugins: vec![],
+        }
+    }
+}
+
+fn get_name(value: impl ValueEnum) -> String {
+    value.to_possible_value().map_or_else(String::new, |v| v.get_name().to_owned())
+}
+
+impl Config {
+    // Turns the config back into the flags it stands for, leaving out anything that's the same as
+    // the defaults
+    pub fn to_args(&self) -> Vec<String> {
+        let defaults = Config::default();
+        let mut args = vec![];
+
+        let mut flag = |name: &str, set: bool| {
+            if set {
+                args.push(format!("--{}", name));
+            }
+        };
+        flag("ascii", self.ascii);
+        flag("single-line", self.single_line);
+        flag("no-blank-line", self.no_blank_line);
+        flag("no-trailing-space", self.no_trailing_space);
+        flag("no-slow-warning", self.no_slow_warning);
+        flag("query-background", self.query_background);
+        flag("battery", self.battery);
+        flag("load", self.load);
+        flag("memory", self.memory);
+        flag("local-ip", self.local_ip);
+        flag("tmux-session", self.tmux_session);
+        flag("ci", self.ci);
+        flag("sudo", self.sudo);
+        flag("always-show-duration", self.always_show_duration);
+        flag("aws-account", self.aws_account);
+        flag("k8s-reachability", self.k8s_reachability);
+        flag("git-lfs", self.git_lfs);
+        flag("merge-requests", self.merge_requests);
+        flag("git-remote", self.git_remote);
+        flag("osc7", self.osc7);
+        flag("links", self.links);
+        flag("repo-accent", self.repo_accent);
+        flag("repo-accent-path", self.repo_accent_path);
+        flag("inherit-env", self.inherit_env);
+        flag("semantic-prompt", self.semantic_prompt);
+        flag("physical", self.path.physical);
+
+        let mut options: Vec<(&str, String)> = vec![];
+        if self.theme != defaults.theme {
+            options.push(("theme", get_name(self.theme)));
+        }
+        if let Some(background) = self.background {
+            options.push(("background", get_name(background)));
+        }
+        if let Some(colour) = self.color {
+            options.push(("color", get_name(colour)));
+        }
+        if self.style != defaults.style {
+            options.push(("style", get_name(self.style)));
+        }
+        if self.dedupe != defaults.dedupe {
+            options.push(("dedupe", get_name(self.dedupe)));
+        }
+        if self.prompt_style != defaults.prompt_style {
+            options.push(("prompt-style", get_name(self.prompt_style)));
+        }
+        if self.icons != defaults.icons {
+            options.push(("icons", get_name(self.icons)));
+        }
+        if self.icon_width != defaults.icon_width {
+            options.push(("icon-width", self.icon_width.to_string()));
+        }
+        if self.trailing != defaults.trailing {
+            options.push(("trailing", self.trailing.clone()));
+        }
+        if self.separator != defaults.separator {
+            options.push(("separator", self.separator.clone()));
+        }
+        if let Some(template) = &self.template {
+            options.push(("template", template.to_string()));
+        }
+        if let Some(fill) = self.fill {
+            options.push(("fill", fill.to_string()));
+        }
+        if !self.order.is_empty() {
+            options.push(("order", self.order.iter().map(|s| get_name(*s)).collect::<Vec<_>>().join(",")));
+        }
+        if let Some(timeout) = self.timeout_ms {
+            options.push(("timeout-ms", timeout.to_string()));
+        }
+        if let Some(budget) = self.budget_ms {
+            options.push(("budget-ms", budget.to_string()));
+        }
+        if self.battery_threshold != defaults.battery_threshold {
+            options.push(("battery-threshold", self.battery_threshold.to_string()));
+        }
+        if self.load_threshold != defaults.load_threshold {
+            options.push(("load-threshold", self.load_threshold.to_string()));
+        }
+        if self.memory_threshold != defaults.memory_threshold {
+            options.push(("memory-threshold", self.memory_threshold.to_string()));
+        }
+        if self.ip_probe != defaults.ip_probe {
+            options.push(("ip-probe", self.ip_probe.to_string()));
+        }
+        if self.merge_request_ttl != defaults.merge_request_ttl {
+            options.push(("merge-request-ttl", self.merge_request_ttl.to_string()));
+        }
+        if self.fsmonitor != defaults.fsmonitor {
+            options.push(("fsmonitor", get_name(self.fsmonitor)));
+        }
+        if self.duration_threshold_ms != defaults.duration_threshold_ms {
+            options.push(("duration-threshold-ms", self.duration_threshold_ms.to_string()));
+        }
+        if self.idle_threshold_mins != defaults.idle_threshold_mins {
+            options.push(("idle-threshold-mins", self.idle_threshold_mins.to_string()));
+        }
+        if self.last_command_length != defaults.last_command_length {
+            options.push(("last-command-length", self.last_command_length.to_string()));
+        }
+        if let Some(title) = &self.title {
+            options.push(("title", title.clone()));
+        }
+        if self.path.style != defaults.path.style {
+            options.push(("path-style", get_name(self.path.style)));
+        }
+        if let Some(keep) = self.path.keep {
+            options.push(("path-keep", keep.to_string()));
+        }
+        if self.project_manifests != defaults.project_manifests {
+            options.push(("project-manifests", self.project_manifests.join(",")));
+        }
+        if !self.aws_markers.is_empty() {
+            options.push(("aws-markers", self.aws_markers.join(",")));
+        }
+        if !self.aws_dirs.is_empty() {
+            options.push(("aws-dirs", self.aws_dirs.join(",")));
+        }
+        if !self.unprobed_clusters.is_empty() {
+            options.push(("unprobed-clusters", self.unprobed_clusters.join(",")));
+        }
+
+        options.extend(self.path.aliases.iter().map(|(prefix, alias)| ("path-alias", format!("{}={}", prefix, alias))));
+        options.extend(self.colors.iter().map(|(role, colour)| ("role-color", format!("{}={}", get_name(*role), colour))));
+        options.extend(self.chevrons.iter().map(|(position, glyph)| ("chevron", format!("{}={}", get_name(*position), glyph))));
+        if let Some(colour) = &self.repo_accent_color {
+            options.push(("repo-accent-color", colour.to_string()));
+        }
+        options.extend(self.chevron_colors.iter().map(|(state, colour)| ("chevron-color", format!("{}={}", get_name(*state), colour))));
+        options.extend(self.forge_hosts.iter().map(|(host, forge)| ("forge-host", format!("{}={}", host, get_name(*forge)))));
+        options.extend(self.remote_labels.iter().map(|(host, label)| ("remote-label", format!("{}={}", host, label))));
+        options.extend(self.interface_markers.iter().map(|(name, label)| ("interface-marker", format!("{}={}", name, label))));
+        options.extend(self.keymaps.iter().map(|(keymap, style)| ("keymap-style", format!("{}={}", keymap, style))));
+
+        for (kind, segment) in &self.segments {
+            let name = get_name(*kind);
+
+            let side = if segment.enabled { segment.side } else { Some(Side::Hidden) };
+            if let Some(side) = side {
+                options.push(("side", format!("{}={}", name, get_name(side))));
+            }
+            if let Some(colour) = &segment.color {
+                options.push(("segment-color", format!("{}={}", name, colour)));
+            }
+            if let Some(label) = &segment.label {
+                options.push(("label", format!("{}={}", name, label)));
+            }
+            if let Some(icon) = &segment.icon {
+                options.push(("icon", format!("{}={}", name, icon)));
+            }
+            if let Some(width) = segment.max_width {
+                let position = segment.ellipsis.map_or(String::new(), |x| format!(":{}", get_name(x)));
+                options.push(("max-width", format!("{}={}{}", name, width, position)));
+            }
+            options.extend(segment.when.iter().map(|condition| ("when", format!("{}={}", name, condition))));
+        }
+
+        if !self.disable.is_empty() {
+            options.push(("disable", self.disable.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",")));
+        }
+
+        // Written with = so values starting with a dash aren't taken for flags
+        args.extend(options.into_iter().map(|(name, value)| format!("--{}={}", name, value)));
+        args
+    }
+}
+
+// Everything written out so there's something to start editing, with the settings that are off
+// unless they're given left commented out
+pub fn get_default_config() -> Result<String, String> {
+    let segments = SegmentKind::value_variants().iter()
+        .map(|x| (*x, SegmentConfig { side: Some(x.default_side()), ..SegmentConfig::default() }))
+        .collect();
+    let config = Config { segments, ..Config::default() };
+
+    let text = toml::to_string(&config).map_err(|e| e.to_string())?;
+    let (top, tables) = text.split_once("\n[").ok_or("the default config has no tables")?;
+
+    let optional = [
+        "# color = \"auto\"",
+        "# template = \"{path}{git-branch: on }\"",
+        "# fill = \" \"",
+        "# order = [\"git-branch\", \"path\"]",
+        "# disable = [\"k8s\"]",
+        "# timeout-ms = 200",
+        "# budget-ms = 400",
+        "# title = \"{path}[ — {branch}]\"",
+        "# trusted = [\"~/work\"]",
+    ];
+    let tables = tables.replacen("path]\n", "path]\n# keep = 3\n", 1);
+
+    Ok(format!("{}\n{}\n\n[{}", top, optional.join("\n"), tables))
+}
+
+#[cfg(windows)]
+fn get_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").filter(|x| !x.is_empty()).map(PathBuf::from))
+}
+
+#[cfg(not(windows))]
+fn get_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| get_home_directory().map(|h| h.join(".config")))
+}
+
+// --config beats PROMPT_CONFIG which beats the usual place
+pub fn get_config_path(path: Option<&Path>) -> Option<PathBuf> {
+    path.map(|p| p.to_owned())
+        .or_else(|| env::var_os("PROMPT_CONFIG").filter(|x| !x.is_empty()).map(PathBuf::from))
+        .or_else(|| get_config_dir().map(|d| d.join("prompt").join("config.toml")))
+}
+
+const OVERLAY_NAME: &str = ".prompt.toml";
+
+// Settings that start processes, talk to the network or hand more of the environment to what's
+// run, which a repository can't change for itself without being trusted
+const COMMAND_SETTINGS: [&str; 15] = [
+    "sudo", "tmux-session", "custom", "plugins", "merge-requests", "merge-request-ttl", "forge-hosts", "k8s-reachability", "unprobed-clusters",
+    "git-lfs", "git-remote", "fsmonitor", "inherit-env", "local-ip", "ip-probe"
+];
+
+fn to_error(path: &Path, e: toml::de::Error) -> String {
+    format!("{}: {}", path.display(), e.to_string().trim_end())
+}
+
+// Each file is read as a whole config first, as that's what can say where in the file a mistake is
+fn read_table(path: &Path) -> Result<Option<toml::Table>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path.display(), e))
+    };
+
+    toml::from_str::<Config>(&text).map_err(|e| to_error(path, e))?;
+    text.parse().map(Some).map_err(|e| to_error(path, e))
+}
+
+fn to_config(table: toml::Table, path: &Path) -> Result<Config, String> {
+    table.try_into().map_err(|e| to_error(path, e))
+}
+
+// Tables are merged key by key, anything else is replaced outright
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// Overlays are looked for from the working directory up to the top of the repository or the home
+// directory, and nowhere at all outside of them so a stray file in /tmp can't change anything.
+// They're returned outermost first so the closest ones win
+pub fn find_overlays(dir: &Path) -> Vec<PathBuf> {
+    let home = get_home_directory();
+
+    let mut overlays = vec![];
+    for ancestor in dir.ancestors() {
+        let overlay = ancestor.join(OVERLAY_NAME);
+        if overlay.is_file() {
+            overlays.push(overlay);
+        }
+
+        if ancestor.join(".git").exists() || home.as_deref() == Some(ancestor) {
+            overlays.reverse();
+            return overlays;
+        }
+    }
+
+    vec![]
+}
+
+fn is_trusted(dir: &Path, trusted: &[PathBuf]) -> bool {
+    let home = get_home_directory();
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_owned());
+
+    trusted.iter().any(|x| {
+        let x = match (x.strip_prefix("~"), &home) {
+            (Ok(remainder), Some(home)) => home.join(remainder),
+            _ => x.to_owned()
+        };
+        dir.starts_with(fs::canonicalize(&x).unwrap_or(x))
+    })
+}
+
+// Some of what's shown runs commands, so an untrusted overlay can turn segments off but never turn
+// back on what's been turned off
+fn keep_disabled(base: &toml::Table, overlay: &mut toml::Table) {
+    if let (Some(toml::Value::Array(base)), Some(toml::Value::Array(overlay))) = (base.get("disable"), overlay.get_mut("disable")) {
+        for group in base {
+            if !overlay.contains(group) {
+                overlay.push(group.clone());
+            }
+        }
+    }
+
+    if let Some(toml::Value::Table(segments)) = overlay.get_mut("segments") {
+        for settings in segments.iter_mut().filter_map(|(_, x)| x.as_table_mut()) {
+            if settings.get("enabled").and_then(|x| x.as_bool()) == Some(true) {
+                settings.remove("enabled");
+            }
+        }
+    }
+}
+
+// Merges any .prompt.toml files over the global config, failing if any of them are broken
+pub fn apply_overlays(config: Config, dir: &Path) -> Result<Config, String> {
+    let trusted = config.trusted.clone();
+    let mut table = toml::Table::try_from(config).map_err(|e| e.to_string())?;
+
+    for path in find_overlays(dir) {
+        let Some(mut overlay) = read_table(&path)? else { continue };
+
+        overlay.remove("trusted");
+        if !path.parent().is_some_and(|x| is_trusted(x, &trusted)) {
+            for setting in COMMAND_SETTINGS {
+                overlay.remove(setting);
+            }
+            keep_disabled(&table, &mut overlay);
+        }
+
+        merge(&mut table, overlay);
+        to_config(table.clone(), &path)?;
+    }
+
+    to_config(table, dir)
+}
+
+// PROMPT_* variables go over the top of the config files, but still beneath any flags
+pub fn apply_env(config: Config) -> Result<Config, String> {
+    let base = toml::Value::try_from(config).map_err(|e| e.to_string())?;
+    apply_env_overrides(base, "PROMPT_")
+}
+
+// Segments that were left out of the build but still have settings, which are fine to have but
+// won't do anything
+pub fn get_unavailable_segments(config: &Config) -> Vec<SegmentKind> {
+    let mut segments: Vec<_> = config.segments.keys().chain(&config.order).copied().filter(|x| !x.is_available()).collect();
+    segments.sort();
+    segments.dedup();
+    segments
+}
+
+// Not having a config file is fine, it just means sticking with the defaults
+pub fn load_config(path: &Path) -> Result<Option<Config>, String> {
+    read_table(path)?.map(|x| to_config(x, path)).transpose()
+}
@@ -0,0 +1,139 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use colored::Color;
+use tokio::process::Command;
+
+use crate::config::{CustomSegment, EnvSegment};
+use crate::logging::run_command;
+use crate::path::parse_path_alias;
+use crate::store::{CacheKey, Store};
+use crate::text::{sanitize, truncate_to_width, EllipsisPosition};
+
+pub enum CustomCondition {
+    FileExists(String),
+    EnvSet(String),
+}
+
+pub fn parse_custom_condition(s: &str) -> Result<(CustomCondition, bool), String> {
+    let (condition, negated) = match s.strip_prefix('!') {
+        Some(condition) => (condition, true),
+        None => (s, false)
+    };
+
+    match condition.split_once(':') {
+        Some(("file", pattern)) if !pattern.is_empty() => Ok((CustomCondition::FileExists(pattern.to_owned()), negated)),
+        Some(("env", name)) if !name.is_empty() => Ok((CustomCondition::EnvSet(name.to_owned()), negated)),
+        _ => Err(format!("unknown condition '{}', expected file:GLOB or env:VARIABLE", condition))
+    }
+}
+
+// Just * and ? which is all anyone uses for this
+fn matches_glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches_glob(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && matches_glob(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_glob(rest, &name[1..])
+    }
+}
+
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    matches_glob(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+}
+
+// Only the last part of the pattern can have wildcards in, so this never walks the tree
+pub fn file_exists(dir: &Path, pattern: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return dir.join(pattern).exists();
+    }
+
+    let pattern = Path::new(pattern);
+    let parent = dir.join(pattern.parent().unwrap_or(Path::new("")));
+    let Some(file_pattern) = pattern.file_name().map(|x| x.to_string_lossy().chars().collect::<Vec<_>>()) else {
+        return false;
+    };
+
+    fs::read_dir(parent).map(|entries| entries.flatten().any(|entry| {
+        matches_glob(&file_pattern, &entry.file_name().to_string_lossy().chars().collect::<Vec<_>>())
+    })).unwrap_or(false)
+}
+
+fn is_enabled(segment: &CustomSegment, dir: &Path) -> bool {
+    segment.when.iter().all(|condition| {
+        let Ok((condition, negated)) = parse_custom_condition(&condition.to_string()) else { return false };
+
+        let holds = match condition {
+            CustomCondition::FileExists(pattern) => file_exists(dir, &pattern),
+            CustomCondition::EnvSet(name) => env::var_os(name).is_some_and(|x| !x.is_empty())
+        };
+        holds != negated
+    })
+}
+
+#[cfg(windows)]
+fn get_shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn get_shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+// Anything going wrong just means the segment isn't shown, the same as if the command printed
+// nothing
+pub async fn run_custom_segment(name: &str, segment: &CustomSegment, dir: &Path, store: &Store, timeout_ms: Option<u64>) -> Option<String> {
+    if !is_enabled(segment, dir) {
+        tracing::debug!(name, "custom segment's conditions don't hold");
+        return None;
+    }
+
+    let key = CacheKey::new("custom").with((name, &segment.command, dir));
+    if let Some(cached) = segment.cache_ttl.and_then(|ttl| store.load(&key, Duration::from_secs(ttl))) {
+        tracing::debug!(name, cached, "using the cached custom segment");
+        return Some(cached).filter(|x| !x.is_empty());
+    }
+
+    let output_future = run_command(get_shell_command(&segment.command)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true));
+
+    let timeout = Duration::from_millis(segment.timeout_ms.or(timeout_ms).unwrap_or(500));
+    let output = tokio::time::timeout(timeout, output_future).await.ok()?.ok().filter(|x| x.status.success())?;
+
+    let text = sanitize(String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim());
+
+    if segment.cache_ttl.is_some() {
+        store.save(&key, &text);
+    }
+
+    Some(text).filter(|x| !x.is_empty())
+}
+
+// Variables that aren't set, are empty or don't match aren't shown, and long ones are cut short
+// so one can't take over the whole line. The value's given back as well as what to show for it
+pub fn get_env_segment(segment: &EnvSegment) -> Option<(String, String, Option<Color>)> {
+    let value = env::var(&segment.variable).ok().filter(|x| !x.is_empty())?;
+    if !segment.matches.is_empty() && !segment.matches.iter().any(|x| matches_pattern(x, &value)) {
+        tracing::debug!(variable = segment.variable, value, "variable doesn't match");
+        return None;
+    }
+
+    let (text, colour) = match segment.aliases.get(&value).and_then(|x| parse_path_alias(&format!("~={}", x)).ok()) {
+        Some(alias) => (alias.replacement, alias.colour),
+        None => (value.clone(), None)
+    };
+
+    let text = truncate_to_width(&sanitize(&text), segment.max_width.unwrap_or(32), EllipsisPosition::End);
+    Some((value, text, colour)).filter(|(_, x, _)| !x.is_empty())
+}
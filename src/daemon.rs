@@ -0,0 +1,327 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::Duration;
+
+use crate::session::{get_own_session, set_session, Session};
+
+const RENDER: u8 = b'R';
+const STOP: u8 = b'S';
+const STARTED: u8 = b'A';
+
+// Nothing sent to the daemon comes close to this, so anything bigger is something that isn't us
+const MAX_FIELD: usize = 1024 * 1024;
+
+// A daemon that hasn't said it's started a render by then is treated as not being there
+const START_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Prompts send everything as soon as they connect, so anything slower than this is holding the
+// daemon up and is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Somewhere only we can get at, so there's nowhere for it to go without XDG_RUNTIME_DIR
+pub fn get_socket_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR").filter(|x| !x.is_empty()).map(|x| PathBuf::from(x).join("prompt.sock"))
+}
+
+fn is_our_socket(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|x| x.file_type().is_socket() && x.uid() == unsafe { libc::getuid() })
+}
+
+// The socket's already only ours to connect to, but whoever's on the other end is checked too
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_peer_uid(stream: &UnixStream) -> Option<libc::uid_t> {
+    let mut credentials: libc::ucred = unsafe { mem::zeroed() };
+    let mut length = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe { libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, ptr::addr_of_mut!(credentials).cast(), &mut length) };
+    (result == 0).then_some(credentials.uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn get_peer_uid(stream: &UnixStream) -> Option<libc::uid_t> {
+    let (mut uid, mut gid) = (0, 0);
+    (unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } == 0).then_some(uid)
+}
+
+fn write_field(stream: &mut UnixStream, field: &[u8]) -> io::Result<()> {
+    stream.write_all(&(field.len() as u32).to_le_bytes())?;
+    stream.write_all(field)
+}
+
+fn read_field(stream: &mut UnixStream) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FIELD {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "field too long"));
+    }
+
+    let mut field = vec![0; length];
+    stream.read_exact(&mut field)?;
+    Ok(field)
+}
+
+fn write_list(stream: &mut UnixStream, list: &[&[u8]]) -> io::Result<()> {
+    write_field(stream, &(list.len() as u32).to_le_bytes())?;
+    list.iter().try_for_each(|x| write_field(stream, x))
+}
+
+fn read_list(stream: &mut UnixStream) -> io::Result<Vec<Vec<u8>>> {
+    let count = read_field(stream)?;
+    let count = u32::from_le_bytes(count.try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad count"))?);
+    (0..count).map(|_| read_field(stream)).collect()
+}
+
+// The control buffer's made of u64s so the header in it is lined up properly
+fn get_control_buffer(fds: usize) -> Vec<u64> {
+    let space = unsafe { libc::CMSG_SPACE((fds * mem::size_of::<RawFd>()) as u32) } as usize;
+    vec![0; space.div_ceil(mem::size_of::<u64>())]
+}
+
+// The terminal the prompt's for is handed over as it is, so whatever's printed goes straight to it
+fn send_with_fds(stream: &UnixStream, kind: u8, fds: &[RawFd]) -> io::Result<()> {
+    let mut data = [kind];
+    let mut iov = libc::iovec { iov_base: data.as_mut_ptr().cast(), iov_len: 1 };
+    let mut control = get_control_buffer(fds.len());
+
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = (control.len() * mem::size_of::<u64>()) as _;
+
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(mem::size_of_val(fds) as u32) as _;
+        ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(header).cast(), fds.len());
+    }
+
+    match unsafe { libc::sendmsg(stream.as_raw_fd(), &message, 0) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(())
+    }
+}
+
+fn receive_with_fds(stream: &UnixStream) -> io::Result<(u8, Vec<OwnedFd>)> {
+    let mut data = [0];
+    let mut iov = libc::iovec { iov_base: data.as_mut_ptr().cast(), iov_len: 1 };
+    let mut control = get_control_buffer(3);
+
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = (control.len() * mem::size_of::<u64>()) as _;
+
+    match unsafe { libc::recvmsg(stream.as_raw_fd(), &mut message, 0) } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+        _ => ()
+    }
+
+    let mut fds = vec![];
+    unsafe {
+        let mut header = libc::CMSG_FIRSTHDR(&message);
+        while !header.is_null() {
+            if (*header).cmsg_level == libc::SOL_SOCKET && (*header).cmsg_type == libc::SCM_RIGHTS {
+                let count = ((*header).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / mem::size_of::<RawFd>();
+                let data: *const RawFd = libc::CMSG_DATA(header).cast();
+                fds.extend((0..count).map(|i| OwnedFd::from_raw_fd(ptr::read_unaligned(data.add(i)))));
+            }
+            header = libc::CMSG_NXTHDR(&message, header);
+        }
+    }
+
+    Ok((data[0], fds))
+}
+
+// Returns what the prompt exited with, or nothing if there isn't a daemon to ask and the prompt
+// should be drawn here instead. Once a render's started there's no going back as it may already
+// have printed something
+pub fn render_in_daemon(cli: &[OsString]) -> Option<i32> {
+    let path = get_socket_path().filter(|x| is_our_socket(x))?;
+    let cwd = env::current_dir().ok()?;
+
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        // Whatever was listening has gone without cleaning up after itself
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            let _ = fs::remove_file(&path);
+            return None;
+        },
+        Err(_) => return None
+    };
+
+    let env: Vec<Vec<u8>> = env::vars_os().map(|(key, value)| [key.as_bytes(), b"=", value.as_bytes()].concat()).collect();
+    let session = get_own_session();
+    let sent = send_with_fds(&stream, RENDER, &[0, 1, 2])
+        .and_then(|_| write_list(&mut stream, &cli.iter().map(|x| x.as_bytes()).collect::<Vec<_>>()))
+        .and_then(|_| write_field(&mut stream, cwd.as_os_str().as_bytes()))
+        .and_then(|_| write_list(&mut stream, &env.iter().map(|x| x.as_slice()).collect::<Vec<_>>()))
+        .and_then(|_| write_field(&mut stream, &session.id.map_or(vec![], |x| x.to_le_bytes().to_vec())))
+        .and_then(|_| write_field(&mut stream, session.key.as_bytes()));
+    if sent.is_err() {
+        return None;
+    }
+
+    let mut reply = [0];
+    stream.set_read_timeout(Some(START_TIMEOUT)).ok()?;
+    stream.read_exact(&mut reply).ok().filter(|_| reply[0] == STARTED)?;
+
+    // A render that died part way through counts as failing
+    stream.set_read_timeout(None).ok()?;
+    Some(match stream.read_exact(&mut reply) {
+        Ok(()) => reply[0] as i32,
+        Err(_) => 1
+    })
+}
+
+pub fn stop_daemon() -> Result<(), String> {
+    let path = get_socket_path().ok_or("XDG_RUNTIME_DIR isn't set so there's no daemon")?;
+    let mut stream = UnixStream::connect(&path).map_err(|_| format!("no daemon is listening on {}", path.display()))?;
+
+    stream.write_all(&[STOP]).map_err(|e| format!("couldn't ask the daemon to stop: {}", e))?;
+
+    // It hangs up once the socket's gone, so there's nothing left behind by the time this returns
+    let _ = stream.read(&mut [0]);
+    Ok(())
+}
+
+struct Request {
+    fds: Vec<OwnedFd>,
+    cli: Vec<OsString>,
+    cwd: PathBuf,
+    env: Vec<(OsString, OsString)>,
+    session: Session,
+}
+
+fn read_request(stream: &mut UnixStream, fds: Vec<OwnedFd>) -> io::Result<Request> {
+    let cli = read_list(stream)?.into_iter().map(OsString::from_vec).collect();
+    let cwd = PathBuf::from(OsString::from_vec(read_field(stream)?));
+    let env = read_list(stream)?.into_iter().filter_map(|x| {
+        let i = x.iter().position(|c| *c == b'=')?;
+        Some((OsString::from_vec(x[..i].to_vec()), OsString::from_vec(x[i + 1..].to_vec())))
+    }).collect();
+
+    // No ID is sent when the client couldn't get one
+    let id = read_field(stream)?;
+    let id = if id.is_empty() { None } else { Some(u32::from_le_bytes(id.try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad session"))?)) };
+    let key = String::from_utf8(read_field(stream)?).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad session"))?;
+
+    Ok(Request { fds, cli, cwd, env, session: Session { id, key } })
+}
+
+// Everything the render needs is swapped in before anything's said back, so if any of it can't be
+// done the client draws the prompt itself
+fn start_render(request: Request, stream: &mut UnixStream) -> io::Result<()> {
+    env::set_current_dir(&request.cwd)?;
+
+    for (i, fd) in request.fds.into_iter().enumerate().take(3) {
+        if unsafe { libc::dup2(fd.as_raw_fd(), i as RawFd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if fd.as_raw_fd() == i as RawFd {
+            mem::forget(fd);
+        }
+    }
+
+    for (key, _) in env::vars_os() {
+        env::remove_var(key);
+    }
+    for (key, value) in request.env {
+        env::set_var(key, value);
+    }
+    set_session(request.session);
+
+    stream.write_all(&[STARTED])
+}
+
+// Someone else's socket, or a daemon that's still running, is left alone
+fn bind_socket(path: &Path) -> Result<UnixListener, String> {
+    if fs::symlink_metadata(path).is_ok() {
+        if !is_our_socket(path) {
+            return Err(format!("{} is already there and isn't a socket of ours", path.display()));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("a daemon is already listening on {}", path.display()));
+        }
+        fs::remove_file(path).map_err(|e| format!("couldn't remove the old socket at {}: {}", path.display(), e))?;
+    }
+
+    let mask = unsafe { libc::umask(0o077) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(mask) };
+
+    let listener = listener.map_err(|e| format!("couldn't listen on {}: {}", path.display(), e))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| format!("couldn't set the permissions on {}: {}", path.display(), e))?;
+    Ok(listener)
+}
+
+// Each prompt is drawn in a fork of the daemon, which skips starting a process. The forks are
+// never waited on, and nothing in the daemon itself starts any threads, so forking is safe
+pub fn run_daemon(render: impl Fn(Vec<OsString>) -> i32) -> Result<(), String> {
+    let path = get_socket_path().ok_or("XDG_RUNTIME_DIR isn't set so there's nowhere safe for the socket")?;
+    let listener = bind_socket(&path)?;
+
+    // The daemon shouldn't keep hold of whatever directory it was started in
+    let _ = env::set_current_dir("/");
+    unsafe { libc::signal(libc::SIGCHLD, libc::SIG_IGN) };
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+
+        if get_peer_uid(&stream) != Some(unsafe { libc::getuid() }) {
+            tracing::debug!("ignored a connection from someone else");
+            continue;
+        }
+        if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+            continue;
+        }
+
+        let (kind, fds) = match receive_with_fds(&stream) {
+            Ok(x) => x,
+            Err(_) => continue
+        };
+
+        if kind == STOP {
+            let _ = fs::remove_file(&path);
+            return Ok(());
+        }
+
+        if kind != RENDER {
+            continue;
+        }
+        let Ok(mut request) = read_request(&mut stream, fds) else { continue };
+
+        match unsafe { libc::fork() } {
+            0 => {
+                unsafe { libc::signal(libc::SIGCHLD, libc::SIG_DFL) };
+
+                let cli = mem::take(&mut request.cli);
+                if start_render(request, &mut stream).is_err() {
+                    std::process::exit(1);
+                }
+
+                let code = render(cli);
+                let _ = io::stdout().flush();
+                let _ = stream.write_all(&[code as u8]);
+                std::process::exit(0);
+            },
+            -1 => tracing::debug!(error = %io::Error::last_os_error(), "couldn't fork"),
+            _ => ()
+        }
+    }
+
+    Ok(())
+}
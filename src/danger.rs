@@ -0,0 +1,68 @@
+use colored::Color;
+
+use crate::colour::parse_colour;
+use crate::config::DangerPattern;
+use crate::custom::matches_pattern;
+use crate::layout::Segment;
+use crate::styled::Stylize;
+
+// A colour and any of bold, dimmed, italic, underline and reversed, with anything not given left as
+// the segment already has it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DangerStyle {
+    pub colour: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reversed: bool,
+}
+
+impl DangerStyle {
+    pub fn apply(&self, segment: Segment) -> Segment {
+        segment.into_iter().map(|span| span.styled(|x| {
+            x.fg = self.colour.or(x.fg);
+            x.bold |= self.bold;
+            x.dimmed |= self.dimmed;
+            x.italic |= self.italic;
+            x.underline |= self.underline;
+            x.reversed |= self.reversed;
+        })).collect()
+    }
+}
+
+pub fn parse_danger_style(s: &str) -> Result<DangerStyle, String> {
+    let mut style = DangerStyle::default();
+
+    for word in s.split(',').map(str::trim) {
+        match word {
+            "bold" => style.bold = true,
+            "dimmed" => style.dimmed = true,
+            "italic" => style.italic = true,
+            "underline" => style.underline = true,
+            "reversed" => style.reversed = true,
+            "none" => (),
+            _ if style.colour.is_none() => style.colour = Some(parse_colour(word)?),
+            _ => return Err(format!("'{}' is a second colour, expected one colour and any of bold, dimmed, italic, underline and reversed", word))
+        }
+    }
+
+    Ok(style)
+}
+
+// Later patterns win, so the broad ones go first and the exceptions to them after, which can be
+// none to be left alone. Without a style the value is just reversed, which stands out whatever the
+// theme
+pub fn find_danger(patterns: &[DangerPattern], value: &str) -> Option<DangerStyle> {
+    let pattern = patterns.iter().rev().find(|x| matches_pattern(&x.pattern, value))?;
+
+    match &pattern.style {
+        Some(style) => parse_danger_style(&style.to_string()).ok(),
+        None => Some(DangerStyle { reversed: true, ..DangerStyle::default() })
+    }
+}
+
+// A pattern with none as its style is an exception that's left alone, so it isn't dangerous
+pub fn is_dangerous(patterns: &[DangerPattern], value: &str) -> bool {
+    find_danger(patterns, value).is_some_and(|x| x != DangerStyle::default())
+}
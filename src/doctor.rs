@@ -0,0 +1,138 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::git::is_fsmonitor_set;
+use crate::path::get_home_directory;
+
+// Past this many untracked files git status gets noticeably slow
+const UNTRACKED_WARNING: usize = 1000;
+
+pub fn get_tool_version(name: &str, args: &[&str]) -> String {
+    match Command::new(name).args(args).stdin(Stdio::null()).output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim().to_owned(),
+        Ok(output) => format!("found, but failed with {}", output.status),
+        Err(_) => "not found on PATH".to_owned()
+    }
+}
+
+// The prompt reports on itself through the JSON output, so what's shown here is exactly what the
+// prompt would have worked out
+pub fn get_segment_report(config: Option<&Path>) -> Result<Vec<String>, String> {
+    let exe = env::current_exe().map_err(|e| format!("couldn't find the prompt itself: {}", e))?;
+
+    let mut command = Command::new(exe);
+    command.args(["--format", "json", "--combined"]).stdin(Stdio::null());
+    if let Some(config) = config {
+        command.arg("--config").arg(config);
+    }
+
+    let start = Instant::now();
+    let output = command.output().map_err(|e| format!("couldn't run the prompt: {}", e))?;
+    let elapsed = start.elapsed();
+
+    if !output.status.success() {
+        return Err(format!("the prompt failed with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let report: Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("the prompt printed something that isn't JSON: {}", e))?;
+    let segments = report["segments"].as_array().ok_or("the prompt didn't list any segments")?;
+
+    let mut lines: Vec<String> = segments.iter().map(|segment| {
+        let duration = segment["duration_ms"].as_f64().map_or(String::new(), |x| format!("{:.1}ms", x));
+        let result = match (segment["text"].as_str(), segment["error"].as_str()) {
+            (_, Some(error)) => format!("failed: {}", error),
+            (Some(text), None) => text.to_owned(),
+            (None, None) if segment["side"] == "hidden" => "hidden".to_owned(),
+            (None, None) => segment["reason"].as_str().unwrap_or("nothing to show").to_owned()
+        };
+
+        format!("{:<14} {:<7} {:>9}  {}", segment["name"].as_str().unwrap_or_default(), segment["side"].as_str().unwrap_or_default(), duration, result)
+    }).collect();
+
+    lines.push(format!("{:<22} {:>9}", "total", format!("{:.1}ms", elapsed.as_secs_f64() * 1000.0)));
+    Ok(lines)
+}
+
+fn count_untracked(dir: &Path) -> Option<usize> {
+    let output = Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard"])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|x| x.status.success())?;
+
+    Some(output.stdout.split(|x| *x == b'\n').filter(|x| !x.is_empty()).count())
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(dir).stdin(Stdio::null()).stderr(Stdio::null()).output().ok().filter(|x| x.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// How changes are looked for depends on whether the repository has a file system monitor
+pub fn get_fsmonitor_report(dir: &Path) -> Option<String> {
+    run_git(dir, &["rev-parse", "--is-inside-work-tree"])?;
+
+    Some(match run_git(dir, &["config", "--get", "core.fsmonitor"]).filter(|x| is_fsmonitor_set(x)) {
+        Some(value) => format!("core.fsmonitor is {}, so changes are looked for with git status, which asks the monitor. --fsmonitor never uses diffs without it instead", value),
+        None => "core.fsmonitor isn't set, so changes are looked for with git diff. In a big repository 'git config core.fsmonitor true' makes git status quicker and the prompt will use it".to_owned()
+    })
+}
+
+pub fn get_kubeconfig_paths() -> Vec<PathBuf> {
+    match env::var_os("KUBECONFIG").filter(|x| !x.is_empty()) {
+        Some(paths) => env::split_paths(&paths).collect(),
+        None => get_home_directory().map(|h| h.join(".kube").join("config")).into_iter().collect()
+    }
+}
+
+// Exec plugins fetch credentials by running a program, which kubectl does on every prompt
+fn uses_exec_plugin(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|text| text.lines().any(|x| x.trim_start().starts_with("exec:")))
+}
+
+#[cfg(target_os = "linux")]
+fn is_network_filesystem(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const NFS: u32 = 0x6969;
+    const CIFS: u32 = 0xff534d42;
+    const SMB2: u32 = 0xfe534d42;
+
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else { return false };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+
+    let found = unsafe { libc::statfs(path.as_ptr(), &mut stat) } == 0;
+    found && [NFS, CIFS, SMB2].contains(&(stat.f_type as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_filesystem(_dir: &Path) -> bool {
+    false
+}
+
+pub fn get_slowness_warnings(dir: Option<&Path>) -> Vec<String> {
+    let mut warnings = vec![];
+
+    if let Some(dir) = dir {
+        if let Some(count) = count_untracked(dir).filter(|x| *x > UNTRACKED_WARNING) {
+            warnings.push(format!("{} untracked files, which git has to look through every time. Adding them to .gitignore will help", count));
+        }
+        if is_network_filesystem(dir) {
+            warnings.push(format!("{} is on a network filesystem, so git is slow here", dir.display()));
+        }
+    }
+
+    for path in get_kubeconfig_paths().into_iter().filter(|x| uses_exec_plugin(x)) {
+        warnings.push(format!("{} uses an exec plugin which kubectl may run every time, --disable k8s skips it", path.display()));
+    }
+
+    warnings
+}
@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::command::{get_output, parse_output, CommandRunner};
+use crate::git::get_git_remote_url;
+use crate::osc::percent_encode;
+use crate::remote::{get_forge, parse_remote_url, Forge, ForgeHost, RemoteUrl};
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::store::{CacheKey, Store};
+use crate::theme::Role;
+
+// Forges can be a long way away, so they get less time than anything else whatever the timeout is
+pub const FORGE_TIMEOUT_MS: u64 = 1000;
+
+// --variable and --expand-header are what keep a token off the command line, and curl only has
+// them from 8.3. Which curl's there hardly ever changes so it's only asked once a day
+pub const CURL_MIN_VERSION: (u32, u32) = (8, 3);
+const CURL_VERSION_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pipeline {
+    Passed,
+    Failed,
+    Running
+}
+
+impl Pipeline {
+    // GitLab's pipeline statuses and Gitea's commit statuses, where anything not listed is a
+    // pipeline that was skipped or is waiting on someone
+    pub fn from_status(status: &str) -> Option<Pipeline> {
+        match status {
+            "success" => Some(Pipeline::Passed),
+            "failed" | "failure" | "error" => Some(Pipeline::Failed),
+            "running" | "pending" | "created" | "preparing" | "waiting_for_resource" | "scheduled" => Some(Pipeline::Running),
+            _ => None
+        }
+    }
+
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            Pipeline::Passed => "✓",
+            Pipeline::Failed => "✗",
+            Pipeline::Running => "●"
+        }
+    }
+
+    fn role(&self) -> Role {
+        match self {
+            Pipeline::Passed => Role::Ok,
+            Pipeline::Failed => Role::Error,
+            Pipeline::Running => Role::Warning
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeRequest {
+    pub forge: Forge,
+    pub number: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Pipeline>,
+}
+
+impl MergeRequest {
+    // GitLab calls them !42 and everyone else #42
+    pub fn get_name(&self) -> String {
+        match self.forge {
+            Forge::Gitlab => format!("!{}", self.number),
+            Forge::Github | Forge::Gitea => format!("#{}", self.number)
+        }
+    }
+}
+
+fn get_number(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str()?.parse().ok())
+}
+
+// The list of merge requests has already been narrowed down to the branch
+pub fn parse_gitlab_merge_requests(json: &str) -> Option<u64> {
+    let requests: Value = serde_json::from_str(json).ok()?;
+    get_number(requests.as_array()?.first()?.get("iid")?)
+}
+
+pub fn parse_gitlab_pipelines(json: &str) -> Option<Pipeline> {
+    let pipelines: Value = serde_json::from_str(json).ok()?;
+    Pipeline::from_status(pipelines.as_array()?.first()?.get("status")?.as_str()?)
+}
+
+// Gitea can't filter pull requests by branch, so it's done here
+pub fn parse_gitea_pulls(json: &str, branch: &str) -> Option<u64> {
+    let pulls: Value = serde_json::from_str(json).ok()?;
+    let pull = pulls.as_array()?.iter().find(|x| x.pointer("/head/ref").and_then(|x| x.as_str()) == Some(branch))?;
+    get_number(pull.get("number")?)
+}
+
+pub fn parse_gitea_status(json: &str) -> Option<Pipeline> {
+    let status: Value = serde_json::from_str(json).ok()?;
+    Pipeline::from_status(status.get("state")?.as_str()?)
+}
+
+// tea prints every field as a string, with the commit status in ci
+pub fn parse_tea_pulls(json: &str, branch: &str) -> Option<(u64, Option<Pipeline>)> {
+    let pulls: Value = serde_json::from_str(json).ok()?;
+    let pull = pulls.as_array()?.iter().find(|x| x.get("head").and_then(|x| x.as_str()) == Some(branch))?;
+    Some((get_number(pull.get("index")?)?, pull.get("ci").and_then(|x| x.as_str()).and_then(Pipeline::from_status)))
+}
+
+// The first line of curl --version is like curl 8.5.0 (x86_64-pc-linux-gnu) libcurl/8.5.0
+pub fn parse_curl_version(text: &str) -> Option<(u32, u32)> {
+    let version = text.strip_prefix("curl ")?.split_whitespace().next()?;
+    let mut parts = version.split('.');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+// A token's only sent to the forge's own public host or to a host that's been named as that forge,
+// never to anything that just has gitlab or gitea in its name
+pub fn is_token_host(host: &str, forge: Forge, hosts: &[ForgeHost]) -> bool {
+    let public = match forge {
+        Forge::Github => "github.com",
+        Forge::Gitlab => "gitlab.com",
+        Forge::Gitea => "gitea.com"
+    };
+    host.eq_ignore_ascii_case(public) || hosts.iter().any(|x| x.forge == forge && x.host.eq_ignore_ascii_case(host))
+}
+
+struct Request<'a> {
+    runner: &'a dyn CommandRunner,
+    timeout_ms: u64,
+}
+
+impl Request<'_> {
+    // Failing and printing nothing are both failures here, as a forge always says something
+    async fn run(&self, program: &str, args: &[&str]) -> Result<String, String> {
+        let output = get_output(self.runner, program, args, Some(self.timeout_ms)).await.map_err(|e| format!("{}: {}", program, e))?;
+        if !output.status.success() {
+            return Err(format!("{}: {}", program, output.status));
+        }
+
+        String::from_utf8(output.stdout).map_err(|_| format!("{} didn't print UTF-8", program))
+    }
+
+    // A curl that's too old to read the token itself isn't used at all, rather than putting the
+    // token on the command line or asking without it
+    async fn check_curl(&self, store: &Store) -> Result<(), String> {
+        let key = CacheKey::new("curl-version").with_env("PATH");
+        let version = match store.load(&key, Duration::from_secs(CURL_VERSION_TTL_SECS)) {
+            Some(version) => version,
+            None => {
+                let version = self.run("curl", &["--version"]).await?.lines().next().unwrap_or_default().to_owned();
+                store.save(&key, &version);
+                version
+            }
+        };
+
+        match parse_curl_version(&version) {
+            Some(version) if version >= CURL_MIN_VERSION => Ok(()),
+            Some((major, minor)) => Err(format!("curl {}.{} can't send a token without it showing up in ps, it needs to be {}.{} or newer", major, minor, CURL_MIN_VERSION.0, CURL_MIN_VERSION.1)),
+            None => Err("curl didn't say which version it is".to_owned())
+        }
+    }
+
+    // The token's read from the environment by curl itself so it never shows up in ps
+    async fn curl(&self, url: &str, variable: &str, header: &str) -> Result<String, String> {
+        let max_time = format!("{:.1}", self.timeout_ms as f64 / 1000.0);
+        let variable = format!("%{}", variable);
+        self.run("curl", &["--silent", "--fail", "--max-time", &max_time, "--variable", &variable, "--expand-header", header, url]).await
+    }
+}
+
+async fn get_gitlab_merge_request(request: &Request<'_>, remote: &RemoteUrl<'_>, branch: &str, head: &str, token: bool) -> Result<Option<MergeRequest>, String> {
+    let project = percent_encode(remote.path.as_bytes()).replace('/', "%2F");
+    let merge_requests = format!("projects/{}/merge_requests?state=opened&source_branch={}&per_page=1", project, percent_encode(branch.as_bytes()));
+    let pipelines = format!("projects/{}/pipelines?sha={}&per_page=1", project, head);
+
+    let (merge_requests, pipelines) = match token {
+        true => {
+            let get = |endpoint: String| async move {
+                request.curl(&format!("{}/api/v4/{}", remote.get_api_base_url(), endpoint), "GITLAB_TOKEN", "PRIVATE-TOKEN: {{GITLAB_TOKEN}}").await
+            };
+            futures::join!(get(merge_requests), get(pipelines))
+        },
+        false => {
+            let get = |endpoint: String| async move { request.run("glab", &["api", "--hostname", remote.host, &endpoint]).await };
+            futures::join!(get(merge_requests), get(pipelines))
+        }
+    };
+
+    let Some(number) = parse_gitlab_merge_requests(&merge_requests?) else { return Ok(None) };
+    Ok(Some(MergeRequest { forge: Forge::Gitlab, number, pipeline: pipelines.ok().and_then(|x| parse_gitlab_pipelines(&x)) }))
+}
+
+async fn get_gitea_merge_request(request: &Request<'_>, remote: &RemoteUrl<'_>, branch: &str, head: &str, token: bool) -> Result<Option<MergeRequest>, String> {
+    if !token {
+        let pulls = request.run("tea", &["pulls", "list", "--state", "open", "--output", "json", "--fields", "index,head,ci"]).await?;
+        return Ok(parse_tea_pulls(&pulls, branch).map(|(number, pipeline)| MergeRequest { forge: Forge::Gitea, number, pipeline }));
+    }
+
+    let get = |endpoint: String| async move {
+        request.curl(&format!("{}/api/v1/repos/{}/{}", remote.get_api_base_url(), remote.path, endpoint), "GITEA_TOKEN", "Authorization: token {{GITEA_TOKEN}}").await
+    };
+    let (pulls, status) = futures::join!(get("pulls?state=open&limit=50".to_owned()), get(format!("commits/{}/status", head)));
+
+    let Some(number) = parse_gitea_pulls(&pulls?, branch) else { return Ok(None) };
+    Ok(Some(MergeRequest { forge: Forge::Gitea, number, pipeline: status.ok().and_then(|x| parse_gitea_status(&x)) }))
+}
+
+fn has_token(name: &str) -> bool {
+    std::env::var_os(name).is_some_and(|x| !x.is_empty())
+}
+
+// The open merge request for the branch that's checked out on GitLab and Gitea. What the forge said
+// is kept for a while for each commit, so it's only asked again once that's run out or something's
+// been committed
+pub struct MergeRequestSegment {
+    pub hosts: Vec<ForgeHost>,
+    pub ttl: u64,
+}
+
+impl MergeRequestSegment {
+    async fn get_merge_request(&self, context: &Context<'_>) -> SegmentResult<Option<MergeRequest>> {
+        let runner = context.runner;
+        let (remote_url, branch, head) = futures::join!(
+            get_git_remote_url(runner),
+            async { parse_output(runner.run("git", &["branch", "--show-current"]).await) },
+            async { parse_output(runner.run("git", &["rev-parse", "HEAD"]).await) }
+        );
+
+        let Some(remote_url) = remote_url else { return SegmentResult::Absent(Reason::Nothing("there's no origin remote")) };
+        let Some(remote) = parse_remote_url(&remote_url) else { return SegmentResult::Absent(Reason::Nothing("origin isn't on a forge")) };
+        let Some((branch, head)) = branch.zip(head) else { return SegmentResult::Absent(Reason::Nothing("no branch is checked out")) };
+
+        let forge = match get_forge(remote.host, &self.hosts) {
+            Some(forge @ (Forge::Gitlab | Forge::Gitea)) => forge,
+            _ => return SegmentResult::Absent(Reason::Nothing("origin isn't on GitLab or Gitea"))
+        };
+
+        let key = CacheKey::new("merge-request").with((&remote_url, &branch, &head));
+        if let Some(cached) = context.store.load(&key, Duration::from_secs(self.ttl)).and_then(|x| serde_json::from_str(&x).ok()) {
+            tracing::debug!(?cached, "using the cached merge request");
+            return SegmentResult::Present(cached);
+        }
+
+        let request = Request { runner, timeout_ms: context.timeout_ms.map_or(FORGE_TIMEOUT_MS, |x| x.min(FORGE_TIMEOUT_MS)) };
+        let token = has_token(if forge == Forge::Gitlab { "GITLAB_TOKEN" } else { "GITEA_TOKEN" }) && is_token_host(remote.host, forge, &self.hosts);
+        if token {
+            if let Err(e) = request.check_curl(context.store).await {
+                return SegmentResult::Absent(Reason::Failed(e));
+            }
+        }
+
+        let merge_request = match forge {
+            Forge::Gitlab => get_gitlab_merge_request(&request, &remote, &branch, &head, token).await,
+            _ => get_gitea_merge_request(&request, &remote, &branch, &head, token).await
+        };
+
+        // Only an answer is kept, so a forge that's down is asked again next time
+        match merge_request {
+            Ok(merge_request) => {
+                context.store.save(&key, &serde_json::to_string(&merge_request).unwrap_or_default());
+                SegmentResult::Present(merge_request)
+            },
+            Err(e) => SegmentResult::Absent(Reason::Failed(e))
+        }
+    }
+}
+
+impl Segment for MergeRequestSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitMergeRequest
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match self.get_merge_request(context).await {
+                SegmentResult::Present(Some(merge_request)) => {
+                    let name = context.theme.paint(Role::Git, &merge_request.get_name());
+                    let spans = match merge_request.pipeline {
+                        Some(pipeline) => vec![name, " ".into(), context.theme.paint(pipeline.role(), pipeline.glyph())],
+                        None => vec![name]
+                    };
+                    SegmentResult::Present(Rendered::Spans(spans))
+                },
+                SegmentResult::Present(None) => SegmentResult::Absent(Reason::Nothing("there's no open merge request for the branch")),
+                SegmentResult::Absent(reason) => SegmentResult::Absent(reason)
+            }
+        }.boxed_local()
+    }
+}
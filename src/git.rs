@@ -0,0 +1,284 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use futures::TryFutureExt;
+use serde::{Deserialize, Serialize};
+
+use crate::command::{parse_output, CommandRunner};
+use crate::remote::{apply_instead_of, parse_instead_of};
+use crate::segment::{Reason, SegmentResult};
+
+#[cfg(feature = "git")]
+pub async fn is_in_git_repository(runner: &dyn CommandRunner) -> bool {
+    let output_res = runner.run("git", &["rev-parse", "--is-inside-work-tree"]).await;
+
+    parse_output(output_res).map(|x| x == "true").unwrap_or(false)
+}
+
+// Everything else here is only used once we know we're in a repository, so without git in the
+// build none of it is
+#[cfg(not(feature = "git"))]
+pub async fn is_in_git_repository(_runner: &dyn CommandRunner) -> bool {
+    false
+}
+
+pub enum GitState {
+    RebaseInteractive,
+    RebaseMerge,
+    Rebase,
+    ApplyMailbox,
+    ApplyMailboxOrRebase,
+    Merge,
+    Revert,
+    CherryPick,
+    Bisect
+}
+
+pub fn get_git_state(dir: &Path) -> Option<GitState> {
+    let git_dir = dir.join(".git");
+
+    // Taken from libgit https://github.com/libgit2/libgit2/blob/main/src/libgit2/repository.c#L3697 - git_repository_state function
+    if git_dir.join("rebase-merge").join("interactive").is_file() {
+		Some(GitState::RebaseInteractive)
+    } else if git_dir.join("rebase-merge").is_dir() {
+		Some(GitState::RebaseMerge)
+    } else if git_dir.join("rebase-apply").join("rebasing").is_file() {
+		Some(GitState::Rebase)
+    } else if git_dir.join("rebase-apply").join("applying").is_file() {
+		Some(GitState::ApplyMailbox)
+    } else if git_dir.join("rebase-apply").is_dir() {
+		Some(GitState::ApplyMailboxOrRebase)
+    } else if git_dir.join("MERGE_HEAD").is_file() {
+		Some(GitState::Merge)
+    } else if git_dir.join("REVERT_HEAD").is_file() {
+        Some(GitState::Revert)
+	} else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
+        Some(GitState::CherryPick)
+	} else if git_dir.join("BISECT_LOG").is_file() {
+        Some(GitState::Bisect)
+    } else {
+        None
+    }
+}
+
+pub async fn get_best_git_name(runner: &dyn CommandRunner) -> SegmentResult<String> {
+    let branch_future = get_git_branch(runner);
+    let commit_future = get_git_commit(runner);
+    let tag_future = get_git_tag(runner);
+
+    let (branch, commit, tag) = futures::join!(branch_future, commit_future, tag_future);
+    tracing::debug!(?branch, ?commit, ?tag, "picking the git name");
+
+    if branch.is_some() || commit.is_some() || tag.is_some() {
+        SegmentResult::Present(branch.unwrap_or(commit.unwrap_or("".to_owned())) + &tag.as_ref().map(|t| " [".to_string() + t + "]").unwrap_or("".to_string()))
+    } else {
+        SegmentResult::Absent(Reason::Empty("git"))
+    }
+}
+
+async fn get_git_tag(runner: &dyn CommandRunner) -> Option<String> {
+    let output_res = runner.run("git", &["tag", "--points-at", "HEAD"]).await;
+
+    parse_output(output_res)
+}
+
+async fn get_git_branch(runner: &dyn CommandRunner) -> Option<String> {
+    let output_res = runner.run("git", &["branch", "--show-current"]).await;
+
+    parse_output(output_res)
+}
+
+// With any url.<base>.insteadOf rewrites done the same way git would before using it
+pub async fn get_git_remote_url(runner: &dyn CommandRunner) -> Option<String> {
+    let (url, rewrites) = futures::join!(
+        async { parse_output(runner.run("git", &["config", "--get", "remote.origin.url"]).await) },
+        runner.run("git", &["config", "--null", "--get-regexp", r"^url\..*\.insteadof$"])
+    );
+
+    // Having none at all is a failure as far as git config is concerned
+    let rewrites = rewrites.ok().filter(|x| x.status.success()).map(|x| parse_instead_of(&String::from_utf8_lossy(&x.stdout))).unwrap_or_default();
+    Some(apply_instead_of(&url?, &rewrites))
+}
+
+pub async fn get_git_repo_name(runner: &dyn CommandRunner) -> Option<String> {
+    let output_res = runner.run("git", &["rev-parse", "--show-toplevel"]).await;
+
+    // Directory names can have spaces in them so this can't use parse_output
+    let output = output_res.ok().filter(|x| x.status.success())?;
+    let toplevel = String::from_utf8(output.stdout).ok()?;
+
+    Path::new(toplevel.trim_end()).file_name().map(|x| x.to_string_lossy().into_owned())
+}
+
+async fn get_git_commit(runner: &dyn CommandRunner) -> Option<String> {
+    let output_res = runner.run("git", &["rev-parse", "--short", "HEAD"]).await;
+
+    parse_output(output_res)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UncommittedChanges {
+    None,
+    FilesChanged,
+    FilesNotAdded
+}
+
+// Whether changes are looked for with git status, which is what a file system monitor speeds up, or
+// with diffs that are quicker without one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fsmonitor {
+    // Whatever the repository's core.fsmonitor says
+    Auto,
+    Always,
+    // The diffs, with git told not to ask the monitor even if the repository has one
+    Never
+}
+
+// core.fsmonitor is either true for git's own daemon or the path to a hook like watchman's
+pub fn is_fsmonitor_set(value: &str) -> bool {
+    !matches!(value.trim().to_lowercase().as_str(), "false" | "no" | "off" | "0" | "")
+}
+
+pub async fn has_fsmonitor(runner: &dyn CommandRunner) -> bool {
+    let output_res = runner.run("git", &["config", "--get", "core.fsmonitor"]).await;
+
+    parse_output(output_res).is_some_and(|x| is_fsmonitor_set(&x))
+}
+
+// Untracked files are marked ?? and anything else is a change, including conflicts
+pub fn parse_porcelain_changes(status: &str) -> UncommittedChanges {
+    let lines: Vec<_> = status.lines().filter(|x| !x.is_empty()).collect();
+
+    if lines.iter().any(|x| !x.starts_with("??")) {
+        UncommittedChanges::FilesChanged
+    } else if lines.is_empty() {
+        UncommittedChanges::None
+    } else {
+        UncommittedChanges::FilesNotAdded
+    }
+}
+
+pub async fn get_uncommitted_changes(runner: &dyn CommandRunner, fsmonitor: Fsmonitor) -> UncommittedChanges {
+    let use_status = match fsmonitor {
+        Fsmonitor::Auto => has_fsmonitor(runner).await,
+        Fsmonitor::Always => true,
+        Fsmonitor::Never => false
+    };
+
+    if use_status {
+        let output_future = runner.run("git", &["status", "--porcelain"]);
+        let output = tokio::time::timeout(std::time::Duration::from_millis(500), output_future).await;
+
+        return match output {
+            Ok(Ok(output)) if output.status.success() => parse_porcelain_changes(&String::from_utf8_lossy(&output.stdout)),
+            _ => UncommittedChanges::FilesChanged
+        };
+    }
+
+    let prefix: &[&str] = if fsmonitor == Fsmonitor::Never { &["-c", "core.fsmonitor=false"] } else { &[] };
+    let diff_args = [prefix, &["diff", "--quiet"]].concat();
+    let cached_args = [prefix, &["diff", "--cached", "--quiet"]].concat();
+    let files_args = [prefix, &["ls-files", "--other", "--exclude-standard", "--deleted"]].concat();
+
+    let output1_future = runner.run("git", &diff_args);
+
+    let output1_timed_future = tokio::time::timeout(std::time::Duration::from_millis(500), output1_future).unwrap_or_else(|e| Result::Err(e.into()));
+
+    let output2_future = runner.run("git", &cached_args);
+
+    if let Ok((output1, output2)) = futures::try_join!(output1_timed_future, output2_future) {
+        if output1.status.success() && output2.status.success() {
+            let output3 = runner.run("git", &files_args).await;
+
+            if output3.map(|x| x.stdout.is_empty()).unwrap_or(false) {
+                UncommittedChanges::None
+            } else {
+                UncommittedChanges::FilesNotAdded
+            }
+        } else {
+            UncommittedChanges::FilesChanged
+        }
+    } else {
+        UncommittedChanges::FilesChanged
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnpushedChanges {
+    None,
+    UnpushedChanges,
+    UnpulledChanges,
+    DivergedChanges,
+    NoUpstreamBranch
+}
+
+// Where git push goes when that isn't the upstream, like with remote.pushDefault or a branch's
+// pushRemote. When either of them can't be worked out it's taken to be the usual sort of workflow
+pub async fn get_push_branch(runner: &dyn CommandRunner) -> Option<String> {
+    let (push, upstream) = futures::join!(
+        async { parse_output(runner.run("git", &["rev-parse", "--symbolic-full-name", "@{push}"]).await) },
+        async { parse_output(runner.run("git", &["rev-parse", "--symbolic-full-name", "@{u}"]).await) }
+    );
+
+    push.zip(upstream).filter(|(push, upstream)| push != upstream).map(|(push, _)| push)
+}
+
+// Compared with wherever git push will go. That's usually the upstream, so it's compared with that
+// while the push branch is being worked out and only done again when it's somewhere else
+pub async fn get_unpushed_changes(runner: &dyn CommandRunner) -> UnpushedChanges {
+    let (push_branch, changes) = futures::join!(get_push_branch(runner), get_changes_from(runner, "@{u}"));
+
+    match push_branch {
+        Some(push_branch) => get_changes_from(runner, &push_branch).await,
+        None => changes
+    }
+}
+
+async fn get_changes_from(runner: &dyn CommandRunner, target: &str) -> UnpushedChanges {
+    let ahead = format!("{}..", target);
+    let output1 = runner.run("git", &["log", &ahead]).await;
+
+    if output1.map(|x| x.stdout.is_empty()).unwrap_or(false) {
+        let output2_future = runner.run("git", &["rev-parse", "HEAD"]);
+
+        let target_args = ["rev-parse", target];
+        let output3_future = runner.run("git", &target_args);
+
+        let (output2, output3) = futures::join!(output2_future, output3_future);
+
+        let head = parse_output(output2);
+
+        let u = parse_output(output3);
+
+        if u.is_none() {
+            UnpushedChanges::NoUpstreamBranch
+        } else if head == u {
+            UnpushedChanges::None
+        } else {
+            UnpushedChanges::UnpulledChanges
+        }
+    } else {
+        let behind = format!("..{}", target);
+        let output4 = runner.run("git", &["log", "-1", &behind]).await;
+
+        if output4.map(|x| x.stdout.is_empty()).unwrap_or(true) {
+            UnpushedChanges::UnpushedChanges
+        } else {
+            UnpushedChanges::DivergedChanges
+        }
+    }
+}
+
+pub async fn get_git_errors(runner: &dyn CommandRunner) -> bool {
+    let output_res = runner.run("git", &["diff", "--check"]).await;
+
+    output_res.map_or(true, |x| !x.status.success())
+}
+
+pub async fn get_git_unmerged(runner: &dyn CommandRunner) -> usize {
+    let output_res = runner.run("git", &["status", "--porcelain"]).await;
+
+    parse_output(output_res).map_or(0, |x| x.split("\n").filter(|y| y.starts_with("UU")).count())
+}
@@ -0,0 +1,72 @@
+use crate::text::display_width;
+
+// Everything outside of ASCII that the prompt draws itself, and what to draw instead on terminals
+// that can't show it
+const GLYPHS: &[(&str, &str)] = &[
+    ("❯", ">"),
+    ("❮", "<"),
+    ("…", "..."),
+    ("✦", "&"),
+    ("✗", "x"),
+    ("✓", "ok"),
+    ("●", "*"),
+    ("⚡", "!"),
+    ("🔋", "bat "),
+    ("\u{26A0}\u{FE0F}", "!!"),
+    ("↑", "^"),
+    ("↓", "v"),
+    ("↕", "^v"),
+    ("✚", "+"),
+    ("⇡", "^"),
+    ("⇣", "v"),
+    ("⇕", "^v"),
+    ("│", "|"),
+    ("└", "`"),
+    ("─", "-"),
+    ("·", "."),
+    ("\u{E0B0}", ">"),
+];
+
+fn to_ascii(text: &str) -> String {
+    GLYPHS.iter().fold(text.to_owned(), |text, (glyph, ascii)| text.replace(glyph, ascii))
+}
+
+// Nerd Font icons live in the private use areas
+fn is_private_use(c: char) -> bool {
+    matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{FFFFD}' | '\u{100000}'..='\u{10FFFD}')
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Glyphs {
+    pub ascii: bool,
+    pub icon_width: usize,
+}
+
+impl Glyphs {
+    pub fn draw(&self, text: &str) -> String {
+        if self.ascii {
+            to_ascii(text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    // unicode-width can't know how wide the font draws its icons so that's left up to the user
+    pub fn get_width(&self, text: &str) -> usize {
+        let drawn = self.draw(text);
+        let icons = drawn.chars().filter(|c| is_private_use(*c)).count();
+
+        display_width(&drawn) - icons + icons * self.icon_width
+    }
+}
+
+// Terminals follow the locale, and one that isn't UTF-8 probably can't show any of the above
+pub fn is_ascii_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            !locale.contains("utf-8") && !locale.contains("utf8")
+        })
+}
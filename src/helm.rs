@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::Path;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::plugin::find_repo_root;
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::text::sanitize;
+use crate::theme::Role;
+
+// Only the top level of Chart.yaml is needed, and anything else in it is skipped over
+#[derive(Deserialize)]
+struct Chart {
+    name: Option<Value>,
+    version: Option<Value>,
+}
+
+// A version that looks like a number is still a version, but a list or a mapping isn't anything
+fn get_scalar(value: Option<Value>) -> Option<String> {
+    let text = match value? {
+        Value::String(text) => text,
+        Value::Number(number) => number.to_string(),
+        _ => return None
+    };
+
+    Some(text.trim().to_owned()).filter(|x| !x.is_empty())
+}
+
+// Only the first document's read, like helm does
+pub fn parse_chart(text: &str) -> Option<(String, Option<String>)> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let document = serde_yaml::Deserializer::from_str(text).next()?;
+    let chart = Chart::deserialize(document).ok()?;
+
+    Some((get_scalar(chart.name)?, get_scalar(chart.version)))
+}
+
+// The closest chart going up, so inside an umbrella chart's charts directory it's the subchart.
+// Nothing's looked for above the top of the repository, or above here outside of one
+pub fn find_chart(dir: &Path) -> Option<(String, Option<String>)> {
+    let root = find_repo_root(dir).unwrap_or(dir);
+
+    for ancestor in dir.ancestors() {
+        let path = ancestor.join("Chart.yaml");
+        if let Ok(text) = fs::read_to_string(&path) {
+            tracing::debug!(chart = %path.display(), "found a helm chart");
+            return parse_chart(&text);
+        }
+
+        if ancestor == root {
+            break;
+        }
+    }
+
+    None
+}
+
+pub struct HelmChartSegment;
+
+impl Segment for HelmChartSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::HelmChart
+    }
+
+    fn timing_name(&self) -> Option<String> {
+        None
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match find_chart(context.dir) {
+            Some((name, version)) => {
+                let text = version.map_or(name.clone(), |x| format!("{} {}", name, x));
+                SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::K8s, &sanitize(&text))]))
+            },
+            None => SegmentResult::Absent(Reason::Nothing("there's no Chart.yaml between here and the top of the repository"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
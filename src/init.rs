@@ -0,0 +1,40 @@
+use std::env;
+
+use crate::render::Shell;
+
+fn quote_posix(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn quote_fish(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn quote_nu(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_powershell(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+pub fn get_init_script(shell: Shell, background: bool) -> Result<String, String> {
+    if background && shell != Shell::Zsh {
+        return Err("only zsh can draw the prompt in the background".to_owned());
+    }
+
+    // Point the script at this exact binary so it works even when it's not on the PATH
+    let exe = env::current_exe().ok().map(|p| p.display().to_string()).unwrap_or_else(|| "prompt".to_owned());
+
+    Ok(match shell {
+        Shell::Bash => include_str!("init/prompt.bash").replace("::PROMPT::", &quote_posix(&exe)),
+        // The asynchronous parts go on the end and take over drawing the prompt
+        Shell::Zsh if background => [include_str!("init/prompt.zsh"), include_str!("init/async.zsh")].concat().replace("::PROMPT::", &quote_posix(&exe)),
+        Shell::Zsh => include_str!("init/prompt.zsh").replace("::PROMPT::", &quote_posix(&exe)),
+        Shell::Fish => include_str!("init/prompt.fish").replace("::PROMPT::", &quote_fish(&exe)),
+        Shell::PowerShell => include_str!("init/prompt.ps1").replace("::PROMPT::", &quote_powershell(&exe)),
+        Shell::Nu => include_str!("init/prompt.nu").replace("::PROMPT::", &quote_nu(&exe)),
+        // Single quotes work the same way as they do in PowerShell
+        Shell::Elvish => include_str!("init/prompt.elv").replace("::PROMPT::", &quote_powershell(&exe))
+    })
+}
@@ -0,0 +1,27 @@
+_prompt_command() {
+    # Grab these before anything else runs and replaces them
+    local exit_code=$? codes="${PIPESTATUS[*]}"
+
+    local job_pids=($(jobs -p))
+    local args=(--exit-code "$exit_code" --pipestatus "$codes" --jobs "${#job_pids[@]}" --shell bash)
+
+    # There's no right prompt in bash so everything goes on the top line
+    args+=(--side duration=left --side jobs=left --side pipestatus=left)
+
+    if [[ "$TERM_PROGRAM" == "iTerm.app" ]]; then
+        args+=(--iterm2)
+    fi
+
+    if [[ -n "$COLUMNS" ]]; then
+        args+=(--columns "$COLUMNS")
+    fi
+
+    PS1="$(::PROMPT:: "${args[@]}")"
+}
+
+# PS0 is expanded just before a command runs, which makes it the only place to record when it
+# started and what it was. Nothing's printed, the prompt picks it up from the state file afterwards
+PS0+="\$(::PROMPT:: pre-exec -- \"\$(fc -ln -0 2>/dev/null)\")"
+
+# This has to go first or $? will be from whatever else is in there
+PROMPT_COMMAND="_prompt_command${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
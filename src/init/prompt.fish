@@ -0,0 +1,38 @@
+# Newer versions of fish redraw the prompt with --final-rendering once a command's entered, which
+# shrinks it down to just the chevrons
+set -g fish_transient_prompt 1
+
+function fish_prompt
+    # Grab these before anything else runs and replaces them
+    set -l prompt_args --exit-code $status --pipestatus "$pipestatus" --shell fish
+
+    if contains -- --final-rendering $argv
+        ::PROMPT:: $prompt_args --transient
+        return
+    end
+
+    set -a prompt_args --combined
+
+    # CMD_DURATION isn't set until the first command has run
+    set -q CMD_DURATION; and set -a prompt_args --duration-ms $CMD_DURATION
+    set -a prompt_args --jobs (count (jobs --pid))
+
+    if test "$TERM_PROGRAM" = "iTerm.app"
+        set -a prompt_args --iterm2
+    end
+
+    # Both sides come out of the one run and the right side is kept for fish_right_prompt
+    set -l output (::PROMPT:: $prompt_args | string split0)
+    set -g _prompt_right $output[2]
+
+    printf '%s' $output[1]
+end
+
+# What the command was is left for the next prompt to pick up, fish already knows how long it took
+function _prompt_preexec --on-event fish_preexec
+    ::PROMPT:: pre-exec -- $argv
+end
+
+function fish_right_prompt
+    contains -- --final-rendering $argv; or printf '%s' $_prompt_right
+end
@@ -0,0 +1,39 @@
+$global:PromptLastHistoryId = -1
+
+if (Get-Module PSReadLine) {
+    # The blank line and the top line both come before the line being edited
+    Set-PSReadLineOption -ExtraPromptLineCount 2
+}
+
+function global:prompt {
+    $success = $?
+    $lastExitCode = $global:LASTEXITCODE
+
+    $exitCode = if ($success) { 0 } elseif ($lastExitCode) { $lastExitCode } else { 1 }
+
+    $promptArgs = @('--exit-code', $exitCode, '--shell', 'powershell', '--jobs', @(Get-Job -State Running).Count)
+
+    # There's no right prompt in PowerShell so everything goes on the top line
+    $promptArgs += @('--side', 'duration=left', '--side', 'jobs=left', '--side', 'pipestatus=left')
+
+    # Only show the duration once, pressing enter on an empty line shouldn't show it again
+    $lastCommand = Get-History -Count 1
+    if ($lastCommand -and $lastCommand.Id -ne $global:PromptLastHistoryId) {
+        $global:PromptLastHistoryId = $lastCommand.Id
+        $promptArgs += @('--duration-ms', [int64]($lastCommand.EndExecutionTime - $lastCommand.StartExecutionTime).TotalMilliseconds)
+        $promptArgs += "--last-command=$($lastCommand.CommandLine)"
+    }
+
+    $env:PWD = $executionContext.SessionState.Path.CurrentFileSystemLocation.ProviderPath
+
+    $encoding = [Console]::OutputEncoding
+    [Console]::OutputEncoding = [Text.Encoding]::UTF8
+    try {
+        $output = (& ::PROMPT:: @promptArgs) -join "`n"
+    } finally {
+        [Console]::OutputEncoding = $encoding
+    }
+
+    $global:LASTEXITCODE = $lastExitCode
+    $output
+}
@@ -0,0 +1,186 @@
+use clap::ValueEnum;
+use colored::Color;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::glyph::Glyphs;
+use crate::segment::SegmentKind;
+use crate::styled::{Span, StyledText, Stylize};
+use crate::text::{get_kept_graphemes, EllipsisPosition};
+
+pub type Segment = StyledText;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dedupe {
+    Off,
+    Equal,
+    Contained
+}
+
+// Glyphs are measured as they'll be drawn so the ASCII versions still fit
+pub fn get_display_width(segment: &[Span], glyphs: &Glyphs) -> usize {
+    segment.iter().map(|span| glyphs.get_width(span)).sum()
+}
+
+pub fn get_line_width(segments: &[(Option<SegmentKind>, Segment)], separator: &str, glyphs: &Glyphs) -> usize {
+    segments.iter().map(|(_, s)| get_display_width(s, glyphs)).sum::<usize>() + segments.len().saturating_sub(1) * glyphs.get_width(separator)
+}
+
+// Cuts a segment down to size across however many spans it has, with the ellipsis taking the look
+// of whichever span it replaced part of
+pub fn truncate_segment(segment: &[Span], width: usize, position: EllipsisPosition, glyphs: &Glyphs) -> Segment {
+    let graphemes: Vec<_> = segment.iter().enumerate().flat_map(|(i, span)| span.graphemes(true).map(move |g| (i, g))).collect();
+    let widths: Vec<_> = graphemes.iter().map(|(_, g)| glyphs.get_width(g)).collect();
+
+    let Some((head, tail)) = get_kept_graphemes(&widths, width, glyphs.get_width("…"), position) else {
+        return segment.to_vec();
+    };
+
+    let cut = match position {
+        EllipsisPosition::Start => graphemes[graphemes.len() - tail - 1].0,
+        _ => graphemes[head].0
+    };
+    let kept = graphemes[..head].iter().copied().chain([(cut, "…")]).chain(graphemes[graphemes.len() - tail..].iter().copied());
+
+    // Whatever's left of each span is put back together with its look
+    let mut truncated: Vec<(usize, String)> = vec![];
+    for (i, g) in kept {
+        match truncated.last_mut() {
+            Some((last, text)) if *last == i => *text += g,
+            _ => truncated.push((i, g.to_owned()))
+        }
+    }
+
+    truncated.into_iter().map(|(i, text)| segment[i].with_text(&text)).collect()
+}
+
+fn collect_segments(kinds: &[SegmentKind], segments: &[Option<Segment>]) -> Vec<(Option<SegmentKind>, Segment)> {
+    let mut collected: Vec<(Option<SegmentKind>, Segment)> = vec![];
+    let mut dropped = false;
+
+    for (kind, segment) in kinds.iter().zip(segments) {
+        match segment {
+            Some(segment) => {
+                collected.push((Some(*kind), segment.clone()));
+                dropped = false;
+            },
+            // Consecutive dropped segments only need one ellipsis between them
+            None if !dropped => {
+                collected.push((None, vec!["…".dimmed()]));
+                dropped = true;
+            },
+            None => {}
+        }
+    }
+
+    collected
+}
+
+pub fn recolour(segment: Segment, colour: Color) -> Segment {
+    segment.into_iter().map(|span| span.color(colour)).collect()
+}
+
+// Labels are dimmed so they stand out from the segment itself
+pub fn add_label(segment: Segment, label: &str) -> Segment {
+    std::iter::once(label.dimmed()).chain(segment).collect()
+}
+
+// Icons take on the look of whatever they're in front of
+pub fn add_icon(segment: Segment, icon: &str) -> Segment {
+    match segment.first() {
+        Some(first) => std::iter::once(first.with_text(&format!("{} ", icon))).chain(segment).collect(),
+        None => segment
+    }
+}
+
+// Powerline segments are filled in with their colour and have dark text on top, with a bit of
+// padding so the text doesn't run into the separators
+pub fn to_powerline(segment: Segment) -> Segment {
+    let background = segment.iter().find_map(|s| s.styling.fg).unwrap_or(Color::White);
+
+    std::iter::once(" ".normal())
+        .chain(segment)
+        .chain(std::iter::once(" ".normal()))
+        .map(|span| span.color(Color::Black).on_color(background))
+        .collect()
+}
+
+// The text as it's written, without any colours or styles
+pub fn get_text(segment: &[Span]) -> String {
+    segment.iter().map(|span| &**span).collect()
+}
+
+fn is_duplicate(previous: &str, text: &str, dedupe: Dedupe) -> bool {
+    match dedupe {
+        Dedupe::Off => false,
+        Dedupe::Equal => previous == text,
+        Dedupe::Contained => previous.contains(text) || text.contains(previous)
+    }
+}
+
+// Drops a segment that says the same as the one before it on the same side, like a Kubernetes
+// context named after its namespace, keeping whichever of the two is more important
+pub fn dedupe_segments(segments: Vec<(SegmentKind, Segment)>, dedupe: Dedupe, same_side: impl Fn(SegmentKind, SegmentKind) -> bool) -> Vec<(SegmentKind, Segment)> {
+    let mut kept: Vec<(SegmentKind, Segment, String)> = vec![];
+
+    for (kind, segment) in segments {
+        let text = get_text(&segment).trim().to_owned();
+        let previous = kept.iter().rposition(|(x, _, _)| same_side(*x, kind));
+
+        match previous {
+            Some(i) if is_duplicate(&kept[i].2, &text, dedupe) => {
+                if kind.priority() > kept[i].0.priority() {
+                    kept.remove(i);
+                    kept.push((kind, segment, text));
+                }
+            },
+            _ => kept.push((kind, segment, text))
+        }
+    }
+
+    kept.into_iter().map(|(kind, segment, _)| (kind, segment)).collect()
+}
+
+// Drops the least important segments until the line fits, then truncates whatever is left if
+// even that is too much. The ellipses left in place of dropped segments don't have a kind
+pub fn fit_to_width(segments: Vec<(SegmentKind, Segment)>, width: usize, separator: &str, glyphs: &Glyphs) -> Vec<(Option<SegmentKind>, Segment)> {
+    let kinds: Vec<_> = segments.iter().map(|(kind, _)| *kind).collect();
+    let mut remaining: Vec<Option<Segment>> = segments.into_iter().map(|(_, segment)| Some(segment)).collect();
+
+    while get_line_width(&collect_segments(&kinds, &remaining), separator, glyphs) > width && remaining.iter().filter(|s| s.is_some()).count() > 1 {
+        let lowest = (0..remaining.len())
+            .filter(|i| remaining[*i].is_some())
+            .rev()
+            .min_by_key(|i| kinds[*i].priority())
+            .unwrap();
+
+        remaining[lowest] = None;
+    }
+
+    let line_width = get_line_width(&collect_segments(&kinds, &remaining), separator, glyphs);
+
+    if line_width > width {
+        if let Some(survivor) = remaining.iter_mut().flatten().next() {
+            let available = width.saturating_sub(line_width - get_display_width(survivor, glyphs));
+            *survivor = truncate_segment(survivor, available, EllipsisPosition::Start, glyphs);
+        }
+    }
+
+    collect_segments(&kinds, &remaining)
+}
+
+// Pushes the right hand segments to the end of the line, shrinking the gap down to a single space
+// and then dropping the least important of them when there isn't room. Gives back how wide the gap
+// needs to be
+pub fn fit_right(left_width: usize, right: Vec<(SegmentKind, Segment)>, width: usize, separator: &str, glyphs: &Glyphs) -> (Vec<(Option<SegmentKind>, Segment)>, usize) {
+    let mut right: Vec<_> = right.into_iter().map(|(kind, segment)| (Some(kind), segment)).collect();
+
+    while !right.is_empty() && left_width + 1 + get_line_width(&right, separator, glyphs) > width {
+        let lowest = (0..right.len()).rev().min_by_key(|i| right[*i].0.map_or(0, |k| k.priority())).unwrap();
+        right.remove(lowest);
+    }
+
+    let gap = width.saturating_sub(left_width + get_line_width(&right, separator, glyphs));
+    (right, gap)
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use crate::command::{get_output, CommandRunner};
+use crate::plugin::find_repo_root;
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::theme::Role;
+
+// git lfs status only looks at what's here, but it's a program of its own that can be slow to start
+pub const LFS_TIMEOUT_MS: u64 = 500;
+
+// Whether anything in the repository is stored in LFS, which is only ever said in its attributes.
+// filter.lfs.required is set for every repository once git lfs install has been run so it isn't
+// any help
+pub fn uses_lfs(root: &Path) -> bool {
+    [root.join(".gitattributes"), root.join(".git").join("info").join("attributes")].iter().any(|path| {
+        fs::read_to_string(path).is_ok_and(|text| text.lines().any(|line| {
+            !line.trim_start().starts_with('#') && line.split_whitespace().skip(1).any(|x| x == "filter=lfs")
+        }))
+    })
+}
+
+// How many objects git lfs status lists under "Objects to be pushed to origin/main:", each of
+// which is on a line of its own starting with a tab until the next heading
+pub fn parse_lfs_pending(status: &str) -> usize {
+    let mut lines = status.lines().skip_while(|x| !x.starts_with("Objects to be pushed to"));
+    if lines.next().is_none() {
+        return 0;
+    }
+
+    lines.take_while(|x| x.is_empty() || x.starts_with('\t')).filter(|x| !x.trim().is_empty()).count()
+}
+
+pub async fn get_lfs_pending(runner: &dyn CommandRunner, timeout_ms: Option<u64>) -> Result<usize, String> {
+    let output_res = get_output(runner, "git", &["lfs", "status"], Some(timeout_ms.map_or(LFS_TIMEOUT_MS, |x| x.min(LFS_TIMEOUT_MS)))).await;
+    let output = output_res.map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or("git lfs status failed").trim().to_owned());
+    }
+
+    Ok(parse_lfs_pending(&String::from_utf8_lossy(&output.stdout)))
+}
+
+pub struct LfsSegment;
+
+impl Segment for LfsSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::GitLfs
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            // Nobody who doesn't use LFS should have to wait for it
+            if !find_repo_root(context.dir).is_some_and(uses_lfs) {
+                return SegmentResult::Absent(Reason::Nothing("nothing in the repository is stored in LFS"));
+            }
+
+            match get_lfs_pending(context.runner, context.timeout_ms).await {
+                Ok(0) => SegmentResult::Absent(Reason::Nothing("every LFS object has been pushed")),
+                Ok(_) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Warning, "⇡lfs")])),
+                Err(e) => SegmentResult::Absent(Reason::Failed(format!("git lfs: {}", e)))
+            }
+        }.boxed_local()
+    }
+}
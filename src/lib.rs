@@ -0,0 +1,55 @@
+#[cfg(feature = "aws")]
+pub mod aws;
+pub mod background;
+pub mod budget;
+pub mod builtin;
+pub mod cache;
+pub mod chevron;
+pub mod ci;
+#[cfg(feature = "kubernetes")]
+pub mod cluster;
+pub mod colour;
+pub mod command;
+pub mod condition;
+pub mod config;
+pub mod custom;
+pub mod danger;
+#[cfg(unix)]
+pub mod daemon;
+pub mod doctor;
+#[cfg(feature = "git")]
+pub mod forge;
+pub mod git;
+pub mod glyph;
+#[cfg(feature = "kubernetes")]
+pub mod helm;
+pub mod init;
+pub mod layout;
+#[cfg(feature = "git")]
+pub mod lfs;
+pub mod logging;
+pub mod message;
+pub mod network;
+pub mod osc;
+pub mod overrides;
+pub mod path;
+pub mod plugin;
+pub mod preexec;
+pub mod preview;
+pub mod project;
+pub mod remote;
+pub mod render;
+pub mod report;
+pub mod segment;
+pub mod session;
+pub mod slow;
+pub mod store;
+pub mod styled;
+#[cfg(feature = "svn")]
+pub mod svn;
+pub mod system;
+pub mod template;
+pub mod text;
+pub mod theme;
+pub mod title;
+pub mod vcs;
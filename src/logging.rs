@@ -0,0 +1,91 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::process::Output;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::process::Command;
+use tracing::Level;
+
+use crate::message::get_state_home;
+
+// Once the log is this big it's moved aside, so leaving logging on never takes more than twice this
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+// Output is cut short in the log as it's the start of it that says what went wrong
+const MAX_LOGGED_OUTPUT: usize = 200;
+
+fn get_log_path() -> Option<PathBuf> {
+    env::var_os("PROMPT_LOG_FILE").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| get_state_home().map(|d| d.join("prompt").join("prompt.log")))
+}
+
+// Nothing is ever logged to stdout or stderr as that's where the prompt goes, so without a file
+// there's no logging at all
+pub fn init_logging() {
+    let Some(level) = env::var("PROMPT_LOG").ok().and_then(|x| x.parse::<Level>().ok()) else { return };
+    let Some(path) = get_log_path() else { return };
+
+    if fs::metadata(&path).is_ok_and(|x| x.len() > MAX_LOG_SIZE) {
+        let _ = fs::rename(&path, path.with_extension("log.old"));
+    }
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let Ok(file) = OpenOptions::new().create(true).append(true).open(&path) else { return };
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_ansi(false)
+        .with_writer(Mutex::new(file))
+        .try_init();
+}
+
+fn truncate(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    let text = text.trim_end();
+
+    match text.char_indices().nth(MAX_LOGGED_OUTPUT) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text.to_owned()
+    }
+}
+
+pub fn log_output(command: &str, output: &io::Result<Output>, duration: Duration) {
+    match output {
+        Ok(output) => tracing::debug!(
+            command,
+            status = %output.status,
+            stdout = truncate(&output.stdout),
+            stderr = truncate(&output.stderr),
+            duration_ms = duration.as_secs_f64() * 1000.0,
+            "command finished"
+        ),
+        Err(e) => tracing::debug!(command, error = %e, duration_ms = duration.as_secs_f64() * 1000.0, "command failed")
+    }
+}
+
+// Just the program and its arguments, as the environment it's given could have anything in it
+fn describe(command: &Command) -> String {
+    let command = command.as_std();
+    std::iter::once(command.get_program()).chain(command.get_args()).map(|x| format!("{:?}", x)).collect::<Vec<_>>().join(" ")
+}
+
+// Starts the command straight away like Command::output, logging what it said once it's done. The
+// command's only written out when something's going to log it
+pub fn run_command(command: &mut Command) -> impl Future<Output = io::Result<Output>> {
+    let description = tracing::enabled!(Level::DEBUG).then(|| describe(command));
+    let start = Instant::now();
+    let output_future = command.output();
+
+    async move {
+        let output = output_future.await;
+        if let Some(description) = description {
+            log_output(&description, &output, start.elapsed());
+        }
+        output
+    }
+}
@@ -1,414 +1,1815 @@
 use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{stdout, IsTerminal, Write};
+use std::net::Ipv4Addr;
 use std::path::{Path, PathBuf};
-
-use async_process::Command;
-use clap::Parser;
-use colored::Colorize;
-use futures::TryFutureExt;
-use homedir::get_my_home;
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use colored::Color;
+
+#[cfg(feature = "aws")]
+use prompt::aws::AwsAccountSegment;
+use prompt::background::{detect_background, Background};
+use prompt::budget::{run_until, Slot};
+use prompt::builtin::{get_registry, BatterySegment, CiSegment, DurationSegment, IdleSegment, JobsSegment, PathSegment, PipestatusSegment, ProjectSegment, SudoSegment, TmuxSessionSegment};
+#[cfg(feature = "git")]
+use prompt::builtin::GitRemoteSegment;
+use prompt::cache::CachedRunner;
+use prompt::init::get_init_script;
+use prompt::chevron::{parse_position_glyph, parse_state_colour, Chevrons, Position, PositionGlyph, PromptStyle, State, StateColour};
+#[cfg(feature = "kubernetes")]
+use prompt::cluster::K8sReachableSegment;
+use prompt::colour::{get_accent, get_colour_depth, get_colour_name, parse_colour};
+use prompt::command::{CannedRunner, ProcessRunner};
+use prompt::condition::{get_marker_condition, Condition, Multiplexer, Surroundings};
+use prompt::config::{apply_env, apply_overlays, find_overlays, get_config_path, get_default_config, get_unavailable_segments, load_config, Config, DangerPattern};
+use prompt::custom::{get_env_segment, run_custom_segment};
+use prompt::danger::{find_danger, is_dangerous};
+#[cfg(unix)]
+use prompt::daemon::{render_in_daemon, run_daemon, stop_daemon};
+use prompt::doctor::{get_fsmonitor_report, get_segment_report, get_slowness_warnings, get_tool_version};
+#[cfg(feature = "git")]
+use prompt::forge::MergeRequestSegment;
+use prompt::git::{get_best_git_name, get_git_remote_url, get_git_repo_name, is_in_git_repository, Fsmonitor};
+use prompt::glyph::{is_ascii_locale, Glyphs};
+#[cfg(feature = "git")]
+use prompt::lfs::LfsSegment;
+use prompt::layout::{add_icon, add_label, dedupe_segments, fit_right, fit_to_width, get_line_width, get_text, recolour, to_powerline, truncate_segment, Dedupe, Segment};
+use prompt::logging::init_logging;
+use prompt::message::{parse_message, read_message_file, read_stdin_message, Message};
+use prompt::network::{parse_interface_marker, InterfaceMarker, NetworkSegment, DEFAULT_IP_PROBE};
+use prompt::osc::{get_cwd_sequence, get_hostname, PROMPT_END, PROMPT_START};
+use prompt::path::{get_current_working_directory, get_full_working_directory, get_home_directory, parse_path_alias, PathAlias, PathStyle, WorkingDirectory};
+use prompt::plugin::{find_repo_root, run_plugin, PluginContext};
+use prompt::preexec::{get_command_name, peek_last_prompt, peek_record, take_last_prompt, take_record, write_record};
+use prompt::preview::get_scenarios;
+use prompt::project::DEFAULT_MANIFESTS;
+use prompt::remote::{get_branch_url, parse_forge_host, parse_remote_label, ForgeHost, RemoteLabel};
+use prompt::render::{get_colour_enabled, ColourMode, Format, Renderer, Shell, Style};
+#[cfg(windows)]
+use prompt::render::set_up_console;
+use prompt::report::{get_report, get_timings_table, Detections, ReportStates};
+use prompt::system::{LoadSegment, MemorySegment};
+use prompt::template::{parse_template, Template};
+use prompt::text::{truncate_to_width, EllipsisPosition};
+use prompt::title::render_title;
+use prompt::vcs::{detect_vcs, Vcs};
+use prompt::theme::{parse_role_colour, Look, Role, RoleColour, Theme, ThemeName};
+use prompt::segment::{
+    get_conditions, get_icon, get_label, get_max_width, get_segment_colour, get_side, parse_segment_colour, parse_segment_condition, parse_segment_group, parse_segment_icon, parse_segment_label,
+    parse_segment_max_width, parse_segment_side, Context, IconStyle, Reason, Rendered, SegmentColour, SegmentGroupParser, SegmentCondition, SegmentIcon, SegmentKind, SegmentLabel,
+    SegmentMaxWidth, SegmentResult, SegmentSide, Side
+};
+use prompt::session::get_session;
+use prompt::slow::{get_hint, should_warn};
+use prompt::store::{clear_cache, get_cache_dir, Store};
+use prompt::styled::{Span, Stylize};
+#[cfg(feature = "svn")]
+use prompt::svn::{SvnBranchSegment, SvnStatusSegment};
+
+// What --instant exits with when the full prompt could have more in it, so the shell knows to draw it
+const INSTANT_PARTIAL: i32 = 2;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true, args_override_self = true)]
 struct Args {
-    #[arg(short, long, default_value_t = 0)]
-    exit_code: u8,
+    #[command(subcommand)]
+    command: Option<Subcommands>,
+
+    #[arg(short, long, default_value_t = 0, allow_negative_numbers = true)]
+    exit_code: i32,
 
-    #[arg(short, long)]
-    message: Option<String>,
+    #[arg(short, long, value_parser = parse_message)]
+    message: Vec<Message>,
 
     #[arg(long)]
     explain: bool,
 
+    #[arg(long)]
+    explain_missing: bool,
+
+    #[arg(long, value_enum, default_value_t = PathStyle::Full)]
+    path_style: PathStyle,
+
+    #[arg(long)]
+    path_keep: Option<usize>,
+
+    #[arg(long)]
+    physical: bool,
+
+    #[arg(long, value_name = "FILES", value_delimiter = ',', default_values_t = DEFAULT_MANIFESTS.map(String::from))]
+    project_manifests: Vec<String>,
+
+    #[arg(long, value_name = "FILES", value_delimiter = ',')]
+    aws_markers: Vec<String>,
+
+    #[arg(long, value_name = "DIRS", value_delimiter = ',')]
+    aws_dirs: Vec<String>,
+
+    #[arg(long = "path-alias", value_name = "PREFIX=REPLACEMENT[:COLOUR]", value_parser = parse_path_alias)]
+    path_aliases: Vec<PathAlias>,
+
     #[arg(long)]
     iterm2: bool,
+
+    #[arg(long)]
+    keymap: Option<String>,
+
+    #[arg(long, value_name = "KEYMAP=GLYPH[:COLOUR]", value_parser = parse_keymap_style)]
+    keymap_style: Vec<KeymapStyle>,
+
+    #[arg(long, requires = "keymap")]
+    redraw: bool,
+
+    #[arg(long)]
+    battery: bool,
+
+    #[arg(long, default_value_t = 20)]
+    battery_threshold: u8,
+
+    #[arg(long)]
+    load: bool,
+
+    #[arg(long, default_value_t = 1.0)]
+    load_threshold: f64,
+
+    #[arg(long)]
+    memory: bool,
+
+    #[arg(long, default_value_t = 10)]
+    memory_threshold: u8,
+
+    #[arg(long)]
+    local_ip: bool,
+
+    #[arg(long, default_value_t = DEFAULT_IP_PROBE)]
+    ip_probe: Ipv4Addr,
+
+    #[arg(long = "interface-marker", value_name = "NAME=LABEL", value_parser = parse_interface_marker)]
+    interface_markers: Vec<InterfaceMarker>,
+
+    #[arg(long)]
+    tmux_session: bool,
+
+    #[arg(long)]
+    ci: bool,
+
+    #[arg(long)]
+    sudo: bool,
+
+    #[arg(long)]
+    aws_account: bool,
+
+    #[arg(long)]
+    k8s_reachability: bool,
+
+    #[arg(long, value_name = "CLUSTERS", value_delimiter = ',')]
+    unprobed_clusters: Vec<String>,
+
+    #[arg(long)]
+    git_lfs: bool,
+
+    #[arg(long)]
+    merge_requests: bool,
+
+    #[arg(long, default_value_t = 60)]
+    merge_request_ttl: u64,
+
+    #[arg(long = "forge-host", value_name = "HOST=FORGE", value_parser = parse_forge_host)]
+    forge_hosts: Vec<ForgeHost>,
+
+    #[arg(long)]
+    git_remote: bool,
+
+    #[arg(long = "remote-label", value_name = "HOST=LABEL", value_parser = parse_remote_label)]
+    remote_labels: Vec<RemoteLabel>,
+
+    #[arg(long, value_enum, default_value_t = Fsmonitor::Auto)]
+    fsmonitor: Fsmonitor,
+
+    #[arg(long)]
+    columns: Option<usize>,
+
+    #[arg(long, value_enum)]
+    shell: Option<Shell>,
+
+    #[arg(long)]
+    ps1: bool,
+
+    #[arg(long, value_enum, default_value_t = Format::Ansi)]
+    format: Format,
+
+    #[arg(long = "color", value_enum)]
+    colour: Option<ColourMode>,
+
+    #[arg(long)]
+    ascii: bool,
+
+    #[arg(long, value_enum, default_value_t = Style::Plain)]
+    style: Style,
+
+    #[arg(long, value_enum, default_value_t = Dedupe::Off)]
+    dedupe: Dedupe,
+
+    #[arg(long, value_enum, default_value_t = IconStyle::None)]
+    icons: IconStyle,
+
+    #[arg(long = "icon", value_name = "SEGMENT=ICON", value_parser = parse_segment_icon)]
+    icon_overrides: Vec<SegmentIcon>,
+
+    #[arg(long, default_value_t = 1)]
+    icon_width: usize,
+
+    #[arg(long, value_enum, default_value_t = ThemeName::Default)]
+    theme: ThemeName,
+
+    #[arg(long, value_enum)]
+    background: Option<Background>,
+
+    #[arg(long)]
+    query_background: bool,
+
+    #[arg(long = "role-color", value_name = "ROLE=COLOUR", value_parser = parse_role_colour)]
+    role_colours: Vec<RoleColour>,
+
+    #[arg(long, value_enum, default_value_t = PromptStyle::Chevrons)]
+    prompt_style: PromptStyle,
+
+    #[arg(long)]
+    single_line: bool,
+
+    #[arg(long)]
+    no_blank_line: bool,
+
+    #[arg(long)]
+    no_trailing_space: bool,
+
+    #[arg(long, default_value = " ")]
+    trailing: String,
+
+    #[arg(long)]
+    no_slow_warning: bool,
+
+    #[arg(long)]
+    transient: bool,
+
+    #[arg(long, conflicts_with_all = ["transient", "redraw"])]
+    instant: bool,
+
+    #[arg(long, value_name = "TEMPLATE", num_args = 0..=1, default_missing_value = "{path}[ — {branch}]")]
+    title: Option<String>,
+
+    #[arg(long)]
+    osc7: bool,
+
+    #[arg(long)]
+    links: bool,
+
+    #[arg(long)]
+    inherit_env: bool,
+
+    #[arg(long)]
+    semantic_prompt: bool,
+
+    #[arg(long = "chevron", value_name = "POSITION=GLYPH", value_parser = parse_position_glyph)]
+    chevron_glyphs: Vec<PositionGlyph>,
+
+    #[arg(long = "chevron-color", value_name = "STATE=COLOUR", value_parser = parse_state_colour)]
+    chevron_colours: Vec<StateColour>,
+
+    #[arg(long = "segment-color", value_name = "SEGMENT=COLOUR", value_parser = parse_segment_colour)]
+    segment_colours: Vec<SegmentColour>,
+
+    #[arg(long)]
+    repo_accent: bool,
+
+    #[arg(long)]
+    repo_accent_path: bool,
+
+    #[arg(long = "repo-accent-color", value_name = "COLOUR", value_parser = parse_colour)]
+    repo_accent_colour: Option<Color>,
+
+    #[arg(long = "when", value_name = "SEGMENT=CONDITION", value_parser = parse_segment_condition)]
+    conditions: Vec<SegmentCondition>,
+
+    #[arg(long)]
+    duration_ms: Option<u64>,
+
+    #[arg(long, default_value_t = 2000)]
+    duration_threshold_ms: u64,
+
+    #[arg(long)]
+    always_show_duration: bool,
+
+    #[arg(long, default_value_t = 60)]
+    idle_threshold_mins: u64,
+
+    #[arg(long)]
+    jobs: Option<usize>,
+
+    #[arg(long, value_delimiter = ' ', allow_negative_numbers = true)]
+    pipestatus: Vec<i32>,
+
+    #[arg(long)]
+    last_command: Option<String>,
+
+    #[arg(long, default_value_t = 16)]
+    last_command_length: usize,
+
+    #[arg(long = "side", value_name = "SEGMENT=SIDE", value_parser = parse_segment_side)]
+    sides: Vec<SegmentSide>,
+
+    #[arg(long)]
+    right: bool,
+
+    #[arg(long, conflicts_with = "right")]
+    combined: bool,
+
+    #[arg(long, value_parser = parse_template)]
+    template: Option<Template>,
+
+    #[arg(long, default_value = " ")]
+    separator: String,
+
+    #[arg(long = "label", value_name = "SEGMENT=LABEL", value_parser = parse_segment_label)]
+    labels: Vec<SegmentLabel>,
+
+    #[arg(long = "max-width", value_name = "SEGMENT=WIDTH[:start|middle|end]", value_parser = parse_segment_max_width)]
+    max_widths: Vec<SegmentMaxWidth>,
+
+    #[arg(long, value_name = "CHAR", num_args = 0..=1, default_missing_value = " ", conflicts_with_all = ["right", "single_line"])]
+    fill: Option<char>,
+
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with_all = ["right", "combined"])]
+    only: Vec<SegmentKind>,
+
+    #[arg(long, value_enum, value_delimiter = ',')]
+    order: Vec<SegmentKind>,
+
+    #[arg(long, value_name = "SEGMENTS", value_delimiter = ',', value_parser = SegmentGroupParser)]
+    disable: Vec<String>,
+
+    #[arg(long, value_name = "SEGMENTS", value_delimiter = ',', value_parser = SegmentGroupParser)]
+    enable: Vec<String>,
+
+    #[arg(long)]
+    timeout_ms: Option<u64>,
+
+    #[arg(long)]
+    budget_ms: Option<u64>,
+
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[arg(long)]
+    cwd: Option<PathBuf>,
+
+    #[arg(long)]
+    timings: bool,
 }
 
-fn parse_output(output_res: Result<async_process::Output, std::io::Error>) -> Option<String> {
-    if let Ok(output) = output_res {
-        if output.status.success() {
-            String::from_utf8(output.stdout).map_or(None, |mut x| {
-                x.retain(|c| !c.is_whitespace());
-                if x.len() == 0 {
-                    None
-                } else {
-                    Some(x)
-                }
-            })
-        } else {
-            None
-        }
+#[derive(Subcommand, Debug)]
+enum Subcommands {
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+
+        #[arg(long = "async")]
+        background: bool,
+    },
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    Doctor {
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    Daemon {
+        #[arg(long)]
+        stop: bool,
+    },
+    Preview {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        flags: Vec<OsString>,
+    },
+    Explain {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        flags: Vec<OsString>,
+    },
+    PreExec {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    Check {
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    Show {
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    Default
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    Clear
+}
+
+#[derive(Clone, Debug)]
+struct KeymapStyle {
+    keymap: String,
+    glyph: String,
+    colour: Option<Color>,
+}
+
+fn parse_keymap_style(s: &str) -> Result<KeymapStyle, String> {
+    let (keymap, style) = s.split_once('=').ok_or("expected KEYMAP=GLYPH[:COLOUR]")?;
+
+    let (glyph, colour) = match style.split_once(':') {
+        Some((glyph, colour)) => (glyph, Some(parse_colour(colour)?)),
+        None => (style, None)
+    };
+
+    Ok(KeymapStyle { keymap: keymap.to_owned(), glyph: glyph.to_owned(), colour })
+}
+
+fn get_keymap_style(keymap: &str, styles: &[KeymapStyle]) -> Option<KeymapStyle> {
+    styles.iter().rev().find(|s| s.keymap == keymap).cloned().or_else(|| match keymap {
+        // Normal mode and friends flip the last chevron round, insert mode looks like any other prompt
+        "vicmd" | "visual" | "viopp" => Some(KeymapStyle { keymap: keymap.to_owned(), glyph: "❮".to_owned(), colour: None }),
+        _ => None
+    })
+}
+
+#[cfg(unix)]
+fn get_terminal_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        Some(size.ws_col as usize)
     } else {
         None
     }
 }
 
-fn get_current_working_directory() -> PathBuf {
-    let current_dir = env::current_dir().expect("No current working directory?");
+#[cfg(not(unix))]
+fn get_terminal_width() -> Option<usize> {
+    None
+}
+
+// Segments that didn't get to say why they're missing are empty for reasons that can be told from
+// where the prompt is
+fn get_missing_reason(kind: SegmentKind, deleted: bool, in_repository: bool, in_working_copy: bool) -> Reason {
+    match kind.family() {
+        _ if deleted && kind.needs_directory() => Reason::Deleted,
+        Some("git") if !in_repository => Reason::NotInRepository,
+        Some("svn") if !in_working_copy => Reason::Nothing("not in an svn working copy"),
+        _ => Reason::Nothing("nothing to show")
+    }
+}
+
+// Everything that's only drawn when a flag asks for it, which is also what the legend goes by
+fn get_opt_in_segments(args: &Args, in_tmux: bool) -> Vec<Box<dyn prompt::segment::Segment>> {
+    let mut segments: Vec<Box<dyn prompt::segment::Segment>> = vec![];
+
+    // What the machine's up to has nothing to do with where it is
+    if args.load {
+        segments.push(Box::new(LoadSegment { threshold: args.load_threshold }));
+    }
+    if args.memory {
+        segments.push(Box::new(MemorySegment { threshold: args.memory_threshold }));
+    }
+    if args.local_ip || !args.interface_markers.is_empty() {
+        segments.push(Box::new(NetworkSegment { markers: args.interface_markers.clone(), local_ip: args.local_ip, probe: args.ip_probe }));
+    }
+    if args.tmux_session {
+        segments.push(Box::new(TmuxSessionSegment { in_tmux }));
+    }
+    if args.sudo {
+        segments.push(Box::new(SudoSegment));
+    }
+    if args.battery {
+        segments.push(Box::new(BatterySegment { threshold: args.battery_threshold }));
+    }
+    if args.ci {
+        segments.push(Box::new(CiSegment));
+    }
 
-    if let Ok(Some(home_dir)) = get_my_home() {
-        if current_dir.starts_with(&home_dir) {
-            return Path::new("~").join(current_dir.strip_prefix(&home_dir).unwrap());
+    // Asking the forge takes a while, as does running git lfs
+    #[cfg(feature = "git")]
+    {
+        if args.git_remote {
+            segments.push(Box::new(GitRemoteSegment { labels: args.remote_labels.clone(), hosts: args.forge_hosts.clone() }));
         }
+        if args.git_lfs {
+            segments.push(Box::new(LfsSegment));
+        }
+        if args.merge_requests {
+            segments.push(Box::new(MergeRequestSegment { hosts: args.forge_hosts.clone(), ttl: args.merge_request_ttl }));
+        }
+    }
+    // Nothing's ever probed unless it's asked for
+    #[cfg(feature = "kubernetes")]
+    if args.k8s_reachability {
+        segments.push(Box::new(K8sReachableSegment { unprobed: args.unprobed_clusters.clone() }));
+    }
+    #[cfg(feature = "aws")]
+    if args.aws_account {
+        segments.push(Box::new(AwsAccountSegment));
+    }
 
-        return current_dir;
-    }
-
-    current_dir
-}
-
-async fn is_in_git_repository() -> bool {
-    let output_res = Command::new("git")
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()
-        .await;
-
-    parse_output(output_res).map(|x| x == "true").unwrap_or(false)
-}
-
-enum GitState {
-    RebaseInteractive,
-    RebaseMerge,
-    Rebase,
-    ApplyMailbox,
-    ApplyMailboxOrRebase,
-    Merge,
-    Revert,
-    CherryPick,
-    Bisect
-}
-
-fn get_git_state() -> Option<GitState> {
-    let git_dir = Path::new("./.git");
-
-    // Taken from libgit https://github.com/libgit2/libgit2/blob/main/src/libgit2/repository.c#L3697 - git_repository_state function
-    if git_dir.join("rebase-merge").join("interactive").is_file() {
-		Some(GitState::RebaseInteractive)
-    } else if git_dir.join("rebase-merge").is_dir() {
-		Some(GitState::RebaseMerge)
-    } else if git_dir.join("rebase-apply").join("rebasing").is_file() {
-		Some(GitState::Rebase)
-    } else if git_dir.join("rebase-apply").join("applying").is_file() {
-		Some(GitState::ApplyMailbox)
-    } else if git_dir.join("rebase-apply").is_dir() {
-		Some(GitState::ApplyMailboxOrRebase)
-    } else if git_dir.join("MERGE_HEAD").is_file() {
-		Some(GitState::Merge)
-    } else if git_dir.join("REVERT_HEAD").is_file() {
-        Some(GitState::Revert)
-	} else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
-        Some(GitState::CherryPick)
-	} else if git_dir.join("BISECT_LOG").is_file() {
-        Some(GitState::Bisect)
-    } else {
-        None
+    segments
+}
+
+// The line's in the registry's order. Everything the chevrons' states go into is drawn the once as
+// the status, wherever the first of it is
+fn get_line<'a>(results: impl IntoIterator<Item = (SegmentKind, Option<&'a Rendered>)>, status: &[Span]) -> Vec<(SegmentKind, Segment)> {
+    let mut line: Vec<(SegmentKind, Segment)> = vec![];
+    for (kind, result) in results {
+        let spans = match result {
+            Some(Rendered::Spans(spans) | Rendered::Aliased(spans, _)) => spans.clone(),
+            _ if line.iter().any(|(k, _)| *k == kind) => continue,
+            Some(Rendered::State(..)) => status.to_vec(),
+            None => vec![]
+        };
+        line.push((kind, spans));
     }
+
+    line
 }
 
-async fn get_best_git_name() -> Option<String> {
-    let branch_future = get_git_branch();
-    let commit_future = get_git_commit();
-    let tag_future = get_git_tag();
+fn get_last_command(args: &Args) -> Option<String> {
+    let name = args.last_command.as_deref().and_then(get_command_name).filter(|_| args.last_command_length > 0)?;
+    Some(truncate_to_width(&name, args.last_command_length, EllipsisPosition::End))
+}
 
-    let (branch, commit, tag) = futures::join!(branch_future, commit_future, tag_future);
+// Anything that looks like production is made to stand out over whatever colour the segment has,
+// going by the whole value before any of it's cut off or given a name of its own
+fn colour_segment(args: &Args, kind: SegmentKind, segment: Segment, value: Option<&str>, danger_patterns: &[DangerPattern]) -> Segment {
+    let segment = match get_segment_colour(kind, &args.segment_colours) {
+        Some(colour) => recolour(segment, colour),
+        None => segment
+    };
 
-    if branch.is_some() || commit.is_some() || tag.is_some() {
-        Some(branch.unwrap_or(commit.unwrap_or("".to_owned())) + &tag.as_ref().map(|t| " [".to_string() + t + "]").unwrap_or("".to_string()))
-    } else {
-        None
+    let value = value.map_or_else(|| get_text(&segment), |x| x.to_owned());
+    match find_danger(danger_patterns, &value).filter(|_| kind.is_sensitive()) {
+        Some(style) => style.apply(segment),
+        None => segment
     }
 }
 
-async fn get_git_tag() -> Option<String> {
-    let output_res = Command::new("git")
-        .arg("tag")
-        .arg("--points-at")
-        .arg("HEAD")
-        .output()
-        .await;
+// Before anything's added to them, and before the line is fitted to the terminal
+fn fit_segment(args: &Args, kind: SegmentKind, segment: Segment, value: Option<&str>, danger_patterns: &[DangerPattern], glyphs: &Glyphs) -> Segment {
+    let segment = colour_segment(args, kind, segment, value, danger_patterns);
 
-    parse_output(output_res)
+    match get_max_width(kind, &args.max_widths) {
+        Some(max) => truncate_segment(&segment, max.width, max.position, glyphs),
+        None => segment
+    }
 }
 
-async fn get_git_branch() -> Option<String> {
-    let output_res = Command::new("git")
-        .arg("branch")
-        .arg("--show-current")
-        .output()
-        .await;
+fn decorate_segment(args: &Args, kind: SegmentKind, segment: Segment, icon_style: IconStyle) -> Segment {
+    let segment = match get_icon(kind, icon_style, &args.icon_overrides) {
+        Some(icon) => add_icon(segment, &icon),
+        None => segment
+    };
 
-    parse_output(output_res)
+    match get_label(kind, &args.labels) {
+        Some(label) => add_label(segment, label),
+        None => segment
+    }
 }
 
-async fn get_git_commit() -> Option<String> {
-    let output_res = Command::new("git")
-        .arg("rev-parse")
-        .arg("--short")
-        .arg("HEAD")
-        .output()
-        .await;
+// Made up prompts for each of the states the chevrons can be in and a few of the segments, drawn
+// with the theme and config as they are right now. Nothing is run as git and kubectl's answers are
+// all written down already
+async fn draw_previews(args: &Args, config: &Config, renderer: &Renderer, theme: &Theme, chevrons: &Chevrons<'_>, icon_style: IconStyle) {
+    let home = get_home_directory().unwrap_or_else(|| PathBuf::from("/home/you"));
+    let disabled = get_disabled_segments(&args.disable, &args.enable);
+    let is_shown = |kind: SegmentKind| kind.is_available() && !disabled.contains(&kind) && get_side(kind, &args.sides) == Side::Left;
+
+    let separator = get_separator(args, renderer);
+    let line_separator = match args.style {
+        Style::Plain => args.separator.as_str(),
+        Style::Powerline => " "
+    };
+    let columns = args.columns.or_else(get_terminal_width);
+    let store = Store::disabled();
+
+    for scenario in get_scenarios() {
+        let dir = home.join(scenario.path);
+        let current_dir = WorkingDirectory { path: Some(Path::new("~").join(scenario.path)), full: Some(dir.clone()), deleted: false };
+        let runner = CannedRunner { outputs: scenario.outputs };
+        let context = Context { dir: &dir, current_dir: &current_dir, config, theme, exit_code: scenario.exit_code, timeout_ms: None, runner: &runner, store: &store };
+
+        // The AWS segments read the environment so they're made up here instead
+        let path_segment = PathSegment { style: args.path_style, keep: args.path_keep, aliases: args.path_aliases.clone() };
+        let registry: Vec<_> = get_registry(path_segment, vec![], args.fsmonitor).into_iter().filter(|x| match x.kind().family() {
+            Some("git") => scenario.repository,
+            Some("aws") => false,
+            _ => true
+        }).collect();
+        let rendered = futures::future::join_all(registry.iter().map(|x| async { (x.kind(), x.render(&context).await.ok()) })).await;
+
+        let get_state = |position| rendered.iter().find_map(|(_, x)| match x {
+            Some(Rendered::State(p, state)) if *p == position => Some(*state),
+            _ => None
+        });
+        let has_conflicts = rendered.iter().any(|(kind, x)| *kind == SegmentKind::GitUnmerged && x.is_some());
+        let exit_state = if scenario.exit_code == 0 { State::ExitOk } else { State::ExitFail };
+        let changes_state = get_state(Position::Changes).map(|x| if has_conflicts { State::Conflict } else { x });
+        let upstream_state = get_state(Position::Upstream);
+
+        let status = match args.prompt_style {
+            PromptStyle::Chevrons => vec![],
+            PromptStyle::Single => chevrons.draw_status(&[changes_state, upstream_state])
+        };
+        let mut segments = get_line(rendered.iter().map(|(kind, x)| (*kind, x.as_ref())), &status);
+        if let Some((profile, region)) = scenario.aws {
+            segments.push((SegmentKind::AwsProfile, vec![theme.paint(Role::Aws, profile)]));
+            segments.push((SegmentKind::AwsRegion, vec![theme.paint(Role::Aws, region)]));
+        }
 
-    parse_output(output_res)
-}
+        let mut line: Vec<_> = segments.into_iter()
+            .filter(|(kind, x)| !x.is_empty() && is_shown(*kind))
+            .map(|(kind, x)| (kind, fit_segment(args, kind, x, None, &config.danger_patterns, &renderer.glyphs)))
+            .collect();
+        line.sort_by_key(|(kind, _)| args.order.iter().position(|x| x == kind).unwrap_or(args.order.len()));
+
+        let line = dedupe_segments(line, args.dedupe, |_, _| true).into_iter().map(|(kind, x)| {
+            let x = decorate_segment(args, kind, x, icon_style);
+            match args.style {
+                Style::Plain => (kind, x),
+                Style::Powerline => (kind, to_powerline(x))
+            }
+        }).collect();
 
-enum UncommittedChanges {
-    None,
-    FilesChanged,
-    FilesNotAdded
+        let line = match scenario.columns.into_iter().chain(columns).min() {
+            Some(columns) => fit_to_width(line, columns.saturating_sub(usize::from(args.style == Style::Powerline)), line_separator, &renderer.glyphs),
+            None => line.into_iter().map(|(kind, x)| (Some(kind), x)).collect()
+        };
+        let (_, line): (Vec<_>, Vec<_>) = line.into_iter().unzip();
+
+        let top_line = match args.style {
+            Style::Plain => line.iter().map(|x| renderer.segment(x)).collect::<Vec<_>>().join(&separator),
+            Style::Powerline => renderer.powerline(&line, &vec![None; line.len()])
+        };
+
+        let final_state = match args.prompt_style {
+            PromptStyle::Chevrons => upstream_state,
+            PromptStyle::Single => Some(exit_state)
+        };
+        let leading_chevrons = match args.prompt_style {
+            PromptStyle::Chevrons => vec![chevrons.draw(Position::Exit, Some(exit_state)), chevrons.draw(Position::Changes, changes_state)],
+            PromptStyle::Single => vec![]
+        };
+        let final_chevron = chevrons.draw(get_final_position(args.prompt_style), final_state);
+
+        println!("{}", renderer.span(&format!("# {}", scenario.name).dimmed()));
+        println!("{}", top_line);
+        println!("{}{}\n", leading_chevrons.iter().map(|x| renderer.span(x)).collect::<String>(), renderer.span(&final_chevron));
+    }
 }
 
-async fn get_uncommitted_changes() -> UncommittedChanges {
-    let output1_future = Command::new("git")
-        .arg("diff")
-        .arg("--quiet")
-        .output();
+// What each chevron and segment means, worked out from the same tables and settings the prompt is
+// drawn with so it's always what the prompt actually looks like
+fn get_legend(args: &Args, renderer: &Renderer, chevrons: &Chevrons) -> String {
+    let mut legend = renderer.span(&"Chevrons".bold()) + "\n";
 
-    let output1_timed_future = tokio::time::timeout(std::time::Duration::from_millis(500), output1_future).unwrap_or_else(|e| Result::Err(e.into()));
+    let positions = match args.prompt_style {
+        PromptStyle::Chevrons => vec![Position::Exit, Position::Changes, Position::Upstream],
+        PromptStyle::Single => vec![Position::Exit]
+    };
+
+    for position in positions {
+        legend += &format!("  {}\n", position.description());
 
-    let output2_future = Command::new("git")
-        .arg("diff")
-        .arg("--cached")
-        .arg("--quiet")
-        .output();
+        let states = position.states().iter().map(|x| (Some(*x), x.description()));
+        let states = states.chain(Some((None, "outside a git repository")).filter(|_| position != Position::Exit));
 
-    if let Ok((output1, output2)) = futures::try_join!(output1_timed_future, output2_future) {
-        if output1.status.success() && output2.status.success() {
-            let output3 = Command::new("git")
-                .arg("ls-files")
-                .arg("--other")
-                .arg("--exclude-standard")
-                .arg("--deleted")
-                .output()
-                .await;
+        for (state, description) in states {
+            let colour = chevrons.get_look(state).colour.map_or("no colour".into(), get_colour_name);
+            let chevron = renderer.span(&chevrons.draw(position, state));
 
-            if output3.map(|x| x.stdout.len() == 0).unwrap_or(false) {
-                return UncommittedChanges::None;
-            } else {
-                return UncommittedChanges::FilesNotAdded;
+            match chevrons.colour {
+                true => legend += &format!("    {} {} ({})\n", chevron, description, colour),
+                false => legend += &format!("    {} {}\n", chevron, description)
             }
-        } else {
-            return UncommittedChanges::FilesChanged;
         }
+    }
+
+    let disabled = get_disabled_segments(&args.disable, &args.enable);
+    let opt_ins: Vec<_> = get_opt_in_segments(args, false).iter().map(|x| x.kind()).collect();
+    let is_enabled = |kind: SegmentKind| kind.is_available() && !disabled.contains(&kind) && match kind {
+        SegmentKind::GitStatus => args.prompt_style == PromptStyle::Single,
+        _ => !kind.is_opt_in() || opt_ins.contains(&kind)
+    } && match get_side(kind, &args.sides) {
+        Side::Left => args.template.as_ref().is_none_or(|t| t.contains(kind)),
+        Side::Right => true,
+        Side::Hidden => false
+    };
+
+    let segments: Vec<_> = SegmentKind::value_variants().iter().copied().filter(|x| is_enabled(*x)).collect();
+    let names: Vec<_> = segments.iter().map(|x| x.to_possible_value().map_or(String::new(), |v| v.get_name().to_owned())).collect();
+    let width = names.iter().map(|x| x.len()).max().unwrap_or(0);
+
+    legend += "\n";
+    legend += &(renderer.span(&"Segments".bold()) + "\n");
+    for (kind, name) in segments.iter().zip(&names) {
+        let mut when = match get_conditions(*kind, &args.conditions)[..] {
+            [] => String::new(),
+            [condition] => format!(", shown only when {}", condition),
+            ref conditions => format!(", shown only when {}", Condition::All(conditions.iter().copied().cloned().collect()))
+        };
+        if let Some(markers) = get_marker_condition(&args.aws_markers, &args.aws_dirs).filter(|_| matches!(kind, SegmentKind::AwsProfile | SegmentKind::AwsAccount | SegmentKind::AwsRegion)) {
+            when += &format!(", shown only when {} or the profile matches a danger pattern", markers);
+        }
+        legend += &format!("  {:width$}  {}, hidden when {}{}\n", name, kind.description(), kind.hidden_when(), when, width = width);
+    }
+
+    legend
+}
+
+// Anything but spaces is dimmed so it doesn't get mistaken for part of a segment
+fn get_separator(args: &Args, renderer: &Renderer) -> String {
+    match args.separator.trim() {
+        "" => renderer.span(&args.separator.normal()),
+        _ => renderer.span(&args.separator.dimmed())
+    }
+}
+
+// Anything other than spaces keeps a space either side so it doesn't run into the segments
+fn get_fill(fill: char, width: usize) -> String {
+    if fill == ' ' || width < 3 {
+        " ".repeat(width)
     } else {
-        return UncommittedChanges::FilesChanged;
+        format!(" {} ", fill.to_string().repeat(width - 2))
     }
 }
 
-enum UnpushedChanges {
-    None,
-    UnpushedChanges,
-    UnpulledChanges,
-    NoUpstreamBranch
+// Emacs' shell mode and TRAMP can't cope with escape codes or anything fancy
+fn is_dumb_terminal() -> bool {
+    match env::var("TERM") {
+        Ok(term) => term == "dumb",
+        Err(_) => env::var_os("INSIDE_EMACS").is_some()
+    }
 }
 
-async fn get_unpushed_changes() -> UnpushedChanges {
-    let output1 = Command::new("git")
-        .arg("log")
-        .arg("@{u}..")
-        .output()
-        .await;
+fn get_dumb_prompt(path: Option<&str>, branch: Option<&str>) -> String {
+    let mut prompt = String::new();
 
-    if output1.map(|x| x.stdout.len() == 0).unwrap_or(false) {
-        let output2_future = Command::new("git")
-            .arg("rev-parse")
-            .arg("HEAD")
-            .output();
+    if let Some(path) = path {
+        prompt += path;
+        prompt += " ";
+    }
+    if let Some(branch) = branch {
+        prompt += &format!("({}) ", branch);
+    }
 
-        let output3_future = Command::new("git")
-            .arg("rev-parse")
-            .arg("@{u}")
-            .output();
+    prompt + "> "
+}
 
-        let (output2, output3) = futures::join!(output2_future, output3_future);
+fn print_prompt(shell: Option<Shell>, prompt: &str) {
+    match shell {
+        // These use the output as it is, where bash and zsh take it from a command substitution that
+        // drops the newline
+        Some(Shell::Fish | Shell::PowerShell | Shell::Nu | Shell::Elvish) => print!("{}", prompt),
+        _ => println!("{}", prompt)
+    }
+}
 
-        let head = parse_output(output2);
+fn get_final_chevron(keymap_style: Option<&KeymapStyle>, position: Position, state: Option<State>, chevrons: &Chevrons) -> Span {
+    match keymap_style {
+        Some(style) => {
+            let look = chevrons.get_look(state);
+            chevrons.add_marker(Look { colour: style.colour.or(look.colour), ..look }.paint(&style.glyph), state)
+        },
+        None => chevrons.draw(position, state)
+    }
+}
 
-        let u = parse_output(output3);
+fn get_state_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(env::temp_dir).join("prompt")
+}
 
-        if u.is_none() {
-            return UnpushedChanges::NoUpstreamBranch;
-        } else if head == u {
-            return UnpushedChanges::None;
-        } else {
-            return UnpushedChanges::UnpulledChanges;
+// Where the shell's pre-exec hook leaves when the command started, one for each terminal
+fn get_record_path() -> PathBuf {
+    get_state_dir().join(get_session().key + ".state")
+}
+
+// When the last whole prompt was drawn on each terminal
+fn get_last_prompt_path() -> PathBuf {
+    get_state_dir().join(get_session().key + ".drawn")
+}
+
+// Everything but the final chevron is remembered so a keymap change can be redrawn without
+// running git or kubectl again
+fn save_redraw_state(prefix: &str, state: Option<State>) {
+    let dir = get_state_dir();
+    let key = state.and_then(|s| s.to_possible_value()).map_or(String::new(), |v| v.get_name().to_owned());
+
+    if fs::create_dir_all(&dir).is_ok() {
+        let _ = fs::write(dir.join(get_session().key + ".redraw"), format!("{}\n{}", key, prefix));
+    }
+}
+
+fn load_redraw_state() -> Option<(String, Option<State>)> {
+    let contents = fs::read_to_string(get_state_dir().join(get_session().key + ".redraw")).ok()?;
+    let (key, prefix) = contents.split_once('\n')?;
+
+    Some((prefix.to_owned(), State::from_str(key, false).ok()))
+}
+
+// With a single chevron the last one is the exit code rather than the upstream
+fn get_final_position(prompt_style: PromptStyle) -> Position {
+    match prompt_style {
+        PromptStyle::Chevrons => Position::Upstream,
+        PromptStyle::Single => Position::Exit
+    }
+}
+
+// The config file is turned into flags that go before the ones given so the ones given win
+// --message - is swapped for whatever's piped in, and the message file goes after everything else
+fn get_messages(messages: &[Message]) -> Vec<Message> {
+    let mut stdin = None;
+    let mut messages: Vec<_> = messages.iter().filter_map(|x| match x.text.as_str() {
+        "-" if x.colour.is_none() && !x.dimmed => stdin.get_or_insert_with(read_stdin_message).clone(),
+        _ => Some(x.clone())
+    }).collect();
+
+    messages.extend(read_message_file());
+    messages.retain(|x| !x.text.is_empty());
+    messages
+}
+
+// --enable beats --disable, so something turned off in the config can be turned back on. A typo
+// here shouldn't cost anyone their prompt so it's only complained about
+fn get_disabled_segments(disable: &[String], enable: &[String]) -> Vec<SegmentKind> {
+    let resolve = |names: &[String], flag: &str| -> Vec<SegmentKind> {
+        names.iter().flat_map(|name| parse_segment_group(name).unwrap_or_else(|e| {
+            eprintln!("prompt: {} for --{}", e, flag);
+            vec![]
+        })).collect()
+    };
+
+    let enabled = resolve(enable, "enable");
+    resolve(disable, "disable").into_iter().filter(|x| !enabled.contains(x)).collect()
+}
+
+// Custom segments and plugins don't have flags so the config's handed back for them
+fn get_args(cli: Vec<OsString>) -> (Args, Config) {
+    let mut args = Args::parse_from(&cli);
+
+    // These are drawn with the config just like a prompt, along with any flags given after them
+    if let Some(Subcommands::Preview { flags } | Subcommands::Explain { flags }) = &mut args.command {
+        let flags = std::mem::take(flags);
+        let (drawn, config) = get_args(cli.iter().take(1).cloned().chain(flags).collect());
+        return (Args { command: args.command, ..drawn }, config);
+    }
+
+    if args.command.is_some() {
+        return (args, Config::default());
+    }
+
+    // A broken config file mustn't break the prompt, `prompt config check` says what's wrong with it
+    let config = match get_config_path(args.config.as_deref()).map(|x| load_config(&x)) {
+        Some(Ok(config)) => config.unwrap_or_default(),
+        Some(Err(_)) => return (args, Config::default()),
+        None => Config::default()
+    };
+    let config = get_full_working_directory(args.physical, args.cwd.as_deref()).and_then(|x| apply_overlays(config.clone(), &x).ok()).unwrap_or(config);
+    let config = apply_env(config.clone()).unwrap_or(config);
+
+    let config_args = config.to_args().into_iter().map(OsString::from);
+    match Args::try_parse_from(cli.iter().take(1).cloned().chain(config_args).chain(cli.iter().skip(1).cloned())) {
+        Ok(args) => (args, config),
+        Err(_) => (args, Config::default())
+    }
+}
+
+fn check_config(config: &Config) -> Result<(), String> {
+    let args = std::iter::once("prompt".to_owned()).chain(config.to_args());
+    Args::try_parse_from(args).map(|_| ()).map_err(|e| {
+        let message = e.to_string();
+        message.lines().next().unwrap_or_default().trim_start_matches("error: ").to_owned()
+    })
+}
+
+// Only the prompt not working at all counts as failing, anything else is just reported
+fn run_doctor(config: Option<PathBuf>) -> Result<(), String> {
+    println!("Tools");
+    println!("  git      {}", get_tool_version("git", &["--version"]));
+    if cfg!(feature = "kubernetes") {
+        println!("  kubectl  {}", get_tool_version("kubectl", &["version", "--client"]));
+    }
+
+    println!("\nConfig");
+    if let Err(e) = check_config_files(config.as_deref(), |x| println!("  {}", x)) {
+        println!("  {}", e);
+    }
+
+    println!("\nSegments");
+    for line in get_segment_report(config.as_deref())? {
+        println!("  {}", line);
+    }
+
+    let dir = get_full_working_directory(true, None);
+    if let Some(report) = dir.as_deref().and_then(get_fsmonitor_report) {
+        println!("\nGit");
+        println!("  {}", report);
+    }
+
+    let warnings = get_slowness_warnings(dir.as_deref());
+    if !warnings.is_empty() {
+        println!("\nPossible slowness");
+        for warning in warnings {
+            println!("  {}", warning);
         }
-    } else {
-        return UnpushedChanges::UnpushedChanges;
     }
+
+    Ok(())
 }
 
-async fn get_git_errors() -> bool {
-    let output_res = Command::new("git")
-        .arg("diff")
-        .arg("--check")
-        .output()
-        .await;
+fn print_completions(shell: Shell) -> Result<(), String> {
+    let generator = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+        Shell::PowerShell => clap_complete::Shell::PowerShell,
+        Shell::Nu | Shell::Elvish => return Err(format!("there aren't any completions for {} yet", shell.to_possible_value().map_or(String::new(), |v| v.get_name().to_owned())))
+    };
 
-    return output_res.map_or(true, |x| !x.status.success())
+    clap_complete::generate(generator, &mut Args::command(), "prompt", &mut std::io::stdout());
+    Ok(())
 }
 
-async fn get_git_unmerged() -> usize {
-    let output_res = Command::new("git")
-        .arg("status")
-        .arg("--porcelain")
-        .output()
-        .await;
+// The global file, then each overlay over it, then the PROMPT_* variables over all of them
+fn check_config_files(config: Option<&Path>, mut report: impl FnMut(String)) -> Result<(), String> {
+    let path = get_config_path(config).ok_or("couldn't work out where the config file is")?;
+    let config = match load_config(&path)? {
+        Some(config) => {
+            check_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+            report(format!("{}: ok", path.display()));
+            for segment in get_unavailable_segments(&config) {
+                let name = segment.to_possible_value().map_or(String::new(), |v| v.get_name().to_owned());
+                report(format!("{}: {} needs the {} feature, which isn't in this build, so its settings are ignored", path.display(), name, segment.family().unwrap_or_default()));
+            }
+            config
+        },
+        None => {
+            report(format!("{}: not found, using the defaults", path.display()));
+            Config::default()
+        }
+    };
+
+    // Each overlay is checked along with everything beneath it, as that's how they're used
+    if let Some(dir) = get_full_working_directory(false, None) {
+        for overlay in find_overlays(&dir) {
+            let merged = apply_overlays(config.clone(), overlay.parent().unwrap_or(&dir))?;
+            check_config(&merged).map_err(|e| format!("{}: {}", overlay.display(), e))?;
+            report(format!("{}: ok", overlay.display()));
+        }
+    }
+
+    let config = get_full_working_directory(false, None).and_then(|x| apply_overlays(config.clone(), &x).ok()).unwrap_or(config);
+    let config = apply_env(config).map_err(|e| format!("PROMPT_* variables: {}", e))?;
+    check_config(&config).map_err(|e| format!("PROMPT_* variables: {}", e))?;
 
-    return parse_output(output_res).map_or(0, |x| x.split("\n").filter(|y| y.starts_with("UU")).count());
+    Ok(())
 }
 
-async fn get_k8s_context() -> Option<String> {
-    let output_res = Command::new("kubectl")
-        .arg("config")
-        .arg("current-context")
-        .output()
-        .await;
+fn run_config_command(command: ConfigCommand) -> Result<(), String> {
+    match command {
+        ConfigCommand::Check { config } => check_config_files(config.as_deref(), |x| println!("{}", x))?,
+        ConfigCommand::Show { config } => {
+            let path = get_config_path(config.as_deref());
+            let config = path.and_then(|x| load_config(&x).ok().flatten()).unwrap_or_default();
+            let config = get_full_working_directory(false, None).and_then(|x| apply_overlays(config.clone(), &x).ok()).unwrap_or(config);
+            let config = apply_env(config.clone()).unwrap_or(config);
+            print!("{}", toml::to_string(&config).map_err(|e| e.to_string())?);
+        },
+        ConfigCommand::Default => print!("{}", get_default_config()?)
+    }
 
-    parse_output(output_res)
+    Ok(())
 }
 
-async fn get_k8s_namespace() -> Option<String> {
-    let output_res = Command::new("kubectl")
-        .arg("config")
-        .arg("view")
-        .arg("--minify")
-        .arg("--output")
-        .arg("jsonpath={..namespace}")
-        .output()
-        .await;
+fn run_cache_command(command: CacheCommand) -> Result<(), String> {
+    match command {
+        CacheCommand::Clear => {
+            let dir = get_cache_dir();
+            let removed = clear_cache(&dir).map_err(|e| format!("couldn't clear {}: {}", dir.display(), e))?;
+            println!("removed {} {} from {}", removed, if removed == 1 { "entry" } else { "entries" }, dir.display());
+        }
+    }
 
-    parse_output(output_res)
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_daemon_command(stop: bool) -> Result<(), String> {
+    match stop {
+        true => stop_daemon(),
+        false => run_daemon(draw_prompt)
+    }
 }
 
-fn get_aws_profile() -> Option<String> {
-    env::var("AWS_PROFILE").ok()
+#[cfg(windows)]
+fn run_daemon_command(_stop: bool) -> Result<(), String> {
+    Err("the daemon needs unix sockets so isn't available on Windows".to_owned())
 }
 
-fn get_aws_region() -> Option<String> {
-    env::var("AWS_REGION").ok().or(env::var("AWS_DEFAULT_REGION").ok()).or(env::var("AWS_PROFILE_REGION").ok())
+// Nothing needs more than the one thread, and the daemon forks so mustn't have a runtime of its own
+fn draw_prompt(cli: Vec<OsString>) -> i32 {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("couldn't start the runtime")
+        .block_on(run(cli))
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Args::parse();
+fn main() {
+    let cli: Vec<OsString> = env::args_os().collect();
 
-    // Colored likes to follow the environment, however prompts appear like pipes and it disables
-    // colour!
-    colored::control::set_override(true);
+    // Prompts are drawn by the daemon whenever there's one running
+    match Args::try_parse_from(&cli).map(|x| x.command) {
+        Ok(Some(Subcommands::Daemon { stop })) => {
+            if let Err(e) = run_daemon_command(stop) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        },
+        #[cfg(unix)]
+        Ok(None) => if let Some(code) = render_in_daemon(&cli) {
+            std::process::exit(code);
+        },
+        _ => ()
+    }
+
+    let code = draw_prompt(cli);
+    if code != 0 {
+        let _ = stdout().flush();
+        std::process::exit(code);
+    }
+}
+
+async fn run(cli: Vec<OsString>) -> i32 {
+    let start = Instant::now();
+    init_logging();
+    let (mut args, config) = get_args(cli);
+    let preview = matches!(args.command, Some(Subcommands::Preview { .. }));
+    let legend = matches!(args.command, Some(Subcommands::Explain { .. }));
+
+    match args.command {
+        Some(Subcommands::Init { shell, background }) => {
+            match get_init_script(shell, background) {
+                Ok(script) => print!("{}", script),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return 0;
+        },
+        Some(Subcommands::Config { command }) => {
+            if let Err(e) = run_config_command(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return 0;
+        },
+        Some(Subcommands::Cache { command }) => {
+            if let Err(e) = run_cache_command(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return 0;
+        },
+        Some(Subcommands::Completions { shell }) => {
+            if let Err(e) = print_completions(shell) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return 0;
+        },
+        Some(Subcommands::Doctor { config }) => {
+            if let Err(e) = run_doctor(config) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return 0;
+        },
+        // This runs before every command so it mustn't ever get in the way, and failing just means
+        // no duration afterwards
+        Some(Subcommands::PreExec { command }) => {
+            if let Err(e) = write_record(&get_record_path(), &command.join(" ")) {
+                tracing::debug!(error = %e, "couldn't write the pre-exec record");
+            }
+            return 0;
+        },
+        // This is dealt with before there's a runtime
+        Some(Subcommands::Daemon { .. }) => return 0,
+        // These need the theme, so they're dealt with once there is one
+        Some(Subcommands::Preview { .. } | Subcommands::Explain { .. }) | None => ()
+    }
 
-    let current_dir = get_current_working_directory();
+    // Shells always say which they are, or --ps1 for those that need nothing escaping, so without
+    // that it's someone reading the prompt through a pipe. They get it plain, without the blank line
+    // or anything only a terminal would understand
+    if args.shell.is_none() && !args.ps1 && args.format == Format::Ansi && !stdout().is_terminal() {
+        args.colour = args.colour.or(Some(ColourMode::Auto));
+        args.no_blank_line = true;
+        args.no_trailing_space = true;
+        args.title = None;
+        args.osc7 = false;
+        args.iterm2 = false;
+        args.links = false;
+        args.semantic_prompt = false;
+    }
 
-    let is_in_git_repostory = is_in_git_repository().await;
+    // Elvish only makes sense of colours in what the prompt prints, and draws the blank line and the
+    // trailing space oddly, so the init script adds the space itself
+    if args.shell == Some(Shell::Elvish) {
+        args.no_blank_line = true;
+        args.no_trailing_space = true;
+        args.title = None;
+        args.osc7 = false;
+        args.iterm2 = false;
+        args.links = false;
+        args.semantic_prompt = false;
+    }
 
-    let current_context_future = get_k8s_context();
-    let current_namespace_future = get_k8s_namespace();
+    // Everything's styled by the renderer at the end, so this is the only place colour's decided
+    let colour = get_colour_enabled(args.colour);
 
-    let aws_profile = get_aws_profile();
-    let aws_region = get_aws_region();
+    #[cfg(windows)]
+    set_up_console();
 
-    let chevron_a = match args.exit_code {
-        0 => "❯".green().bold(),
-        _ => "❯".red().bold()
+    let glyphs = Glyphs { ascii: args.ascii || is_ascii_locale(), icon_width: args.icon_width };
+    let renderer = Renderer { format: args.format, shell: args.shell, colour, depth: get_colour_depth(), glyphs };
+    // Without colour there's nothing for the background to change, so the terminal's never asked
+    let background = match args.background {
+        Some(background) => background,
+        None if colour && args.theme != ThemeName::Monochrome => detect_background(args.query_background),
+        None => Background::Dark
     };
+    let theme = Theme { name: args.theme, background, overrides: args.role_colours.clone() };
+    let chevrons = Chevrons { theme: &theme, glyphs: &args.chevron_glyphs, colours: &args.chevron_colours, colour };
 
-    let current_context;
-    let current_namespace;
-    let current_branch;
-    let git_state;
-    let git_errors;
-    let git_unmerged;
-    let chevron_b;
-    let chevron_c;
-    if is_in_git_repostory {
-        git_state = get_git_state();
+    // The icons don't have ASCII versions so they're left out entirely
+    let icon_style = if glyphs.ascii { IconStyle::None } else { args.icons };
 
-        let current_branch_future = get_best_git_name();
+    // Whatever goes after the last chevron, which is usually a space for the cursor to sit after but
+    // can be nothing for shells that add their own or a non-breaking space for selecting lines
+    let trailing = if args.no_trailing_space { String::new() } else { renderer.text(&args.trailing) };
 
-        let uncommitted_changes_future = get_uncommitted_changes();
+    let exit_state = if args.exit_code == 0 { State::ExitOk } else { State::ExitFail };
 
-        let unpushed_changes_future = get_unpushed_changes();
+    if preview {
+        draw_previews(&args, &config, &renderer, &theme, &chevrons, icon_style).await;
+        return 0;
+    }
+    if legend {
+        print!("{}", get_legend(&args, &renderer, &chevrons));
+        return 0;
+    }
 
-        let git_errors_future = get_git_errors();
+    let (prompt_start, prompt_end) = if args.semantic_prompt && !is_dumb_terminal() {
+        (renderer.non_printing(PROMPT_START), renderer.non_printing(PROMPT_END))
+    } else {
+        ("".to_owned(), "".to_owned())
+    };
 
-        let git_unmerged_future = get_git_unmerged();
+    // Prompts left behind in the scrollback only keep their chevrons, all in the exit code's colour
+    // as nothing else is looked up. This runs after every command so it mustn't start any processes
+    if args.transient {
+        let positions = match args.prompt_style {
+            PromptStyle::Chevrons => vec![Position::Exit, Position::Changes, Position::Upstream],
+            PromptStyle::Single => vec![Position::Exit]
+        };
 
-        let uncommitted_changes;
-        let unpushed_changes;
-        (current_context, current_namespace, current_branch, uncommitted_changes, unpushed_changes, git_errors, git_unmerged) = futures::join!(
-            current_context_future,
-            current_namespace_future,
-            current_branch_future,
-            uncommitted_changes_future,
-            unpushed_changes_future,
-            git_errors_future,
-            git_unmerged_future
-        );
+        let transient: String = if is_dumb_terminal() {
+            ">".to_owned()
+        } else {
+            let look = chevrons.get_look(Some(exit_state));
+            positions.iter().enumerate().map(|(i, position)| {
+                let chevron = look.paint(chevrons.get_glyph(*position));
+                renderer.span(&if i == 0 { chevrons.add_marker(chevron, Some(exit_state)) } else { chevron })
+            }).collect()
+        };
+
+        print_prompt(args.shell, &format!("{}{}{}{}", prompt_start, transient, trailing, prompt_end));
+        return 0;
+    }
+
+    let path_segment = PathSegment { style: args.path_style, keep: args.path_keep, aliases: args.path_aliases.clone() };
 
-        chevron_b = match uncommitted_changes {
-            UncommittedChanges::None => "❯".green().bold(),
-            UncommittedChanges::FilesChanged => "❯".yellow().bold(),
-            UncommittedChanges::FilesNotAdded => "❯".blue().bold()
+    // This comes before everything else so nothing else can sneak any styling in
+    if args.format == Format::Ansi && is_dumb_terminal() {
+        let current_dir = get_current_working_directory(args.physical, args.cwd.as_deref());
+        let path = current_dir.path.as_ref().map(|x| path_segment.get_spans(x, &theme).iter().map(|s| &**s).collect::<String>());
+        let branch = match current_dir.full.as_deref().map(|dir| ProcessRunner { dir, inherit_env: args.inherit_env }) {
+            Some(runner) if is_in_git_repository(&runner).await => get_best_git_name(&runner).await.ok(),
+            _ => None
         };
 
-        chevron_c = match unpushed_changes {
-            UnpushedChanges::None => "❯".green().bold(),
-            UnpushedChanges::UnpushedChanges => "❯".yellow().bold(),
-            UnpushedChanges::UnpulledChanges => "❯".blue().bold(),
-            UnpushedChanges::NoUpstreamBranch => "❯".white().bold()
+        print_prompt(args.shell, &renderer.text(&get_dumb_prompt(path.as_deref(), branch.as_deref())));
+        return 0;
+    }
+
+    let keymap_style = args.keymap.as_deref().and_then(|k| get_keymap_style(k, &args.keymap_style));
+
+    if args.redraw {
+        if let Some((prefix, state)) = load_redraw_state() {
+            let chevron_c = get_final_chevron(keymap_style.as_ref(), get_final_position(args.prompt_style), state, &chevrons);
+            print_prompt(args.shell, &format!("{}{}{}{}", prefix, renderer.span(&chevron_c), trailing, prompt_end));
+            return 0;
+        }
+    }
+
+    // Whatever the shell's pre-exec hook left behind. Only a whole prompt takes it, so the transient
+    // prompt and redraws before it leave it be, and an instant prompt only does once it's sure
+    // nothing's coming after it
+    let record_path = get_record_path();
+    if args.format == Format::Ansi && args.only.is_empty() {
+        let record = if args.instant { peek_record(&record_path) } else { take_record(&record_path) };
+        if let Some((elapsed, command)) = record {
+            args.duration_ms = args.duration_ms.or(Some(elapsed.as_millis() as u64));
+            args.last_command = args.last_command.or(command);
+        }
+    }
+
+    // How long the previous prompt sat there before anything was run, so a long build doesn't
+    // count as the terminal being left alone
+    let last_prompt_path = get_last_prompt_path();
+    let idle_ms = match args.format == Format::Ansi && args.only.is_empty() {
+        true => if args.instant { peek_last_prompt(&last_prompt_path) } else { take_last_prompt(&last_prompt_path) },
+        false => None
+    }.map(|x| (x.as_millis() as u64).saturating_sub(args.duration_ms.unwrap_or(0)));
+
+    let show_left = !args.right;
+    let show_right = args.right || args.combined || args.fill.is_some();
+
+    let current_dir = get_current_working_directory(args.physical, args.cwd.as_deref());
+
+    // Nothing is run anywhere once the directory's gone, so this is only here to have something
+    let dir = current_dir.full.as_deref().unwrap_or(Path::new("."));
+
+    let process_runner = ProcessRunner { dir, inherit_env: args.inherit_env };
+    let store = &Store::new(get_cache_dir());
+    let runner = CachedRunner { dir, inherit_env: args.inherit_env, runner: &process_runner, store };
+
+    // Each repository gets a colour of its own from its remote, so every clone of it has the same
+    // one, and from where it is when there's no remote. The instant prompt only has the remote if
+    // it's been cached. It goes in front of any --segment-color so those still win
+    let accent = match (find_repo_root(dir).filter(|_| args.repo_accent), args.repo_accent_colour) {
+        (Some(_), Some(colour)) => Some(colour),
+        (Some(root), None) => {
+            let cached = CachedRunner { dir, inherit_env: args.inherit_env, runner: &CannedRunner { outputs: vec![] }, store };
+            let remote = get_git_remote_url(if args.instant { &cached } else { &runner }).await;
+            Some(remote.map_or_else(|| get_accent(root.as_os_str().as_encoded_bytes()), |x| get_accent(x.as_bytes())))
+        },
+        (None, _) => None
+    };
+    if let Some(colour) = accent {
+        let accented = [SegmentKind::GitBranch].into_iter().chain(args.repo_accent_path.then_some(SegmentKind::Path));
+        args.segment_colours.splice(0..0, accented.map(|segment| SegmentColour { segment, colour }));
+    }
+
+    let surroundings = Surroundings::detect(dir);
+
+    // The AWS segments can be kept to where they're of use, apart from a profile that looks like
+    // production, which is always worth knowing about
+    let is_aws_wanted = get_marker_condition(&args.aws_markers, &args.aws_dirs).is_none_or(|x| surroundings.holds(&x))
+        || env::var("AWS_PROFILE").is_ok_and(|x| is_dangerous(&config.danger_patterns, &x));
+
+    let disabled = get_disabled_segments(&args.disable, &args.enable);
+
+    // Anything on a side that isn't being printed is skipped along with all its detection work
+    let is_shown = |segment: SegmentKind| {
+        // --only picks out segments wherever they'd usually go
+        let side_shown = match get_side(segment, &args.sides) {
+            _ if !args.only.is_empty() => args.only.contains(&segment),
+            _ if disabled.contains(&segment) => false,
+            // A template says which segments go on the top line itself
+            Side::Left => show_left && args.template.as_ref().is_none_or(|t| t.contains(segment)),
+            Side::Right => show_right,
+            Side::Hidden => false
         };
+
+        let is_wanted = is_aws_wanted || segment.family() != Some("aws");
+        side_shown && segment.is_available() && surroundings.is_visible(segment, &args.conditions) && is_wanted
+    };
+
+    // An instant prompt only has what's there without having to go and find it
+    let is_visible = |segment: SegmentKind| (!args.instant || segment.is_instant()) && is_shown(segment);
+
+    let mut registry = get_registry(path_segment, get_messages(&args.message), args.fsmonitor);
+    registry.extend(get_opt_in_segments(&args, surroundings.multiplexer == Some(Multiplexer::Tmux)));
+    registry.push(Box::new(ProjectSegment { manifests: args.project_manifests.clone() }));
+
+    // Whatever the shell said about the last command is only missing when it said something
+    registry.push(Box::new(IdleSegment { idle_ms, threshold_ms: args.idle_threshold_mins * 60_000 }));
+    if let Some(duration_ms) = args.duration_ms {
+        registry.push(Box::new(DurationSegment { duration_ms, threshold_ms: args.duration_threshold_ms, always: args.always_show_duration }));
+    }
+    if let Some(jobs) = args.jobs {
+        registry.push(Box::new(JobsSegment { jobs }));
+    }
+    let last_command = get_last_command(&args);
+    if args.pipestatus.len() > 1 || last_command.is_some() {
+        registry.push(Box::new(PipestatusSegment { codes: args.pipestatus.clone(), last_command }));
+    }
+    #[cfg(feature = "svn")]
+    registry.extend([Box::new(SvnBranchSegment) as Box<dyn prompt::segment::Segment>, Box::new(SvnStatusSegment)]);
+
+    // The kinds are in the order they go on the top line, and messages stay in theirs
+    registry.sort_by_key(|x| x.kind());
+
+    // The chevrons' states are needed whenever the left side is printed, but tmux and --only don't
+    // get any chevrons
+    let needs_states = (show_left && !args.instant && args.format != Format::Tmux && args.only.is_empty()) || is_visible(SegmentKind::GitStatus) || is_visible(SegmentKind::SvnStatus);
+    let needs_git = needs_states || registry.iter().any(|x| x.kind().family() == Some("git") && is_visible(x.kind()));
+
+    // Only the one backend runs, whichever working copy is closest
+    let vcs = if current_dir.deleted { None } else { detect_vcs(dir) };
+    let is_in_git_repostory = !current_dir.deleted && needs_git && vcs != Some(Vcs::Svn) && is_in_git_repository(&runner).await;
+    let is_in_svn_working_copy = vcs == Some(Vcs::Svn) && SegmentKind::SvnBranch.is_available();
+
+    let detections = Detections::default();
+    let timed = &detections;
+
+    let context = Context { dir, current_dir: &current_dir, config: &config, theme: &theme, exit_code: args.exit_code, timeout_ms: args.timeout_ms, runner: &runner, store };
+
+    // Nothing's run where it can't find anything, though the chevrons and the title want some
+    // segments that aren't being shown
+    let is_needed = |kind: SegmentKind| {
+        let is_possible = match kind.family() {
+            Some("git") => is_in_git_repostory,
+            Some("svn") => is_in_svn_working_copy,
+            _ => !(current_dir.deleted && kind.needs_directory())
+        };
+        let is_wanted = (kind.feeds_chevrons() && needs_states) || (kind.is_in_title() && args.title.is_some());
+        is_possible && (is_visible(kind) || is_wanted)
+    };
+    let needed: Vec<_> = registry.iter().filter(|x| is_needed(x.kind())).collect();
+
+    // Everything leaves its result in a slot of its own as it finishes, so once the budget's gone
+    // whatever has finished can still be drawn
+    let rendered_slots: Vec<Slot<_>> = needed.iter().map(|_| Slot::default()).collect();
+    let registry_future = futures::future::join_all(needed.iter().zip(&rendered_slots).map(|(segment, slot)| slot.fill(async {
+        let result = match segment.timing_name() {
+            Some(name) => timed.time_named(name, segment.render(&context)).await,
+            None => segment.render(&context).await
+        };
+        timed.record(segment.kind(), result)
+    })));
+
+    // Custom segments go on the end of their side, so they're left out when a template lays out
+    // the top line and can't be picked with --only
+    let is_custom_shown = |side: Side| !current_dir.deleted && args.only.is_empty() && args.format != Format::Json && match side {
+        Side::Left => show_left && args.template.is_none(),
+        Side::Right => show_right,
+        Side::Hidden => false
+    };
+    let custom_segments: Vec<_> = config.custom.iter().filter(|(_, x)| !args.instant && is_custom_shown(x.side)).collect();
+    let custom_slots: Vec<Slot<_>> = custom_segments.iter().map(|_| Slot::default()).collect();
+    let custom_future = futures::future::join_all(custom_segments.iter().zip(&custom_slots).map(|((name, segment), slot)| slot.fill(async move {
+        let text = timed.time_named(format!("custom {}", name), run_custom_segment(name, segment, dir, store, args.timeout_ms)).await;
+        text.map(|x| (*segment, x))
+    })));
+
+    // Plugins go on the top line after the custom segments
+    let plugins_shown = !current_dir.deleted && show_left && args.template.is_none() && args.only.is_empty() && args.format != Format::Json && !config.plugins.is_empty();
+    let plugin_dir = current_dir.full.as_deref().filter(|_| plugins_shown && !args.instant);
+    let plugin_names: Vec<_> = plugin_dir.iter().flat_map(|dir| config.plugins.iter().map(move |name| (*dir, name))).collect();
+    let plugin_slots: Vec<Slot<_>> = plugin_names.iter().map(|_| Slot::default()).collect();
+    let plugin_future = futures::future::join_all(plugin_names.iter().zip(&plugin_slots).map(|((dir, name), slot)| slot.fill(async move {
+        let context = PluginContext { dir, repo_root: find_repo_root(dir), exit_code: args.exit_code };
+        timed.time_named(format!("plugin {}", name), run_plugin(&name.to_string(), &context, args.timeout_ms)).await
+    })));
+
+    // Whether the full prompt could have more in it than the instant one, which is worked out
+    // without running anything so the shell knows whether it's worth asking
+    let is_partial = args.instant && {
+        let has_vcs = |kind: SegmentKind| match kind.family() {
+            Some("git") => vcs == Some(Vcs::Git),
+            Some("svn") => vcs == Some(Vcs::Svn),
+            _ => true
+        };
+
+        (vcs.is_some() && show_left && args.format != Format::Tmux && args.only.is_empty())
+            || registry.iter().map(|x| x.kind()).any(|kind| !kind.is_instant() && is_shown(kind) && has_vcs(kind))
+            || config.custom.iter().any(|(_, x)| is_custom_shown(x.side))
+            || plugins_shown
+    };
+    if args.instant && !is_partial && args.format == Format::Ansi && args.only.is_empty() {
+        let _ = fs::remove_file(&record_path);
+    }
+
+    let chevron_a = chevrons.draw(Position::Exit, Some(exit_state));
+
+    // The budget's counted from when the prompt started, as that's what the shell is waiting on
+    let deadline = args.budget_ms.map(|x| start + Duration::from_millis(x));
+
+    let repo_name_slot = Slot::default();
+    let remote_url_slot = Slot::default();
+    let finished = if is_in_git_repostory {
+        let repo_name_future = repo_name_slot.fill(async {
+            if args.title.as_ref().is_some_and(|t| t.contains("{repo}")) { get_git_repo_name(&runner).await } else { None }
+        });
+
+        let remote_url_future = remote_url_slot.fill(async { if args.links && is_visible(SegmentKind::GitBranch) { get_git_remote_url(&runner).await } else { None } });
+
+        run_until(deadline, async {
+            futures::join!(
+                registry_future,
+                custom_future,
+                plugin_future,
+                repo_name_future,
+                remote_url_future
+            );
+        }).await
+
     } else {
-        git_state = None;
-        current_branch = None;
-        git_errors = false;
-        git_unmerged = 0;
-
-        chevron_b = "❯".bold();
-        chevron_c = "❯".bold();
-
-        (current_context, current_namespace) = futures::join!(current_context_future, current_namespace_future);
-    }
-
-    // Not really sure what some of these git states are but they seem important
-    let git_state = git_state.map(|s| match s {
-        GitState::RebaseInteractive => "(interactive rebase)",
-        GitState::RebaseMerge => "(rebase merge)",
-        GitState::Rebase => "(rebase)",
-        GitState::ApplyMailbox => "(AM)",
-        GitState::ApplyMailboxOrRebase => "(AM/rebase)",
-        GitState::Merge => "(merge)",
-        GitState::Revert => "(revert)",
-        GitState::CherryPick => "(cherry-pick)",
-        GitState::Bisect => "(bisect)"
+        run_until(deadline, async {
+            futures::join!(
+                registry_future,
+                custom_future,
+                plugin_future
+            );
+        }).await
+    };
+
+    if !finished {
+        tracing::debug!(budget_ms = args.budget_ms, "ran out of time, drawing whatever has finished");
+    }
+
+    // Anything the budget cut short is left out, with the reason kept for --explain-missing
+    let out_of_time = |kind| detections.record::<()>(kind, SegmentResult::Absent(Reason::OutOfTime));
+    let mut results = needed.iter().zip(&rendered_slots).map(|(segment, slot)| slot.take().unwrap_or_else(|| {
+        out_of_time(segment.kind());
+        None
+    }));
+    let rendered: Vec<_> = registry.iter().map(|x| (x.kind(), if is_needed(x.kind()) { results.next().flatten() } else { None })).collect();
+    let custom: Vec<_> = custom_slots.iter().filter_map(|x| x.take()).collect();
+    let plugins: Vec<_> = plugin_slots.iter().filter_map(|x| x.take()).collect();
+    let repo_name = repo_name_slot.take().flatten();
+    let remote_url = remote_url_slot.take().flatten();
+
+    if args.timings {
+        eprint!("{}", get_timings_table(&detections, &store.get_counts(), start.elapsed()));
+    }
+
+    // Only the prompt itself counts, and anyone looking at the timings already knows
+    let counts_as_slow = show_left && !args.instant && !args.timings && !args.no_slow_warning && args.only.is_empty() && args.format == Format::Ansi && !current_dir.deleted;
+    if counts_as_slow && should_warn(find_repo_root(dir).unwrap_or(dir), start.elapsed()) {
+        eprintln!("{}", get_hint(detections.get_slowest()));
+    }
+
+    let get_spans = |kind| rendered.iter().filter_map(move |(k, x)| match x {
+        Some(Rendered::Spans(spans) | Rendered::Aliased(spans, _)) if *k == kind => Some(spans.clone()),
+        _ => None
+    });
+    let get_value = |kind| rendered.iter().find_map(|(k, x)| match x {
+        Some(Rendered::Aliased(_, value)) if *k == kind => Some(value.as_str()),
+        _ => None
+    });
+    let get_state = |position| rendered.iter().find_map(|(_, x)| match x {
+        Some(Rendered::State(p, state)) if *p == position => Some(*state),
+        _ => None
     });
 
-    if args.explain {
+    let has_conflicts = get_spans(SegmentKind::GitUnmerged).next().is_some();
+    let changes_state = get_state(Position::Changes).map(|x| if has_conflicts { State::Conflict } else { x });
+    let upstream_state = get_state(Position::Upstream);
+    if is_in_git_repostory {
+        tracing::debug!(?changes_state, ?upstream_state, has_conflicts, "worked out the git states");
+    }
+
+    let chevron_b = chevrons.draw(Position::Changes, changes_state);
+
+    let final_state = match args.prompt_style {
+        PromptStyle::Chevrons => upstream_state,
+        PromptStyle::Single => Some(exit_state)
+    };
+    let chevron_c = get_final_chevron(keymap_style.as_ref(), get_final_position(args.prompt_style), final_state, &chevrons);
+
+    // The single chevron only shows the exit code so the git state moves onto the top line instead
+    // The chevrons already show all this unless they've been cut down to one or asked for by name
+    let wants_status = args.only.contains(&SegmentKind::GitStatus) || args.template.as_ref().is_some_and(|t| t.contains(SegmentKind::GitStatus));
+    let status_in_chevrons = args.prompt_style == PromptStyle::Chevrons && !wants_status;
+    let status = match status_in_chevrons {
+        true => vec![],
+        false => chevrons.draw_status(&[changes_state, upstream_state])
+    };
+    if status_in_chevrons {
+        for (kind, _) in rendered.iter().filter(|(_, x)| matches!(x, Some(Rendered::State(..)))) {
+            detections.record::<()>(*kind, SegmentResult::Absent(Reason::Nothing("the chevrons show it instead")));
+        }
+    }
+
+    if args.explain && !colour {
+        println!("{}", renderer.text("\n    ❯❯❯\n    ││└ Unpushed changes (↑)/Unpulled changes (↓)/Diverged (↕)/No upstream (~)\n    │└─ Uncommitted changes (*)/Untracked files (?)/Conflicts (=)\n    └── Exit code (! on failure)"));
+    } else if args.explain {
         println!(
-            "\n    {}{}{}\n    ││└ Unpushed changes (yellow)/Unpulled changes (blue)/No upstream (white)\n    │└─ Uncommitted changes (yellow)/Untracked files (blue)\n    └── Exit code",
-            chevron_a,
-            chevron_b,
-            chevron_c
+            "\n    {}{}{}{}",
+            renderer.span(&chevron_a),
+            renderer.span(&chevron_b),
+            renderer.span(&chevron_c),
+            renderer.text("\n    ││└ Unpushed changes (yellow)/Unpulled changes (blue)/No upstream (white)\n    │└─ Uncommitted changes (yellow)/Untracked files (blue)\n    └── Exit code")
         );
     } else {
-        let top_line = vec![
-            Some(format!("{}", current_dir.display()).cyan().bold()),
-            args.message.map(|x| x.green().bold()),
-            current_branch.map(|x| x.purple().bold()),
-            git_state.map(|x| x.purple().bold()),
-            if git_errors { Some("\u{26A0}\u{FE0F}".bold()) } else { None },
-            if git_unmerged > 0 { Some(format!("({})", git_unmerged).purple().bold()) } else { None },
-            current_context.map(|x| x.bright_blue().bold()),
-            current_namespace.map(|x| x.bright_blue().bold()),
-            aws_profile.map(|x| x.red().bold()),
-            aws_region.map(|x| x.red().bold()),
-        ];
+        let path = get_spans(SegmentKind::Path).next().unwrap_or_default();
+        let current_branch = get_spans(SegmentKind::GitBranch).next().or_else(|| get_spans(SegmentKind::SvnBranch).next()).map(|x| x.iter().map(|s| &**s).collect::<String>());
+
+        let title = args.title.as_ref().map(|template| {
+            let path_text = get_value(SegmentKind::Path).map_or_else(|| get_text(&path), |x| x.to_owned());
+            let host = if template.contains("{host}") { get_hostname().map(|h| h.split('.').next().unwrap_or_default().to_owned()) } else { None };
+
+            let title = render_title(template, &[
+                ("path", Some(&path_text).filter(|x| !x.is_empty()).map(|x| x.as_str())),
+                ("repo", repo_name.as_deref()),
+                ("branch", current_branch.as_deref()),
+                ("host", host.as_deref())
+            ]);
+
+            renderer.non_printing(&format!("\x1b]0;{}\x07", renderer.text(&title)))
+        });
+
+        // Only the branch's own name goes in the link, not any tag after it
+        let current_branch_name = current_branch.as_ref().and_then(|x| x.split(" [").next()).filter(|x| !x.is_empty()).map(|x| x.to_owned());
+
+        let segments = get_line(rendered.iter().map(|(kind, x)| (*kind, x.as_ref())), &status);
+
+        // With debug logging on, anything the budget cut short is marked so it's clear it didn't
+        // just have nothing to show. The chevrons already say when the git status is missing
+        let mark_unfinished = tracing::enabled!(tracing::Level::DEBUG);
+        let segments: Vec<_> = segments.into_iter().map(|(kind, x)| {
+            let unfinished = x.is_empty() && (kind != SegmentKind::GitStatus || wants_status) && detections.get_reason(kind) == Some(Reason::OutOfTime);
+            match mark_unfinished && unfinished {
+                true => (kind, vec!["…".dimmed()]),
+                false => (kind, x)
+            }
+        }).collect();
+
+        let missing: Vec<_> = SegmentKind::value_variants().iter().copied()
+            .filter(|kind| is_visible(*kind) && !segments.iter().any(|(k, x)| k == kind && !x.is_empty()))
+            .filter_map(|kind| {
+                let is_registered = registry.iter().any(|x| x.kind() == kind);
+                let reason = || is_registered.then(|| get_missing_reason(kind, current_dir.deleted, is_in_git_repostory, is_in_svn_working_copy));
+                detections.get_reason(kind).or_else(reason).map(|x| (kind, x))
+            })
+            .collect();
 
-        println!(
-            "\n{}{}\n{}{}{} ",
-            if args.iterm2 { "\x1b]1337;SetMark\x07 " } else { "" },
-            top_line.iter().filter(|x| x.is_some()).map(|x| x.as_ref().unwrap().to_string()).collect::<Vec<_>>().join(" "),
-            chevron_a,
-            chevron_b,
-            chevron_c
+        if args.explain_missing {
+            for (kind, reason) in &missing {
+                eprintln!("{}: {}", kind.to_possible_value().map_or(String::new(), |v| v.get_name().to_owned()), reason);
+            }
+        }
+
+        if args.format == Format::Json {
+            let segments: Vec<_> = segments.into_iter().map(|(kind, x)| {
+                let x = if is_visible(kind) { colour_segment(&args, kind, x, get_value(kind), &config.danger_patterns) } else { vec![] };
+                (kind, get_side(kind, &args.sides), x)
+            }).collect();
+
+            let states = ReportStates { exit: exit_state, changes: changes_state, upstream: upstream_state };
+            println!("{}", get_report(args.exit_code, &args.pipestatus, states, &segments, &detections, &missing));
+            return 0;
+        }
+
+        let shown: Vec<_> = segments.into_iter()
+            .filter(|(kind, x)| !x.is_empty() && is_visible(*kind))
+            .map(|(kind, x)| (kind, fit_segment(&args, kind, x, get_value(kind), &config.danger_patterns, &glyphs)))
+            .collect();
+
+        // Anything not given an order keeps its usual place after the ones that were
+        let mut shown = shown;
+        shown.sort_by_key(|(kind, _)| args.order.iter().position(|x| x == kind).unwrap_or(args.order.len()));
+
+        // Compared on their text alone, before labels or icons make them look different
+        if args.only.is_empty() {
+            shown = dedupe_segments(shown, args.dedupe, |a, b| get_side(a, &args.sides) == get_side(b, &args.sides));
+        }
+
+        let mut shown: Vec<_> = shown.into_iter().map(|(kind, x)| (kind, decorate_segment(&args, kind, x, icon_style))).collect();
+
+        let branch_url = remote_url.filter(|_| args.links).zip(current_branch_name.as_deref()).and_then(|(remote, branch)| get_branch_url(&remote, branch, &args.forge_hosts));
+        let get_link = |kind| if kind == Some(SegmentKind::GitBranch) { branch_url.as_deref() } else { None };
+
+        // Just the segments asked for, in the order they were asked for
+        if !args.only.is_empty() {
+            shown.sort_by_key(|(kind, _)| args.only.iter().position(|x| x == kind));
+
+            let only = shown.iter().map(|(kind, x)| renderer.link(get_link(Some(*kind)), renderer.segment(x))).collect::<Vec<_>>().join(" ");
+            match args.format {
+                Format::Tmux => print!("{}", only),
+                _ => print_prompt(args.shell, &only)
+            }
+            return 0;
+        }
+
+        let (mut top_line, mut right): (Vec<_>, Vec<_>) = shown.into_iter().partition(|(kind, _)| get_side(*kind, &args.sides) == Side::Left);
+
+        for (segment, text) in custom.into_iter().flatten() {
+            let mut x = vec![theme.paint(Role::Message, &text)];
+            if let Some(colour) = segment.color.as_ref().and_then(|x| parse_colour(&x.to_string()).ok()) {
+                x = recolour(x, colour);
+            }
+            if let Some(label) = segment.label.as_deref().filter(|x| !x.is_empty()) {
+                x = add_label(x, label);
+            }
+
+            match segment.side {
+                Side::Left => top_line.push((SegmentKind::Custom, x)),
+                _ => right.push((SegmentKind::Custom, x))
+            }
+        }
+
+        // Variables go after the custom segments on their side
+        for segment in config.env.values().filter(|x| is_custom_shown(x.side)) {
+            let Some((value, text, colour)) = get_env_segment(segment) else { continue };
+
+            let mut x = vec![theme.paint(Role::Message, &text)];
+            if let Some(colour) = colour.or_else(|| segment.color.as_ref().and_then(|x| parse_colour(&x.to_string()).ok())) {
+                x = recolour(x, colour);
+            }
+            if let Some(style) = find_danger(&config.danger_patterns, &value) {
+                x = style.apply(x);
+            }
+            if let Some(label) = segment.label.as_deref().filter(|x| !x.is_empty()) {
+                x = add_label(x, label);
+            }
+
+            match segment.side {
+                Side::Left => top_line.push((SegmentKind::Custom, x)),
+                _ => right.push((SegmentKind::Custom, x))
+            }
+        }
+
+        for plugin in plugins.into_iter().flatten() {
+            let x = vec![theme.paint(Role::Message, &plugin.text)];
+            top_line.push((SegmentKind::Custom, match plugin.colour {
+                Some(colour) => recolour(x, colour),
+                None => x
+            }));
+        }
+
+        let separator = get_separator(&args, &renderer);
+
+        // Powerline has separators of its own that take up a column
+        let line_separator = match args.style {
+            Style::Plain => args.separator.as_str(),
+            Style::Powerline => " "
+        };
+
+        // tmux draws the status line around the segments itself so there's no prompt to go with them
+        if args.format == Format::Tmux {
+            let side = if args.right { &right } else { &top_line };
+            print!("{}", side.iter().map(|(_, x)| renderer.segment(x)).collect::<Vec<_>>().join(&separator));
+            return 0;
+        }
+
+        // With a fill the right hand segments go on the end of the top line instead
+        let (right, aligned) = if args.fill.is_some() { (vec![], right) } else { (right, vec![]) };
+
+        let right = right.iter().map(|(kind, x)| renderer.link(get_link(Some(*kind)), renderer.segment(x))).collect::<Vec<_>>().join(&separator);
+
+        if args.right {
+            print_prompt(args.shell, &right);
+            return 0;
+        }
+
+        let to_style = |line: Vec<(SegmentKind, Segment)>| -> Vec<_> {
+            match args.style {
+                Style::Plain => line,
+                Style::Powerline => line.into_iter().map(|(kind, x)| (kind, to_powerline(x))).collect()
+            }
+        };
+
+        let top_line = to_style(top_line);
+        let aligned = to_style(aligned);
+
+        let leading_chevrons = match args.prompt_style {
+            PromptStyle::Chevrons => vec![&chevron_a, &chevron_b],
+            PromptStyle::Single => vec![]
+        };
+
+        // The iTerm2 mark is followed by a space which takes up a column of its own, and powerline
+        // has a separator hanging off the end
+        let mut reserved = usize::from(args.iterm2) + usize::from(args.style == Style::Powerline);
+
+        // On one line the chevrons, and the spaces either side of them, need to fit in as well
+        if args.single_line {
+            reserved += leading_chevrons.iter().copied().chain([&chevron_c]).map(|c| glyphs.get_width(c)).sum::<usize>();
+            reserved += 1 + if args.no_trailing_space { 0 } else { glyphs.get_width(&args.trailing) };
+        }
+
+        let columns = args.columns.or_else(get_terminal_width);
+
+        let draw_line = |line: Vec<(Option<SegmentKind>, Segment)>| {
+            let (links, line): (Vec<_>, Vec<_>) = line.into_iter().map(|(kind, x)| (get_link(kind), x)).unzip();
+
+            match args.style {
+                Style::Plain => line.iter().zip(&links).map(|(x, link)| renderer.link(*link, renderer.segment(x))).collect::<Vec<_>>().join(&separator),
+                Style::Powerline => renderer.powerline(&line, &links)
+            }
+        };
+
+        let (mut top_line, top_line_width) = match &args.template {
+            // Templates lay out the line themselves so they only need to know which segments are left
+            Some(template) => {
+                let line = match columns {
+                    Some(columns) => template.fit_to_width(top_line, columns.saturating_sub(reserved), &glyphs),
+                    None => top_line
+                };
+
+                let drawn = template.render(&renderer, |kind| {
+                    line.iter().find(|(k, _)| *k == kind).map(|(_, x)| renderer.link(get_link(Some(kind)), renderer.segment(x)))
+                });
+
+                (drawn, template.get_width(&line, &glyphs))
+            },
+            None => {
+                let line = match columns {
+                    Some(columns) => fit_to_width(top_line, columns.saturating_sub(reserved), line_separator, &glyphs),
+                    None => top_line.into_iter().map(|(kind, x)| (Some(kind), x)).collect()
+                };
+
+                let width = get_line_width(&line, line_separator, &glyphs);
+                (draw_line(line), width)
+            }
+        };
+
+        // The right hand segments get a powerline separator of their own
+        let (aligned, gap) = match columns {
+            Some(columns) => {
+                let width = columns.saturating_sub(reserved + usize::from(args.style == Style::Powerline));
+                fit_right(top_line_width, aligned, width, line_separator, &glyphs)
+            },
+            None => (aligned.into_iter().map(|(kind, x)| (Some(kind), x)).collect(), 1)
+        };
+
+        if !aligned.is_empty() {
+            top_line += &renderer.span(&get_fill(args.fill.unwrap_or(' '), gap).dimmed());
+            top_line += &draw_line(aligned);
+        }
+
+        let line_break = match (args.single_line, top_line.is_empty()) {
+            (false, _) => "\n",
+            (true, false) => " ",
+            (true, true) => ""
+        };
+
+        let cwd_sequence = if args.osc7 { current_dir.full.clone() } else { None }
+            .map(|d| renderer.non_printing(&renderer.text(&get_cwd_sequence(&d, get_hostname().as_deref()))));
+
+        // The blank line goes before anything that's printed, and only print_prompt decides what goes
+        // after the trailing text
+        let blank_line = if args.no_blank_line { "" } else { "\n" };
+        let prefix = format!(
+            "{}{}{}{}{}{}{}{}",
+            prompt_start,
+            cwd_sequence.unwrap_or_default(),
+            title.unwrap_or_default(),
+            blank_line,
+            if args.iterm2 { renderer.non_printing("\x1b]1337;SetMark\x07") + " " } else { "".to_owned() },
+            top_line,
+            line_break,
+            leading_chevrons.iter().map(|c| renderer.span(c)).collect::<String>()
         );
+
+        if args.keymap.is_some() {
+            save_redraw_state(&prefix, final_state);
+        }
+
+        let left = format!("{}{}{}{}", prefix, renderer.span(&chevron_c), trailing, prompt_end);
+
+        // Both sides from one run so the shell doesn't have to do all the detection twice
+        if args.combined {
+            print_prompt(args.shell, &format!("{}\0{}", left, right));
+        } else {
+            print_prompt(args.shell, &left);
+        }
     }
+
+    if is_partial { INSTANT_PARTIAL } else { 0 }
 }
@@ -0,0 +1,78 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use colored::Color;
+
+use crate::colour::parse_colour;
+#[cfg(not(windows))]
+use crate::path::get_home_directory;
+use crate::text::sanitize;
+
+// A message is never more than a line so there's no reason to read much of one
+const MAX_MESSAGE: u64 = 4096;
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub text: String,
+    pub colour: Option<Color>,
+    pub dimmed: bool,
+}
+
+// Only a colour's name or hex counts as a prefix, so "10:30 standup" and "note: this" are left
+// alone as messages
+pub fn parse_message(s: &str) -> Result<Message, String> {
+    let (text, colour, dimmed) = match s.split_once(':') {
+        Some(("dim", text)) => (text, None, true),
+        Some((colour, text)) if !colour.starts_with(|c: char| c.is_ascii_digit()) => match parse_colour(colour) {
+            Ok(colour) => (text, Some(colour), false),
+            Err(_) => (s, None, false)
+        },
+        _ => (s, None, false)
+    };
+
+    let text = sanitize(text);
+
+    Ok(Message { text, colour, dimmed })
+}
+
+#[cfg(windows)]
+pub fn get_state_home() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").filter(|x| !x.is_empty()).map(PathBuf::from))
+}
+
+#[cfg(not(windows))]
+pub fn get_state_home() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| get_home_directory().map(|h| h.join(".local").join("state")))
+}
+
+pub fn get_message_path() -> Option<PathBuf> {
+    env::var_os("PROMPT_MESSAGE_FILE").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| get_state_home().map(|d| d.join("prompt").join("message")))
+}
+
+fn to_message(text: &str) -> Option<Message> {
+    parse_message(text.lines().next()?.trim()).ok().filter(|x| !x.text.is_empty())
+}
+
+// Anything that isn't a plain file, like a pipe nobody's writing to, could hang the prompt so
+// it's left alone
+pub fn read_message_file() -> Option<Message> {
+    let path = get_message_path()?;
+    if !fs::metadata(&path).is_ok_and(|x| x.is_file()) {
+        return None;
+    }
+
+    let mut text = String::new();
+    File::open(&path).ok()?.take(MAX_MESSAGE).read_to_string(&mut text).ok()?;
+    to_message(&text)
+}
+
+pub fn read_stdin_message() -> Option<Message> {
+    let mut text = String::new();
+    io::stdin().take(MAX_MESSAGE).read_to_string(&mut text).ok()?;
+    to_message(&text)
+}
@@ -0,0 +1,78 @@
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::text::sanitize;
+use crate::theme::Role;
+
+// Nothing's ever sent to it, it's only what the routing table would pick to get there
+pub const DEFAULT_IP_PROBE: Ipv4Addr = Ipv4Addr::new(1, 1, 1, 1);
+
+// An interface that's shown as a label of its own while it's there, like a VPN's tunnel
+#[derive(Clone, Debug)]
+pub struct InterfaceMarker {
+    pub name: String,
+    pub label: String,
+}
+
+pub fn parse_interface_marker(s: &str) -> Result<InterfaceMarker, String> {
+    let (name, label) = s.split_once('=').ok_or("expected NAME=LABEL")?;
+    if name.is_empty() || label.is_empty() {
+        return Err("expected NAME=LABEL".to_owned());
+    }
+
+    Ok(InterfaceMarker { name: name.to_owned(), label: label.to_owned() })
+}
+
+// Connecting a UDP socket only picks a route and sends nothing, so the address it's bound to is the
+// one traffic would come from. Without a route, like when offline, there's no address to show
+pub fn get_local_ip(probe: Ipv4Addr) -> Option<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((probe, 53)).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+        _ => None
+    }
+}
+
+#[cfg(unix)]
+pub fn has_interface(name: &str) -> bool {
+    std::ffi::CString::new(name).is_ok_and(|x| unsafe { libc::if_nametoindex(x.as_ptr()) } != 0)
+}
+
+#[cfg(not(unix))]
+pub fn has_interface(_name: &str) -> bool {
+    false
+}
+
+// The markers for the interfaces that are there, in the order they were given, then the address
+pub fn get_network(markers: &[InterfaceMarker], has_interface: impl Fn(&str) -> bool, local_ip: Option<Ipv4Addr>) -> Option<String> {
+    let parts: Vec<_> = markers.iter().filter(|x| has_interface(&x.name)).map(|x| x.label.clone()).chain(local_ip.map(|x| x.to_string())).collect();
+    Some(parts.join(" ")).filter(|x| !x.is_empty())
+}
+
+// Only there when there's an address or a marker to show, so a laptop that's offline just says nothing
+pub struct NetworkSegment {
+    pub markers: Vec<InterfaceMarker>,
+    pub local_ip: bool,
+    pub probe: Ipv4Addr,
+}
+
+impl Segment for NetworkSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Network
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let local_ip = if self.local_ip { get_local_ip(self.probe) } else { None };
+        let result = match get_network(&self.markers, has_interface, local_ip) {
+            Some(network) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Info, &sanitize(&network))])),
+            None if self.local_ip => SegmentResult::Absent(Reason::Nothing("there's no route out")),
+            None => SegmentResult::Absent(Reason::Nothing("none of the interfaces are there"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
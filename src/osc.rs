@@ -0,0 +1,57 @@
+use std::path::Path;
+
+// Marks where the prompt starts and where the command being typed starts so terminals can jump
+// between prompts and pick out each command's output
+pub const PROMPT_START: &str = "\x1b]133;A\x07";
+pub const PROMPT_END: &str = "\x1b]133;B\x07";
+
+// Turns text into a link where terminals support it
+pub fn get_link_start(url: &str) -> String {
+    format!("\x1b]8;;{}\x07", url)
+}
+
+pub const LINK_END: &str = "\x1b]8;;\x07";
+
+#[cfg(unix)]
+pub fn get_hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+
+    if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+        return None;
+    }
+
+    let length = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..length]).into_owned()).filter(|h| !h.is_empty())
+}
+
+#[cfg(windows)]
+pub fn get_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+// Lets the terminal know the current directory so new tabs can be opened in the same place
+pub fn get_cwd_sequence(path: &Path, host: Option<&str>) -> String {
+    format!("\x1b]7;file://{}{}\x07", percent_encode(host.unwrap_or("").as_bytes()), percent_encode(&get_url_path(path)))
+}
+
+#[cfg(unix)]
+fn get_url_path(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // Paths don't have to be UTF-8 here so the raw bytes are encoded as they are
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+fn get_url_path(path: &Path) -> Vec<u8> {
+    // C:\Users\me becomes /C:/Users/me
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') { path.into_bytes() } else { format!("/{}", path).into_bytes() }
+}
+
+pub fn percent_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => (*b as char).to_string(),
+        b => format!("%{:02X}", b)
+    }).collect()
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+// Reads PROMPT_* variables over the top of an existing config, working out their names from the
+// config's own fields. Fields of tables are joined on with _ (PROMPT_PATH_STYLE), keys of maps the
+// same way and ended with __ when there's more to come (PROMPT_SEGMENTS_K8S_CONTEXT__SIDE) and
+// lists are split on commas
+pub fn apply_env_overrides<T: DeserializeOwned>(base: toml::Value, prefix: &str) -> Result<T, String> {
+    let vars: BTreeMap<String, String> = env::vars().filter(|(name, _)| name.starts_with(prefix)).collect();
+
+    T::deserialize(Node { name: prefix.trim_end_matches('_').to_owned(), separator: "_", base: Some(base), vars: &vars })
+        .map_err(|e| e.0)
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+fn from_toml(e: toml::de::Error) -> Error {
+    Error(e.to_string())
+}
+
+fn to_key(name: &str) -> String {
+    name.to_ascii_uppercase().replace('-', "_")
+}
+
+fn from_key(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', "-")
+}
+
+struct Node<'a> {
+    name: String,
+    separator: &'static str,
+    base: Option<toml::Value>,
+    vars: &'a BTreeMap<String, String>,
+}
+
+impl<'a> Node<'a> {
+    fn get_value(&self) -> Option<&'a str> {
+        self.vars.get(&self.name).map(|x| x.as_str())
+    }
+
+    fn get_nested(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        let prefix = format!("{}{}", self.name, self.separator);
+        self.vars.iter().filter_map(move |(name, value)| name.strip_prefix(&prefix).map(|x| (x, value.as_str())))
+    }
+
+    fn is_set(&self) -> bool {
+        self.base.is_some() || self.get_value().is_some() || self.get_nested().next().is_some()
+    }
+
+    fn get_table(&self) -> toml::Table {
+        match &self.base {
+            Some(toml::Value::Table(table)) => table.clone(),
+            _ => toml::Table::new()
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, Error> where T::Err: fmt::Display {
+        value.trim().parse().map_err(|e| Error(format!("invalid value '{}' for {}: {}", value, name, e)))
+    }
+}
+
+// Anything without a variable of its own is left to the config underneath
+macro_rules! deserialize_leaf {
+    ($method:ident, $visit:ident) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match (self.get_value(), self.base) {
+                (Some(value), _) => visitor.$visit(Node::parse(&self.name, value)?),
+                (None, Some(base)) => base.$method(visitor).map_err(from_toml),
+                (None, None) => Err(Error(format!("{} isn't set", self.name)))
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match (self.get_value(), self.base) {
+            (Some(value), _) => visitor.visit_str(value),
+            (None, Some(base)) => base.deserialize_any(visitor).map_err(from_toml),
+            (None, None) => Err(Error(format!("{} isn't set", self.name)))
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match (self.get_value(), self.base) {
+            (Some(value), _) => match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => visitor.visit_bool(true),
+                "" | "0" | "false" | "no" | "off" => visitor.visit_bool(false),
+                _ => Err(Error(format!("invalid value '{}' for {}, expected true or false", value, self.name)))
+            },
+            (None, Some(base)) => base.deserialize_bool(visitor).map_err(from_toml),
+            (None, None) => Err(Error(format!("{} isn't set", self.name)))
+        }
+    }
+
+    deserialize_leaf!(deserialize_u8, visit_u8);
+    deserialize_leaf!(deserialize_u16, visit_u16);
+    deserialize_leaf!(deserialize_u32, visit_u32);
+    deserialize_leaf!(deserialize_u64, visit_u64);
+    deserialize_leaf!(deserialize_i8, visit_i8);
+    deserialize_leaf!(deserialize_i16, visit_i16);
+    deserialize_leaf!(deserialize_i32, visit_i32);
+    deserialize_leaf!(deserialize_i64, visit_i64);
+    deserialize_leaf!(deserialize_f32, visit_f32);
+    deserialize_leaf!(deserialize_f64, visit_f64);
+    deserialize_leaf!(deserialize_char, visit_char);
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_set() {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match (self.get_value(), self.base) {
+            (Some(value), _) => visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(value.trim().to_ascii_lowercase())),
+            (None, Some(base)) => base.deserialize_enum(name, variants, visitor).map_err(from_toml),
+            (None, None) => Err(Error(format!("{} isn't set", self.name)))
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match (self.get_value(), self.base) {
+            (Some(value), _) => {
+                let items = value.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(|x| x.to_owned()).collect::<Vec<_>>();
+                visitor.visit_seq(Items { items: items.into_iter(), name: self.name })
+            },
+            (None, Some(base)) => base.deserialize_seq(visitor).map_err(from_toml),
+            (None, None) => visitor.visit_seq(Items { items: vec![].into_iter(), name: self.name })
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        let table = self.get_table();
+
+        let entries = fields.iter().map(|field| (Node {
+            name: format!("{}{}{}", self.name, self.separator, to_key(field)),
+            separator: "_",
+            base: table.get(*field).cloned(),
+            vars: self.vars,
+        }, field.to_string())).filter(|(x, _)| x.is_set()).collect::<Vec<_>>();
+
+        visitor.visit_map(Entries { entries: entries.into_iter(), value: None })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let table = self.get_table();
+
+        let mut keys: Vec<String> = table.keys().cloned().collect();
+        for (name, _) in self.get_nested() {
+            let key = from_key(name.split_once("__").map_or(name, |(key, _)| key));
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        let entries = keys.into_iter().map(|key| (Node {
+            name: format!("{}{}{}", self.name, self.separator, to_key(&key)),
+            separator: "__",
+            base: table.get(&key).cloned(),
+            vars: self.vars,
+        }, key)).collect::<Vec<_>>();
+
+        visitor.visit_map(Entries { entries: entries.into_iter(), value: None })
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct newtype_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
+struct Items {
+    items: std::vec::IntoIter<String>,
+    name: String,
+}
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        let Some(item) = self.items.next() else { return Ok(None) };
+
+        // Each item is read as though it had a variable all to itself
+        let vars = BTreeMap::from([(self.name.clone(), item)]);
+        seed.deserialize(Node { name: self.name.clone(), separator: "_", base: None, vars: &vars }).map(Some)
+    }
+}
+
+struct Entries<'a> {
+    entries: std::vec::IntoIter<(Node<'a>, String)>,
+    value: Option<Node<'a>>,
+}
+
+impl<'de, 'a> MapAccess<'de> for Entries<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let Some((node, key)) = self.entries.next() else { return Ok(None) };
+
+        self.value = Some(node);
+        seed.deserialize(IntoDeserializer::<Error>::into_deserializer(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(self.value.take().ok_or_else(|| Error("value asked for before its key".to_owned()))?)
+    }
+}
@@ -0,0 +1,272 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+#[cfg(windows)]
+use std::path::Prefix;
+
+use clap::ValueEnum;
+use colored::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::colour::parse_colour;
+use homedir::get_my_home;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathStyle {
+    Full,
+    Fish,
+    Truncate
+}
+
+// Shells keep the path as it was typed in $PWD, so follow symlinks the way the user did as long
+// as it really is the same directory. --cwd is kept as it was typed in the same way
+fn get_logical_directory(physical_dir: &Path, cwd: Option<&Path>) -> Option<PathBuf> {
+    let pwd = match cwd {
+        Some(cwd) => std::path::absolute(cwd).ok()?,
+        None => PathBuf::from(env::var_os("PWD")?)
+    };
+
+    if !pwd.is_absolute() {
+        return None;
+    }
+
+    if is_same_directory(&pwd, physical_dir) {
+        Some(pwd)
+    } else {
+        None
+    }
+}
+
+fn get_physical_directory(cwd: Option<&Path>) -> Option<PathBuf> {
+    match cwd {
+        Some(cwd) => fs::canonicalize(cwd).ok().filter(|x| x.is_dir()),
+        None => env::current_dir().ok()
+    }
+}
+
+#[cfg(unix)]
+fn is_same_directory(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_directory(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false
+    }
+}
+
+pub struct WorkingDirectory {
+    pub path: Option<PathBuf>,
+    // The whole path, for running things in, as long as it's still there
+    pub full: Option<PathBuf>,
+    pub deleted: bool,
+}
+
+// Wherever we are, or wherever --cwd says to pretend we are
+pub fn get_current_working_directory(physical: bool, cwd: Option<&Path>) -> WorkingDirectory {
+    let Some(full) = get_full_working_directory(physical, cwd) else {
+        // Someone's removed the directory out from under us, the shell's idea of where we are is
+        // the best there is
+        let pwd = match cwd {
+            Some(cwd) => std::path::absolute(cwd).ok(),
+            None => env::var_os("PWD").map(PathBuf::from).filter(|p| p.is_absolute())
+        };
+
+        return WorkingDirectory { path: pwd.map(abbreviate_home), full: None, deleted: true };
+    };
+
+    WorkingDirectory { path: Some(abbreviate_home(full.clone())), full: Some(full), deleted: false }
+}
+
+// The whole path without ~ for anything that isn't being shown to the user
+pub fn get_full_working_directory(physical: bool, cwd: Option<&Path>) -> Option<PathBuf> {
+    let physical_dir = get_physical_directory(cwd)?;
+
+    if physical {
+        Some(physical_dir)
+    } else {
+        Some(get_logical_directory(&physical_dir, cwd).unwrap_or(physical_dir))
+    }
+}
+
+#[cfg(windows)]
+pub fn get_home_directory() -> Option<PathBuf> {
+    env::var_os("USERPROFILE").map(PathBuf::from).or_else(|| get_my_home().ok().flatten())
+}
+
+#[cfg(not(windows))]
+pub fn get_home_directory() -> Option<PathBuf> {
+    get_my_home().ok().flatten()
+}
+
+fn abbreviate_home(current_dir: PathBuf) -> PathBuf {
+    if let Some(home_dir) = get_home_directory() {
+        if let Some(remainder) = strip_home_directory(&current_dir, &home_dir) {
+            return Path::new("~").join(remainder);
+        }
+
+        return strip_home_drive(current_dir, &home_dir);
+    }
+
+    current_dir
+}
+
+#[cfg(windows)]
+fn get_drive_letter(path: &Path) -> Option<u8> {
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => Some(letter.to_ascii_uppercase()),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// Like cmd, the drive letter is only worth showing when it's not the one home is on
+#[cfg(windows)]
+fn strip_home_drive(dir: PathBuf, home_dir: &Path) -> PathBuf {
+    match get_drive_letter(&dir) {
+        Some(letter) if Some(letter) == get_drive_letter(home_dir) => dir.components().skip(1).collect(),
+        _ => dir
+    }
+}
+
+#[cfg(not(windows))]
+fn strip_home_drive(dir: PathBuf, _home_dir: &Path) -> PathBuf {
+    dir
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    // Both default to case insensitive filesystems so /users/rob is just as much home as /Users/rob
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    a == b
+}
+
+// Returns the part of the directory below home, as it was written in the directory rather than
+// how the home directory is spelled
+fn strip_home_directory<'a>(dir: &'a Path, home_dir: &Path) -> Option<&'a Path> {
+    if let Ok(remainder) = dir.strip_prefix(home_dir) {
+        return Some(remainder);
+    }
+
+    // Symlinks (/home -> /usr/home on FreeBSD) or different casing can hide that we're in home
+    let home_dir = fs::canonicalize(home_dir).ok()?;
+
+    dir.ancestors()
+        .find(|ancestor| fs::canonicalize(ancestor).is_ok_and(|a| is_same_path(&a, &home_dir)))
+        .map(|ancestor| dir.strip_prefix(ancestor).unwrap())
+}
+
+// Shortens a directory name to its first character like fish does, keeping a second character
+// for dotfiles since every one of them would otherwise be "."
+fn shorten_component(name: &str) -> String {
+    let length = if name.starts_with('.') { 2 } else { 1 };
+
+    name.graphemes(true).take(length).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct PathAlias {
+    pub prefix: PathBuf,
+    pub replacement: String,
+    pub colour: Option<Color>,
+}
+
+pub fn parse_path_alias(s: &str) -> Result<PathAlias, String> {
+    let (prefix, replacement) = s.split_once('=').ok_or("expected PREFIX=REPLACEMENT[:COLOUR]")?;
+
+    // Only treat the end as a colour if it is one, so replacements can still have colons in them
+    let (replacement, colour) = match replacement.rsplit_once(':').map(|(r, c)| (r, parse_colour(c))) {
+        Some((replacement, Ok(colour))) => (replacement, Some(colour)),
+        _ => (replacement, None)
+    };
+
+    // Paths are compared after home has been swapped for ~ so do the same to the prefix
+    Ok(PathAlias { prefix: abbreviate_home(PathBuf::from(prefix)), replacement: replacement.to_owned(), colour })
+}
+
+enum Part<'a> {
+    Alias(&'a PathAlias),
+    Component(Component<'a>),
+    Text(String)
+}
+
+impl Part<'_> {
+    fn is_directory(&self) -> bool {
+        !matches!(self, Part::Component(Component::RootDir | Component::Prefix(_)))
+    }
+
+    fn as_os_str(&self) -> &OsStr {
+        match self {
+            Part::Alias(alias) => alias.replacement.as_ref(),
+            Part::Component(component) => component.as_os_str(),
+            Part::Text(text) => text.as_ref()
+        }
+    }
+}
+
+fn join_parts(parts: &[Part]) -> String {
+    parts.iter().map(|p| p.as_os_str()).collect::<PathBuf>().display().to_string()
+}
+
+// Returns the alias the path starts with, if any, separately from the rest of the path so it can
+// be given its own colour
+pub fn format_path<'a>(path: &Path, style: PathStyle, keep: Option<usize>, aliases: &'a [PathAlias]) -> (Option<&'a PathAlias>, String) {
+    let alias = aliases.iter()
+        .filter(|a| path.starts_with(&a.prefix))
+        .max_by_key(|a| a.prefix.components().count());
+
+    let remainder = alias.map_or(path, |a| path.strip_prefix(&a.prefix).unwrap());
+
+    let parts = alias.map(Part::Alias).into_iter().chain(remainder.components().map(Part::Component));
+
+    let parts: Vec<_> = match style {
+        PathStyle::Full => parts.collect(),
+        PathStyle::Fish => {
+            let keep = keep.unwrap_or(1);
+            let parts: Vec<_> = parts.collect();
+            let count = parts.len();
+
+            parts.into_iter()
+                .enumerate()
+                .map(|(i, part)| match part {
+                    Part::Component(Component::Normal(name)) if i + keep < count => Part::Text(shorten_component(&name.to_string_lossy())),
+                    part => part
+                })
+                .collect()
+        },
+        PathStyle::Truncate => {
+            let keep = keep.unwrap_or(3);
+            let parts: Vec<_> = parts.collect();
+            let count = parts.len();
+
+            // Only truncate when it actually hides something, otherwise ~ and / are more useful
+            if parts.iter().filter(|p| p.is_directory()).count() <= keep {
+                parts
+            } else {
+                std::iter::once(Part::Text("…".to_owned())).chain(parts.into_iter().skip(count - keep)).collect()
+            }
+        }
+    };
+
+    match parts.split_first() {
+        Some((Part::Alias(_), [])) => (alias, String::new()),
+        Some((Part::Alias(_), rest)) => (alias, format!("{}{}", MAIN_SEPARATOR, join_parts(rest))),
+        _ => (None, join_parts(&parts))
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use colored::Color;
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use crate::colour::parse_colour;
+use crate::text::sanitize;
+
+// Plugins only get to print one line so anything bigger than this has gone wrong
+const MAX_OUTPUT: u64 = 4096;
+
+pub fn parse_plugin_name(s: &str) -> Result<(), String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("invalid plugin name '{}', expected letters, numbers, - and _", s));
+    }
+
+    Ok(())
+}
+
+pub fn find_repo_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|x| x.join(".git").exists())
+}
+
+#[derive(Deserialize)]
+struct PluginOutput {
+    text: String,
+    #[serde(default)]
+    color: Option<String>,
+}
+
+pub struct PluginSegment {
+    pub text: String,
+    pub colour: Option<Color>,
+}
+
+pub struct PluginContext<'a> {
+    pub dir: &'a Path,
+    pub repo_root: Option<&'a Path>,
+    pub exit_code: i32,
+}
+
+// A line is either just the text, the text with a colour and a colon in front of it or a JSON object
+// with both. Something that looks like a colour but isn't one is left as part of the text
+fn parse_plugin_output(line: &str) -> Option<PluginSegment> {
+    let (text, colour) = if line.starts_with('{') {
+        tracing::debug!(line, "reading plugin output as JSON");
+        let output: PluginOutput = serde_json::from_str(line).ok()?;
+        let colour = match output.color {
+            Some(colour) => Some(parse_colour(&colour).ok()?),
+            None => None
+        };
+        (output.text, colour)
+    } else {
+        match line.split_once(':').and_then(|(colour, text)| parse_colour(colour).ok().map(|colour| (text, colour))) {
+            Some((text, colour)) => {
+                tracing::debug!(line, "reading plugin output as a colour and text");
+                (text.to_owned(), Some(colour))
+            },
+            None => {
+                tracing::debug!(line, "reading plugin output as text");
+                (line.to_owned(), None)
+            }
+        }
+    };
+
+    let text = sanitize(text.trim());
+
+    Some(PluginSegment { text, colour }).filter(|x| !x.text.is_empty())
+}
+
+async fn read_plugin(name: &str, context: &PluginContext<'_>) -> Option<Vec<u8>> {
+    let mut command = Command::new(format!("prompt-segment-{}", name));
+    command.current_dir(context.dir)
+        .env("PROMPT_CWD", context.dir)
+        .env("PROMPT_EXIT_CODE", context.exit_code.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    match context.repo_root {
+        Some(root) => command.env("PROMPT_REPO_ROOT", root),
+        None => command.env_remove("PROMPT_REPO_ROOT")
+    };
+
+    tracing::debug!(command = ?command, "running plugin");
+    let mut child = command.spawn().ok()?;
+    let stdout = child.stdout.take()?;
+
+    let mut output = vec![];
+    stdout.take(MAX_OUTPUT + 1).read_to_end(&mut output).await.ok()?;
+
+    // Dropping the child kills it, so one that won't stop talking is stopped here
+    if output.len() as u64 > MAX_OUTPUT {
+        tracing::debug!(name, "plugin printed too much");
+        return None;
+    }
+
+    let status = child.wait().await.ok();
+    tracing::debug!(name, status = ?status, "plugin finished");
+    status.filter(|x| x.success()).map(|_| output)
+}
+
+// Plugins that fail, hang, print too much or print anything that isn't text are just left out
+pub async fn run_plugin(name: &str, context: &PluginContext<'_>, timeout_ms: Option<u64>) -> Option<PluginSegment> {
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(500));
+    let output = tokio::time::timeout(timeout, read_plugin(name, context)).await.ok()??;
+
+    let output = String::from_utf8(output).ok()?;
+    parse_plugin_output(output.lines().next()?)
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::session::get_session;
+use crate::text::sanitize;
+
+// Anything older than this was left behind by a shell that never made it back to its prompt
+pub const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// Left by the shell's pre-exec hook for the next prompt to pick up
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub started_ms: u64,
+    // The terminal session the command was run in, so another shell that ends up on the same tty
+    // doesn't take it for its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+fn get_now_ms() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |x| x.as_millis() as u64)
+}
+
+// Just the program, skipping over any variables set for it like FOO=1 make
+pub fn get_command_name(line: &str) -> Option<String> {
+    let word = line.split_whitespace().find(|x| !x.contains('='))?;
+    Some(sanitize(word)).filter(|x| !x.is_empty())
+}
+
+pub fn write_record(path: &Path, line: &str) -> io::Result<()> {
+    let record = Record { started_ms: get_now_ms(), session: get_session().id, command: get_command_name(line) };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string(&record).map_err(io::Error::other)?)
+}
+
+fn check_record(text: &str) -> Option<(Duration, Option<String>)> {
+    let record: Record = serde_json::from_str(text).ok()?;
+    if record.session.is_some() && record.session != get_session().id {
+        tracing::debug!(?record, "the pre-exec record is from another session");
+        return None;
+    }
+
+    let elapsed = Duration::from_millis(get_now_ms().checked_sub(record.started_ms)?);
+    if elapsed > MAX_AGE {
+        tracing::debug!(?record, "the pre-exec record is too old");
+        return None;
+    }
+
+    Some((elapsed, record.command))
+}
+
+// The record is always removed, even when it's no good, so a stale one only ever gets looked at once.
+// Gives back how long ago the command started and what it was
+pub fn take_record(path: &Path) -> Option<(Duration, Option<String>)> {
+    let text = fs::read_to_string(path).ok()?;
+    let _ = fs::remove_file(path);
+
+    check_record(&text)
+}
+
+// For a prompt that's about to be drawn again in full, which is the one that takes it
+pub fn peek_record(path: &Path) -> Option<(Duration, Option<String>)> {
+    check_record(&fs::read_to_string(path).ok()?)
+}
+
+// When the last whole prompt on the terminal was drawn. It's kept apart from the record, which is
+// only there between a command starting and the prompt after it
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct LastPrompt {
+    pub drawn_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<u32>,
+}
+
+pub fn write_last_prompt(path: &Path) -> io::Result<()> {
+    let last_prompt = LastPrompt { drawn_ms: get_now_ms(), session: get_session().id };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string(&last_prompt).map_err(io::Error::other)?)
+}
+
+// There's no limit on how old it can be since a terminal left over a long weekend is the one that
+// most needs it, but one from another shell on the same tty is nothing to do with this one
+fn check_last_prompt(text: &str) -> Option<Duration> {
+    let last_prompt: LastPrompt = serde_json::from_str(text).ok()?;
+    if last_prompt.session.is_some() && last_prompt.session != get_session().id {
+        tracing::debug!(?last_prompt, "the last prompt was drawn by another session");
+        return None;
+    }
+
+    Some(Duration::from_millis(get_now_ms().checked_sub(last_prompt.drawn_ms)?))
+}
+
+// How long ago the last prompt was drawn, which is then written over with now whether or not what
+// was there could be read, so a missing or broken file only costs a single prompt
+pub fn take_last_prompt(path: &Path) -> Option<Duration> {
+    let text = fs::read_to_string(path).ok();
+    if let Err(e) = write_last_prompt(path) {
+        tracing::debug!(error = %e, "couldn't write when the prompt was drawn");
+    }
+
+    check_last_prompt(&text?)
+}
+
+pub fn peek_last_prompt(path: &Path) -> Option<Duration> {
+    check_last_prompt(&fs::read_to_string(path).ok()?)
+}
@@ -0,0 +1,123 @@
+use crate::command::Canned;
+
+#[derive(Clone, Copy)]
+pub enum Changes {
+    Clean,
+    Changed,
+    Untracked,
+    Conflict
+}
+
+#[derive(Clone, Copy)]
+pub enum Upstream {
+    InSync,
+    Unpushed,
+    Unpulled,
+    Diverged,
+    Missing
+}
+
+// One made up prompt, with everything git and kubectl would have said for it already written down
+pub struct Scenario {
+    pub name: &'static str,
+    pub exit_code: i32,
+    // Under the home directory
+    pub path: &'static str,
+    pub repository: bool,
+    pub outputs: Vec<(&'static str, Canned)>,
+    pub aws: Option<(&'static str, &'static str)>,
+    // Narrower than the terminal so the line has to be cut down to fit
+    pub columns: Option<usize>,
+}
+
+fn get_git_outputs(branch: &'static str, changes: Changes, upstream: Upstream) -> Vec<(&'static str, Canned)> {
+    let mut outputs = vec![
+        ("git branch --show-current", Canned::Exit(0, branch)),
+        ("git tag --points-at HEAD", Canned::Exit(0, "")),
+        ("git diff --check", Canned::Exit(0, "")),
+    ];
+
+    outputs.extend(match changes {
+        Changes::Clean => vec![
+            ("git diff --quiet", Canned::Exit(0, "")),
+            ("git diff --cached --quiet", Canned::Exit(0, "")),
+            ("git ls-files --other --exclude-standard --deleted", Canned::Exit(0, "")),
+        ],
+        Changes::Changed => vec![("git diff --quiet", Canned::Exit(1, "")), ("git diff --cached --quiet", Canned::Exit(0, ""))],
+        Changes::Untracked => vec![
+            ("git diff --quiet", Canned::Exit(0, "")),
+            ("git diff --cached --quiet", Canned::Exit(0, "")),
+            ("git ls-files --other --exclude-standard --deleted", Canned::Exit(0, "notes.txt\n")),
+        ],
+        Changes::Conflict => vec![
+            ("git diff --quiet", Canned::Exit(1, "")),
+            ("git diff --cached --quiet", Canned::Exit(0, "")),
+            ("git status --porcelain", Canned::Exit(0, "UU src/main.rs\n")),
+        ]
+    });
+
+    outputs.extend(match upstream {
+        Upstream::InSync => vec![
+            ("git log @{u}..", Canned::Exit(0, "")),
+            ("git rev-parse HEAD", Canned::Exit(0, "abc\n")),
+            ("git rev-parse @{u}", Canned::Exit(0, "abc\n")),
+        ],
+        Upstream::Unpushed => vec![("git log @{u}..", Canned::Exit(0, "commit abc\n")), ("git log -1 ..@{u}", Canned::Exit(0, ""))],
+        Upstream::Unpulled => vec![
+            ("git log @{u}..", Canned::Exit(0, "")),
+            ("git rev-parse HEAD", Canned::Exit(0, "abc\n")),
+            ("git rev-parse @{u}", Canned::Exit(0, "def\n")),
+        ],
+        Upstream::Diverged => vec![("git log @{u}..", Canned::Exit(0, "commit abc\n")), ("git log -1 ..@{u}", Canned::Exit(0, "commit def\n"))],
+        Upstream::Missing => vec![
+            ("git log @{u}..", Canned::Exit(128, "")),
+            ("git rev-parse HEAD", Canned::Exit(0, "abc\n")),
+            ("git rev-parse @{u}", Canned::Exit(128, "")),
+        ]
+    });
+
+    outputs
+}
+
+fn get_k8s_outputs(context: &'static str, namespace: &'static str) -> Vec<(&'static str, Canned)> {
+    vec![
+        ("kubectl config current-context", Canned::Exit(0, context)),
+        ("kubectl config view --minify --output jsonpath={..namespace}", Canned::Exit(0, namespace)),
+    ]
+}
+
+fn get_repo_scenario(name: &'static str, exit_code: i32, branch: &'static str, changes: Changes, upstream: Upstream) -> Scenario {
+    Scenario { name, exit_code, path: "src/prompt", repository: true, outputs: get_git_outputs(branch, changes, upstream), aws: None, columns: None }
+}
+
+// Every chevron state at least once, then the segments that only some people have, then a line
+// that's too long for its terminal
+pub fn get_scenarios() -> Vec<Scenario> {
+    vec![
+        get_repo_scenario("clean and up to date", 0, "main\n", Changes::Clean, Upstream::InSync),
+        get_repo_scenario("last command failed", 1, "main\n", Changes::Clean, Upstream::InSync),
+        get_repo_scenario("uncommitted changes, unpushed", 0, "main\n", Changes::Changed, Upstream::Unpushed),
+        get_repo_scenario("untracked files, unpulled", 0, "main\n", Changes::Untracked, Upstream::Unpulled),
+        get_repo_scenario("conflicts, diverged, command failed", 1, "main\n", Changes::Conflict, Upstream::Diverged),
+        get_repo_scenario("no upstream", 0, "feature/preview\n", Changes::Clean, Upstream::Missing),
+        Scenario { name: "outside a repository", exit_code: 0, path: "Downloads", repository: false, outputs: vec![], aws: None, columns: None },
+        Scenario {
+            name: "with kubernetes and aws",
+            exit_code: 0,
+            path: "src/infra",
+            repository: true,
+            outputs: get_git_outputs("main\n", Changes::Changed, Upstream::InSync).into_iter().chain(get_k8s_outputs("prod-eu\n", "payments")).collect(),
+            aws: Some(("work", "eu-west-1")),
+            columns: None
+        },
+        Scenario {
+            name: "long path in a narrow terminal",
+            exit_code: 0,
+            path: "src/work/platform/services/billing/internal/reconciliation",
+            repository: true,
+            outputs: get_git_outputs("feature/reconcile-invoices\n", Changes::Changed, Upstream::Unpushed).into_iter().chain(get_k8s_outputs("prod-eu\n", "billing")).collect(),
+            aws: Some(("work", "eu-west-1")),
+            columns: Some(50)
+        },
+    ]
+}
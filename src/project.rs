@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::Path;
+
+use crate::path::get_home_directory;
+use crate::plugin::find_repo_root;
+use crate::text::sanitize;
+
+pub const DEFAULT_MANIFESTS: [&str; 4] = ["Cargo.toml", "package.json", "BUILD.bazel", "pyproject.toml"];
+
+// Manifests are tiny so reading one is cheaper than it sounds, and anything that can't be read
+// just falls back to the directory's name
+fn get_package_name(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+
+    let name = match path.file_name()?.to_str()? {
+        "Cargo.toml" => text.parse::<toml::Table>().ok()?.get("package")?.get("name")?.as_str()?.to_owned(),
+        "pyproject.toml" => {
+            let table = text.parse::<toml::Table>().ok()?;
+            let project = table.get("project").or_else(|| table.get("tool")?.get("poetry"))?;
+            project.get("name")?.as_str()?.to_owned()
+        },
+        "package.json" => serde_json::from_str::<serde_json::Value>(&text).ok()?.get("name")?.as_str()?.to_owned(),
+        _ => return None
+    };
+
+    Some(name)
+}
+
+// The closest directory with a manifest in, between here and the top of the repository. One at the
+// top itself is just the repository so isn't worth showing, and nothing's shown outside of one
+pub fn find_project(dir: &Path, manifests: &[String]) -> Option<String> {
+    let root = find_repo_root(dir)?;
+    let home = get_home_directory();
+
+    for ancestor in dir.ancestors() {
+        if ancestor == root || home.as_deref() == Some(ancestor) {
+            return None;
+        }
+
+        if let Some(manifest) = manifests.iter().map(|x| ancestor.join(x)).find(|x| x.is_file()) {
+            tracing::debug!(manifest = %manifest.display(), "found the project");
+            let name = get_package_name(&manifest).or_else(|| ancestor.file_name().map(|x| x.to_string_lossy().into_owned()))?;
+            return Some(sanitize(&name)).filter(|x| !x.is_empty());
+        }
+    }
+
+    None
+}
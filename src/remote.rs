@@ -0,0 +1,175 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::osc::percent_encode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Forge {
+    Github,
+    Gitlab,
+    Gitea
+}
+
+// Self hosted forges can be on any host at all so they have to be named
+#[derive(Clone, Debug)]
+pub struct ForgeHost {
+    pub host: String,
+    pub forge: Forge,
+}
+
+pub fn parse_forge_host(s: &str) -> Result<ForgeHost, String> {
+    let (host, forge) = s.split_once('=').ok_or("expected HOST=FORGE")?;
+    if host.is_empty() {
+        return Err("expected HOST=FORGE".to_owned());
+    }
+
+    Ok(ForgeHost { host: host.to_owned(), forge: Forge::from_str(forge, true)? })
+}
+
+// A host given a label of its own to show before the branch, which can be empty to show nothing
+#[derive(Clone, Debug)]
+pub struct RemoteLabel {
+    pub host: String,
+    pub label: String,
+}
+
+pub fn parse_remote_label(s: &str) -> Result<RemoteLabel, String> {
+    let (host, label) = s.split_once('=').ok_or("expected HOST=LABEL")?;
+    if host.is_empty() {
+        return Err("expected HOST=LABEL".to_owned());
+    }
+
+    Ok(RemoteLabel { host: host.to_owned(), label: label.to_owned() })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RemoteUrl<'a> {
+    pub scheme: &'a str,
+    pub host: &'a str,
+    pub port: Option<&'a str>,
+    // Like org/repo, which GitLab lets go on to subgroups
+    pub path: &'a str,
+}
+
+impl RemoteUrl<'_> {
+    // Self hosted forges are usually on a plain http server when the remote says so. An ssh port
+    // isn't where the web pages are so only a port for the web is kept
+    pub fn get_base_url(&self) -> String {
+        let scheme = if self.scheme == "http" { "http" } else { "https" };
+        match self.port.filter(|_| matches!(self.scheme, "http" | "https")) {
+            Some(port) => format!("{}://{}:{}", scheme, self.host, port),
+            None => format!("{}://{}", scheme, self.host)
+        }
+    }
+
+    // A forge's API can be sent a token so it's only ever asked over https, even when the remote's
+    // on plain http, and only an https remote's port is known to be where https is
+    pub fn get_api_base_url(&self) -> String {
+        match self.port.filter(|_| self.scheme == "https") {
+            Some(port) => format!("https://{}:{}", self.host, port),
+            None => format!("https://{}", self.host)
+        }
+    }
+}
+
+// Splits git@github.com:org/repo.git, ssh://git@github.com:22/org/repo.git and
+// https://github.com/org/repo.git alike into the host and the repo's path
+pub fn parse_remote_url(remote: &str) -> Option<RemoteUrl<'_>> {
+    let (scheme, host, path) = match remote.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            (scheme, authority, path)
+        },
+        // scp style remotes have their colon before any slash, without one it's a local path
+        None if remote.find(':').is_some_and(|colon| remote.find('/').is_none_or(|slash| colon < slash)) => {
+            let (host, path) = remote.split_once(':')?;
+            ("ssh", host, path)
+        },
+        None => return None
+    };
+
+    let host = host.rsplit_once('@').map_or(host, |(_, h)| h);
+    let (host, port) = match host.split_once(':') {
+        Some((host, port)) => (host, Some(port).filter(|x| !x.is_empty())),
+        None => (host, None)
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    if host.is_empty() || !path.contains('/') {
+        return None;
+    }
+
+    Some(RemoteUrl { scheme, host, port, path })
+}
+
+// What git config --null --get-regexp gives for url.<base>.insteadOf, as each prefix and the base
+// it's swapped for
+pub fn parse_instead_of(text: &str) -> Vec<(String, String)> {
+    text.split('\0').filter_map(|entry| {
+        let (key, prefix) = entry.split_once('\n')?;
+        let base = key.strip_prefix("url.")?.strip_suffix(".insteadof")?;
+        Some((prefix.to_owned(), base.to_owned())).filter(|(prefix, _)| !prefix.is_empty())
+    }).collect()
+}
+
+// Like git, the longest prefix that matches is the one that's used
+pub fn apply_instead_of(url: &str, rewrites: &[(String, String)]) -> String {
+    let rewrite = rewrites.iter().filter(|(prefix, _)| url.starts_with(prefix.as_str())).max_by_key(|(prefix, _)| prefix.len());
+    match rewrite {
+        Some((prefix, base)) => format!("{}{}", base, &url[prefix.len()..]),
+        None => url.to_owned()
+    }
+}
+
+// The hosts given win, then the big public ones and anything with gitlab or gitea in its name
+pub fn get_forge(host: &str, hosts: &[ForgeHost]) -> Option<Forge> {
+    if let Some(known) = hosts.iter().rev().find(|x| x.host.eq_ignore_ascii_case(host)) {
+        return Some(known.forge);
+    }
+
+    let host = host.to_ascii_lowercase();
+    match host.as_str() {
+        "github.com" | "www.github.com" => Some(Forge::Github),
+        "codeberg.org" | "gitea.com" => Some(Forge::Gitea),
+        _ if host.split('.').any(|part| part == "gitlab") => Some(Forge::Gitlab),
+        _ if host.split('.').any(|part| part == "gitea") => Some(Forge::Gitea),
+        _ => None
+    }
+}
+
+// What's shown before the branch for where origin is. The labels given win, then the public
+// forges and anything else that's known to be a forge are named after it
+pub fn get_remote_label(host: &str, labels: &[RemoteLabel], hosts: &[ForgeHost]) -> Option<String> {
+    if let Some(known) = labels.iter().rev().find(|x| x.host.eq_ignore_ascii_case(host)) {
+        return Some(known.label.clone()).filter(|x| !x.is_empty());
+    }
+
+    let name = match host.to_ascii_lowercase().as_str() {
+        "bitbucket.org" => "bitbucket",
+        "codeberg.org" => "codeberg",
+        _ => match get_forge(host, hosts)? {
+            Forge::Github => "github",
+            Forge::Gitlab => "gitlab",
+            Forge::Gitea => "gitea"
+        }
+    };
+    Some(name.to_owned())
+}
+
+// Anything that isn't on a forge doesn't get a link
+pub fn get_branch_url(remote: &str, branch: &str, hosts: &[ForgeHost]) -> Option<String> {
+    let remote = parse_remote_url(remote)?;
+    let branch = percent_encode(branch.as_bytes());
+
+    match get_forge(remote.host, hosts)? {
+        Forge::Github if remote.host.eq_ignore_ascii_case("github.com") || remote.host.eq_ignore_ascii_case("www.github.com") => {
+            Some(format!("https://github.com/{}/tree/{}", remote.path, branch))
+        },
+        Forge::Github => Some(format!("{}/{}/tree/{}", remote.get_base_url(), remote.path, branch)),
+        Forge::Gitlab => Some(format!("{}/{}/-/tree/{}", remote.get_base_url(), remote.path, branch)),
+        Forge::Gitea => Some(format!("{}/{}/src/branch/{}", remote.get_base_url(), remote.path, branch))
+    }
+}
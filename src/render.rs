@@ -0,0 +1,212 @@
+use std::env;
+use std::io::{stdout, IsTerminal};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::ci::is_ci;
+use crate::colour::{get_bg_code, get_fg_code, get_colour_name, ColourDepth};
+use crate::glyph::Glyphs;
+use crate::osc::{get_link_start, LINK_END};
+use crate::styled::{Span, Styling, Stylize};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(name = "powershell")]
+    PowerShell,
+    Nu,
+    Elvish
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColourMode {
+    Always,
+    Never,
+    Auto
+}
+
+fn is_env_set(name: &str) -> bool {
+    env::var_os(name).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+// Prompts are always captured by the shell so they look like a pipe, which means colour is on
+// unless something says otherwise. CI logs are pipes too but don't want escape codes in them, so
+// there it's the same as auto
+pub fn get_colour_enabled(mode: Option<ColourMode>) -> bool {
+    match mode {
+        Some(ColourMode::Always) => true,
+        Some(ColourMode::Never) => false,
+        _ if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => false,
+        _ if is_env_set("CLICOLOR_FORCE") => true,
+        Some(ColourMode::Auto) => stdout().is_terminal(),
+        None if is_ci() => stdout().is_terminal(),
+        None => true
+    }
+}
+
+// conhost needs to be told to understand colour codes rather than printing them, and to read what
+// it's given as UTF-8 whichever code page it was left on. Neither matters when it isn't a console
+#[cfg(windows)]
+pub fn set_up_console() {
+    use windows_sys::Win32::Globalization::CP_UTF8;
+    use windows_sys::Win32::System::Console::{GetConsoleMode, GetStdHandle, SetConsoleMode, SetConsoleOutputCP, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_OUTPUT_HANDLE};
+
+    unsafe {
+        SetConsoleOutputCP(CP_UTF8);
+
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) != 0 {
+            SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Ansi,
+    Tmux,
+    Json
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Style {
+    Plain,
+    Powerline
+}
+
+const RESET: &str = "\x1b[0m";
+
+// The attributes in the order colored wrote them, so everything comes out as it always did
+fn get_attributes(styling: &Styling) -> [(bool, &'static str, &'static str); 8] {
+    [
+        (styling.bold, "1", "bold"),
+        (styling.dimmed, "2", "dim"),
+        (styling.underline, "4", "underscore"),
+        (styling.reversed, "7", "reverse"),
+        (styling.italic, "3", "italics"),
+        (styling.blink, "5", "blink"),
+        (styling.hidden, "8", "hidden"),
+        (styling.strikethrough, "9", "strikethrough"),
+    ]
+}
+
+fn get_sgr(styling: &Styling, depth: ColourDepth) -> Option<String> {
+    let codes: Vec<_> = get_attributes(styling).iter()
+        .filter(|(on, _, _)| *on)
+        .map(|(_, code, _)| (*code).into())
+        .chain(styling.bg.map(|c| get_bg_code(c, depth)))
+        .chain(styling.fg.map(|c| get_fg_code(c, depth)))
+        .collect();
+
+    if codes.is_empty() {
+        None
+    } else {
+        Some(format!("\x1b[{}m", codes.join(";")))
+    }
+}
+
+// The same styles written the way tmux's status line wants them
+fn get_tmux_style(styling: &Styling) -> Option<String> {
+    let attributes: Vec<_> = get_attributes(styling).iter()
+        .filter(|(on, _, _)| *on)
+        .map(|(_, _, name)| (*name).to_owned())
+        .chain(styling.bg.map(|c| format!("bg={}", get_colour_name(c))))
+        .chain(styling.fg.map(|c| format!("fg={}", get_colour_name(c))))
+        .collect();
+
+    if attributes.is_empty() {
+        None
+    } else {
+        Some(format!("#[{}]", attributes.join(",")))
+    }
+}
+
+pub struct Renderer {
+    pub format: Format,
+    pub shell: Option<Shell>,
+    pub colour: bool,
+    pub depth: ColourDepth,
+    pub glyphs: Glyphs,
+}
+
+impl Renderer {
+    // Anything that doesn't take up space on screen, shells need telling or they'll miscount the
+    // prompt's length
+    pub fn non_printing(&self, sequence: &str) -> String {
+        // Escape sequences would end up drawn as text in the status line
+        if self.format == Format::Tmux {
+            return "".to_owned();
+        }
+
+        match self.shell {
+            Some(Shell::Bash) => format!("\\[{}\\]", sequence),
+            Some(Shell::Zsh) => format!("%{{{}%}}", sequence),
+            Some(Shell::Fish | Shell::PowerShell | Shell::Nu | Shell::Elvish) | None => sequence.to_owned()
+        }
+    }
+
+    pub fn text(&self, text: &str) -> String {
+        let text = self.glyphs.draw(text);
+
+        if self.format == Format::Tmux {
+            return text.replace('#', "##");
+        }
+
+        match self.shell {
+            // PS1 has its escapes decoded and is then expanded again, so these need escaping twice
+            Some(Shell::Bash) => text.replace('\\', "\\\\\\\\").replace('`', "\\\\`").replace('$', "\\\\$"),
+            Some(Shell::Zsh) => text.replace('%', "%%"),
+            Some(Shell::Fish | Shell::PowerShell | Shell::Nu | Shell::Elvish) | None => text
+        }
+    }
+
+    pub fn span(&self, span: &Span) -> String {
+        if self.format == Format::Tmux {
+            return match get_tmux_style(&span.styling).filter(|_| self.colour) {
+                Some(style) => style + &self.text(span) + "#[default]",
+                None => self.text(span)
+            };
+        }
+
+        match get_sgr(&span.styling, self.depth).filter(|_| self.colour) {
+            Some(sgr) => self.non_printing(&sgr) + &self.text(span) + &self.non_printing(RESET),
+            None => self.text(span)
+        }
+    }
+
+    pub fn segment(&self, segment: &[Span]) -> String {
+        segment.iter().map(|span| self.span(span)).collect()
+    }
+
+    pub fn link(&self, url: Option<&str>, text: String) -> String {
+        match url {
+            Some(url) => self.non_printing(&self.text(&get_link_start(url))) + &text + &self.non_printing(LINK_END),
+            None => text
+        }
+    }
+
+    // Each separator is drawn in the colour of the segment before it on top of the colour of the
+    // one after, and the last one fades into the terminal's background
+    pub fn powerline(&self, segments: &[Vec<Span>], links: &[Option<&str>]) -> String {
+        let backgrounds: Vec<_> = segments.iter().map(|s| s.first().and_then(|x| x.styling.bg)).collect();
+
+        segments.iter().enumerate().map(|(i, segment)| {
+            let mut separator = "\u{E0B0}".normal();
+            if let Some(colour) = backgrounds[i] {
+                separator = separator.color(colour);
+            }
+            if let Some(colour) = backgrounds.get(i + 1).copied().flatten() {
+                separator = separator.on_color(colour);
+            }
+
+            self.link(links[i], self.segment(segment)) + &self.span(&separator)
+        }).collect()
+    }
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::chevron::State;
+use crate::colour::get_colour_name;
+use crate::layout::Segment;
+use crate::segment::{Reason, SegmentKind, SegmentResult, Side};
+use crate::styled::Span;
+
+// Remembers how long each segment took to work out and why any came out empty, for the JSON
+// output, --timings and --explain-missing. Anything that isn't a segment of its own, like custom
+// segments, goes by a name
+#[derive(Default)]
+pub struct Detections {
+    timings: RefCell<Vec<(String, Duration)>>,
+    reasons: RefCell<Vec<(SegmentKind, Reason)>>,
+}
+
+impl Detections {
+    pub async fn time_named<T>(&self, name: String, future: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let value = future.await;
+        self.timings.borrow_mut().push((name, start.elapsed()));
+        value
+    }
+
+    pub async fn time<T>(&self, segment: SegmentKind, future: impl Future<Output = T>) -> T {
+        self.time_named(get_name(segment), future).await
+    }
+
+    // Failures look the same as there being nothing to show everywhere else
+    pub fn record<T>(&self, segment: SegmentKind, result: SegmentResult<T>) -> Option<T> {
+        match result {
+            SegmentResult::Present(x) => Some(x),
+            SegmentResult::Absent(reason) => {
+                self.reasons.borrow_mut().push((segment, reason));
+                None
+            }
+        }
+    }
+
+    pub async fn run<T>(&self, segment: SegmentKind, future: impl Future<Output = SegmentResult<T>>) -> Option<T> {
+        let result = self.time(segment, future).await;
+        self.record(segment, result)
+    }
+
+    pub fn get_slowest(&self) -> Option<(String, Duration)> {
+        self.timings.borrow().iter().max_by_key(|(_, duration)| *duration).cloned()
+    }
+
+    pub fn get_reason(&self, segment: SegmentKind) -> Option<Reason> {
+        self.reasons.borrow().iter().find(|(k, _)| *k == segment).map(|(_, r)| r.clone())
+    }
+
+    fn get_error(&self, name: &str) -> Option<String> {
+        self.reasons.borrow().iter().find_map(|(k, r)| match r {
+            Reason::Failed(error) if get_name(*k) == name => Some(error.clone()),
+            _ => None
+        })
+    }
+}
+
+// Everything that was timed in the order it finished, how often each kind of cache entry was there
+// to be used, and how long the whole prompt took
+pub fn get_timings_table(detections: &Detections, cache_counts: &[(String, usize, usize)], total: Duration) -> String {
+    let to_ms = |x: Duration| format!("{:.1}ms", x.as_secs_f64() * 1000.0);
+
+    let mut table: String = detections.timings.borrow().iter().map(|(name, duration)| {
+        let timed_out = detections.get_error(name).is_some_and(|e| e.contains("timed out"));
+        format!("{:<24} {:>9}{}\n", name, to_ms(*duration), if timed_out { "  timed out" } else { "" })
+    }).collect();
+
+    for (name, hits, misses) in cache_counts {
+        let plural = |count: usize, word: &str, words: &str| format!("{} {}", count, if count == 1 { word } else { words });
+        table += &format!("{:<24} {}, {}\n", format!("cache {}", name), plural(*hits, "hit", "hits"), plural(*misses, "miss", "misses"));
+    }
+
+    table += &format!("{:<24} {:>9}\n", "total", to_ms(total));
+    table
+}
+
+#[derive(Serialize)]
+struct SpanStyle {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+    reversed: bool,
+}
+
+#[derive(Serialize)]
+struct SpanReport {
+    text: String,
+    style: SpanStyle,
+}
+
+#[derive(Serialize)]
+struct SegmentReport {
+    name: String,
+    side: String,
+    text: Option<String>,
+    spans: Vec<SpanReport>,
+    duration_ms: Option<f64>,
+    error: Option<String>,
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    exit_code: i32,
+    pipestatus: Vec<i32>,
+    exit_state: String,
+    uncommitted_changes: Option<String>,
+    unpushed_changes: Option<String>,
+    segments: Vec<SegmentReport>,
+}
+
+fn get_name(value: impl ValueEnum) -> String {
+    value.to_possible_value().map_or_else(String::new, |v| v.get_name().to_owned())
+}
+
+fn get_span_report(span: &Span) -> SpanReport {
+    let styling = &span.styling;
+    SpanReport {
+        text: span.text.clone(),
+        style: SpanStyle {
+            fg: styling.fg.map(|c| get_colour_name(c).into_owned()),
+            bg: styling.bg.map(|c| get_colour_name(c).into_owned()),
+            bold: styling.bold,
+            dimmed: styling.dimmed,
+            italic: styling.italic,
+            underline: styling.underline,
+            reversed: styling.reversed,
+        }
+    }
+}
+
+pub struct ReportStates {
+    pub exit: State,
+    pub changes: Option<State>,
+    pub upstream: Option<State>,
+}
+
+// Segments that aren't shown are still listed, just without any text, and any that should have been
+// say why they're empty
+pub fn get_report(
+    exit_code: i32,
+    pipestatus: &[i32],
+    states: ReportStates,
+    segments: &[(SegmentKind, Side, Segment)],
+    detections: &Detections,
+    missing: &[(SegmentKind, Reason)]
+) -> String {
+    let timings = detections.timings.borrow();
+
+    let report = Report {
+        exit_code,
+        pipestatus: pipestatus.to_vec(),
+        exit_state: get_name(states.exit),
+        uncommitted_changes: states.changes.map(get_name),
+        unpushed_changes: states.upstream.map(get_name),
+        segments: segments.iter().map(|(kind, side, segment)| SegmentReport {
+            name: get_name(*kind),
+            side: get_name(*side),
+            text: Some(segment.iter().map(|s| &**s).collect::<String>()).filter(|t| !t.is_empty()),
+            spans: segment.iter().map(get_span_report).collect(),
+            duration_ms: timings.iter().find(|(k, _)| *k == get_name(*kind)).map(|(_, d)| d.as_secs_f64() * 1000.0),
+            error: detections.get_error(&get_name(*kind)),
+            reason: missing.iter().find(|(k, _)| k == kind).map(|(_, r)| r.to_string()),
+        }).collect()
+    };
+
+    serde_json::to_string(&report).unwrap_or_default()
+}
@@ -0,0 +1,545 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser};
+use clap::ValueEnum;
+use colored::Color;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::chevron::{Position, State};
+use crate::colour::parse_colour;
+use crate::command::CommandRunner;
+use crate::condition::{parse_condition, Condition};
+use crate::config::Config;
+use crate::path::WorkingDirectory;
+use crate::store::Store;
+use crate::styled::Span;
+use crate::text::EllipsisPosition;
+use crate::theme::Theme;
+
+// The families of segments that were built in
+const FAMILIES: &[&str] = &[
+    #[cfg(feature = "git")]
+    "git",
+    #[cfg(feature = "kubernetes")]
+    "kubernetes",
+    #[cfg(feature = "aws")]
+    "aws",
+    #[cfg(feature = "svn")]
+    "svn",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SegmentKind {
+    Path,
+    Message,
+    Project,
+    GitRemote,
+    GitBranch,
+    GitStatus,
+    GitPush,
+    GitState,
+    GitErrors,
+    GitUnmerged,
+    GitLfs,
+    GitMergeRequest,
+    SvnBranch,
+    SvnStatus,
+    K8sContext,
+    K8sReachable,
+    K8sNamespace,
+    HelmChart,
+    AwsProfile,
+    AwsAccount,
+    AwsRegion,
+    TmuxSession,
+    Ci,
+    Sudo,
+    Battery,
+    Load,
+    Memory,
+    Network,
+    Idle,
+    Duration,
+    Jobs,
+    Pipestatus,
+    // Segments from the config, which can't be picked out by name like the rest
+    #[value(skip)]
+    #[serde(skip)]
+    Custom
+}
+
+impl SegmentKind {
+    // When the line's too long the lowest priority segments are dropped first
+    pub fn priority(&self) -> u8 {
+        match self {
+            SegmentKind::Path => 100,
+            SegmentKind::GitBranch => 90,
+            SegmentKind::GitRemote => 52,
+            SegmentKind::GitStatus => 85,
+            SegmentKind::GitPush => 68,
+            SegmentKind::Message => 80,
+            SegmentKind::GitState => 70,
+            SegmentKind::GitUnmerged => 65,
+            SegmentKind::GitLfs => 63,
+            SegmentKind::GitErrors => 60,
+            SegmentKind::GitMergeRequest => 62,
+            SegmentKind::SvnBranch => 90,
+            SegmentKind::SvnStatus => 85,
+            SegmentKind::Pipestatus => 75,
+            SegmentKind::Project => 72,
+            SegmentKind::Jobs => 58,
+            SegmentKind::Sudo => 55,
+            SegmentKind::Battery => 50,
+            SegmentKind::Load => 48,
+            SegmentKind::Memory => 49,
+            SegmentKind::Network => 46,
+            SegmentKind::Idle => 42,
+            SegmentKind::Duration => 45,
+            SegmentKind::K8sContext => 40,
+            SegmentKind::K8sReachable => 38,
+            SegmentKind::AwsProfile => 35,
+            SegmentKind::AwsAccount => 32,
+            SegmentKind::TmuxSession => 30,
+            SegmentKind::Ci => 28,
+            SegmentKind::Custom => 25,
+            SegmentKind::K8sNamespace => 20,
+            SegmentKind::HelmChart => 15,
+            SegmentKind::AwsRegion => 10
+        }
+    }
+
+    pub fn icon(&self, style: IconStyle) -> Option<&'static str> {
+        match style {
+            IconStyle::Nerd => match self {
+                SegmentKind::Path => Some("\u{F07C}"),
+                SegmentKind::GitBranch | SegmentKind::SvnBranch => Some("\u{E0A0}"),
+                SegmentKind::K8sContext => Some("\u{2638}"),
+                SegmentKind::AwsProfile => Some("\u{F270}"),
+                SegmentKind::TmuxSession => Some("\u{EBC8}"),
+                SegmentKind::Duration => Some("\u{F017}"),
+                _ => None
+            },
+            IconStyle::Emoji => match self {
+                SegmentKind::Path => Some("📂"),
+                SegmentKind::GitBranch | SegmentKind::SvnBranch => Some("🌿"),
+                SegmentKind::K8sContext => Some("☸\u{FE0F}"),
+                SegmentKind::AwsProfile => Some("☁\u{FE0F}"),
+                SegmentKind::TmuxSession => Some("🪟"),
+                SegmentKind::Duration => Some("⏱\u{FE0F}"),
+                _ => None
+            },
+            IconStyle::None => None
+        }
+    }
+
+    // Said before the segment when nothing else is asked for, for ones that could be mistaken for
+    // something else
+    pub fn default_label(&self) -> Option<&'static str> {
+        match self {
+            SegmentKind::Project => Some("pkg:"),
+            SegmentKind::HelmChart => Some("⎈ "),
+            _ => None
+        }
+    }
+
+    // Where a segment that's too wide loses text from. Contexts and paths tend to say the most at
+    // the end
+    pub fn default_ellipsis(&self) -> EllipsisPosition {
+        match self {
+            SegmentKind::Path | SegmentKind::K8sContext | SegmentKind::K8sNamespace => EllipsisPosition::Start,
+            _ => EllipsisPosition::End
+        }
+    }
+
+    // What prompt explain says each segment shows, and when it isn't there
+    pub fn description(&self) -> &'static str {
+        match self {
+            SegmentKind::Path => "the current directory",
+            SegmentKind::Message => "whatever was passed to --message",
+            SegmentKind::Project => "the package of a monorepo you're in",
+            SegmentKind::GitRemote => "which forge or host origin is on",
+            SegmentKind::GitBranch => "the branch, or the commit when no branch is checked out, and any tags on it",
+            SegmentKind::GitStatus => "symbols for uncommitted and unpushed changes",
+            SegmentKind::GitPush => "a △ when git push goes somewhere other than the upstream, which the unpushed chevron is then about",
+            SegmentKind::GitState => "a rebase, merge, cherry-pick, revert or bisect that's in progress",
+            SegmentKind::GitErrors => "a warning when git diff --check finds whitespace errors or conflict markers",
+            SegmentKind::GitUnmerged => "how many files have conflicts",
+            SegmentKind::GitLfs => "a warning when there are LFS objects that haven't been pushed",
+            SegmentKind::GitMergeRequest => "the open merge request for the branch on GitLab or Gitea and how its pipeline is doing",
+            SegmentKind::SvnBranch => "the trunk, branch or tag an svn working copy is on",
+            SegmentKind::SvnStatus => "whether an svn working copy has changes, drawn in the chevrons",
+            SegmentKind::K8sContext => "kubectl's current context",
+            SegmentKind::K8sReachable => "a mark when the current cluster's API server can't be reached",
+            SegmentKind::K8sNamespace => "the current context's namespace",
+            SegmentKind::HelmChart => "the name and version of the Helm chart you're in",
+            SegmentKind::AwsProfile => "AWS_PROFILE",
+            SegmentKind::AwsAccount => "the AWS account and role of AWS_ROLE_ARN or the credentials the CLI last cached",
+            SegmentKind::AwsRegion => "AWS_REGION or AWS_DEFAULT_REGION",
+            SegmentKind::TmuxSession => "the tmux session's name",
+            SegmentKind::Ci => "which CI system this is and the number of the run",
+            SegmentKind::Sudo => "that sudo won't ask for a password",
+            SegmentKind::Battery => "how much charge the battery has left",
+            SegmentKind::Load => "the load average over the last minute",
+            SegmentKind::Memory => "how much memory is still available",
+            SegmentKind::Network => "which network interfaces are up and the local address",
+            SegmentKind::Idle => "how long the terminal was left at the previous prompt, when what it said may be out of date",
+            SegmentKind::Duration => "how long the last command took",
+            SegmentKind::Jobs => "how many jobs are in the background",
+            SegmentKind::Pipestatus => "the exit code of each command in the last pipeline, or of a failed command along with its name",
+            SegmentKind::Custom => "the output of a command from the config"
+        }
+    }
+
+    pub fn hidden_when(&self) -> &'static str {
+        match self {
+            SegmentKind::Path => "the directory can't be worked out",
+            SegmentKind::Message => "there isn't one",
+            SegmentKind::Project => "at the top of a repository and outside of one",
+            SegmentKind::GitRemote => "there's no origin or its host isn't known",
+            SegmentKind::GitBranch | SegmentKind::GitErrors => "outside a git repository",
+            SegmentKind::GitStatus => "the chevrons show it instead",
+            SegmentKind::GitPush => "git push goes to the upstream",
+            SegmentKind::GitState => "nothing's in progress",
+            SegmentKind::GitUnmerged => "there are no conflicts",
+            SegmentKind::GitLfs => "every LFS object has been pushed or the repository doesn't use LFS",
+            SegmentKind::GitMergeRequest => "there isn't one or the forge doesn't answer in time",
+            SegmentKind::SvnBranch => "outside an svn working copy",
+            SegmentKind::SvnStatus => "the chevrons show it instead",
+            SegmentKind::K8sContext | SegmentKind::K8sNamespace => "kubectl isn't set up or doesn't answer in time",
+            SegmentKind::K8sReachable => "the API server answers or the cluster is never probed",
+            SegmentKind::HelmChart => "there's no Chart.yaml between here and the top of the repository",
+            SegmentKind::AwsProfile | SegmentKind::AwsRegion => "it isn't set",
+            SegmentKind::AwsAccount => "no role has been assumed",
+            SegmentKind::TmuxSession => "not in tmux",
+            SegmentKind::Ci => "not in CI",
+            SegmentKind::Sudo => "sudo would ask for a password",
+            SegmentKind::Battery => "the battery isn't draining or is above the threshold",
+            SegmentKind::Load => "the load is below the threshold for the number of cores",
+            SegmentKind::Memory => "more memory is available than the threshold",
+            SegmentKind::Network => "none of the interfaces are there and there's no route out",
+            SegmentKind::Idle => "the previous prompt was drawn less than an hour ago",
+            SegmentKind::Duration => "the last command took less than 2 seconds",
+            SegmentKind::Jobs => "there are no background jobs",
+            SegmentKind::Pipestatus => "the last command succeeded",
+            SegmentKind::Custom => "the command prints nothing"
+        }
+    }
+
+    // The cargo feature a segment comes with, for the ones that can be left out of the build
+    pub fn family(&self) -> Option<&'static str> {
+        match self {
+            SegmentKind::GitRemote | SegmentKind::GitBranch | SegmentKind::GitStatus | SegmentKind::GitPush | SegmentKind::GitState | SegmentKind::GitErrors | SegmentKind::GitUnmerged | SegmentKind::GitLfs | SegmentKind::GitMergeRequest => Some("git"),
+            SegmentKind::K8sContext | SegmentKind::K8sReachable | SegmentKind::K8sNamespace | SegmentKind::HelmChart => Some("kubernetes"),
+            SegmentKind::AwsProfile | SegmentKind::AwsAccount | SegmentKind::AwsRegion => Some("aws"),
+            SegmentKind::SvnBranch | SegmentKind::SvnStatus => Some("svn"),
+            _ => None
+        }
+    }
+
+    // Segments left out of the build are still known about so settings for them can be ignored
+    pub fn is_available(&self) -> bool {
+        self.family().is_none_or(|x| FAMILIES.contains(&x))
+    }
+
+    // What these show is the name of something that could be production, so danger patterns are
+    // checked against them
+    pub fn is_sensitive(&self) -> bool {
+        matches!(self, SegmentKind::K8sContext | SegmentKind::K8sNamespace | SegmentKind::AwsProfile | SegmentKind::AwsAccount)
+    }
+
+    // Everything that can be drawn straight away without running anything
+    pub fn is_instant(&self) -> bool {
+        (!matches!(self.family(), Some("git" | "svn" | "kubernetes")) || *self == SegmentKind::HelmChart) && !matches!(self, SegmentKind::TmuxSession | SegmentKind::Sudo | SegmentKind::Custom)
+    }
+
+    // Off unless there's a flag saying otherwise, usually because they're slow or only wanted now
+    // and then
+    pub fn is_opt_in(&self) -> bool {
+        matches!(
+            self,
+            SegmentKind::GitRemote | SegmentKind::GitLfs | SegmentKind::GitMergeRequest | SegmentKind::K8sReachable | SegmentKind::AwsAccount | SegmentKind::TmuxSession
+                | SegmentKind::Ci | SegmentKind::Sudo | SegmentKind::Battery | SegmentKind::Load | SegmentKind::Memory | SegmentKind::Network
+        )
+    }
+
+    // These look around the current directory so there's nothing for them once it's been deleted
+    pub fn needs_directory(&self) -> bool {
+        matches!(self.family(), Some("git" | "svn" | "kubernetes")) || *self == SegmentKind::Project
+    }
+
+    // The chevrons are drawn from these, so they're worked out even when they aren't shown
+    pub fn feeds_chevrons(&self) -> bool {
+        matches!(self, SegmentKind::GitStatus | SegmentKind::GitUnmerged | SegmentKind::SvnStatus)
+    }
+
+    // What the title can be filled in with
+    pub fn is_in_title(&self) -> bool {
+        matches!(self, SegmentKind::Path | SegmentKind::GitBranch | SegmentKind::SvnBranch)
+    }
+
+    // Things about the last command go on the right where the shell has somewhere to put them
+    pub fn default_side(&self) -> Side {
+        match self {
+            SegmentKind::Duration | SegmentKind::Jobs | SegmentKind::Pipestatus => Side::Right,
+            _ => Side::Left
+        }
+    }
+}
+
+// Segments can be picked out by name or by what they start with, so k8s covers every k8s- segment
+pub fn get_segment_group(name: &str) -> Vec<SegmentKind> {
+    SegmentKind::value_variants().iter().copied().filter(|x| x.to_possible_value().is_some_and(|v| {
+        v.get_name() == name || v.get_name().strip_prefix(name).is_some_and(|rest| rest.starts_with('-'))
+    })).collect()
+}
+
+pub fn parse_segment_group(s: &str) -> Result<Vec<SegmentKind>, String> {
+    let segments = get_segment_group(s);
+    match segments.is_empty() {
+        true => Err(format!("unknown segment '{}'", s)),
+        false => Ok(segments)
+    }
+}
+
+// Any name is let through so a typo can be complained about instead of refused, but the segments
+// and the groups of them are still there for completions
+#[derive(Clone)]
+pub struct SegmentGroupParser;
+
+impl TypedValueParser for SegmentGroupParser {
+    type Value = String;
+
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &OsStr) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let names: Vec<String> = SegmentKind::value_variants().iter().filter_map(|x| x.to_possible_value()).map(|x| x.get_name().to_owned()).collect();
+
+        let mut groups: Vec<String> = names.iter().filter_map(|x| x.split_once('-')).map(|(group, _)| group.to_owned())
+            .filter(|x| get_segment_group(x).len() > 1)
+            .collect();
+        groups.dedup();
+
+        Some(Box::new(names.into_iter().chain(groups).map(PossibleValue::new)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconStyle {
+    Nerd,
+    Emoji,
+    None
+}
+
+#[derive(Clone, Debug)]
+pub struct SegmentIcon {
+    pub segment: SegmentKind,
+    pub icon: String,
+}
+
+pub fn parse_segment_icon(s: &str) -> Result<SegmentIcon, String> {
+    let (segment, icon) = s.split_once('=').ok_or("expected SEGMENT=ICON")?;
+
+    Ok(SegmentIcon { segment: SegmentKind::from_str(segment, false)?, icon: icon.to_owned() })
+}
+
+// Icons given for a segment replace the one from the style, and an empty one removes it
+pub fn get_icon(segment: SegmentKind, style: IconStyle, icons: &[SegmentIcon]) -> Option<String> {
+    match icons.iter().rev().find(|i| i.segment == segment) {
+        Some(icon) => Some(icon.icon.clone()).filter(|i| !i.is_empty()),
+        None => segment.icon(style).map(|i| i.to_owned())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SegmentLabel {
+    pub segment: SegmentKind,
+    pub label: String,
+}
+
+pub fn parse_segment_label(s: &str) -> Result<SegmentLabel, String> {
+    let (segment, label) = s.split_once('=').ok_or("expected SEGMENT=LABEL")?;
+
+    Ok(SegmentLabel { segment: SegmentKind::from_str(segment, false)?, label: label.to_owned() })
+}
+
+// Labels given for a segment replace its own, and an empty one removes it
+pub fn get_label(segment: SegmentKind, labels: &[SegmentLabel]) -> Option<&str> {
+    labels.iter().rev().find(|l| l.segment == segment).map(|l| l.label.as_str()).or(segment.default_label()).filter(|l| !l.is_empty())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentMaxWidth {
+    pub segment: SegmentKind,
+    pub width: usize,
+    pub position: EllipsisPosition,
+}
+
+pub fn parse_segment_max_width(s: &str) -> Result<SegmentMaxWidth, String> {
+    let (segment, width) = s.split_once('=').ok_or("expected SEGMENT=WIDTH[:start|middle|end]")?;
+    let segment = SegmentKind::from_str(segment, false)?;
+
+    let (width, position) = match width.split_once(':') {
+        Some((width, position)) => (width, EllipsisPosition::from_str(position, false)?),
+        None => (width, segment.default_ellipsis())
+    };
+
+    Ok(SegmentMaxWidth { segment, width: width.parse().map_err(|_| format!("invalid width '{}'", width))?, position })
+}
+
+pub fn get_max_width(segment: SegmentKind, max_widths: &[SegmentMaxWidth]) -> Option<&SegmentMaxWidth> {
+    max_widths.iter().rev().find(|x| x.segment == segment)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentColour {
+    pub segment: SegmentKind,
+    pub colour: Color,
+}
+
+pub fn parse_segment_colour(s: &str) -> Result<SegmentColour, String> {
+    let (segment, colour) = s.split_once('=').ok_or("expected SEGMENT=COLOUR")?;
+
+    Ok(SegmentColour { segment: SegmentKind::from_str(segment, false)?, colour: parse_colour(colour)? })
+}
+
+pub fn get_segment_colour(segment: SegmentKind, colours: &[SegmentColour]) -> Option<Color> {
+    colours.iter().rev().find(|c| c.segment == segment).map(|c| c.colour)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    Left,
+    Right,
+    Hidden
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentSide {
+    pub segment: SegmentKind,
+    pub side: Side,
+}
+
+pub fn parse_segment_side(s: &str) -> Result<SegmentSide, String> {
+    let (segment, side) = s.split_once('=').ok_or("expected SEGMENT=SIDE")?;
+
+    Ok(SegmentSide {
+        segment: SegmentKind::from_str(segment, false)?,
+        side: Side::from_str(side, false)?
+    })
+}
+
+// The last one given wins so a later flag can override an earlier one
+pub fn get_side(segment: SegmentKind, sides: &[SegmentSide]) -> Side {
+    sides.iter().rev().find(|s| s.segment == segment).map_or(segment.default_side(), |s| s.side)
+}
+
+// Every condition given for a segment has to hold for it to be shown
+#[derive(Clone, Debug)]
+pub struct SegmentCondition {
+    pub segment: SegmentKind,
+    pub condition: Condition,
+}
+
+pub fn parse_segment_condition(s: &str) -> Result<SegmentCondition, String> {
+    let (segment, condition) = s.split_once('=').ok_or("expected SEGMENT=CONDITION")?;
+
+    Ok(SegmentCondition { segment: SegmentKind::from_str(segment, false)?, condition: parse_condition(condition)? })
+}
+
+pub fn get_conditions(segment: SegmentKind, conditions: &[SegmentCondition]) -> Vec<&Condition> {
+    conditions.iter().filter(|c| c.segment == segment).map(|c| &c.condition).collect()
+}
+
+// Why a segment came out empty, for --explain-missing and the JSON output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Failed(String),
+    Empty(&'static str),
+    NotSet(&'static [&'static str]),
+    NotInRepository,
+    Deleted,
+    OutOfTime,
+    Nothing(&'static str)
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Failed(error) => f.write_str(error),
+            Reason::Empty(command) => write!(f, "{} succeeded but printed nothing", command),
+            Reason::NotSet([name]) => write!(f, "{} isn't set", name),
+            Reason::NotSet(names) => match names.split_last() {
+                Some((last, rest)) => write!(f, "none of {} and {} are set", rest.join(", "), last),
+                None => f.write_str("nothing is set")
+            },
+            Reason::NotInRepository => f.write_str("not in a git repository"),
+            Reason::Deleted => f.write_str("the current directory has been deleted"),
+            Reason::OutOfTime => f.write_str("the budget ran out before it finished"),
+            Reason::Nothing(reason) => f.write_str(reason)
+        }
+    }
+}
+
+pub enum SegmentResult<T> {
+    Present(T),
+    Absent(Reason)
+}
+
+impl<T> SegmentResult<T> {
+    pub fn ok(self) -> Option<T> {
+        match self {
+            SegmentResult::Present(x) => Some(x),
+            SegmentResult::Absent(_) => None
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> SegmentResult<U> {
+        match self {
+            SegmentResult::Present(x) => SegmentResult::Present(f(x)),
+            SegmentResult::Absent(reason) => SegmentResult::Absent(reason)
+        }
+    }
+}
+
+// Most segments go on the top line but the git ones that feed the chevrons just say which state
+// they're in
+#[derive(Debug)]
+pub enum Rendered {
+    Spans(Vec<Span>),
+    // Shown under a name of its own or with more added, but danger patterns and the title go by
+    // the real value underneath
+    Aliased(Vec<Span>, String),
+    State(Position, State),
+}
+
+// Everything a segment gets to know about where the prompt's being drawn
+pub struct Context<'a> {
+    // Where to run anything, which is only somewhere made up once the directory's gone
+    pub dir: &'a Path,
+    pub current_dir: &'a WorkingDirectory,
+    pub config: &'a Config,
+    pub theme: &'a Theme,
+    pub exit_code: i32,
+    pub timeout_ms: Option<u64>,
+    pub runner: &'a dyn CommandRunner,
+    pub store: &'a Store,
+}
+
+pub trait Segment {
+    fn kind(&self) -> SegmentKind;
+
+    // What it's listed as in --timings, which the segments that don't run anything are left out of
+    fn timing_name(&self) -> Option<String> {
+        self.kind().to_possible_value().map(|v| v.get_name().to_owned())
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>>;
+}
@@ -0,0 +1,56 @@
+use std::sync::Mutex;
+
+// The terminal the prompt's drawn for. A prompt drawn by the daemon is in a fork of it, where the
+// session and parent are the daemon's, so the client sends its own along to be used instead
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    // So another shell that ends up on the same tty doesn't take what was left for this one
+    pub id: Option<u32>,
+    // What everything kept for the terminal is named after
+    pub key: String,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+#[cfg(unix)]
+fn get_id() -> Option<u32> {
+    u32::try_from(unsafe { libc::getsid(0) }).ok()
+}
+
+#[cfg(windows)]
+fn get_id() -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn get_key() -> String {
+    // Key on the terminal so every shell gets its own state, even across command substitutions
+    let tty = unsafe {
+        let name = libc::ttyname(0);
+        if name.is_null() { None } else { Some(std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned()) }
+    };
+
+    tty.map(|t| t.trim_start_matches('/').replace('/', "-"))
+        .unwrap_or_else(|| format!("ppid-{}", std::os::unix::process::parent_id()))
+}
+
+#[cfg(windows)]
+fn get_key() -> String {
+    // There's no tty to go on but Windows Terminal at least gives each tab an ID
+    std::env::var("WT_SESSION").map(|s| "wt-".to_owned() + &s).unwrap_or_else(|_| "console".to_owned())
+}
+
+// This process's own, whatever's been set
+pub fn get_own_session() -> Session {
+    Session { id: get_id(), key: get_key() }
+}
+
+pub fn set_session(session: Session) {
+    if let Ok(mut current) = SESSION.lock() {
+        *current = Some(session);
+    }
+}
+
+pub fn get_session() -> Session {
+    SESSION.lock().ok().and_then(|x| x.clone()).unwrap_or_else(get_own_session)
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::get_state_home;
+
+pub const SLOW_THRESHOLD: Duration = Duration::from_millis(750);
+
+// One slow prompt could be anything, like the disk waking up, but a few in a row is worth saying
+const SLOW_STREAK: u32 = 3;
+
+#[derive(Default, Serialize, Deserialize)]
+struct DirectoryState {
+    slow: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warned: Option<u64>,
+}
+
+fn get_state_path() -> Option<PathBuf> {
+    get_state_home().map(|x| x.join("prompt").join("slow.json"))
+}
+
+fn get_day() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |x| x.as_secs() / 86400)
+}
+
+// The things that are usually behind each part being slow and what can be done about it. Custom
+// segments and plugins go by their names so they're picked out by what they start with
+pub fn get_remedy(name: &str) -> Option<String> {
+    let remedy = match name {
+        "git-status" => "consider 'git config core.untrackedCache true' and 'git config core.fsmonitor true'",
+        "git-upstream" => "the branch may be a long way from its upstream, consider --budget-ms 300 to draw without it",
+        "git-errors" => "consider --disable git-errors as it diffs the whole working tree",
+        "git-branch" | "git-state" | "git-unmerged" => "consider 'git gc' or --budget-ms 300",
+        "k8s-context" | "k8s-namespace" if cfg!(unix) => "kubectl is slow to start, consider 'prompt daemon' to cache it or --disable k8s",
+        "k8s-context" | "k8s-namespace" => "kubectl is slow to start, consider --disable k8s",
+        "sudo" => "consider leaving out --sudo",
+        "git-merge-request" => "the forge is slow to answer, consider a longer --merge-request-ttl",
+        _ => return match name.split_once(' ') {
+            Some(("custom", custom)) => Some(format!("consider giving custom.{} a cache-ttl", custom)),
+            Some(("plugin", plugin)) => Some(format!("consider taking {} out of plugins", plugin)),
+            _ => None
+        }
+    };
+
+    Some(remedy.to_owned())
+}
+
+fn format_seconds(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+pub fn get_hint(slowest: Option<(String, Duration)>) -> String {
+    let Some((name, duration)) = slowest else {
+        return "prompt: renders are slow here; see --timings".to_owned();
+    };
+
+    match get_remedy(&name) {
+        Some(remedy) => format!("prompt: renders are slow here ({} {}); {} or see --timings", name, format_seconds(duration), remedy),
+        None => format!("prompt: renders are slow here ({} {}); see --timings", name, format_seconds(duration))
+    }
+}
+
+fn load_state(path: &Path) -> BTreeMap<PathBuf, DirectoryState> {
+    fs::read_to_string(path).ok().and_then(|x| serde_json::from_str(&x).ok()).unwrap_or_default()
+}
+
+// Counts how many prompts in a row have been slow in this directory and says whether it's time to
+// mention it, which only happens once a day for each directory. Anything that can't be read or
+// written just means there's never a hint
+pub fn should_warn(dir: &Path, total: Duration) -> bool {
+    let Some(path) = get_state_path() else { return false };
+    let mut state = load_state(&path);
+    let today = get_day();
+
+    let slow = total >= SLOW_THRESHOLD;
+    if !slow && !state.contains_key(dir) {
+        return false;
+    }
+
+    let entry = state.entry(dir.to_owned()).or_default();
+    entry.slow = if slow { entry.slow + 1 } else { 0 };
+
+    let warn = entry.slow >= SLOW_STREAK && entry.warned != Some(today);
+    if warn {
+        entry.warned = Some(today);
+        entry.slow = 0;
+    }
+
+    // Directories that are fast again and weren't warned about today don't need remembering
+    state.retain(|_, x| x.slow > 0 || x.warned == Some(today));
+
+    if let Some(parent) = path.parent() {
+        let written = fs::create_dir_all(parent).and_then(|_| fs::write(&path, serde_json::to_string(&state).unwrap_or_default()));
+        if written.is_err() {
+            return false;
+        }
+    }
+
+    warn
+}
@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime};
+
+// Names an entry by what it was worked out from, so anything changing just means a different file
+// and nothing ever has to be invalidated
+#[derive(Clone)]
+pub struct CacheKey {
+    name: String,
+    hasher: DefaultHasher,
+}
+
+impl CacheKey {
+    pub fn new(name: &str) -> CacheKey {
+        CacheKey { name: name.to_owned(), hasher: DefaultHasher::new() }
+    }
+
+    pub fn with(mut self, input: impl Hash) -> CacheKey {
+        input.hash(&mut self.hasher);
+        self
+    }
+
+    // A file that's missing is an input too, so one turning up is noticed
+    pub fn with_modified(self, path: &Path) -> CacheKey {
+        let modified = fs::metadata(path).and_then(|x| x.modified()).ok();
+        self.with((path, modified))
+    }
+
+    pub fn with_env(self, name: &str) -> CacheKey {
+        let value = env::var_os(name);
+        self.with((name, value))
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}-{:016x}", self.name, self.hasher.finish())
+    }
+}
+
+#[cfg(windows)]
+pub fn get_cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").filter(|x| !x.is_empty()).map(PathBuf::from))
+        .unwrap_or_else(env::temp_dir)
+        .join("prompt")
+}
+
+#[cfg(not(windows))]
+pub fn get_cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+        .or_else(|| crate::path::get_home_directory().map(|h| h.join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("prompt")
+}
+
+// Every entry is a file of its own that's written somewhere else first and moved into place, so
+// prompts in other panes only ever see a whole entry or none at all. Each lookup is remembered for
+// --timings
+pub struct Store {
+    dir: Option<PathBuf>,
+    lookups: RefCell<Vec<(String, bool)>>,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf) -> Store {
+        Store { dir: Some(dir), lookups: RefCell::new(vec![]) }
+    }
+
+    // For previews, where nothing's real so nothing should be kept
+    pub fn disabled() -> Store {
+        Store { dir: None, lookups: RefCell::new(vec![]) }
+    }
+
+    pub fn load(&self, key: &CacheKey, ttl: Duration) -> Option<String> {
+        let text = self.dir.as_ref().and_then(|dir| {
+            let path = dir.join(key.file_name());
+            let age = fs::metadata(&path).and_then(|x| x.modified()).ok().and_then(|x| SystemTime::now().duration_since(x).ok())?;
+            if age > ttl {
+                return None;
+            }
+
+            fs::read_to_string(path).ok()
+        });
+
+        self.lookups.borrow_mut().push((key.name.clone(), text.is_some()));
+        text
+    }
+
+    // Failing to save only means working it out again next time
+    pub fn save(&self, key: &CacheKey, text: &str) {
+        let Some(dir) = &self.dir else { return };
+
+        let path = dir.join(key.file_name());
+        let temp_path = dir.join(format!(".{}.{}.tmp", key.file_name(), process::id()));
+        let saved = fs::create_dir_all(dir).and_then(|_| fs::write(&temp_path, text)).and_then(|_| fs::rename(&temp_path, &path));
+
+        if let Err(e) = saved {
+            tracing::debug!(error = %e, entry = key.file_name(), "couldn't save to the cache");
+            let _ = fs::remove_file(temp_path);
+        }
+    }
+
+    // How many lookups for each kind of entry were answered and how many weren't, in the order
+    // they were first looked up
+    pub fn get_counts(&self) -> Vec<(String, usize, usize)> {
+        let mut counts: Vec<(String, usize, usize)> = vec![];
+        for (name, hit) in self.lookups.borrow().iter() {
+            let index = match counts.iter().position(|x| x.0 == *name) {
+                Some(index) => index,
+                None => {
+                    counts.push((name.clone(), 0, 0));
+                    counts.len() - 1
+                }
+            };
+
+            match hit {
+                true => counts[index].1 += 1,
+                false => counts[index].2 += 1
+            }
+        }
+        counts
+    }
+}
+
+// Gives back how many entries there were. A directory that was never made has nothing in it
+pub fn clear_cache(dir: &Path) -> io::Result<usize> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e)
+    };
+
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
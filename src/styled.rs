@@ -0,0 +1,124 @@
+use std::ops::Deref;
+
+use colored::Color;
+
+use crate::text::display_width;
+
+// How a span should look, kept apart from its text until the renderer knows what it's drawing for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Styling {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub reversed: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub styling: Styling,
+}
+
+// A line or a segment is just its spans one after the other
+pub type StyledText = Vec<Span>;
+
+impl Span {
+    pub fn new(text: &str, styling: Styling) -> Span {
+        Span { text: text.to_owned(), styling }
+    }
+
+    // The same look with different text
+    pub fn with_text(&self, text: &str) -> Span {
+        Span::new(text, self.styling)
+    }
+
+    pub fn width(&self) -> usize {
+        display_width(&self.text)
+    }
+}
+
+impl Deref for Span {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl From<&str> for Span {
+    fn from(text: &str) -> Span {
+        Span::new(text, Styling::default())
+    }
+}
+
+impl From<String> for Span {
+    fn from(text: String) -> Span {
+        Span { text, styling: Styling::default() }
+    }
+}
+
+pub fn get_width(text: &[Span]) -> usize {
+    text.iter().map(Span::width).sum()
+}
+
+// Named after colored's, which everything was written against first
+pub trait Stylize: Into<Span> {
+    fn styled(self, apply: impl FnOnce(&mut Styling)) -> Span {
+        let mut span = self.into();
+        apply(&mut span.styling);
+        span
+    }
+
+    fn normal(self) -> Span {
+        self.into()
+    }
+
+    fn color(self, colour: Color) -> Span {
+        self.styled(|x| x.fg = Some(colour))
+    }
+
+    fn on_color(self, colour: Color) -> Span {
+        self.styled(|x| x.bg = Some(colour))
+    }
+
+    fn bold(self) -> Span {
+        self.styled(|x| x.bold = true)
+    }
+
+    fn dimmed(self) -> Span {
+        self.styled(|x| x.dimmed = true)
+    }
+
+    fn italic(self) -> Span {
+        self.styled(|x| x.italic = true)
+    }
+
+    fn underline(self) -> Span {
+        self.styled(|x| x.underline = true)
+    }
+
+    fn blink(self) -> Span {
+        self.styled(|x| x.blink = true)
+    }
+
+    fn reversed(self) -> Span {
+        self.styled(|x| x.reversed = true)
+    }
+
+    fn hidden(self) -> Span {
+        self.styled(|x| x.hidden = true)
+    }
+
+    fn strikethrough(self) -> Span {
+        self.styled(|x| x.strikethrough = true)
+    }
+}
+
+impl Stylize for Span {}
+impl Stylize for &str {}
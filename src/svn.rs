@@ -0,0 +1,109 @@
+use std::path::Path;
+use std::time::Duration;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use crate::chevron::{Position, State};
+use crate::command::get_output;
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::store::CacheKey;
+use crate::text::sanitize;
+use crate::theme::Role;
+
+// The working copy changes whenever anything's switched or updated, so this is only ever a backstop
+const BRANCH_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub fn find_working_copy(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|x| x.join(".svn").is_dir())
+}
+
+// ^/branches/release-2/src becomes release-2 and ^/project/trunk becomes trunk, anything laid out
+// some other way is shown as it is
+pub fn parse_relative_url(url: &str) -> Option<String> {
+    let path = url.trim().trim_start_matches('^').trim_matches('/');
+    let parts: Vec<_> = path.split('/').collect();
+
+    let branch = parts.iter().enumerate().find_map(|(i, part)| match *part {
+        "trunk" => Some("trunk"),
+        "branches" | "tags" => parts.get(i + 1).copied(),
+        _ => None
+    });
+
+    Some(sanitize(branch.unwrap_or(path))).filter(|x| !x.is_empty())
+}
+
+// Only what's changed is listed with -q, with conflicts marked in the first or seventh column
+pub fn parse_status(status: &str) -> State {
+    let lines: Vec<_> = status.lines().filter(|x| !x.trim().is_empty()).collect();
+
+    if lines.iter().any(|x| x.starts_with('C') || x.chars().nth(6) == Some('C')) {
+        State::Conflict
+    } else if lines.is_empty() {
+        State::Clean
+    } else {
+        State::Dirty
+    }
+}
+
+// svn info goes through the whole working copy's database so it's slow, and it's kept for each
+// directory until the database changes
+pub struct SvnBranchSegment;
+
+impl SvnBranchSegment {
+    async fn get_relative_url(&self, context: &Context<'_>) -> Result<String, String> {
+        let key = find_working_copy(context.dir).map(|root| CacheKey::new("svn-branch").with(context.dir).with_modified(&root.join(".svn").join("wc.db")));
+        if let Some(cached) = key.as_ref().and_then(|x| context.store.load(x, BRANCH_TTL)) {
+            tracing::debug!(cached, "using the cached svn url");
+            return Ok(cached);
+        }
+
+        let output = get_output(context.runner, "svn", &["info", "--show-item", "relative-url"], context.timeout_ms).await.map_err(|e| format!("svn: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("svn: {}", output.status));
+        }
+
+        let url = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        if let Some(key) = key {
+            context.store.save(&key, &url);
+        }
+        Ok(url)
+    }
+}
+
+impl Segment for SvnBranchSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::SvnBranch
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match self.get_relative_url(context).await.map(|x| parse_relative_url(&x)) {
+                Ok(Some(branch)) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Git, &branch)])),
+                Ok(None) => SegmentResult::Absent(Reason::Empty("svn")),
+                Err(e) => SegmentResult::Absent(Reason::Failed(e))
+            }
+        }.boxed_local()
+    }
+}
+
+// There's nothing to push in svn, so only the middle chevron is ever drawn from this
+pub struct SvnStatusSegment;
+
+impl Segment for SvnStatusSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::SvnStatus
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        async move {
+            match get_output(context.runner, "svn", &["status", "-q"], context.timeout_ms).await {
+                Ok(output) if output.status.success() => {
+                    SegmentResult::Present(Rendered::State(Position::Changes, parse_status(&String::from_utf8_lossy(&output.stdout))))
+                },
+                Ok(output) => SegmentResult::Absent(Reason::Failed(format!("svn: {}", output.status))),
+                Err(e) => SegmentResult::Absent(Reason::Failed(format!("svn: {}", e)))
+            }
+        }.boxed_local()
+    }
+}
@@ -0,0 +1,133 @@
+use std::thread;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use crate::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use crate::theme::Role;
+
+// The first of the three load averages in /proc/loadavg, which is the last minute's
+pub fn parse_loadavg(text: &str) -> Option<f64> {
+    text.split_whitespace().next()?.parse().ok().filter(|x: &f64| x.is_finite() && *x >= 0.0)
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_load_average() -> Option<f64> {
+    parse_loadavg(&std::fs::read_to_string("/proc/loadavg").ok()?)
+}
+
+// macOS and the BSDs don't have /proc, but libc asks the kernel the same thing
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn get_load_average() -> Option<f64> {
+    let mut loads = [0.0; 1];
+    match unsafe { libc::getloadavg(loads.as_mut_ptr(), 1) } {
+        1 => Some(loads[0]),
+        _ => None
+    }
+}
+
+#[cfg(not(unix))]
+pub fn get_load_average() -> Option<f64> {
+    None
+}
+
+// What the process is allowed to run on, so a container limited to a few cores counts only those
+pub fn get_core_count() -> usize {
+    thread::available_parallelism().map_or(1, |x| x.get())
+}
+
+// Only a machine that's busier than its cores can keep up with is worth mentioning
+pub fn is_overloaded(load: f64, cores: usize, threshold: f64) -> bool {
+    load > cores as f64 * threshold
+}
+
+pub fn format_load(load: f64) -> String {
+    format!("load {:.1}", load)
+}
+
+pub struct LoadSegment {
+    // How many times the number of cores the load has to be over to be shown
+    pub threshold: f64,
+}
+
+impl Segment for LoadSegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Load
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match get_load_average() {
+            Some(load) if is_overloaded(load, get_core_count(), self.threshold) => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Warning, &format_load(load))])),
+            Some(_) => SegmentResult::Absent(Reason::Nothing("the load is below the threshold for the number of cores")),
+            None => SegmentResult::Absent(Reason::Nothing("there's no load average to read here"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+// How much of the memory is available as a percentage, from MemAvailable rather than MemFree
+// because the page cache is given back as soon as anything needs it
+pub fn parse_meminfo(text: &str) -> Option<u8> {
+    let get_kb = |key: &str| text.lines().find_map(|line| {
+        line.strip_prefix(key)?.strip_prefix(':')?.split_whitespace().next()?.parse::<u64>().ok()
+    });
+
+    let total = get_kb("MemTotal").filter(|x| *x > 0)?;
+    let available = get_kb("MemAvailable")?.min(total);
+    Some((available * 100 / total) as u8)
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_memory_available() -> Option<u8> {
+    parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+// Elsewhere there's no one number that means the same as MemAvailable
+#[cfg(not(target_os = "linux"))]
+pub fn get_memory_available() -> Option<u8> {
+    None
+}
+
+pub fn format_memory(percent: u8) -> String {
+    format!("mem {}%", percent)
+}
+
+pub struct MemorySegment {
+    // The percentage of memory available that it has to drop below to be shown
+    pub threshold: u8,
+}
+
+impl Segment for MemorySegment {
+    fn kind(&self) -> SegmentKind {
+        SegmentKind::Memory
+    }
+
+    fn render<'a>(&'a self, context: &'a Context<'a>) -> LocalBoxFuture<'a, SegmentResult<Rendered>> {
+        let result = match get_memory_available() {
+            Some(percent) if percent < self.threshold => SegmentResult::Present(Rendered::Spans(vec![context.theme.paint(Role::Error, &format_memory(percent))])),
+            Some(_) => SegmentResult::Absent(Reason::Nothing("more memory is available than the threshold")),
+            None => SegmentResult::Absent(Reason::Nothing("there's no telling how much memory is available here"))
+        };
+
+        async move { result }.boxed_local()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_battery_level() -> Option<u8> {
+    let entries = std::fs::read_dir("/sys/class/power_supply").ok()?;
+
+    // With more than one battery the emptiest one that's draining is the one to worry about
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("BAT"))
+        .filter(|entry| std::fs::read_to_string(entry.path().join("status")).map(|s| s.trim() == "Discharging").unwrap_or(false))
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("capacity")).ok()?.trim().parse().ok())
+        .min()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_battery_level() -> Option<u8> {
+    None
+}
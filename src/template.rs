@@ -0,0 +1,115 @@
+use clap::ValueEnum;
+
+use crate::glyph::Glyphs;
+use crate::layout::{get_display_width, Segment};
+use crate::render::Renderer;
+use crate::segment::SegmentKind;
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+    Segment { kind: SegmentKind, prefix: String, suffix: String }
+}
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+fn get_segment_names() -> String {
+    SegmentKind::value_variants().iter()
+        .filter_map(|k| k.to_possible_value())
+        .map(|v| v.get_name().to_owned())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_segment(name: &str) -> Result<SegmentKind, String> {
+    SegmentKind::from_str(&name.trim().replace('_', "-"), false)
+        .map_err(|_| format!("unknown segment '{}', expected one of {}", name, get_segment_names()))
+}
+
+// {segment} is replaced with the segment and {segment:prefix|suffix} puts text either side of it
+// only when it's there. Braces outside of placeholders are doubled up to use them as they are
+pub fn parse_template(s: &str) -> Result<Template, String> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            },
+            '}' => return Err("unmatched '}', use '}}' for a literal one".to_owned()),
+            '{' => {
+                // The first closing brace always ends the placeholder so {{{segment}}} works
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("unclosed '{', use '{{' for a literal one".to_owned())
+                    }
+                }
+
+                let (name, modifier) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+                let (prefix, suffix) = modifier.split_once('|').unwrap_or((modifier, ""));
+
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Segment { kind: parse_segment(name)?, prefix: prefix.to_owned(), suffix: suffix.to_owned() });
+            },
+            c => literal.push(c)
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+
+    Ok(Template { parts })
+}
+
+impl Template {
+    pub fn contains(&self, segment: SegmentKind) -> bool {
+        self.parts.iter().any(|p| matches!(p, Part::Segment { kind, .. } if *kind == segment))
+    }
+
+    pub fn get_width(&self, segments: &[(SegmentKind, Segment)], glyphs: &Glyphs) -> usize {
+        self.parts.iter().map(|part| match part {
+            Part::Literal(text) => glyphs.get_width(text),
+            Part::Segment { kind, prefix, suffix } => match segments.iter().find(|(k, _)| k == kind) {
+                Some((_, segment)) => glyphs.get_width(prefix) + get_display_width(segment, glyphs) + glyphs.get_width(suffix),
+                None => 0
+            }
+        }).sum()
+    }
+
+    // Segments are dropped, least important first, until the line fits. There's nowhere to put an
+    // ellipsis so they're treated as if they weren't there at all
+    pub fn fit_to_width(&self, mut segments: Vec<(SegmentKind, Segment)>, width: usize, glyphs: &Glyphs) -> Vec<(SegmentKind, Segment)> {
+        while self.get_width(&segments, glyphs) > width && segments.len() > 1 {
+            let lowest = (0..segments.len()).rev().min_by_key(|i| segments[*i].0.priority()).unwrap();
+            segments.remove(lowest);
+        }
+
+        segments
+    }
+
+    pub fn render(&self, renderer: &Renderer, draw: impl Fn(SegmentKind) -> Option<String>) -> String {
+        self.parts.iter().map(|part| match part {
+            Part::Literal(text) => renderer.text(text),
+            Part::Segment { kind, prefix, suffix } => match draw(*kind) {
+                Some(segment) => renderer.text(prefix) + &segment + &renderer.text(suffix),
+                None => "".to_owned()
+            }
+        }).collect()
+    }
+}
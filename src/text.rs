@@ -0,0 +1,151 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Anything that came from outside the prompt, so branch names, environment variables, messages and
+// whatever commands print, goes through here before it's styled. An escape sequence in any of them
+// could retitle the terminal or move the cursor, so whole sequences are taken out rather than just
+// the control characters that start them
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => skip_control_sequence(&mut chars),
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_control_string(&mut chars),
+                // Intermediate bytes and then the one that ends it
+                Some(' '..='/') => {
+                    while chars.next_if(|x| matches!(x, ' '..='/')).is_some() {}
+                    chars.next();
+                },
+                _ => {}
+            },
+            '\u{9b}' => skip_control_sequence(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => skip_control_string(&mut chars),
+            c if c.is_control() => {},
+            c => sanitized.push(c)
+        }
+    }
+
+    sanitized
+}
+
+// Parameters and intermediates up to a final byte, which is anything from @ to ~
+fn skip_control_sequence(chars: &mut impl Iterator<Item = char>) {
+    for c in chars.by_ref() {
+        if matches!(c, '@'..='~') {
+            break;
+        }
+    }
+}
+
+// OSC, DCS and the rest go on until a BEL or a string terminator
+fn skip_control_string(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => break,
+            '\x1b' => {
+                chars.next_if_eq(&'\\');
+                break;
+            },
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EllipsisPosition {
+    Start,
+    Middle,
+    End
+}
+
+// Measured a grapheme at a time so it always agrees with where things get cut
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(|g| g.width()).sum()
+}
+
+// Cut down rather than rounded, like a stopwatch, so nothing says it's reached the next unit before
+// it has. 9.95s is 9.9s, never 10.0s, and 59.9s is 59s rather than 1m00s
+pub fn format_duration(duration_ms: u64) -> String {
+    let seconds = duration_ms / 1000;
+
+    if duration_ms < 10_000 {
+        format!("{}.{}s", seconds, duration_ms % 1000 / 100)
+    } else if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 3600 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+// Only the biggest unit, since nobody coming back to a terminal needs the minutes after an hour
+pub fn format_idle(idle_ms: u64) -> String {
+    let minutes = idle_ms / 60_000;
+
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 48 * 60 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}d", minutes / (24 * 60))
+    }
+}
+
+pub fn truncate_to_width(text: &str, max: usize, position: EllipsisPosition) -> String {
+    truncate_by(text, max, position, "…", display_width)
+}
+
+// Anything that's too wide has graphemes taken away from the start, middle or end and an ellipsis
+// put there instead, so nothing is ever split part way through an emoji or accent. Whoever's
+// drawing it says how wide things are so glyphs that get swapped out can be measured as drawn
+pub fn truncate_by(text: &str, max: usize, position: EllipsisPosition, ellipsis: &str, measure: impl Fn(&str) -> usize) -> String {
+    let graphemes: Vec<_> = text.graphemes(true).collect();
+    let widths: Vec<_> = graphemes.iter().map(|g| measure(g)).collect();
+
+    match get_kept_graphemes(&widths, max, measure(ellipsis), position) {
+        None => text.to_owned(),
+        Some(_) if max < measure(ellipsis) => "".to_owned(),
+        Some((head, tail)) => graphemes[..head].iter().copied().chain([ellipsis]).chain(graphemes[graphemes.len() - tail..].iter().copied()).collect()
+    }
+}
+
+// How many graphemes to keep from the start and from the end so they fit with an ellipsis between
+// them, or nothing when they all fit as they are. Styled text is cut in the same place by
+// measuring every grapheme in every span
+pub fn get_kept_graphemes(widths: &[usize], max: usize, ellipsis: usize, position: EllipsisPosition) -> Option<(usize, usize)> {
+    if widths.iter().sum::<usize>() <= max {
+        return None;
+    }
+
+    let available = max.saturating_sub(ellipsis);
+    let (front, back) = match position {
+        EllipsisPosition::Start => (0, available),
+        EllipsisPosition::Middle => (available - available / 2, available / 2),
+        EllipsisPosition::End => (available, 0)
+    };
+
+    let (head, used) = count_to_width(widths.iter(), front);
+    // A wide character at the front can leave a gap, which the back gets instead
+    let (tail, _) = count_to_width(widths[head..].iter().rev(), if back == 0 { 0 } else { available - used });
+    Some((head, tail))
+}
+
+fn count_to_width<'a>(widths: impl Iterator<Item = &'a usize>, width: usize) -> (usize, usize) {
+    let mut used = 0;
+    let count = widths.take_while(|w| {
+        let fits = used + *w <= width;
+        if fits {
+            used += *w;
+        }
+        fits
+    }).count();
+
+    (count, used)
+}
@@ -0,0 +1,246 @@
+use clap::ValueEnum;
+use colored::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::background::Background;
+use crate::colour::parse_colour;
+use crate::styled::{Span, Stylize};
+
+// What something in the prompt is for, rather than what it looks like
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    Path,
+    Message,
+    Git,
+    K8s,
+    Aws,
+    Tmux,
+    Sudo,
+    Battery,
+    Duration,
+    Jobs,
+    Pipestatus,
+    Ok,
+    Warning,
+    Info,
+    Error,
+    NoUpstream
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    Default,
+    Solarized,
+    Dracula,
+    Monochrome,
+    HighContrast
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Look {
+    pub colour: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reversed: bool,
+}
+
+impl Look {
+    const fn colour(colour: Color) -> Look {
+        Look { colour: Some(colour), bold: true, dimmed: false, italic: false, underline: false, reversed: false }
+    }
+
+    const fn hex(hex: u32) -> Look {
+        Look::colour(Color::TrueColor { r: (hex >> 16) as u8, g: (hex >> 8) as u8, b: hex as u8 })
+    }
+
+    pub const fn plain() -> Look {
+        Look { colour: None, bold: true, dimmed: false, italic: false, underline: false, reversed: false }
+    }
+
+    pub fn paint(&self, text: &str) -> Span {
+        let mut painted = Span::from(text);
+
+        if let Some(colour) = self.colour {
+            painted = painted.color(colour);
+        }
+        if self.bold {
+            painted = painted.bold();
+        }
+        if self.dimmed {
+            painted = painted.dimmed();
+        }
+        if self.italic {
+            painted = painted.italic();
+        }
+        if self.underline {
+            painted = painted.underline();
+        }
+        if self.reversed {
+            painted = painted.reversed();
+        }
+
+        painted
+    }
+}
+
+fn get_default_look(role: Role) -> Look {
+    match role {
+        Role::Path => Look::colour(Color::Cyan),
+        Role::Message => Look::colour(Color::Green),
+        Role::Git => Look::colour(Color::Magenta),
+        Role::K8s => Look::colour(Color::BrightBlue),
+        Role::Aws => Look::colour(Color::Red),
+        Role::Tmux | Role::Sudo | Role::Duration => Look::colour(Color::Yellow),
+        Role::Battery | Role::Pipestatus => Look::colour(Color::Red),
+        Role::Jobs => Look::colour(Color::Blue),
+        Role::Ok => Look::colour(Color::Green),
+        Role::Warning => Look::colour(Color::Yellow),
+        Role::Info => Look::colour(Color::Blue),
+        Role::Error => Look::colour(Color::Red),
+        Role::NoUpstream => Look::colour(Color::White)
+    }
+}
+
+// Bright blue and white are hard to make out on a light background
+fn get_default_light_look(role: Role) -> Look {
+    match role {
+        Role::K8s => Look::colour(Color::Blue),
+        Role::NoUpstream => Look::colour(Color::Black),
+        _ => get_default_look(role)
+    }
+}
+
+fn get_solarized_look(role: Role) -> Look {
+    match role {
+        Role::Path | Role::Jobs | Role::Info => Look::hex(0x268bd2),
+        Role::Message | Role::Ok => Look::hex(0x859900),
+        Role::Git => Look::hex(0x6c71c4),
+        Role::K8s => Look::hex(0x2aa198),
+        Role::Aws => Look::hex(0xcb4b16),
+        Role::Tmux | Role::Sudo | Role::Duration | Role::Warning => Look::hex(0xb58900),
+        Role::Battery | Role::Pipestatus | Role::Error => Look::hex(0xdc322f),
+        Role::NoUpstream => Look::hex(0x93a1a1)
+    }
+}
+
+// The accents are the same either way, it's only the greys that swap over
+fn get_solarized_light_look(role: Role) -> Look {
+    match role {
+        Role::NoUpstream => Look::hex(0x586e75),
+        _ => get_solarized_look(role)
+    }
+}
+
+fn get_dracula_look(role: Role) -> Look {
+    match role {
+        Role::Path | Role::Jobs | Role::Info => Look::hex(0x8be9fd),
+        Role::Message | Role::Ok => Look::hex(0x50fa7b),
+        Role::Git => Look::hex(0xff79c6),
+        Role::K8s => Look::hex(0xbd93f9),
+        Role::Aws => Look::hex(0xffb86c),
+        Role::Tmux | Role::Sudo | Role::Duration | Role::Warning => Look::hex(0xf1fa8c),
+        Role::Battery | Role::Pipestatus | Role::Error => Look::hex(0xff5555),
+        Role::NoUpstream => Look::hex(0xf8f8f2)
+    }
+}
+
+// Alucard, Dracula's own light version
+fn get_dracula_light_look(role: Role) -> Look {
+    match role {
+        Role::Path | Role::Jobs | Role::Info => Look::hex(0x036a96),
+        Role::Message | Role::Ok => Look::hex(0x14710a),
+        Role::Git => Look::hex(0xa3144d),
+        Role::K8s => Look::hex(0x644ac9),
+        Role::Aws => Look::hex(0xa34d14),
+        Role::Tmux | Role::Sudo | Role::Duration | Role::Warning => Look::hex(0x846e15),
+        Role::Battery | Role::Pipestatus | Role::Error => Look::hex(0xcb3a2a),
+        Role::NoUpstream => Look::hex(0x1f1f1f)
+    }
+}
+
+// No colour at all so nothing relies on telling hues apart
+fn get_monochrome_look(role: Role) -> Look {
+    let plain = Look::plain();
+
+    match role {
+        Role::Path | Role::Message | Role::Git | Role::Ok => plain,
+        Role::K8s | Role::Aws | Role::Tmux | Role::Duration | Role::Jobs | Role::NoUpstream => Look { bold: false, dimmed: true, ..plain },
+        Role::Warning => Look { underline: true, ..plain },
+        Role::Info => Look { italic: true, ..plain },
+        Role::Sudo | Role::Battery | Role::Pipestatus | Role::Error => Look { reversed: true, ..plain }
+    }
+}
+
+fn get_high_contrast_look(role: Role) -> Look {
+    match role {
+        Role::Path => Look::colour(Color::BrightCyan),
+        Role::Message | Role::Ok => Look::colour(Color::BrightGreen),
+        Role::Git => Look::colour(Color::BrightMagenta),
+        Role::K8s | Role::Jobs | Role::Info => Look::colour(Color::BrightBlue),
+        Role::Tmux | Role::Sudo | Role::Duration | Role::Warning => Look::colour(Color::BrightYellow),
+        Role::Aws | Role::Battery | Role::Pipestatus | Role::Error => Look::colour(Color::BrightRed),
+        Role::NoUpstream => Look::colour(Color::BrightWhite)
+    }
+}
+
+// The bright colours wash out on a light background so the normal ones stand out more there
+fn get_high_contrast_light_look(role: Role) -> Look {
+    match role {
+        Role::Path => Look::colour(Color::Cyan),
+        Role::Message | Role::Ok => Look::colour(Color::Green),
+        Role::Git => Look::colour(Color::Magenta),
+        Role::K8s | Role::Jobs | Role::Info => Look::colour(Color::Blue),
+        Role::Tmux | Role::Sudo | Role::Duration | Role::Warning => Look::colour(Color::Yellow),
+        Role::Aws | Role::Battery | Role::Pipestatus | Role::Error => Look::colour(Color::Red),
+        Role::NoUpstream => Look::colour(Color::Black)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoleColour {
+    pub role: Role,
+    pub colour: Color,
+}
+
+pub fn parse_role_colour(s: &str) -> Result<RoleColour, String> {
+    let (role, colour) = s.split_once('=').ok_or("expected ROLE=COLOUR")?;
+
+    Ok(RoleColour { role: Role::from_str(role, false)?, colour: parse_colour(colour)? })
+}
+
+// A built in theme, in its light or dark variant, with any roles the user wanted to change swapped
+// out
+pub struct Theme {
+    pub name: ThemeName,
+    pub background: Background,
+    pub overrides: Vec<RoleColour>,
+}
+
+impl Theme {
+    pub fn get_look(&self, role: Role) -> Look {
+        let look = match (self.name, self.background) {
+            (ThemeName::Default, Background::Dark) => get_default_look(role),
+            (ThemeName::Default, Background::Light) => get_default_light_look(role),
+            (ThemeName::Solarized, Background::Dark) => get_solarized_look(role),
+            (ThemeName::Solarized, Background::Light) => get_solarized_light_look(role),
+            (ThemeName::Dracula, Background::Dark) => get_dracula_look(role),
+            (ThemeName::Dracula, Background::Light) => get_dracula_light_look(role),
+            (ThemeName::Monochrome, _) => get_monochrome_look(role),
+            (ThemeName::HighContrast, Background::Dark) => get_high_contrast_look(role),
+            (ThemeName::HighContrast, Background::Light) => get_high_contrast_light_look(role)
+        };
+
+        match self.overrides.iter().rev().find(|o| o.role == role) {
+            Some(o) => Look { colour: Some(o.colour), ..look },
+            None => look
+        }
+    }
+
+    pub fn paint(&self, role: Role, text: &str) -> Span {
+        self.get_look(role).paint(text)
+    }
+}
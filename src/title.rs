@@ -0,0 +1,47 @@
+use crate::text::sanitize;
+
+// Fills in the {placeholders} in a title template. Anything in [brackets] is only kept when all
+// of its placeholders have a value so separators aren't left hanging about
+pub fn render_title(template: &str, values: &[(&str, Option<&str>)]) -> String {
+    let mut title = String::new();
+    let mut group: Option<(String, bool)> = None;
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        let text = match c {
+            '[' if group.is_none() => {
+                group = Some((String::new(), true));
+                continue;
+            },
+            ']' => {
+                if let Some((text, true)) = group.take() {
+                    title += &text;
+                }
+                continue;
+            },
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+
+                match values.iter().find(|(n, _)| *n == name) {
+                    Some((_, Some(value))) => value.to_string(),
+                    Some((_, None)) => {
+                        if let Some((_, found)) = group.as_mut() {
+                            *found = false;
+                        }
+                        String::new()
+                    },
+                    None => format!("{{{}}}", name)
+                }
+            },
+            c => c.to_string()
+        };
+
+        match group.as_mut() {
+            Some((group_text, _)) => *group_text += &text,
+            None => title += &text
+        }
+    }
+
+    // Terminals end the title at the first control character so there can't be any in it
+    sanitize(&title)
+}
@@ -0,0 +1,22 @@
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vcs {
+    Git,
+    Svn
+}
+
+// The closest working copy wins, so a git checkout inside an svn one or the other way round only
+// ever gets the one backend. Nothing found here can still be a git repository git knows about some
+// other way, like GIT_DIR
+pub fn detect_vcs(dir: &Path) -> Option<Vcs> {
+    dir.ancestors().find_map(|x| {
+        if x.join(".git").exists() {
+            Some(Vcs::Git)
+        } else if x.join(".svn").is_dir() {
+            Some(Vcs::Svn)
+        } else {
+            None
+        }
+    })
+}
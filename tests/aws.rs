@@ -0,0 +1,255 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use common::{Fixture, TempDir};
+use prompt::aws::{find_cached_role, get_profile_role_arn, parse_role_arn, parse_timestamp};
+
+const CONFIG: &str = r#"
+aws-markers = ["*.tf", "cdk.json"]
+aws-dirs = ["~/infra"]
+
+[[danger-patterns]]
+pattern = "prod*"
+
+[[danger-patterns]]
+pattern = "prod-sandbox"
+style = "none"
+"#;
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name)
+        .dir("home/infra/network")
+        .dir("home/app/.git")
+        .dir("home/app/src")
+        .file("home/app/cdk.json", "{}")
+        .file("home/novel/main.tf", "")
+        .build()
+}
+
+fn run_prompt(dir: &Path, cwd: &str, profile: &str, config: &str) -> String {
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--disable", "k8s", "--only", "aws-profile,aws-region"])
+        .current_dir(dir.join("home").join(cwd))
+        .env("HOME", dir.join("home"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("AWS_PROFILE", profile)
+        .env("AWS_REGION", "eu-west-2")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn aws_is_shown_everywhere_by_default() {
+    let dir = make_dir("default");
+    let output = run_prompt(&dir, "novel", "dev", "");
+
+    assert!(output.contains("dev") && output.contains("eu-west-2"), "{}", output);
+}
+
+#[test]
+fn aws_is_only_shown_where_it_is_of_use() {
+    let dir = make_dir("markers");
+    fs::remove_file(dir.join("home/novel/main.tf")).unwrap();
+
+    assert!(!run_prompt(&dir, "novel", "dev", CONFIG).contains("dev"));
+    assert!(run_prompt(&dir, "app/src", "dev", CONFIG).contains("dev"), "the repository has a cdk.json");
+    assert!(run_prompt(&dir, "infra/network", "dev", CONFIG).contains("eu-west-2"), "under an allowed directory");
+}
+
+#[test]
+fn markers_in_the_directory_count_too() {
+    let dir = make_dir("files");
+    assert!(run_prompt(&dir, "novel", "dev", CONFIG).contains("dev"));
+}
+
+#[test]
+fn production_is_shown_wherever_it_is() {
+    let dir = make_dir("danger");
+    fs::remove_file(dir.join("home/novel/main.tf")).unwrap();
+
+    assert!(run_prompt(&dir, "novel", "prod-admin", CONFIG).contains("prod-admin"));
+    assert!(!run_prompt(&dir, "novel", "prod-sandbox", CONFIG).contains("prod-sandbox"), "none isn't dangerous");
+}
+
+#[test]
+fn explain_says_where_aws_is_shown() {
+    let dir = make_dir("explain");
+    fs::write(dir.join("config.toml"), CONFIG).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["explain", "--color", "never"])
+        .current_dir(&dir)
+        .env("TERM", "xterm")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+    let legend = String::from_utf8(output.stdout).unwrap();
+
+    let expected = ", shown only when file:*.tf | repo-file:*.tf | file:cdk.json | repo-file:cdk.json | dir:~/infra or the profile matches a danger pattern";
+    assert!(legend.lines().any(|x| x.starts_with("  aws-profile ") && x.ends_with(expected)), "{}", legend);
+}
+
+#[test]
+fn role_arns_give_the_account_and_role() {
+    let role = |account: &str, role: &str| Some((account.to_owned(), role.to_owned()));
+
+    assert_eq!(parse_role_arn("arn:aws:sts::123456789012:assumed-role/AdminRole/alice"), role("123456789012", "AdminRole"));
+    assert_eq!(parse_role_arn("arn:aws:iam::123456789012:role/teams/ReadOnly"), role("123456789012", "ReadOnly"));
+    assert_eq!(parse_role_arn("arn:aws-us-gov:iam::210987654321:role/Deploy"), role("210987654321", "Deploy"));
+    assert_eq!(parse_role_arn("arn:aws:iam::123456789012:user/alice"), None);
+    assert_eq!(parse_role_arn("AdminRole"), None);
+}
+
+#[test]
+fn expiry_times_are_understood() {
+    let at = |seconds: u64| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+
+    assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), at(0));
+    assert_eq!(parse_timestamp("2026-10-14T12:34:56Z"), at(1_791_981_296));
+    assert_eq!(parse_timestamp("2026-10-14T12:34:56.123456Z"), at(1_791_981_296));
+    assert_eq!(parse_timestamp("2026-10-14T13:34:56+01:00"), at(1_791_981_296));
+    assert_eq!(parse_timestamp("2024-02-29T00:00:00UTC"), at(1_709_164_800));
+    assert_eq!(parse_timestamp("2026-13-01T00:00:00Z"), None);
+    assert_eq!(parse_timestamp("tomorrow"), None);
+}
+
+fn write_credentials(dir: &Path, name: &str, arn: &str, expiration: &str) {
+    let json = format!(r#"{{"Credentials": {{"AccessKeyId": "AKIA", "Expiration": "{}"}}, "AssumedRoleUser": {{"Arn": "{}"}}}}"#, expiration, arn);
+    fs::write(dir.join(format!("{}.json", name)), json).unwrap();
+    // The newest is the one that's used, so they're written far enough apart to tell
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[test]
+fn the_newest_credentials_for_the_role_that_have_not_run_out_are_used() {
+    let root = Fixture::new("credentials").dir("home/.aws/cli/cache").build();
+    let dir = root.join("home/.aws/cli/cache");
+    let now = parse_timestamp("2026-10-14T12:00:00Z").unwrap();
+    let role = "arn:aws:iam::222222222222:role/New";
+
+    write_credentials(&dir, "old", "arn:aws:sts::222222222222:assumed-role/New/a", "2026-10-14T13:00:00Z");
+    write_credentials(&dir, "new", "arn:aws:sts::222222222222:assumed-role/New/b", "2026-10-14T13:00:00Z");
+    assert_eq!(find_cached_role(&dir, role, now).as_deref(), Some("arn:aws:sts::222222222222:assumed-role/New/b"));
+
+    write_credentials(&dir, "other", "arn:aws:sts::111111111111:assumed-role/Other/a", "2026-10-14T13:00:00Z");
+    write_credentials(&dir, "expired", "arn:aws:sts::222222222222:assumed-role/New/c", "2026-10-14T11:00:00Z");
+    assert_eq!(find_cached_role(&dir, role, now).as_deref(), Some("arn:aws:sts::222222222222:assumed-role/New/b"));
+    assert_eq!(find_cached_role(&dir, "arn:aws:iam::333333333333:role/Missing", now), None);
+
+    fs::write(dir.join("broken.json"), "{").unwrap();
+    assert!(find_cached_role(&dir, role, now).is_some());
+    assert_eq!(find_cached_role(&dir.join("nowhere"), role, now), None);
+}
+
+#[test]
+fn profiles_say_which_role_they_assume() {
+    let config = "[default]\nregion = eu-west-2\nrole_arn = arn:aws:iam::111111111111:role/Default\n\n# [profile dev]\n[profile dev]\nsource_profile = default\nrole_arn=arn:aws:iam::222222222222:role/Dev\n\n[profile bare]\nregion = us-east-1\n";
+
+    assert_eq!(get_profile_role_arn(config, "default").as_deref(), Some("arn:aws:iam::111111111111:role/Default"));
+    assert_eq!(get_profile_role_arn(config, "dev").as_deref(), Some("arn:aws:iam::222222222222:role/Dev"));
+    assert_eq!(get_profile_role_arn(config, "bare"), None);
+    assert_eq!(get_profile_role_arn(config, "missing"), None);
+}
+
+fn run_account(dir: &Path, config: &str, role_arn: Option<&str>) -> String {
+    run_account_with_profile(dir, config, role_arn, None)
+}
+
+fn run_account_with_profile(dir: &Path, config: &str, role_arn: Option<&str>, profile: Option<&str>) -> String {
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+    command.args(["--ps1", "--color", "never", "--only", "aws-account"])
+        .current_dir(dir)
+        .env("HOME", dir.join("home"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"));
+    command.env_remove("AWS_CONFIG_FILE");
+    match role_arn {
+        Some(arn) => command.env("AWS_ROLE_ARN", arn),
+        None => command.env_remove("AWS_ROLE_ARN")
+    };
+    match profile {
+        Some(profile) => command.env("AWS_PROFILE", profile),
+        None => command.env_remove("AWS_PROFILE")
+    };
+
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn the_account_is_only_shown_when_asked_for() {
+    let dir = Fixture::new("account")
+        .dir("home/.aws/cli/cache")
+        .file("home/.aws/config", "[default]\nrole_arn = arn:aws:iam::123456789012:role/AdminRole\n\n[profile dev]\nrole_arn = arn:aws:iam::210987654321:role/Deploy\n")
+        .build();
+    let cache = dir.join("home/.aws/cli/cache");
+
+    assert_eq!(run_account(&dir, "aws-account = true\n", None), "\n");
+
+    write_credentials(&cache, "role", "arn:aws:sts::123456789012:assumed-role/AdminRole/alice", "2999-01-01T00:00:00Z");
+    assert_eq!(run_account(&dir, "", None), "\n");
+    assert_eq!(run_account(&dir, "aws-account = true\n", None), "123456789012/AdminRole\n");
+
+    let aliased = "aws-account = true\n[aws-accounts]\n123456789012 = \"prod-main\"\n";
+    assert_eq!(run_account(&dir, aliased, None), "prod-main/AdminRole\n");
+    assert_eq!(run_account(&dir, aliased, Some("arn:aws:iam::210987654321:role/Deploy")), "210987654321/Deploy\n");
+
+    // What's cached for one profile isn't shown for another
+    assert_eq!(run_account_with_profile(&dir, "aws-account = true\n", None, Some("dev")), "\n");
+    assert_eq!(run_account_with_profile(&dir, "aws-account = true\n", None, Some("missing")), "\n");
+    write_credentials(&cache, "dev", "arn:aws:sts::210987654321:assumed-role/Deploy/alice", "2999-01-01T00:00:00Z");
+    assert_eq!(run_account_with_profile(&dir, "aws-account = true\n", None, Some("dev")), "210987654321/Deploy\n");
+    assert_eq!(run_account(&dir, "aws-account = true\n", None), "123456789012/AdminRole\n");
+}
+
+#[test]
+fn danger_patterns_match_the_account_and_not_its_name() {
+    let dir = Fixture::new("account-danger")
+        .dir("home/.aws/cli/cache")
+        .file("home/.aws/config", "[default]\nrole_arn = arn:aws:iam::123456789012:role/AdminRole\n")
+        .build();
+    let cache = dir.join("home/.aws/cli/cache");
+    write_credentials(&cache, "role", "arn:aws:sts::123456789012:assumed-role/AdminRole/alice", "2999-01-01T00:00:00Z");
+
+    let run = |pattern: &str| {
+        let config = format!("aws-account = true\n[aws-accounts]\n123456789012 = \"prod-main\"\n[[danger-patterns]]\npattern = {:?}\nstyle = \"underline\"\n", pattern);
+        fs::write(dir.join("config.toml"), config).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", "always", "--only", "aws-account"])
+            .current_dir(&dir)
+            .env("HOME", dir.join("home"))
+            .env("TERM", "xterm")
+            .env("GIT_CEILING_DIRECTORIES", &dir)
+            .env("PROMPT_CONFIG", dir.join("config.toml"))
+            .env_remove("AWS_ROLE_ARN")
+            .env_remove("AWS_PROFILE")
+            .env_remove("AWS_CONFIG_FILE")
+            .env_remove("NO_COLOR")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let underlined = |x: String| x.split('\x1b').any(|x| x.starts_with('[') && x.split(['[', ';', 'm']).any(|x| x == "4"));
+    assert!(underlined(run("123456789012*")), "{:?}", run("123456789012*"));
+    assert!(run("prod-main*").contains("prod-main/AdminRole"));
+    assert!(!underlined(run("prod-main*")), "{:?}", run("prod-main*"));
+}
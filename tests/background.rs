@@ -0,0 +1,62 @@
+use std::env;
+use std::process::Command;
+
+use prompt::background::{parse_colorfgbg, parse_colour_reply, Background};
+
+#[test]
+fn colorfgbg_goes_by_the_background() {
+    assert_eq!(parse_colorfgbg("15;0"), Some(Background::Dark));
+    assert_eq!(parse_colorfgbg("0;15"), Some(Background::Light));
+    assert_eq!(parse_colorfgbg("0;default;7"), Some(Background::Light));
+    assert_eq!(parse_colorfgbg("7;8"), Some(Background::Dark));
+    assert_eq!(parse_colorfgbg("15;default"), None);
+    assert_eq!(parse_colorfgbg(""), None);
+}
+
+#[test]
+fn terminal_replies_go_by_brightness() {
+    assert_eq!(parse_colour_reply("\x1b]11;rgb:ffff/ffff/ffff\x1b\\"), Some(Background::Light));
+    assert_eq!(parse_colour_reply("\x1b]11;rgb:2828/2c2c/3434\x07"), Some(Background::Dark));
+    assert_eq!(parse_colour_reply("\x1b]11;rgb:fd/f6/e3\x07"), Some(Background::Light));
+    assert_eq!(parse_colour_reply("\x1b]11;rgb:0/0/0\x07"), Some(Background::Dark));
+    assert_eq!(parse_colour_reply("\x1b]11;rgb:ffff/ffff\x07"), None);
+    assert_eq!(parse_colour_reply(""), None);
+}
+
+// The path in the high contrast theme, which is bright cyan on a dark background and cyan on a
+// light one
+fn render_path(colorfgbg: Option<&str>, args: &[&str]) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+    command.args(["--color", "always", "--theme", "high-contrast", "--only", "path", "--cwd"])
+        .arg(env::temp_dir())
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env_remove("COLORFGBG");
+    if let Some(colorfgbg) = colorfgbg {
+        command.env("COLORFGBG", colorfgbg);
+    }
+
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn the_background_picks_the_theme_variant() {
+    assert!(render_path(None, &[]).starts_with("\x1b[1;96m"));
+    assert!(render_path(Some("15;0"), &[]).starts_with("\x1b[1;96m"));
+    assert!(render_path(Some("0;15"), &[]).starts_with("\x1b[1;36m"));
+}
+
+#[test]
+fn the_background_can_be_set() {
+    assert!(render_path(None, &["--background", "light"]).starts_with("\x1b[1;36m"));
+    assert!(render_path(Some("0;15"), &["--background", "dark"]).starts_with("\x1b[1;96m"));
+}
+
+// The output's captured here so there's no terminal to ask and the default is used straight away
+#[test]
+fn the_terminal_is_only_asked_when_its_there() {
+    assert!(render_path(None, &["--query-background"]).starts_with("\x1b[1;96m"));
+}
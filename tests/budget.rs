@@ -0,0 +1,88 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+
+use common::{Fixture, TempDir};
+
+// kubectl takes far longer than the budget, which nothing else gets near
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).script("kubectl", "#!/bin/sh\nsleep 5\necho slow\n").build()
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> (Output, Duration) {
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--message", "hi", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default()))
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("PROMPT_LOG_FILE", dir.join("prompt.log"))
+        .env_remove("PROMPT_LOG")
+        .output()
+        .unwrap();
+
+    (output, start.elapsed())
+}
+
+#[test]
+fn whatever_has_finished_is_drawn_once_the_budget_runs_out() {
+    let dir = make_dir("partial");
+    let (output, elapsed) = run_prompt(&dir, &["--budget-ms", "200", "--explain-missing"]);
+
+    assert!(output.status.success());
+    assert!(elapsed < Duration::from_secs(3), "took {:?}", elapsed);
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(" hi\n❯❯❯ \n"));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("k8s-context: the budget ran out before it finished"), "{}", stderr);
+}
+
+#[test]
+fn the_chevrons_are_drawn_even_if_nothing_finished() {
+    let dir = make_dir("nothing");
+    let (output, _) = run_prompt(&dir, &["--budget-ms", "0", "--only", "k8s-context"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n");
+
+    let (output, _) = run_prompt(&dir, &["--budget-ms", "0"]);
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with("\n❯❯❯ \n"));
+}
+
+#[test]
+fn the_budget_can_come_from_the_config() {
+    let dir = make_dir("config");
+    fs::write(dir.join("config.toml"), "budget-ms = 200\n").unwrap();
+
+    let (output, elapsed) = run_prompt(&dir, &[]);
+    assert!(output.status.success());
+    assert!(elapsed < Duration::from_secs(3), "took {:?}", elapsed);
+}
+
+#[test]
+fn debug_logging_marks_what_was_cut_short() {
+    let dir = make_dir("debug");
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--budget-ms", "200", "--only", "k8s-context"])
+        .current_dir(&dir)
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default()))
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("PROMPT_LOG", "debug")
+        .env("PROMPT_LOG_FILE", dir.join("prompt.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "…\n");
+    assert!(fs::read_to_string(dir.join("prompt.log")).unwrap().contains("ran out of time"));
+}
@@ -0,0 +1,89 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use common::{Fixture, TempDir};
+use prompt::cluster::{is_reachable, parse_server};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).dir("bin").build()
+}
+
+#[test]
+fn servers_give_a_host_and_port() {
+    let server = |host: &str, port: u16| Some((host.to_owned(), port));
+
+    assert_eq!(parse_server("https://api.prod.example.com:6443"), server("api.prod.example.com", 6443));
+    assert_eq!(parse_server("https://10.0.0.1/k8s/clusters/c-abc"), server("10.0.0.1", 443));
+    assert_eq!(parse_server("http://localhost:8080"), server("localhost", 8080));
+    assert_eq!(parse_server("https://[::1]:6443"), server("::1", 6443));
+    assert_eq!(parse_server("https://[fd00::1]"), server("fd00::1", 443));
+    assert_eq!(parse_server("unix:///var/run/k8s.sock"), None);
+    assert_eq!(parse_server("https://:6443"), None);
+    assert_eq!(parse_server("api.example.com"), None);
+}
+
+#[tokio::test]
+async fn only_a_listening_server_is_reachable() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert!(is_reachable("127.0.0.1", port, Duration::from_millis(200)).await);
+
+    drop(listener);
+    assert!(!is_reachable("127.0.0.1", port, Duration::from_millis(200)).await);
+}
+
+fn run_prompt(dir: &Path, port: u16, config: &str) -> String {
+    let kubectl = dir.join("bin").join("kubectl");
+    fs::write(&kubectl, format!("#!/bin/sh\necho 'vpn-dev|https://127.0.0.1:{}'\n", port)).unwrap();
+    fs::set_permissions(&kubectl, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--only", "k8s-reachable"])
+        .current_dir(dir)
+        .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH").unwrap()))
+        .env("TERM", "xterm")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn an_unreachable_cluster_is_marked_when_asked_for() {
+    let dir = make_dir("marked");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    assert_eq!(run_prompt(&dir, port, ""), "", "nothing's probed by default");
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\n"), "◦");
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\nunprobed-clusters = [\"vpn-*\"]\n"), "");
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\nunprobed-clusters = [\"127.0.0.*\"]\n"), "");
+}
+
+#[test]
+fn the_answer_is_kept_for_a_while() {
+    let dir = make_dir("kept");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\n"), "");
+    drop(listener);
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\n"), "");
+
+    fs::remove_dir_all(dir.join("cache")).unwrap();
+    assert_eq!(run_prompt(&dir, port, "k8s-reachability = true\n"), "◦");
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::Fixture;
+use colored::Color;
+use prompt::colour::{get_accent, to_basic, ACCENTS};
+
+// Colour follows the terminal when running in CI, which these tests might well be
+const CI_VARIABLES: [&str; 6] = ["CI", "GITHUB_ACTIONS", "GITLAB_CI", "BUILDKITE", "JENKINS_URL", "CIRCLECI"];
+
+// Renders just the message in the given colour to see what comes out for each kind of terminal
+fn render_message(colour: &str, term: &str, colour_term: &str) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+    command.args(["--ps1", "--message", "x", "--segment-color", &format!("message={}", colour), "--side", "message=right", "--right"])
+        .current_dir(env::temp_dir())
+        .env("TERM", term)
+        .env("COLORTERM", colour_term)
+        .env_remove("NO_COLOR")
+        .env_remove("COLORFGBG")
+        .env_remove("INSIDE_EMACS");
+    for name in CI_VARIABLES {
+        command.env_remove(name);
+    }
+
+    let output = command.output().unwrap();
+
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn truecolour_terminals_get_rgb() {
+    assert_eq!(render_message("#87d7ff", "xterm-256color", "truecolor"), "\x1b[1;38;2;135;215;255mx\x1b[0m");
+    assert_eq!(render_message("#87d7ff", "xterm", "24bit"), "\x1b[1;38;2;135;215;255mx\x1b[0m");
+}
+
+#[test]
+fn palette_terminals_get_the_nearest_palette_colour() {
+    assert_eq!(render_message("#87d7ff", "xterm-256color", ""), "\x1b[1;38;5;117mx\x1b[0m");
+    assert_eq!(render_message("#ff0000", "xterm-256color", ""), "\x1b[1;38;5;196mx\x1b[0m");
+    assert_eq!(render_message("#808080", "xterm-256color", ""), "\x1b[1;38;5;244mx\x1b[0m");
+    assert_eq!(render_message("#000001", "xterm-256color", ""), "\x1b[1;38;5;16mx\x1b[0m");
+    assert_eq!(render_message("117", "xterm-256color", ""), "\x1b[1;38;5;117mx\x1b[0m");
+    assert_eq!(render_message("250", "xterm-256color", ""), "\x1b[1;38;5;250mx\x1b[0m");
+}
+
+#[test]
+fn basic_terminals_get_the_nearest_basic_colour() {
+    assert_eq!(render_message("#87d7ff", "xterm", ""), "\x1b[1;37mx\x1b[0m");
+    assert_eq!(render_message("#ff0000", "xterm", ""), "\x1b[1;91mx\x1b[0m");
+    assert_eq!(render_message("#808080", "xterm", ""), "\x1b[1;90mx\x1b[0m");
+    assert_eq!(render_message("#0a0a0a", "xterm", ""), "\x1b[1;30mx\x1b[0m");
+    assert_eq!(render_message("#00af00", "xterm", ""), "\x1b[1;32mx\x1b[0m");
+}
+
+#[test]
+fn basic_colours_are_left_alone() {
+    assert_eq!(render_message("3", "xterm-256color", "truecolor"), "\x1b[1;33mx\x1b[0m");
+    assert_eq!(render_message("bright blue", "xterm", ""), "\x1b[1;94mx\x1b[0m");
+}
+
+fn render_in_ci(vars: &[(&str, &str)], args: &[&str]) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+    command.args(["--ps1", "--only", "ci,message", "--message", "x"])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env_remove("NO_COLOR")
+        .env_remove("CLICOLOR_FORCE")
+        .env_remove("COLORTERM");
+    for name in CI_VARIABLES.iter().chain(&["GITHUB_RUN_ID"]) {
+        command.env_remove(name);
+    }
+
+    let output = command.envs(vars.iter().copied()).output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn ci_logs_only_get_colour_when_asked_for() {
+    assert_eq!(render_in_ci(&[("CI", "true")], &[]), "x");
+    assert_eq!(render_in_ci(&[("CI", "true")], &["--color", "always"]), "\x1b[1;32mx\x1b[0m");
+    assert_eq!(render_in_ci(&[("CI", "false")], &[]), "\x1b[1;32mx\x1b[0m");
+}
+
+#[test]
+fn the_ci_segment_says_which_system_it_is() {
+    assert_eq!(render_in_ci(&[("CI", "true")], &["--ci", "--color", "never"]), "ci x");
+    assert_eq!(render_in_ci(&[("CI", "true"), ("GITHUB_ACTIONS", "true"), ("GITHUB_RUN_ID", "1234")], &["--ci", "--color", "never"]), "ci:github#1234 x");
+    assert_eq!(render_in_ci(&[("GITLAB_CI", "true")], &["--ci", "--color", "never"]), "ci:gitlab x");
+    assert_eq!(render_in_ci(&[("GITHUB_ACTIONS", "true")], &["--color", "never"]), "x");
+    assert_eq!(render_in_ci(&[], &["--ci", "--color", "never"]), "x");
+}
+
+#[test]
+fn accents_are_the_same_everywhere() {
+    assert_eq!(get_accent(b"/home/me/src/website"), Color::TrueColor { r: 0xff, g: 0x87, b: 0xd7 });
+    assert_eq!(get_accent(b"/srv/repos/api"), Color::TrueColor { r: 0x87, g: 0x87, b: 0xff });
+    assert_eq!(get_accent(b"/home/me/src/prompt"), get_accent(b"/home/me/src/prompt"));
+}
+
+#[test]
+fn accents_are_never_mistaken_for_a_warning() {
+    for (r, g, b) in ACCENTS {
+        assert!(!matches!(to_basic((r, g, b)), Color::Red | Color::Yellow | Color::BrightRed | Color::BrightYellow), "{:?}", (r, g, b));
+
+        // Red is the strongest without blue being anywhere near it, from red through orange to yellow
+        assert!(!(r >= g && r >= b && b < r / 2), "{:?}", (r, g, b));
+    }
+}
+
+fn render_accent(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "always", "--only", "path", "--path-style", "full"])
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm-256color")
+        .env("COLORTERM", "truecolor")
+        .env("PROMPT_CONFIG", dir.join("missing.toml"))
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn each_repository_gets_an_accent_of_its_own() {
+    let repo = Fixture::new("accent").dir(".git").dir("src").build();
+
+    let Color::TrueColor { r, g, b } = get_accent(repo.as_os_str().as_encoded_bytes()) else { unreachable!() };
+    let accent = format!("38;2;{};{};{}m", r, g, b);
+
+    assert!(!render_accent(&repo, &["--repo-accent"]).contains(&accent), "the path is only accented when asked");
+    assert!(render_accent(&repo, &["--repo-accent", "--repo-accent-path"]).contains(&accent));
+    assert!(render_accent(&repo.join("src"), &["--repo-accent", "--repo-accent-path"]).contains(&accent), "the whole repository gets the same one");
+    assert!(render_accent(&repo, &["--repo-accent", "--repo-accent-path", "--segment-color", "path=#010203"]).contains("38;2;1;2;3m"));
+
+    fs::write(repo.join(".prompt.toml"), "repo-accent-color = \"#123456\"\n").unwrap();
+    assert!(render_accent(&repo.join("src"), &["--repo-accent", "--repo-accent-path"]).contains("38;2;18;52;86m"));
+}
+
+#[test]
+fn clones_of_the_same_remote_get_the_same_accent() {
+    let dir = Fixture::new("accent-remote")
+        .git("one", &["init", "--quiet"])
+        .git("one", &["remote", "add", "origin", "git@github.com:me/app.git"])
+        .git("two", &["init", "--quiet"])
+        .git("two", &["remote", "add", "origin", "gh:me/app.git"])
+        .git("two", &["config", "url.git@github.com:.insteadOf", "gh:"])
+        .build();
+
+    let Color::TrueColor { r, g, b } = get_accent(b"git@github.com:me/app.git") else { unreachable!() };
+    let accent = format!("38;2;{};{};{}m", r, g, b);
+
+    assert!(render_accent(&dir.join("one"), &["--repo-accent", "--repo-accent-path"]).contains(&accent));
+    assert!(render_accent(&dir.join("two"), &["--repo-accent", "--repo-accent-path"]).contains(&accent));
+}
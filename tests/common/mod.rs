@@ -0,0 +1,138 @@
+// What the integration tests set themselves up with. Each test file only uses some of it
+#![allow(dead_code)]
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Nothing from whoever's running the tests gets to change what git does
+pub const GIT_ENV: [(&str, &str); 8] = [
+    ("GIT_CONFIG_GLOBAL", "/dev/null"),
+    ("GIT_CONFIG_NOSYSTEM", "1"),
+    ("GIT_AUTHOR_NAME", "Prompt"),
+    ("GIT_AUTHOR_EMAIL", "prompt@example.com"),
+    ("GIT_AUTHOR_DATE", "2024-01-01T00:00:00Z"),
+    ("GIT_COMMITTER_NAME", "Prompt"),
+    ("GIT_COMMITTER_EMAIL", "prompt@example.com"),
+    ("GIT_COMMITTER_DATE", "2024-01-01T00:00:00Z"),
+];
+
+pub fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git").args(args).current_dir(dir).envs(GIT_ENV).output().unwrap();
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+}
+
+// A directory of the test's own, which goes again once the test's finished with it
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<OsStr> for TempDir {
+    fn as_ref(&self) -> &OsStr {
+        self.0.as_os_str()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// A file of the test's own, for when that's all it needs, which goes along with the directory it's in
+pub struct TempFile {
+    path: PathBuf,
+    _dir: TempDir,
+}
+
+impl Deref for TempFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<OsStr> for TempFile {
+    fn as_ref(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+}
+
+// Lays out whatever a test needs under a fresh directory, with every path relative to the top of it
+pub struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    // Tests running at the same time each have a name of their own, and anything a killed run left
+    // behind is cleared out first. It's the real path so it matches what the prompt works out
+    pub fn new(name: &str) -> Fixture {
+        let dir = env::temp_dir().join(format!("prompt-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Fixture { dir: dir.canonicalize().unwrap() }
+    }
+
+    // For anything written into the fixture that has to know where it is
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn dir(self, path: &str) -> Fixture {
+        fs::create_dir_all(self.dir.join(path)).unwrap();
+        self
+    }
+
+    pub fn file(self, path: &str, contents: impl AsRef<[u8]>) -> Fixture {
+        let path = self.dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    #[cfg(unix)]
+    pub fn script(self, path: &str, contents: impl AsRef<[u8]>) -> Fixture {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fixture = self.file(path, contents);
+        fs::set_permissions(fixture.dir.join(path), fs::Permissions::from_mode(0o755)).unwrap();
+        fixture
+    }
+
+    // Run in the directory given, which is made first if it isn't there yet
+    pub fn git(self, path: &str, args: &[&str]) -> Fixture {
+        let fixture = self.dir(path);
+        git(&fixture.dir.join(path), args);
+        fixture
+    }
+
+    pub fn build(self) -> TempDir {
+        TempDir(self.dir)
+    }
+
+    pub fn build_file(self, path: &str) -> TempFile {
+        TempFile { path: self.dir.join(path), _dir: self.build() }
+    }
+}
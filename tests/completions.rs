@@ -0,0 +1,26 @@
+use std::process::Command;
+
+fn get_completions(shell: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["completions", shell]).output().unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn segment_flags_complete_segment_names() {
+    let zsh = get_completions("zsh");
+
+    let only = zsh.lines().find(|x| x.contains("--only=")).unwrap();
+    assert!(only.contains(" git-branch ") && only.contains(" k8s-context "), "{}", only);
+
+    let disable = zsh.lines().find(|x| x.contains("--disable=")).unwrap();
+    assert!(disable.contains(" git-branch ") && disable.contains(" k8s "), "{}", disable);
+}
+
+#[test]
+fn every_shell_gets_completions() {
+    assert!(get_completions("bash").contains("complete -F _prompt"));
+    assert!(get_completions("fish").contains("complete -c prompt"));
+    assert!(get_completions("zsh").starts_with("#compdef prompt"));
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::condition::{is_in_container, parse_condition, Condition, Multiplexer, Surroundings};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).dir("repo/work").build()
+}
+
+fn get_surroundings(dir: &Path) -> Surroundings {
+    Surroundings { multiplexer: Some(Multiplexer::Tmux), ssh: false, container: true, dir: dir.to_path_buf() }
+}
+
+#[test]
+fn not_binds_tighter_than_and_and_and_tighter_than_or() {
+    let condition = parse_condition("!ssh & tmux | container").unwrap();
+
+    assert_eq!(condition, Condition::Any(vec![
+        Condition::All(vec![Condition::Not(Box::new(Condition::Ssh)), Condition::Tmux]),
+        Condition::Container
+    ]));
+    assert_eq!(parse_condition("!(ssh | tmux)").unwrap(), Condition::Not(Box::new(Condition::Any(vec![Condition::Ssh, Condition::Tmux]))));
+}
+
+#[test]
+fn conditions_are_written_back_the_way_they_parse() {
+    for text in ["ssh", "!multiplexer", "env:AWS_PROFILE=prod-*", "ssh & (tmux | screen)", "!(file:*.tf | repo-file:Chart.yaml) & container", "dir:~/work/*"] {
+        let condition = parse_condition(text).unwrap();
+
+        assert_eq!(condition.to_string(), text);
+        assert_eq!(parse_condition(&condition.to_string()).unwrap(), condition);
+    }
+}
+
+#[test]
+fn broken_conditions_say_what_is_wrong() {
+    let broken = [
+        ("mars", "unknown condition 'mars'"),
+        ("ssh &", "expected a condition"),
+        ("(ssh | tmux", "a bracket is never closed"),
+        ("ssh) tmux", "unexpected ') tmux'"),
+        ("env:", "has no variable"),
+        ("env:=prod", "has no variable"),
+    ];
+
+    for (text, message) in broken {
+        let error = parse_condition(text).unwrap_err();
+        assert!(error.contains(message), "{:?}: {}", text, error);
+    }
+}
+
+#[test]
+fn files_are_looked_for_in_the_directory_and_the_repository() {
+    let dir = make_dir("files");
+    fs::create_dir_all(dir.join("repo").join(".git")).unwrap();
+    fs::write(dir.join("repo").join("Chart.yaml"), "").unwrap();
+    fs::write(dir.join("repo").join("work").join("main.tf"), "").unwrap();
+
+    let surroundings = get_surroundings(&dir.join("repo").join("work"));
+    let holds = |text: &str| surroundings.holds(&parse_condition(text).unwrap());
+
+    assert!(holds("file:*.tf") && !holds("file:Chart.yaml"));
+    assert!(holds("repo-file:Chart.yaml") && !holds("repo-file:*.tf"));
+    assert!(holds("tmux & container & !ssh") && holds("ssh | screen | file:main.tf") && !holds("ssh | screen"));
+
+    // Without a repository there's nothing for repo-file to find
+    let outside = get_surroundings(&dir);
+    assert!(!outside.holds(&parse_condition("repo-file:*").unwrap()));
+}
+
+#[test]
+fn containers_leave_a_file_behind() {
+    let dir = make_dir("container");
+    let root = dir.join("repo");
+
+    // Anything running these tests inside a container would always see one
+    if env::var_os("container").is_none() {
+        assert!(!is_in_container(&root));
+    }
+
+    fs::write(root.join(".dockerenv"), "").unwrap();
+    assert!(is_in_container(&root));
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir)
+        .env("PWD", dir)
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("PROMPT_TEST_STAGE", "prod-eu")
+        .env_remove("PROMPT_TEST_UNSET")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn segments_are_only_shown_when_their_conditions_hold() {
+    let dir = make_dir("segments");
+    let shows_path = |condition: &str| run_prompt(&dir, &["--when", &format!("path={}", condition)]).contains("prompt-test-segments");
+
+    assert!(shows_path("env:PROMPT_TEST_STAGE") && !shows_path("env:PROMPT_TEST_UNSET"));
+    assert!(shows_path("env:PROMPT_TEST_STAGE=prod-*") && !shows_path("env:PROMPT_TEST_STAGE=prod"));
+    assert!(shows_path("env:PROMPT_TEST_UNSET | file:repo") && !shows_path("env:PROMPT_TEST_STAGE & !file:repo"));
+
+    // Every condition given for a segment has to hold
+    assert!(!run_prompt(&dir, &["--when", "path=file:repo", "--when", "path=env:PROMPT_TEST_UNSET"]).contains("prompt-test-segments"));
+}
+
+#[test]
+fn the_config_can_give_conditions_too() {
+    let dir = make_dir("config");
+    fs::write(dir.join("config.toml"), "[segments.path]\nwhen = [\"env:PROMPT_TEST_STAGE=staging | ssh\"]\n").unwrap();
+
+    assert!(!run_prompt(&dir, &[]).contains("prompt-test-config"));
+}
+
+#[test]
+fn explain_lists_each_segments_conditions() {
+    let dir = make_dir("explain");
+    fs::write(dir.join("config.toml"), "[segments.path]\nwhen = [\"!ssh\", \"file:*.tf | env:TF_WORKSPACE\"]\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["explain", "--color", "never", "--when", "duration=ssh"])
+        .current_dir(&dir)
+        .env("TERM", "xterm")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+    let legend = String::from_utf8(output.stdout).unwrap();
+
+    assert!(legend.lines().any(|x| x.starts_with("  path ") && x.ends_with(", shown only when !ssh & (file:*.tf | env:TF_WORKSPACE)")), "{}", legend);
+    assert!(legend.lines().any(|x| x.starts_with("  duration ") && x.ends_with(", shown only when ssh")), "{}", legend);
+    assert!(!legend.lines().any(|x| x.starts_with("  git-branch ") && x.contains("shown only when")), "{}", legend);
+}
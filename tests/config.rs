@@ -0,0 +1,308 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{Fixture, TempDir, TempFile};
+
+const CONFIG: &str = r##"
+separator = " | "
+order = ["duration", "path"]
+timeout-ms = 200
+
+[path]
+style = "fish"
+aliases = { "/srv" = "srv:magenta" }
+
+[segments.message]
+color = "red"
+label = "msg:"
+
+[segments.duration]
+side = "left"
+
+[segments.k8s-context]
+enabled = false
+
+[colors]
+path = "#ff00ff"
+
+[chevrons]
+exit = ">"
+"##;
+
+fn write_config(name: &str, contents: &str) -> TempFile {
+    Fixture::new(name).file("config.toml", contents).build_file("config.toml")
+}
+
+fn run(config: &Path, args: &[&str]) -> Output {
+    run_in(&env::temp_dir(), config, args)
+}
+
+fn run_in(dir: &Path, config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(args)
+        .current_dir(dir)
+        .env("PWD", dir)
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("PROMPT_CONFIG", config)
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn config_round_trips() {
+    let path = write_config("round-trip", CONFIG);
+    let shown = stdout(run(&path, &["config", "show"]));
+
+    let again = write_config("round-trip-again", &shown);
+    assert_eq!(stdout(run(&again, &["config", "show"])), shown);
+}
+
+#[test]
+fn default_config_round_trips() {
+    let missing = env::temp_dir().join("prompt-config-test-missing.toml");
+    let defaults = stdout(run(&missing, &["config", "default"]));
+
+    let path = write_config("defaults", &defaults);
+    assert_eq!(stdout(run(&path, &["config", "check"])), format!("{}: ok\n", path.display()));
+
+    let shown = stdout(run(&path, &["config", "show"]));
+    let again = write_config("defaults-again", &shown);
+    assert_eq!(stdout(run(&again, &["config", "show"])), shown);
+
+    // Writing the defaults out in full looks just like having no config at all
+    assert_eq!(stdout(run(&path, &["--message", "hi"])), stdout(run(&missing, &["--message", "hi"])));
+    assert_eq!(stdout(run(&path, &["--message", "hi", "--right", "--duration-ms", "5000"])), stdout(run(&missing, &["--message", "hi", "--right", "--duration-ms", "5000"])));
+}
+
+#[test]
+fn default_config_lists_every_segment() {
+    let missing = env::temp_dir().join("prompt-config-test-missing.toml");
+    let defaults = stdout(run(&missing, &["config", "default"]));
+
+    for segment in ["path", "git-branch", "k8s-context", "aws-region", "duration", "pipestatus"] {
+        assert!(defaults.contains(&format!("[segments.{}]\nenabled = true\n", segment)), "{}", defaults);
+    }
+    assert!(defaults.contains("[segments.duration]\nenabled = true\nside = \"right\"\n"), "{}", defaults);
+}
+
+#[test]
+fn config_changes_the_prompt() {
+    let path = write_config("output", CONFIG);
+    let output = stdout(run(&path, &["--ps1", "--message", "hi", "--duration-ms", "5000", "--color", "never"]));
+
+    let top_line = output.lines().nth(1).unwrap();
+    assert!(top_line.starts_with("5.0s | ") && top_line.ends_with(" | msg:hi"), "{:?}", output);
+    assert!(output.lines().nth(2).unwrap().starts_with('>'), "{:?}", output);
+}
+
+#[test]
+fn flags_beat_the_config() {
+    let path = write_config("flags", CONFIG);
+    let output = stdout(run(&path, &["--ps1", "--message", "hi", "--duration-ms", "5000", "--color", "never", "--separator", ",", "--side", "duration=right"]));
+
+    assert!(output.lines().nth(1).unwrap().ends_with(",msg:hi"), "{:?}", output);
+    assert!(!output.contains("5.0s"), "{:?}", output);
+}
+
+#[test]
+fn broken_config_falls_back_to_the_defaults() {
+    let missing = env::temp_dir().join("prompt-config-test-missing.toml");
+
+    for contents in ["separator = [", "separator = 1", "unknown = true", "[segments.message]\ncolor = \"notacolour\""] {
+        let path = write_config("broken", contents);
+        assert_eq!(stdout(run(&path, &["--message", "hi"])), stdout(run(&missing, &["--message", "hi"])));
+
+        let check = run(&path, &["config", "check"]);
+        assert!(!check.status.success());
+        assert!(String::from_utf8_lossy(&check.stderr).starts_with(&path.display().to_string()));
+    }
+}
+
+#[test]
+fn check_accepts_a_good_config() {
+    let path = write_config("check", CONFIG);
+    assert_eq!(stdout(run(&path, &["config", "check"])), format!("{}: ok\n", path.display()));
+}
+
+#[test]
+fn check_says_where_mistakes_are() {
+    let broken = [
+        ("theme = \"dracula\"\nunknown = true\n", "line 2", "unknown field `unknown`"),
+        ("[segments.path]\ncolour = \"red\"\n", "line 2", "unknown field `colour`"),
+        ("[segments.nope]\nenabled = false\n", "line 1", "unknown variant `nope`"),
+        ("[segments.path]\n\ncolor = \"notacolour\"\n", "line 3", "unknown colour 'notacolour'"),
+        ("[colors]\npath = \"#ff00fg\"\n", "line 2", "#ff00fg"),
+        ("[segments.aws-region]\nwhen = [\"!mars\"]\n", "line 2", "unknown condition 'mars'"),
+        ("template = \"{nope}\"\n", "line 1", "unknown segment 'nope'"),
+        ("[keymaps]\nvicmd = \"❮:nope\"\n", "line 2", "unknown colour 'nope'"),
+        ("icon-width = -1\n", "line 1", "icon-width = -1"),
+    ];
+
+    for (contents, line, message) in broken {
+        let path = write_config("mistakes", contents);
+        let check = run(&path, &["config", "check"]);
+        let stderr = String::from_utf8_lossy(&check.stderr);
+
+        assert!(!check.status.success(), "{:?}", contents);
+        assert!(stderr.contains(line) && stderr.contains(message), "{:?}: {}", contents, stderr);
+    }
+
+    // Options that can't go together are only found out once they're turned into flags
+    let path = write_config("conflict", "fill = \".\"\nsingle-line = true\n");
+    let check = run(&path, &["config", "check"]);
+    assert!(!check.status.success());
+    assert!(String::from_utf8_lossy(&check.stderr).contains("--single-line"));
+}
+
+// These need to work anywhere, including where git or kubectl would hang
+#[cfg(unix)]
+#[test]
+fn config_commands_do_not_run_anything() {
+    let mut fixture = Fixture::new("bin");
+    let log = fixture.path().join("log");
+    for name in ["git", "kubectl", "tmux", "sudo"] {
+        fixture = fixture.script(name, format!("#!/bin/sh\necho {} >> '{}'\n", name, log.display()));
+    }
+    let bin = fixture.build();
+
+    let path = write_config("commands", "sudo = true\ntmux-session = true\n");
+    for args in [["config", "check"], ["config", "default"], ["config", "show"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(args)
+            .current_dir(env::temp_dir())
+            .env("PATH", &bin)
+            .env("PROMPT_CONFIG", &path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+    assert!(!log.exists(), "{}", fs::read_to_string(&log).unwrap_or_default());
+}
+
+// A fake repository with an overlay at the top and another further in
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(&format!("{}-repo", name))
+        .dir(".git")
+        .file(".prompt.toml", "separator = \",\"\nsudo = true\n\n[segments.message]\nlabel = \"m:\"\n")
+        .file("sub/.prompt.toml", "separator = \";\"\n")
+        .build()
+}
+
+#[test]
+fn overlays_merge_over_the_config() {
+    let repo = make_repo("overlay");
+    let path = write_config("overlay", "separator = \" | \"\n\n[segments.message]\ncolor = \"red\"\n");
+
+    let output = stdout(run_in(&repo.join("sub"), &path, &["--ps1", "--message", "hi", "--color", "never"]));
+    assert!(output.lines().nth(1).unwrap().ends_with(";m:hi"), "{:?}", output);
+
+    let shown = stdout(run_in(&repo.join("sub"), &path, &["config", "show"]));
+    assert!(shown.contains("separator = \";\""), "{}", shown);
+    assert!(shown.contains("color = \"red\""), "{}", shown);
+
+    let output = stdout(run_in(&repo, &path, &["--ps1", "--message", "hi", "--color", "never"]));
+    assert!(output.lines().nth(1).unwrap().ends_with(",m:hi"), "{:?}", output);
+}
+
+#[test]
+fn untrusted_overlays_cannot_run_commands() {
+    let repo = make_repo("untrusted");
+    let path = write_config("untrusted", "");
+
+    let shown = stdout(run_in(&repo, &path, &["config", "show"]));
+    assert!(shown.contains("sudo = false"), "{}", shown);
+    assert!(shown.contains("separator = \",\""), "{}", shown);
+
+    let trusted = write_config("trusted", &format!("trusted = [{:?}]\n", repo.display().to_string()));
+    let shown = stdout(run_in(&repo.join("sub"), &trusted, &["config", "show"]));
+    assert!(shown.contains("sudo = true"), "{}", shown);
+}
+
+#[test]
+fn untrusted_overlays_cannot_change_anything_that_runs_commands() {
+    let settings = [
+        "sudo = true\n",
+        "tmux-session = true\n",
+        "[custom.hi]\ncommand = \"echo hi\"\n",
+        "plugins = [\"hi\"]\n",
+        "merge-requests = true\n",
+        "merge-request-ttl = 1\n",
+        "[forge-hosts]\n\"git.example.com\" = \"gitlab\"\n",
+        "k8s-reachability = true\n",
+        "unprobed-clusters = []\n",
+        "git-lfs = true\n",
+        "git-remote = true\n",
+        "fsmonitor = \"always\"\n",
+        "inherit-env = true\n",
+        "local-ip = true\n",
+        "ip-probe = \"10.0.0.1\"\n",
+    ];
+
+    let repo = make_repo("each-setting");
+    fs::remove_file(repo.join("sub").join(".prompt.toml")).unwrap();
+    fs::write(repo.join(".prompt.toml"), "").unwrap();
+    let config = "unprobed-clusters = [\"prod\"]\n";
+    let untrusted = write_config("each-setting-untrusted", config);
+    let trusted = write_config("each-setting-trusted", &format!("{}trusted = [{:?}]\n", config, repo.display().to_string()));
+    let baseline = stdout(run_in(&repo, &untrusted, &["config", "show"]));
+
+    for setting in settings {
+        fs::write(repo.join(".prompt.toml"), setting).unwrap();
+        assert_eq!(stdout(run_in(&repo, &untrusted, &["config", "show"])), baseline, "{}", setting);
+        assert_ne!(stdout(run_in(&repo, &trusted, &["config", "show"])).replace(&repo.display().to_string(), ""), baseline, "{}", setting);
+    }
+}
+
+#[test]
+fn untrusted_overlays_cannot_turn_segments_back_on() {
+    let repo = make_repo("back-on");
+    fs::write(repo.join(".prompt.toml"), "disable = [\"git\"]\n\n[segments.k8s-context]\nenabled = true\n\n[segments.path]\nenabled = false\n").unwrap();
+    let path = write_config("back-on", "disable = [\"aws\"]\n\n[segments.k8s-context]\nenabled = false\n");
+
+    let shown = stdout(run_in(&repo, &path, &["config", "show"]));
+    assert!(shown.contains("disable = [\"git\", \"aws\"]"), "{}", shown);
+    assert!(shown.contains("[segments.k8s-context]\nenabled = false"), "{}", shown);
+    assert!(shown.contains("[segments.path]\nenabled = false"), "{}", shown);
+}
+
+#[test]
+fn overlays_outside_a_repository_are_ignored() {
+    let repo = make_repo("outside");
+    fs::remove_dir(repo.join(".git")).unwrap();
+
+    let missing = env::temp_dir().join("prompt-config-test-missing.toml");
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["config", "show"])
+        .current_dir(repo.join("sub"))
+        .env("PWD", repo.join("sub"))
+        .env("HOME", env::temp_dir().join("prompt-config-test-no-home"))
+        .env("PROMPT_CONFIG", &missing)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(output), stdout(run(&missing, &["config", "show"])));
+}
+
+#[test]
+fn broken_overlays_are_reported() {
+    let repo = make_repo("broken-overlay");
+    fs::write(repo.join("sub").join(".prompt.toml"), "icon-width = \"x\"\n").unwrap();
+    let path = write_config("broken-overlay", "separator = \" | \"\n");
+
+    let output = stdout(run_in(&repo.join("sub"), &path, &["--ps1", "--message", "hi", "--color", "never"]));
+    assert!(output.lines().nth(1).unwrap().ends_with(" | hi"), "{:?}", output);
+
+    let check = run_in(&repo.join("sub"), &path, &["config", "check"]);
+    assert!(!check.status.success());
+    assert!(String::from_utf8_lossy(&check.stderr).starts_with(&repo.join("sub").join(".prompt.toml").display().to_string()));
+}
@@ -0,0 +1,156 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use common::{Fixture, TempDir};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).dir("work").build()
+}
+
+fn run_prompt(dir: &Path, config: &str, args: &[&str]) -> String {
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("PROMPT_TEST_ENVIRONMENT", "production")
+        .env("PROMPT_TEST_ESCAPES", "\x1b]0;title\x07staging\x1b[2J")
+        .env_remove("PROMPT_TEST_UNSET")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn get_top_line(output: &str) -> &str {
+    output.lines().nth(1).unwrap()
+}
+
+#[test]
+fn custom_segments_show_the_first_line() {
+    let dir = make_dir("first-line");
+    let config = "[custom.hello]\ncommand = \"printf 'hello\\\\033[31m\\\\nsecond line'\"\nlabel = \"say:\"\n";
+
+    let output = run_prompt(&dir, config, &[]);
+    assert!(get_top_line(&output).ends_with(" say:hello"), "{:?}", output);
+    assert!(!output.contains("second line"), "{:?}", output);
+    assert!(!output.contains('\x1b'), "{:?}", output);
+}
+
+#[test]
+fn custom_segments_can_go_on_the_right() {
+    let dir = make_dir("right");
+    let config = "[custom.hello]\ncommand = \"echo hello\"\nside = \"right\"\n";
+
+    assert!(!run_prompt(&dir, config, &[]).contains("hello"));
+    assert_eq!(run_prompt(&dir, config, &["--right"]), "hello\n");
+}
+
+#[test]
+fn broken_custom_segments_are_hidden() {
+    let dir = make_dir("broken");
+    let config = concat!(
+        "[custom.fails]\ncommand = \"echo fails; exit 1\"\n\n",
+        "[custom.slow]\ncommand = \"sleep 5; echo slow\"\ntimeout-ms = 100\n\n",
+        "[custom.missing]\ncommand = \"definitely-not-a-command-anywhere\"\n\n",
+        "[custom.works]\ncommand = \"echo works\"\n",
+    );
+
+    let start = Instant::now();
+    let output = run_prompt(&dir, config, &[]);
+    assert!(start.elapsed() < Duration::from_secs(3), "{:?}", start.elapsed());
+
+    assert!(get_top_line(&output).ends_with(" works"), "{:?}", output);
+    assert!(!output.contains("fails") && !output.contains("slow"), "{:?}", output);
+}
+
+#[test]
+fn conditions_decide_whether_custom_segments_run() {
+    let dir = make_dir("conditions");
+    fs::write(dir.join("work").join("Cargo.toml"), "").unwrap();
+
+    let config = concat!(
+        "[custom.rust]\ncommand = \"echo rust\"\nwhen = [\"file:Cargo.*\"]\n\n",
+        "[custom.node]\ncommand = \"echo node\"\nwhen = [\"file:package.json\"]\n\n",
+        "[custom.set]\ncommand = \"echo set\"\nwhen = [\"env:PWD\"]\n\n",
+        "[custom.unset]\ncommand = \"echo unset\"\nwhen = [\"env:PROMPT_TEST_UNSET\"]\n\n",
+        "[custom.negated]\ncommand = \"echo negated\"\nwhen = [\"!env:PROMPT_TEST_UNSET\", \"!file:package.json\"]\n",
+    );
+
+    let output = run_prompt(&dir, config, &[]);
+    let top_line = get_top_line(&output);
+    assert!(top_line.contains(" negated") && top_line.contains(" rust") && top_line.contains(" set"), "{:?}", output);
+    assert!(!top_line.contains("node") && !top_line.contains("unset"), "{:?}", output);
+}
+
+#[test]
+fn custom_segments_are_cached() {
+    let dir = make_dir("cache");
+    let counter = dir.join("counter");
+    let config = format!("[custom.count]\ncommand = \"echo x >> '{}'; wc -l < '{}'\"\ncache-ttl = 600\n", counter.display(), counter.display());
+
+    let first = run_prompt(&dir, &config, &[]);
+    assert!(get_top_line(&first).ends_with(" 1"), "{:?}", first);
+    assert_eq!(run_prompt(&dir, &config, &[]), first);
+    assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn untrusted_repositories_cannot_add_custom_segments() {
+    let dir = make_dir("untrusted");
+    fs::create_dir_all(dir.join("work").join(".git")).unwrap();
+    fs::write(dir.join("work").join(".prompt.toml"), "[custom.evil]\ncommand = \"echo evil\"\n").unwrap();
+
+    assert!(!run_prompt(&dir, "", &[]).contains("evil"));
+
+    let trusted = format!("trusted = [{:?}]\n", dir.join("work").display().to_string());
+    assert!(run_prompt(&dir, &trusted, &[]).contains("evil"));
+}
+
+#[test]
+fn env_segments_show_a_variable() {
+    let dir = make_dir("env");
+    let config = concat!(
+        "[env.environment]\nvariable = \"PROMPT_TEST_ENVIRONMENT\"\nlabel = \"env:\"\n\n",
+        "[env.unset]\nvariable = \"PROMPT_TEST_UNSET\"\nlabel = \"unset:\"\n\n",
+        "[env.escapes]\nvariable = \"PROMPT_TEST_ESCAPES\"\nside = \"right\"\n",
+    );
+
+    let output = run_prompt(&dir, config, &[]);
+    assert!(get_top_line(&output).ends_with(" env:production"), "{:?}", output);
+    assert!(!output.contains("unset:") && !output.contains("staging"), "{:?}", output);
+    assert_eq!(run_prompt(&dir, config, &["--right"]), "staging\n");
+}
+
+#[test]
+fn env_segments_can_be_aliased_and_filtered() {
+    let dir = make_dir("env-aliases");
+    let config = concat!(
+        "[env.aliased]\nvariable = \"PROMPT_TEST_ENVIRONMENT\"\n\n[env.aliased.aliases]\nproduction = \"PROD:red\"\n\n",
+        "[env.matched]\nvariable = \"PROMPT_TEST_ENVIRONMENT\"\nmatches = [\"prod*\"]\nlabel = \"matched:\"\n\n",
+        "[env.unmatched]\nvariable = \"PROMPT_TEST_ENVIRONMENT\"\nmatches = [\"staging\", \"dev\"]\nlabel = \"unmatched:\"\n\n",
+        "[env.short]\nvariable = \"PROMPT_TEST_ENVIRONMENT\"\nmax-width = 5\nlabel = \"short:\"\n",
+    );
+
+    let output = run_prompt(&dir, config, &[]);
+    let top_line = get_top_line(&output);
+    assert!(top_line.contains(" PROD") && top_line.contains(" matched:production") && top_line.contains(" short:prod…"), "{:?}", output);
+    assert!(!top_line.contains("unmatched"), "{:?}", output);
+
+    let output = run_prompt(&dir, config, &["--color", "always"]);
+    assert!(get_top_line(&output).contains("\x1b[1;31mPROD"), "{:?}", output);
+}
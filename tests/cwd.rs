@@ -0,0 +1,126 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name).git("repo", &["init", "--quiet", "--initial-branch", "cwd-branch"]).build()
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir)
+        .env("PWD", dir)
+        .env("TERM", "xterm")
+        .env("HOME", "/nonexistent")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Just the path, for when the shell says it's somewhere other than where it really is
+fn render_path(dir: &Path, pwd: &Path, home: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "path", "--path-style", "full"])
+        .args(args)
+        .current_dir(dir)
+        .env("PWD", pwd)
+        .env("HOME", home)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn the_prompt_is_for_the_directory_given() {
+    let dir = make_repo("repo");
+    let repo = dir.join("repo");
+
+    assert_eq!(run_prompt(&dir, &[]).lines().nth(1).unwrap(), dir.display().to_string());
+
+    let output = run_prompt(&dir, &["--cwd", "repo"]);
+    assert_eq!(output.lines().nth(1).unwrap(), format!("{} cwd-branch", repo.display()));
+}
+
+#[test]
+fn custom_segments_run_in_the_directory_given() {
+    let dir = make_repo("custom");
+    fs::write(dir.join("repo").join("marker"), "").unwrap();
+    fs::write(dir.join("config.toml"), "[custom.here]\ncommand = \"basename \\\"$(pwd)\\\"\"\nwhen = [\"file:marker\"]\n").unwrap();
+
+    assert!(!run_prompt(&dir, &[]).contains(" repo"));
+    assert!(run_prompt(&dir, &["--cwd", "repo"]).lines().nth(1).unwrap().ends_with(" repo"));
+}
+
+#[test]
+fn missing_directories_are_shown_as_deleted() {
+    let dir = make_repo("missing");
+
+    let output = run_prompt(&dir, &["--cwd", "gone"]);
+    assert_eq!(output.lines().nth(1).unwrap(), format!("{} (deleted)", dir.join("gone").display()));
+}
+
+#[test]
+fn a_directory_removed_from_under_the_shell_is_shown_as_deleted() {
+    let dir = Fixture::new("removed").dir("gone").build();
+
+    // The shell's still in it, so there's no getting the current directory back
+    let output = Command::new("sh")
+        .args(["-c", "cd gone && rmdir ../gone && exec \"$0\" --ps1 --color never --side k8s-context=hidden --side k8s-namespace=hidden"])
+        .arg(env!("CARGO_BIN_EXE_prompt"))
+        .current_dir(&dir)
+        .env("PWD", &dir)
+        .env("TERM", "xterm")
+        .env("HOME", "/nonexistent")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().nth(1).unwrap(), format!("{} (deleted)", dir.join("gone").display()));
+}
+
+#[test]
+fn home_is_found_through_a_symlink_either_way_round() {
+    let dir = Fixture::new("linked-home").dir("realhome/project").build();
+    symlink(dir.join("realhome"), dir.join("linkhome")).unwrap();
+    let project = dir.join("realhome").join("project");
+
+    // Home's the link and the shell went the real way
+    assert_eq!(render_path(&project, &project, &dir.join("linkhome"), &[]), "~/project");
+
+    // Home's the real directory and the shell went through the link
+    assert_eq!(render_path(&project, &dir.join("linkhome").join("project"), &dir.join("realhome"), &[]), "~/project");
+}
+
+#[test]
+fn the_shells_idea_of_the_directory_is_kept_while_its_right() {
+    let dir = Fixture::new("logical").dir("real/sub").dir("other").build();
+    symlink(dir.join("real"), dir.join("link")).unwrap();
+    let sub = dir.join("real").join("sub");
+    let home = Path::new("/nonexistent");
+
+    assert_eq!(render_path(&sub, &dir.join("link").join("sub"), home, &[]), dir.join("link").join("sub").display().to_string());
+
+    // Somewhere else entirely, or not a whole path, isn't where the prompt is
+    assert_eq!(render_path(&sub, &dir.join("other"), home, &[]), sub.display().to_string());
+    assert_eq!(render_path(&sub, Path::new("link/sub"), home, &[]), sub.display().to_string());
+
+    assert_eq!(render_path(&sub, &dir.join("link").join("sub"), home, &["--physical"]), sub.display().to_string());
+}
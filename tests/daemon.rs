@@ -0,0 +1,207 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output};
+use std::thread;
+use std::time::Duration;
+
+use common::{Fixture, TempDir};
+use prompt::preexec::LastPrompt;
+
+// Stops the daemon however the test ends so nothing's left running
+struct Daemon {
+    dir: PathBuf,
+    child: Child,
+}
+
+impl Daemon {
+    // In a session of its own, like one started by the service manager or from another terminal
+    fn start(dir: &Path) -> Daemon {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+        command.arg("daemon").env("XDG_RUNTIME_DIR", dir.join("run"));
+        unsafe { command.pre_exec(|| { libc::setsid(); Ok(()) }) };
+        let child = command.spawn().unwrap();
+
+        for _ in 0..100 {
+            if dir.join("run").join("prompt.sock").exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Daemon { dir: dir.to_owned(), child }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["daemon", "--stop"]).env("XDG_RUNTIME_DIR", self.dir.join("run")).status();
+        let _ = self.child.wait();
+    }
+}
+
+// kubectl writes down every time it's run so it's clear whether the cache answered, and what the
+// prompt that ran it was started by so it's clear whether that was the daemon
+fn make_dir(name: &str) -> TempDir {
+    let fixture = Fixture::new(name).dir("run").dir("work").file("kubeconfig", "");
+    fs::set_permissions(fixture.path().join("run"), fs::Permissions::from_mode(0o700)).unwrap();
+
+    let runs = fixture.path().join("runs");
+    let parents = fixture.path().join("parents");
+    let script = format!("#!/bin/sh\necho \"$*\" >> {}\nps -o ppid= -p $PPID >> {}\necho staging\n", runs.display(), parents.display());
+    fixture.script("kubectl", script).build()
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default()))
+        .env("KUBECONFIG", dir.join("kubeconfig"))
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("AWS_PROFILE", "from-the-shell")
+        .output()
+        .unwrap()
+}
+
+fn count_runs(dir: &Path) -> usize {
+    fs::read_to_string(dir.join("runs")).unwrap_or_default().lines().count()
+}
+
+fn get_parents(dir: &Path) -> Vec<u32> {
+    fs::read_to_string(dir.join("parents")).unwrap_or_default().lines().map(|x| x.trim().parse().unwrap()).collect()
+}
+
+#[test]
+fn the_daemon_draws_the_same_prompt() {
+    let dir = make_dir("same");
+    let without = run_prompt(&dir, &["--message", "hi", "--exit-code", "1"]);
+
+    let _daemon = Daemon::start(&dir);
+    let with = run_prompt(&dir, &["--message", "hi", "--exit-code", "1"]);
+
+    assert!(with.status.success());
+    assert_eq!(String::from_utf8(with.stdout).unwrap(), String::from_utf8(without.stdout).unwrap());
+    assert!(String::from_utf8(run_prompt(&dir, &[]).stdout).unwrap().contains(" from-the-shell"));
+}
+
+#[test]
+fn kubectl_is_only_run_again_once_the_kubeconfig_changes() {
+    let dir = make_dir("cache");
+    let _daemon = Daemon::start(&dir);
+
+    run_prompt(&dir, &[]);
+    assert_eq!(count_runs(&dir), 1);
+
+    let output = run_prompt(&dir, &[]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains(" staging "));
+    assert_eq!(count_runs(&dir), 1);
+
+    thread::sleep(Duration::from_millis(10));
+    fs::write(dir.join("kubeconfig"), "changed").unwrap();
+    run_prompt(&dir, &[]);
+    assert_eq!(count_runs(&dir), 2);
+}
+
+#[test]
+fn a_connection_that_says_nothing_does_not_hold_the_daemon_up() {
+    let dir = make_dir("silent");
+    let daemon = Daemon::start(&dir);
+
+    let _silent = std::os::unix::net::UnixStream::connect(dir.join("run").join("prompt.sock")).unwrap();
+    thread::sleep(Duration::from_millis(700));
+
+    run_prompt(&dir, &[]);
+    assert_eq!(get_parents(&dir), [daemon.child.id()], "the daemon didn't answer");
+}
+
+// There's no tty here, so the state's kept under the parent, and that's this process rather than
+// the daemon
+fn run_pre_exec(dir: &Path, line: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_prompt")).arg("pre-exec").arg("--").args(line).env("XDG_RUNTIME_DIR", dir.join("run")).status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn what_the_pre_exec_hook_leaves_is_used_by_the_daemon() {
+    let dir = make_dir("pre-exec");
+    let _daemon = Daemon::start(&dir);
+
+    run_pre_exec(&dir, &["terrafrom", "plan"]);
+    let output = run_prompt(&dir, &["--right", "--exit-code", "127"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), "✗ 127 terrafrom");
+}
+
+#[test]
+fn a_terminal_left_alone_says_so_through_the_daemon() {
+    let dir = make_dir("idle");
+
+    // The last prompt was drawn before the daemon was started
+    assert!(!String::from_utf8(run_prompt(&dir, &[]).stdout).unwrap().contains('⏾'));
+    let drawn = fs::read_dir(dir.join("run").join("prompt")).unwrap().map(|x| x.unwrap().path()).find(|x| x.extension().is_some_and(|e| e == "drawn")).unwrap();
+
+    let mut last_prompt: LastPrompt = serde_json::from_str(&fs::read_to_string(&drawn).unwrap()).unwrap();
+    last_prompt.drawn_ms -= 9 * 60 * 60 * 1000;
+    fs::write(&drawn, serde_json::to_string(&last_prompt).unwrap()).unwrap();
+
+    let _daemon = Daemon::start(&dir);
+    assert!(String::from_utf8(run_prompt(&dir, &[]).stdout).unwrap().contains("⏾ 9h"));
+}
+
+#[test]
+fn the_cache_is_used_without_the_daemon_too() {
+    let dir = make_dir("uncached");
+    run_prompt(&dir, &[]);
+    run_prompt(&dir, &[]);
+    assert_eq!(count_runs(&dir), 1);
+    assert_eq!(get_parents(&dir), [std::process::id()]);
+}
+
+#[test]
+fn stale_sockets_are_cleaned_up() {
+    let dir = make_dir("stale");
+    drop(std::os::unix::net::UnixListener::bind(dir.join("run").join("prompt.sock")).unwrap());
+
+    let output = run_prompt(&dir, &["--message", "hi"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains(" hi"));
+    assert!(!dir.join("run").join("prompt.sock").exists());
+
+    let _daemon = Daemon::start(&dir);
+    assert!(dir.join("run").join("prompt.sock").exists());
+}
+
+#[test]
+fn only_one_daemon_runs_at_a_time() {
+    let dir = make_dir("twice");
+    let _daemon = Daemon::start(&dir);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt")).arg("daemon").env("XDG_RUNTIME_DIR", dir.join("run")).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("already listening"));
+
+    let mode = fs::metadata(dir.join("run").join("prompt.sock")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn stopping_removes_the_socket() {
+    let dir = make_dir("stop");
+    let daemon = Daemon::start(&dir);
+    drop(daemon);
+
+    assert!(!dir.join("run").join("prompt.sock").exists());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["daemon", "--stop"]).env("XDG_RUNTIME_DIR", dir.join("run")).output().unwrap();
+    assert!(!output.status.success());
+}
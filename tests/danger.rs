@@ -0,0 +1,98 @@
+mod common;
+
+use std::env;
+use std::process::Command;
+
+use common::{Fixture, TempFile};
+use colored::Color;
+use prompt::config::DangerPattern;
+use prompt::danger::{find_danger, parse_danger_style, DangerStyle};
+
+const CONFIG: &str = r#"
+[[danger-patterns]]
+pattern = "prod*"
+style = "red,underline"
+
+[[danger-patterns]]
+pattern = "prod-sandbox"
+style = "none"
+
+[[danger-patterns]]
+pattern = "123456789012"
+
+[env.account]
+variable = "ACCOUNT"
+aliases = { "123456789012" = "main", "210987654321" = "prod-ish" }
+"#;
+
+fn get_patterns() -> Vec<DangerPattern> {
+    let config: prompt::config::Config = toml::from_str(CONFIG).unwrap();
+    config.danger_patterns
+}
+
+fn write_config(name: &str) -> TempFile {
+    Fixture::new(name).file("config.toml", CONFIG).build_file("config.toml")
+}
+
+fn render(name: &str, vars: &[(&str, &str)], args: &[&str]) -> String {
+    let config = write_config(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "always", "--disable", "k8s"])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("PROMPT_CONFIG", &config)
+        .env_remove("AWS_PROFILE")
+        .env_remove("ACCOUNT")
+        .env_remove("NO_COLOR")
+        .env_remove("COLORTERM")
+        .envs(vars.iter().copied())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn styles_are_a_colour_and_some_attributes() {
+    assert_eq!(parse_danger_style("red,reversed"), Ok(DangerStyle { colour: Some(Color::Red), reversed: true, ..DangerStyle::default() }));
+    assert_eq!(parse_danger_style("bold, underline"), Ok(DangerStyle { bold: true, underline: true, ..DangerStyle::default() }));
+    assert_eq!(parse_danger_style("none"), Ok(DangerStyle::default()));
+    assert!(parse_danger_style("red,blue").is_err());
+    assert!(parse_danger_style("loud").is_err());
+}
+
+#[test]
+fn the_last_pattern_that_matches_wins() {
+    let patterns = get_patterns();
+
+    assert_eq!(find_danger(&patterns, "prod-eu"), Some(DangerStyle { colour: Some(Color::Red), underline: true, ..DangerStyle::default() }));
+    assert_eq!(find_danger(&patterns, "prod-sandbox"), Some(DangerStyle::default()));
+    assert_eq!(find_danger(&patterns, "123456789012"), Some(DangerStyle { reversed: true, ..DangerStyle::default() }));
+    assert_eq!(find_danger(&patterns, "staging"), None);
+}
+
+#[test]
+fn matching_profiles_stand_out_whatever_their_colour() {
+    assert!(render("profile", &[("AWS_PROFILE", "prod-admin")], &[]).contains("\x1b[1;4;31mprod-admin\x1b[0m"));
+    assert!(render("recoloured", &[("AWS_PROFILE", "prod-admin")], &["--segment-color", "aws-profile=blue"]).contains("\x1b[1;4;31mprod-admin\x1b[0m"));
+    assert!(render("sandbox", &[("AWS_PROFILE", "prod-sandbox")], &[]).contains("\x1b[1;31mprod-sandbox\x1b[0m"));
+    assert!(render("staging", &[("AWS_PROFILE", "staging")], &[]).contains("\x1b[1;31mstaging\x1b[0m"));
+}
+
+#[test]
+fn the_whole_value_is_matched_before_its_cut_short() {
+    let output = render("truncated", &[("AWS_PROFILE", "prod-admin")], &["--max-width", "aws-profile=5"]);
+    assert!(output.contains("\x1b[1;4;31mprod…\x1b[0m"), "{:?}", output);
+}
+
+#[test]
+fn aliased_values_are_matched_as_they_were() {
+    let output = render("alias", &[("ACCOUNT", "123456789012")], &[]);
+    assert!(output.contains("\x1b[1;7;32mmain\x1b[0m"), "{:?}", output);
+
+    let output = render("not-alias", &[("ACCOUNT", "210987654321")], &[]);
+    assert!(output.contains("\x1b[1;32mprod-ish\x1b[0m"), "{:?}", output);
+}
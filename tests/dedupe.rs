@@ -0,0 +1,70 @@
+use std::env;
+use std::process::Command;
+
+use colored::Color;
+use prompt::layout::{dedupe_segments, get_text, Dedupe, Segment};
+use prompt::segment::SegmentKind;
+use prompt::styled::Stylize;
+
+fn dedupe(segments: Vec<(SegmentKind, Segment)>, dedupe: Dedupe) -> Vec<(SegmentKind, String)> {
+    dedupe_segments(segments, dedupe, |_, _| true).into_iter().map(|(kind, x)| (kind, get_text(&x))).collect()
+}
+
+#[test]
+fn colours_dont_stop_segments_matching() {
+    let segments = vec![
+        (SegmentKind::K8sContext, vec!["platform".color(Color::Blue)]),
+        (SegmentKind::K8sNamespace, vec!["platform".color(Color::Magenta).bold()]),
+    ];
+
+    assert_eq!(dedupe(segments.clone(), Dedupe::Equal), vec![(SegmentKind::K8sContext, "platform".to_owned())]);
+    assert_eq!(dedupe(segments, Dedupe::Off).len(), 2);
+}
+
+#[test]
+fn the_more_important_segment_is_kept() {
+    let segments = vec![
+        (SegmentKind::AwsRegion, vec!["eu-west-1".normal()]),
+        (SegmentKind::AwsProfile, vec!["work-".normal(), "eu-west-1".color(Color::Yellow)]),
+    ];
+
+    assert_eq!(dedupe(segments.clone(), Dedupe::Equal).len(), 2);
+    assert_eq!(dedupe(segments, Dedupe::Contained), vec![(SegmentKind::AwsProfile, "work-eu-west-1".to_owned())]);
+}
+
+#[test]
+fn only_neighbours_are_compared() {
+    let segments = vec![
+        (SegmentKind::K8sContext, vec!["platform".normal()]),
+        (SegmentKind::AwsProfile, vec!["work".normal()]),
+        (SegmentKind::K8sNamespace, vec!["platform".normal()]),
+    ];
+
+    assert_eq!(dedupe(segments, Dedupe::Contained).len(), 3);
+}
+
+fn render_aws(profile: &str, region: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--single-line", "--no-trailing-space", "--disable", "k8s", "--cwd"])
+        .arg(env::temp_dir())
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("AWS_PROFILE", profile)
+        .env("AWS_REGION", region)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn the_top_line_can_be_deduplicated() {
+    assert!(render_aws("eu-west-1", "eu-west-1", &[]).contains("eu-west-1 eu-west-1"));
+    assert!(!render_aws("eu-west-1", "eu-west-1", &["--dedupe", "equal"]).contains("eu-west-1 eu-west-1"));
+
+    // Labels are added afterwards so they don't count
+    let output = render_aws("work-eu-west-1", "eu-west-1", &["--dedupe", "contained", "--label", "aws-profile=aws:"]);
+    assert!(output.contains("aws:work-eu-west-1") && output.matches("eu-west-1").count() == 1, "{:?}", output);
+}
@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+fn make_dir(name: &str) -> TempDir {
+    // A kubectl that's slow to give up on its context
+    Fixture::new(name).script("bin/kubectl", "#!/bin/sh\n[ \"$1\" = version ] && { echo 'Client Version: fake'; exit 0; }\nsleep 1\n").build()
+}
+
+fn run_doctor(dir: &Path) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["doctor", "--config"])
+        .arg(dir.join("config.toml"))
+        .current_dir(dir)
+        .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH").unwrap_or_default()))
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("KUBECONFIG", dir.join("kubeconfig"))
+        .output()
+        .unwrap();
+
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn doctor_reports_on_every_segment() {
+    let dir = make_dir("segments");
+    fs::write(dir.join("config.toml"), "timeout-ms = 100\n").unwrap();
+
+    let (success, output) = run_doctor(&dir);
+    assert!(success, "{}", output);
+    assert!(output.contains("  kubectl  Client Version: fake\n"), "{}", output);
+    assert!(output.contains(&format!("  {}: ok\n", dir.join("config.toml").display())), "{}", output);
+    assert!(output.lines().any(|x| x.starts_with("  k8s-context ") && x.ends_with("failed: kubectl: timed out after 100ms")), "{}", output);
+    assert!(output.lines().any(|x| x.starts_with("  total ")), "{}", output);
+}
+
+#[test]
+fn doctor_reports_problems_without_failing() {
+    let dir = make_dir("problems");
+    fs::write(dir.join("config.toml"), "timeout-ms = 100\nicon-width = \"wide\"\n").unwrap();
+    fs::write(dir.join("kubeconfig"), "users:\n- name: cloud\n  user:\n    exec:\n      command: get-token\n").unwrap();
+
+    let (success, output) = run_doctor(&dir);
+    assert!(success, "{}", output);
+    assert!(output.contains("icon-width"), "{}", output);
+    assert!(output.contains("\nPossible slowness\n"), "{}", output);
+    assert!(output.contains("kubeconfig uses an exec plugin"), "{}", output);
+}
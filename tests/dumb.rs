@@ -0,0 +1,26 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::process::Command;
+
+use common::Fixture;
+
+// Emacs needs the exact plain prompt with nothing else mixed in
+#[test]
+fn dumb_terminal_gets_a_plain_prompt() {
+    let dir = Fixture::new("dumb").build();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--exit-code", "1", "--iterm2", "--message", "hello"])
+        .current_dir(&dir)
+        .env("TERM", "dumb")
+        .env("PWD", &dir)
+        .env("HOME", "/nonexistent")
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{} > \n", dir.display()));
+}
@@ -0,0 +1,112 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+// A kubectl that just notes down that it was run
+fn make_fake_kubectl(name: &str) -> (TempDir, PathBuf) {
+    let fixture = Fixture::new(name);
+    let log = fixture.path().join("kubectl.log");
+    let script = format!("#!/bin/sh\necho \"$@\" >> '{}'\necho fake-context\n", log.display());
+
+    (fixture.script("kubectl", script).build(), log)
+}
+
+fn run_prompt(bin: &Path, config: &Path, vars: &[(&str, &str)], args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default()))
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("PROMPT_CONFIG", config)
+        .env("XDG_CACHE_HOME", bin.join("cache"))
+        .envs(vars.iter().copied())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn disabling_k8s_skips_kubectl() {
+    let (bin, log) = make_fake_kubectl("disable");
+    let config = bin.join("missing.toml");
+
+    let output = run_prompt(&bin, &config, &[], &["--ps1", "--color", "never"]);
+    assert!(output.contains("fake-context"), "{:?}", output);
+    assert!(log.exists());
+
+    fs::remove_file(&log).unwrap();
+    let output = run_prompt(&bin, &config, &[("PROMPT_DISABLE", "k8s")], &["--ps1", "--color", "never"]);
+    assert!(!output.contains("fake-context"), "{:?}", output);
+    assert!(!log.exists());
+}
+
+#[test]
+fn variables_go_between_the_config_and_flags() {
+    let (bin, _) = make_fake_kubectl("layers");
+    let config = bin.join("config.toml");
+    fs::write(&config, "separator = \" | \"\nsudo = false\n\n[path]\nstyle = \"truncate\"\n").unwrap();
+
+    let vars = [("PROMPT_SEPARATOR", ","), ("PROMPT_SEGMENTS_MESSAGE__LABEL", "m:"), ("PROMPT_DISABLE", "k8s")];
+
+    let output = run_prompt(&bin, &config, &vars, &["--ps1", "--color", "never", "--message", "hi"]);
+    assert!(output.lines().nth(1).unwrap().ends_with(",m:hi"), "{:?}", output);
+
+    let output = run_prompt(&bin, &config, &vars, &["--ps1", "--color", "never", "--message", "hi", "--separator", ";"]);
+    assert!(output.lines().nth(1).unwrap().ends_with(";m:hi"), "{:?}", output);
+
+    let shown = run_prompt(&bin, &config, &[("PROMPT_PATH_KEEP", "2"), ("PROMPT_TIMEOUT_MS", "200")], &["config", "show"]);
+    assert!(shown.contains("style = \"truncate\"\nkeep = 2\n"), "{}", shown);
+    assert!(shown.contains("timeout-ms = 200\n"), "{}", shown);
+}
+
+#[test]
+fn broken_variables_are_ignored() {
+    let (bin, _) = make_fake_kubectl("broken");
+    let config = bin.join("missing.toml");
+
+    let args = ["--ps1", "--color", "never", "--message", "hi", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"];
+    let output = run_prompt(&bin, &config, &[("PROMPT_ICON_WIDTH", "wide"), ("PROMPT_SEPARATOR", ",")], &args);
+    assert!(output.lines().nth(1).unwrap().ends_with(" hi"), "{:?}", output);
+}
+
+#[test]
+fn disable_and_enable_flags() {
+    let (bin, log) = make_fake_kubectl("flags");
+    let config = bin.join("missing.toml");
+
+    let output = run_prompt(&bin, &config, &[], &["--ps1", "--color", "never", "--disable", "k8s,aws"]);
+    assert!(!output.contains("fake-context"), "{:?}", output);
+    assert!(!log.exists());
+
+    let output = run_prompt(&bin, &config, &[("PROMPT_DISABLE", "k8s")], &["--ps1", "--color", "never", "--enable", "k8s-context"]);
+    assert!(output.contains("fake-context"), "{:?}", output);
+}
+
+#[test]
+fn unknown_segments_are_only_warned_about() {
+    let (bin, _) = make_fake_kubectl("unknown");
+    let config = bin.join("missing.toml");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--message", "hi", "--disable", "k8s,nonsense"])
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default()))
+        .env("PROMPT_CONFIG", &config)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).lines().nth(1).unwrap().ends_with(" hi"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown segment 'nonsense'"));
+}
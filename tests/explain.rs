@@ -0,0 +1,110 @@
+mod common;
+
+use std::env;
+use std::process::{Command, Output};
+
+use common::Fixture;
+
+fn run_prompt(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("AWS_REGION", "eu-west-2")
+        .env_remove("AWS_PROFILE")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn missing_segments_say_why() {
+    let output = run_prompt(&["--explain-missing"]);
+    assert_eq!(output.stdout, run_prompt(&[]).stdout);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.lines().any(|x| x == "git-branch: not in a git repository"), "{}", stderr);
+    assert!(stderr.lines().any(|x| x == "aws-profile: AWS_PROFILE isn't set"), "{}", stderr);
+
+    // Shown, hidden and turned off segments aren't missing
+    assert!(!stderr.contains("aws-region") && !stderr.contains("k8s") && !stderr.contains("battery"), "{}", stderr);
+}
+
+#[test]
+fn the_segments_that_are_turned_on_are_timed_and_explained() {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--config", "/nonexistent/config.toml", "--tmux-session", "--battery", "--battery-threshold", "0", "--timings", "--explain-missing"])
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env_remove("TMUX")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(stderr.lines().any(|x| x == "tmux-session: not in tmux"), "{}", stderr);
+    assert!(stderr.lines().any(|x| x == "battery: no battery is draining below the threshold"), "{}", stderr);
+    assert!(stderr.lines().any(|x| x == "idle: the previous prompt was drawn less than --idle-threshold-mins ago"), "{}", stderr);
+    assert!(stderr.lines().any(|x| x.trim_start().starts_with("battery ")), "{}", stderr);
+
+    // Nothing's asked of tmux outside of it, and how long the prompt sat there isn't worked out here
+    assert!(!stderr.lines().any(|x| x.trim_start().starts_with("tmux-session ") || x.trim_start().starts_with("idle ")), "{}", stderr);
+}
+
+#[test]
+fn json_output_has_the_reasons_too() {
+    let report: serde_json::Value = serde_json::from_slice(&run_prompt(&["--format", "json"]).stdout).unwrap();
+    let segments = report["segments"].as_array().unwrap();
+
+    let profile = segments.iter().find(|s| s["name"] == "aws-profile").unwrap();
+    assert_eq!(profile["reason"], "AWS_PROFILE isn't set");
+
+    let region = segments.iter().find(|s| s["name"] == "aws-region").unwrap();
+    assert_eq!(region["reason"], serde_json::Value::Null);
+}
+
+fn get_legend(name: &str, config: &str, args: &[&str]) -> String {
+    let dir = Fixture::new(name).file("config.toml", config).build();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .arg("explain")
+        .args(args)
+        .current_dir(&dir)
+        .env("TERM", "xterm")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env_remove("COLORFGBG")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn the_legend_says_what_each_chevron_means() {
+    let legend = get_legend("chevrons", "", &["--color", "never"]);
+
+    assert!(legend.contains("  the upstream branch\n    ❯ up to date\n    ❯↑ unpushed commits\n    ❯↓ unpulled commits\n"), "{}", legend);
+    assert!(legend.contains("    ❯! failed\n"), "{}", legend);
+}
+
+#[test]
+fn the_legend_follows_the_config() {
+    let legend = get_legend("config", "[chevrons]\nupstream = \">\"\n[chevron-colors]\nunpulled = \"magenta\"\n", &["--color", "always"]);
+
+    assert!(legend.contains("\x1b[1;35m>\x1b[0m unpulled commits (magenta)\n"), "{}", legend);
+    assert!(legend.contains("\x1b[1;34m❯\x1b[0m untracked files (blue)\n"), "{}", legend);
+}
+
+#[test]
+fn the_legend_only_lists_segments_that_are_enabled() {
+    let legend = get_legend("enabled", "disable = [\"aws\"]\n", &["--color", "never", "--sudo"]);
+
+    assert!(legend.lines().any(|x| x.starts_with("  path ") && x.ends_with("the current directory, hidden when the directory can't be worked out")), "{}", legend);
+    assert!(legend.lines().any(|x| x.starts_with("  sudo ")), "{}", legend);
+    assert!(!legend.contains("aws-profile") && !legend.contains("battery") && !legend.contains("git-status"), "{}", legend);
+
+    let single = get_legend("single", "prompt-style = \"single\"\n", &["--color", "never"]);
+    assert!(!single.contains("the upstream branch") && single.lines().any(|x| x.starts_with("  git-status ")), "{}", single);
+}
@@ -0,0 +1,323 @@
+mod common;
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use common::{Fixture, TempDir};
+use prompt::background::Background;
+use prompt::command::{Canned, CannedRunner};
+use prompt::config::Config;
+use prompt::forge::{is_token_host, parse_curl_version, parse_gitea_pulls, parse_gitea_status, parse_gitlab_merge_requests, parse_gitlab_pipelines, parse_tea_pulls, MergeRequestSegment, Pipeline};
+use prompt::path::WorkingDirectory;
+use prompt::remote::{apply_instead_of, get_branch_url, get_forge, get_remote_label, parse_forge_host, parse_instead_of, parse_remote_label, parse_remote_url, Forge, RemoteUrl};
+use prompt::segment::{Context, Reason, Rendered, Segment, SegmentResult};
+use prompt::store::Store;
+use prompt::theme::{Theme, ThemeName};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).build()
+}
+
+const CURL_VERSION: &str = "curl 8.5.0 (x86_64-pc-linux-gnu) libcurl/8.5.0\n";
+
+fn get_git_outputs(remote: &'static str) -> Vec<(&'static str, Canned)> {
+    vec![
+        ("git config --get remote.origin.url", Canned::Exit(0, remote)),
+        ("git branch --show-current", Canned::Exit(0, "feature/login\n")),
+        ("git rev-parse HEAD", Canned::Exit(0, "abc123\n")),
+    ]
+}
+
+async fn render(segment: &MergeRequestSegment, store: &Store, outputs: Vec<(&'static str, Canned)>) -> SegmentResult<String> {
+    let config = Config::default();
+    let theme = Theme { name: ThemeName::Default, background: Background::Dark, overrides: vec![] };
+    let current_dir = WorkingDirectory { path: Some(PathBuf::from("/repo")), full: Some(PathBuf::from("/repo")), deleted: false };
+    let runner = CannedRunner { outputs };
+    let context = Context { dir: Path::new("/repo"), current_dir: &current_dir, config: &config, theme: &theme, exit_code: 0, timeout_ms: None, runner: &runner, store };
+
+    match segment.render(&context).await {
+        SegmentResult::Present(Rendered::Spans(spans)) => SegmentResult::Present(spans.iter().map(|s| &**s).collect()),
+        SegmentResult::Present(Rendered::Aliased(..) | Rendered::State(..)) => panic!("a merge request is only ever spans"),
+        SegmentResult::Absent(reason) => SegmentResult::Absent(reason)
+    }
+}
+
+#[test]
+fn forges_are_picked_out_by_host() {
+    assert_eq!(get_forge("gitlab.com", &[]), Some(Forge::Gitlab));
+    assert_eq!(get_forge("gitlab.example.com", &[]), Some(Forge::Gitlab));
+    assert_eq!(get_forge("codeberg.org", &[]), Some(Forge::Gitea));
+    assert_eq!(get_forge("gitea.internal", &[]), Some(Forge::Gitea));
+    assert_eq!(get_forge("github.com", &[]), Some(Forge::Github));
+    assert_eq!(get_forge("git.example.com", &[]), None);
+
+    let hosts = [parse_forge_host("git.example.com=gitea").unwrap(), parse_forge_host("gitlab.example.com=gitea").unwrap()];
+    assert_eq!(get_forge("git.example.com", &hosts), Some(Forge::Gitea));
+    assert_eq!(get_forge("gitlab.example.com", &hosts), Some(Forge::Gitea));
+
+    assert!(parse_forge_host("git.example.com").is_err());
+    assert!(parse_forge_host("=gitlab").is_err());
+    assert!(parse_forge_host("git.example.com=bitbucket").is_err());
+}
+
+#[test]
+fn links_use_the_same_hosts() {
+    let hosts = [parse_forge_host("git.example.com=gitea").unwrap()];
+
+    assert_eq!(get_branch_url("git@github.com:org/repo.git", "main", &[]).as_deref(), Some("https://github.com/org/repo/tree/main"));
+    assert_eq!(get_branch_url("http://gitlab.local/group/sub/repo.git", "a b", &[]).as_deref(), Some("http://gitlab.local/group/sub/repo/-/tree/a%20b"));
+    assert_eq!(get_branch_url("git@git.example.com:org/repo.git", "feature/x", &hosts).as_deref(), Some("https://git.example.com/org/repo/src/branch/feature/x"));
+    assert_eq!(get_branch_url("git@git.example.com:org/repo.git", "main", &[]), None);
+}
+
+#[test]
+fn remote_urls_are_split_up() {
+    let url = |scheme, host, port, path| Some(RemoteUrl { scheme, host, port, path });
+
+    assert_eq!(parse_remote_url("git@github.com:org/repo.git"), url("ssh", "github.com", None, "org/repo"));
+    assert_eq!(parse_remote_url("ssh://git@gitlab.example.com:2222/group/sub/repo.git"), url("ssh", "gitlab.example.com", Some("2222"), "group/sub/repo"));
+    assert_eq!(parse_remote_url("https://user@git.example.com:8443/org/repo/"), url("https", "git.example.com", Some("8443"), "org/repo"));
+    assert_eq!(parse_remote_url("/srv/git/repo.git"), None);
+    assert_eq!(parse_remote_url("https://github.com/repo"), None);
+
+    // Only a port for the web ends up in links
+    assert_eq!(parse_remote_url("https://git.example.com:8443/org/repo").unwrap().get_base_url(), "https://git.example.com:8443");
+    assert_eq!(parse_remote_url("ssh://git@git.example.com:2222/org/repo").unwrap().get_base_url(), "https://git.example.com");
+    assert_eq!(parse_remote_url("http://git.example.com:8080/org/repo").unwrap().get_base_url(), "http://git.example.com:8080");
+    assert_eq!(parse_remote_url("http://git.example.com:8080/org/repo").unwrap().get_api_base_url(), "https://git.example.com", "anything that can be sent a token is asked over https");
+    assert_eq!(parse_remote_url("https://git.example.com:8443/org/repo").unwrap().get_api_base_url(), "https://git.example.com:8443");
+}
+
+#[test]
+fn tokens_only_go_to_hosts_that_are_known() {
+    let hosts = [parse_forge_host("git.example.com=gitea").unwrap()];
+
+    assert!(is_token_host("gitlab.com", Forge::Gitlab, &hosts));
+    assert!(is_token_host("Gitea.com", Forge::Gitea, &hosts));
+    assert!(is_token_host("git.example.com", Forge::Gitea, &hosts));
+    assert!(!is_token_host("git.example.com", Forge::Gitlab, &hosts));
+    assert!(!is_token_host("gitlab.evil.example.com", Forge::Gitlab, &hosts));
+    assert!(!is_token_host("gitea.evil.example.com", Forge::Gitea, &hosts));
+}
+
+#[test]
+fn curl_versions_are_understood() {
+    assert_eq!(parse_curl_version("curl 8.5.0 (x86_64-pc-linux-gnu) libcurl/8.5.0 OpenSSL/3.0.13"), Some((8, 5)));
+    assert_eq!(parse_curl_version("curl 7.88.1 (aarch64-apple-darwin23.0) libcurl/7.88.1"), Some((7, 88)));
+    assert_eq!(parse_curl_version("not curl"), None);
+}
+
+#[test]
+fn remote_urls_are_rewritten_like_git_does() {
+    let rewrites = parse_instead_of("url.git@github.com:.insteadof\ngh:\0url.https://git.example.com/.insteadof\nhttps://old.example.com/\0url.git@git.example.com:mirror/.insteadof\nhttps://old.example.com/mirror/\0");
+
+    assert_eq!(rewrites.len(), 3);
+    assert_eq!(apply_instead_of("gh:org/repo", &rewrites), "git@github.com:org/repo");
+    assert_eq!(apply_instead_of("https://old.example.com/org/repo", &rewrites), "https://git.example.com/org/repo");
+    assert_eq!(apply_instead_of("https://old.example.com/mirror/repo", &rewrites), "git@git.example.com:mirror/repo", "the longest prefix wins");
+    assert_eq!(apply_instead_of("git@gitlab.com:org/repo", &rewrites), "git@gitlab.com:org/repo");
+}
+
+#[test]
+fn remotes_are_labelled_by_where_they_are() {
+    let hosts = [parse_forge_host("git.example.com=gitea").unwrap()];
+    let labels = [parse_remote_label("git.corp.example.com=corp").unwrap(), parse_remote_label("gitlab.archive.example.com=").unwrap()];
+    let label = |host| get_remote_label(host, &labels, &hosts);
+
+    assert_eq!(label("github.com").as_deref(), Some("github"));
+    assert_eq!(label("bitbucket.org").as_deref(), Some("bitbucket"));
+    assert_eq!(label("codeberg.org").as_deref(), Some("codeberg"));
+    assert_eq!(label("gitlab.com").as_deref(), Some("gitlab"));
+    assert_eq!(label("git.example.com").as_deref(), Some("gitea"));
+    assert_eq!(label("GIT.corp.example.com").as_deref(), Some("corp"));
+    assert_eq!(label("gitlab.archive.example.com"), None);
+    assert_eq!(label("git.unknown.example.com"), None);
+    assert!(parse_remote_label("corp").is_err());
+}
+
+#[test]
+fn forge_answers_are_understood() {
+    assert_eq!(parse_gitlab_merge_requests(r#"[{"iid": 42, "title": "Log in"}]"#), Some(42));
+    assert_eq!(parse_gitlab_merge_requests("[]"), None);
+    assert_eq!(parse_gitlab_pipelines(r#"[{"id": 7, "status": "running"}]"#), Some(Pipeline::Running));
+    assert_eq!(parse_gitlab_pipelines(r#"[{"id": 7, "status": "manual"}]"#), None);
+
+    let pulls = r#"[{"number": 3, "head": {"ref": "main"}}, {"number": 5, "head": {"ref": "feature/login"}}]"#;
+    assert_eq!(parse_gitea_pulls(pulls, "feature/login"), Some(5));
+    assert_eq!(parse_gitea_pulls(pulls, "other"), None);
+    assert_eq!(parse_gitea_status(r#"{"state": "failure", "statuses": []}"#), Some(Pipeline::Failed));
+
+    assert_eq!(parse_tea_pulls(r#"[{"index": "5", "head": "feature/login", "ci": "success"}]"#, "feature/login"), Some((5, Some(Pipeline::Passed))));
+    assert_eq!(parse_tea_pulls("not json", "feature/login"), None);
+}
+
+// Everything to do with the environment is in the one test so nothing else sees it change
+#[tokio::test]
+async fn merge_requests_come_from_the_forge_and_are_kept() {
+    env::remove_var("GITLAB_TOKEN");
+    env::remove_var("GITEA_TOKEN");
+
+    let dir = make_dir("gitlab");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![], ttl: 60 };
+    let mut outputs = get_git_outputs("git@gitlab.example.com:group/app.git\n");
+    outputs.extend([
+        ("glab api --hostname gitlab.example.com projects/group%2Fapp/merge_requests?state=opened&source_branch=feature/login&per_page=1", Canned::Exit(0, r#"[{"iid": 42}]"#)),
+        ("glab api --hostname gitlab.example.com projects/group%2Fapp/pipelines?sha=abc123&per_page=1", Canned::Exit(0, r#"[{"status": "success"}]"#)),
+    ]);
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "!42 ✓"));
+
+    // Nothing else is asked the second time round
+    let outputs = get_git_outputs("git@gitlab.example.com:group/app.git\n");
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "!42 ✓"));
+
+    // Failures aren't kept
+    let dir = make_dir("gitea");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![], ttl: 60 };
+    let tea = "tea pulls list --state open --output json --fields index,head,ci";
+    let mut outputs = get_git_outputs("https://codeberg.org/org/app.git\n");
+    outputs.push((tea, Canned::Exit(1, "")));
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Absent(Reason::Failed(_))));
+
+    let mut outputs = get_git_outputs("https://codeberg.org/org/app.git\n");
+    outputs.push((tea, Canned::Exit(0, r#"[{"index": "5", "head": "feature/login", "ci": ""}]"#)));
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "#5"));
+
+    // With a token curl's used instead, without it ever going on the command line
+    env::set_var("GITEA_TOKEN", "secret");
+    let dir = make_dir("token");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![parse_forge_host("git.example.com=gitea").unwrap()], ttl: 60 };
+    let mut outputs = get_git_outputs("git@git.example.com:org/app.git\n");
+    outputs.extend([
+        ("curl --version", Canned::Exit(0, CURL_VERSION)),
+        (
+            "curl --silent --fail --max-time 1.0 --variable %GITEA_TOKEN --expand-header Authorization: token {{GITEA_TOKEN}} https://git.example.com/api/v1/repos/org/app/pulls?state=open&limit=50",
+            Canned::Exit(0, r#"[{"number": 9, "head": {"ref": "feature/login"}}]"#)
+        ),
+        (
+            "curl --silent --fail --max-time 1.0 --variable %GITEA_TOKEN --expand-header Authorization: token {{GITEA_TOKEN}} https://git.example.com/api/v1/repos/org/app/commits/abc123/status",
+            Canned::Exit(0, r#"{"state": "failure"}"#)
+        ),
+    ]);
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "#9 ✗"));
+
+    // A host that's only guessed to be a forge from its name never gets the token
+    let dir = make_dir("lookalike");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![], ttl: 60 };
+    let mut outputs = get_git_outputs("git@gitea.evil.example.com:org/app.git\n");
+    outputs.push((tea, Canned::Exit(0, r#"[{"index": "5", "head": "feature/login", "ci": ""}]"#)));
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "#5"));
+
+    // Nor does a curl that would have to put it on the command line
+    let dir = make_dir("old-curl");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![parse_forge_host("git.example.com=gitea").unwrap()], ttl: 60 };
+    let mut outputs = get_git_outputs("git@git.example.com:org/app.git\n");
+    outputs.push(("curl --version", Canned::Exit(0, "curl 7.88.1 (x86_64-pc-linux-gnu) libcurl/7.88.1\n")));
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Absent(Reason::Failed(e)) if e.contains("8.3")));
+    env::remove_var("GITEA_TOKEN");
+
+    // and a remote on plain http is still asked over https
+    env::set_var("GITLAB_TOKEN", "secret");
+    let dir = make_dir("http");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![parse_forge_host("git.example.com=gitlab").unwrap()], ttl: 60 };
+    let mut outputs = get_git_outputs("http://git.example.com:8080/group/app.git\n");
+    outputs.extend([
+        ("curl --version", Canned::Exit(0, CURL_VERSION)),
+        (
+            "curl --silent --fail --max-time 1.0 --variable %GITLAB_TOKEN --expand-header PRIVATE-TOKEN: {{GITLAB_TOKEN}} https://git.example.com/api/v4/projects/group%2Fapp/merge_requests?state=opened&source_branch=feature/login&per_page=1",
+            Canned::Exit(0, r#"[{"iid": 42}]"#)
+        ),
+        (
+            "curl --silent --fail --max-time 1.0 --variable %GITLAB_TOKEN --expand-header PRIVATE-TOKEN: {{GITLAB_TOKEN}} https://git.example.com/api/v4/projects/group%2Fapp/pipelines?sha=abc123&per_page=1",
+            Canned::Exit(0, "[]")
+        ),
+    ]);
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Present(x) if x == "!42"));
+    env::remove_var("GITLAB_TOKEN");
+
+    // Branches without one are kept too, and elsewhere there's nothing to ask
+    let dir = make_dir("none");
+    let store = Store::new(dir.to_path_buf());
+    let segment = MergeRequestSegment { hosts: vec![], ttl: 60 };
+    let mut outputs = get_git_outputs("https://codeberg.org/org/app.git\n");
+    outputs.push((tea, Canned::Exit(0, "[]")));
+    assert!(matches!(render(&segment, &store, outputs).await, SegmentResult::Absent(Reason::Nothing(_))));
+    assert!(matches!(render(&segment, &store, get_git_outputs("https://codeberg.org/org/app.git\n")).await, SegmentResult::Absent(Reason::Nothing(_))));
+
+    assert!(matches!(render(&segment, &store, get_git_outputs("git@github.com:org/app.git\n")).await, SegmentResult::Absent(Reason::Nothing(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn merge_requests_are_drawn_when_asked_for() {
+    use std::process::Command;
+
+    let dir = Fixture::new("drawn")
+        .script("bin/glab", "#!/bin/sh\ncase \"$*\" in\n  *merge_requests*) echo '[{\"iid\": 42}]' ;;\n  *pipelines*) echo '[{\"status\": \"success\"}]' ;;\nesac\n")
+        .git("", &["init", "--quiet", "--initial-branch", "feature/login"])
+        .git("", &["remote", "add", "origin", "git@gitlab.example.com:group/app.git"])
+        .git("", &["commit", "--quiet", "--allow-empty", "--message", "first"])
+        .build();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", "never", "--disable", "k8s,aws"])
+            .args(args)
+            .current_dir(&dir)
+            .env("TERM", "xterm")
+            .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH").unwrap_or_default()))
+            .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+            .env("PROMPT_CONFIG", dir.join("config.toml"))
+            .env("XDG_CACHE_HOME", dir.join("cache"))
+            .env_remove("GITLAB_TOKEN")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert!(run(&["--merge-requests"]).contains("!42 ✓"));
+    assert!(!run(&[]).contains("!42"));
+}
+
+#[cfg(unix)]
+#[test]
+fn the_remote_is_shown_before_the_branch_when_asked_for() {
+    use std::fs;
+    use std::process::Command;
+
+    let dir = Fixture::new("remote")
+        .git("", &["init", "--quiet", "--initial-branch", "main"])
+        .git("", &["commit", "--quiet", "--allow-empty", "--message", "first"])
+        .build();
+
+    let run = |config: &str| {
+        fs::write(dir.join("config.toml"), config).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", "never", "--only", "git-remote,git-branch"])
+            .current_dir(&dir)
+            .env("TERM", "xterm")
+            .env("HOME", &dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+            .env("PROMPT_CONFIG", dir.join("config.toml"))
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run("git-remote = true\n"), "main\n", "there's no remote");
+
+    common::git(&dir, &["remote", "add", "origin", "git@github.com:org/repo.git"]);
+    assert_eq!(run(""), "main\n");
+    assert_eq!(run("git-remote = true\n"), "github main\n");
+
+    common::git(&dir, &["remote", "set-url", "origin", "corp:team/app.git"]);
+    common::git(&dir, &["config", "url.git@git.corp.example.com:.insteadOf", "corp:"]);
+    assert_eq!(run("git-remote = true\n"), "main\n");
+    assert_eq!(run("git-remote = true\n[remote-labels]\n\"git.corp.example.com\" = \"corp\"\n"), "corp main\n");
+}
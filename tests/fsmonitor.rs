@@ -0,0 +1,87 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::git::{is_fsmonitor_set, parse_porcelain_changes, UncommittedChanges};
+
+// A repository whose monitor never has an answer, so git falls back to looking through everything
+fn make_repo(name: &str) -> TempDir {
+    let fixture = Fixture::new(name);
+    let hook = fixture.path().join("hook").display().to_string();
+
+    fixture
+        .script("hook", "#!/bin/sh\nexit 1\n")
+        .git("", &["init", "--quiet", "--initial-branch", "main"])
+        .git("", &["config", "core.fsmonitor", &hook])
+        .file("README.md", "hi\n")
+        .git("", &["add", "README.md"])
+        .git("", &["commit", "--quiet", "--message", "first"])
+        .build()
+}
+
+// Gives back the prompt and the log of what it ran
+fn run_prompt(dir: &Path, args: &[&str]) -> (String, String) {
+    let log = dir.join("prompt.log");
+    let _ = fs::remove_file(&log);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--disable", "k8s"])
+        .args(args)
+        .current_dir(dir)
+        .env("PWD", dir)
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("PROMPT_LOG", "debug")
+        .env("PROMPT_LOG_FILE", &log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    (String::from_utf8(output.stdout).unwrap(), fs::read_to_string(&log).unwrap_or_default())
+}
+
+#[test]
+fn porcelain_status_says_what_changed() {
+    assert_eq!(parse_porcelain_changes(""), UncommittedChanges::None);
+    assert_eq!(parse_porcelain_changes("?? notes.txt\n"), UncommittedChanges::FilesNotAdded);
+    assert_eq!(parse_porcelain_changes("?? notes.txt\n M src/main.rs\n"), UncommittedChanges::FilesChanged);
+    assert_eq!(parse_porcelain_changes("UU src/main.rs\n"), UncommittedChanges::FilesChanged);
+
+    assert!(is_fsmonitor_set("true") && is_fsmonitor_set(".git/hooks/query-watchman"));
+    assert!(!is_fsmonitor_set("false") && !is_fsmonitor_set(""));
+}
+
+#[test]
+fn repositories_with_a_monitor_are_looked_at_with_git_status() {
+    let dir = make_repo("status");
+    let (clean, log) = run_prompt(&dir, &[]);
+    assert!(log.contains("\\\"status\\\" \\\"--porcelain\\\""), "{}", log);
+    assert!(!log.contains("\\\"diff\\\" \\\"--quiet\\\""), "{}", log);
+
+    fs::write(dir.join("README.md"), "changed\n").unwrap();
+    let (changed, _) = run_prompt(&dir, &[]);
+    let (diffed, log) = run_prompt(&dir, &["--fsmonitor", "never"]);
+    assert_ne!(changed, clean);
+    assert_eq!(changed, diffed);
+    assert!(log.contains("\\\"core.fsmonitor=false\\\" \\\"diff\\\" \\\"--quiet\\\""), "{}", log);
+
+    fs::write(dir.join("notes.txt"), "new\n").unwrap();
+    fs::write(dir.join("README.md"), "hi\n").unwrap();
+    assert_eq!(run_prompt(&dir, &[]).0, run_prompt(&dir, &["--fsmonitor", "never"]).0);
+}
+
+#[test]
+fn the_doctor_says_how_changes_are_found() {
+    let dir = make_repo("doctor");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["doctor"]).current_dir(&dir).env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap()).output().unwrap();
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("\nGit\n  core.fsmonitor is ") && text.contains("looked for with git status"), "{}", text);
+}
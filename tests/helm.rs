@@ -0,0 +1,84 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::helm::{find_chart, parse_chart};
+
+// An umbrella chart at the top of a repository in work/ with a subchart in it
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name)
+        .dir("work/.git")
+        .dir("work/deploy/charts/billing-api/templates")
+        .dir("work/docs")
+        .dir("elsewhere/templates")
+        .file("Chart.yaml", "name: outside\nversion: 9.9.9\n")
+        .file("work/deploy/Chart.yaml", "apiVersion: v2\nname: platform\nversion: 2.0.0\ndependencies:\n  - name: billing-api\n    version: 1.4.2\n")
+        .file("work/deploy/charts/billing-api/Chart.yaml", "apiVersion: v2\nname: billing-api # the API\nversion: \"1.4.2\"\n")
+        .file("elsewhere/Chart.yaml", "name: loose\nversion: 0.1.0\n")
+        .build()
+}
+
+fn chart(name: &str, version: Option<&str>) -> Option<(String, Option<String>)> {
+    Some((name.to_owned(), version.map(str::to_owned)))
+}
+
+#[test]
+fn the_name_and_version_are_read_from_the_top_level() {
+    assert_eq!(parse_chart("apiVersion: v2\nname: billing-api\nversion: 1.4.2\n"), chart("billing-api", Some("1.4.2")));
+    assert_eq!(parse_chart("name: 'billing api'\nversion: \"1.4.2\" # released\n"), chart("billing api", Some("1.4.2")));
+    assert_eq!(parse_chart("dependencies:\n  - name: redis\n    version: 17.0.0\nname: platform\n"), chart("platform", None));
+    assert_eq!(parse_chart("version: 1.0.0\n"), None);
+    assert_eq!(parse_chart("name:\n"), None);
+    assert_eq!(parse_chart("name: # nothing\n"), None);
+}
+
+#[test]
+fn charts_written_in_less_usual_ways_are_still_understood() {
+    assert_eq!(parse_chart("\u{feff}name: billing-api\nversion: 1.4.2\n"), chart("billing-api", Some("1.4.2")), "a byte order mark");
+    assert_eq!(parse_chart("description: |\n  name: not-this\nname: >-\n  billing\n  api\nversion: |\n  1.4.2\n"), chart("billing api", Some("1.4.2")));
+    assert_eq!(parse_chart("maintainers: [{name: alice,\nname: bob}]\nannotations: {\n  name: x }\nname: platform\n"), chart("platform", None), "names inside flow mappings");
+    assert_eq!(parse_chart("\"name\": \"say \\\"hi\\\"\"\n'version': 'it''s 1'\n"), chart("say \"hi\"", Some("it's 1")));
+    assert_eq!(parse_chart("name: billing\n  api\n"), chart("billing api", None), "a plain scalar over two lines");
+    assert_eq!(parse_chart("---\nname: platform\n---\nname: other\nversion: 2.0.0\n"), chart("platform", None), "only the first document");
+    assert_eq!(parse_chart("name: [billing]\n"), None);
+    assert_eq!(parse_chart("url: http://example.com\nname: a:b\n"), chart("a:b", None));
+}
+
+#[test]
+fn the_nearest_chart_up_to_the_top_of_the_repository_is_found() {
+    let dir = make_repo("find");
+
+    assert_eq!(find_chart(&dir.join("work/deploy/charts/billing-api/templates")), chart("billing-api", Some("1.4.2")));
+    assert_eq!(find_chart(&dir.join("work/deploy/charts")), chart("platform", Some("2.0.0")));
+    assert_eq!(find_chart(&dir.join("work/docs")), None, "the chart above the repository isn't for it");
+    assert_eq!(find_chart(&dir.join("elsewhere/templates")), None, "outside a repository only here is looked in");
+    assert_eq!(find_chart(&dir.join("elsewhere")), chart("loose", Some("0.1.0")));
+}
+
+fn get_chart(dir: &Path, cwd: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--only", "helm-chart"])
+        .current_dir(dir.join(cwd))
+        .env("TERM", "xterm")
+        .env("HOME", dir)
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn the_chart_is_shown_with_a_marker() {
+    let dir = make_repo("prompt");
+
+    assert_eq!(get_chart(&dir, "work/deploy/charts/billing-api"), "⎈ billing-api 1.4.2");
+    assert_eq!(get_chart(&dir, "work/docs"), "");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::process::Command;
+
+use common::Fixture;
+
+fn run_prompt(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(args).env("TERM", "xterm").output().unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn nu_gets_both_prompt_closures() {
+    let (success, script) = run_prompt(&["init", "nu"]);
+
+    assert!(success);
+    assert!(script.contains("$env.PROMPT_COMMAND = {||"), "{}", script);
+    assert!(script.contains("$env.PROMPT_COMMAND_RIGHT = {||"), "{}", script);
+    assert!(script.contains("$env.LAST_EXIT_CODE") && script.contains("$env.CMD_DURATION_MS"), "{}", script);
+    assert!(script.contains(&format!("^\"{}\"", env!("CARGO_BIN_EXE_prompt").replace('\\', "\\\\"))), "{}", script);
+}
+
+#[test]
+fn nu_prompts_end_without_a_newline() {
+    let (_, left) = run_prompt(&["--shell", "nu", "--color", "always", "--exit-code", "1", "--disable", "git,k8s"]);
+    assert!(left.ends_with("\x1b[0m "), "{:?}", left);
+    assert!(!left.contains("\\[") && !left.contains("%{"), "{:?}", left);
+
+    let (_, transient) = run_prompt(&["--shell", "nu", "--color", "never", "--transient"]);
+    assert!(!transient.ends_with('\n'), "{:?}", transient);
+}
+
+#[test]
+fn nu_has_no_completions() {
+    let (success, output) = run_prompt(&["completions", "nu"]);
+    assert!(!success && output.is_empty());
+}
+
+#[test]
+fn elvish_gets_both_prompts_and_the_hooks() {
+    let (success, script) = run_prompt(&["init", "elvish"]);
+
+    assert!(success);
+    assert!(script.contains("set edit:prompt = {") && script.contains("set edit:rprompt = {"), "{}", script);
+    assert!(script.contains("edit:after-command") && script.contains("pre-exec -- $line"), "{}", script);
+
+    // Only checked where elvish is around to compile it
+    let path = Fixture::new("elvish").file("prompt.elv", &script).build_file("prompt.elv");
+    if let Ok(output) = Command::new("elvish").arg("-compileonly").arg(&path).output() {
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+#[test]
+fn elvish_prompts_leave_the_spacing_to_the_script() {
+    let (_, left) = run_prompt(&["--shell", "elvish", "--color", "always", "--osc7", "--disable", "git,k8s"]);
+
+    assert!(!left.starts_with('\n') && !left.ends_with(' ') && !left.ends_with('\n'), "{:?}", left);
+    assert!(left.contains("\x1b[") && !left.contains("\x1b]"), "{:?}", left);
+}
@@ -0,0 +1,105 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::preexec::Record;
+
+// A repository and somewhere that isn't one, with a git and kubectl that only note down they were
+// run. Nothing else is on the PATH so the shell does that itself
+fn make_dir(name: &str) -> TempDir {
+    let fixture = Fixture::new(name).dir("repo/.git").dir("plain").file("config.toml", "");
+    let script = format!("#!/bin/sh\n: > '{}'\nexit 1\n", fixture.path().join("ran").display());
+    fixture.script("bin/git", &script).script("bin/kubectl", &script).build()
+}
+
+fn run_prompt(dir: &Path, cwd: &str, args: &[&str]) -> (Option<i32>, String) {
+    // Subcommands can't have flags in front of them
+    let flags: &[&str] = if args[0].starts_with('-') { &["--ps1", "--color", "never"] } else { &[] };
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(flags)
+        .args(args)
+        .current_dir(dir.join(cwd))
+        .env("PWD", dir.join(cwd))
+        .env("PATH", dir.join("bin"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap();
+
+    (output.status.code(), String::from_utf8(output.stdout).unwrap())
+}
+
+fn write_record(dir: &Path, ago_ms: u64) -> PathBuf {
+    let (code, _) = run_prompt(dir, "plain", &["pre-exec", "--", "make"]);
+    assert_eq!(code, Some(0));
+
+    let mut paths = fs::read_dir(dir.join("run").join("prompt")).unwrap().map(|x| x.unwrap().path());
+    let path = paths.find(|x| x.extension().is_some_and(|e| e == "state")).unwrap();
+    let mut record: Record = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    record.started_ms -= ago_ms;
+    fs::write(&path, serde_json::to_string(&record).unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn instant_prompts_run_nothing_and_say_when_theres_more() {
+    let dir = make_dir("repo");
+
+    let (code, output) = run_prompt(&dir, "repo", &["--instant", "--exit-code", "1"]);
+    assert_eq!(code, Some(2));
+    assert_eq!(output, format!("\n{}\n❯!❯❯ \n", dir.join("repo").display()));
+    assert!(!dir.join("ran").exists());
+}
+
+#[test]
+fn instant_prompts_with_nothing_left_out_are_the_whole_prompt() {
+    let dir = make_dir("plain");
+
+    let (code, instant) = run_prompt(&dir, "plain", &["--instant", "--disable", "k8s"]);
+    assert_eq!(code, Some(0));
+    assert!(!dir.join("ran").exists());
+
+    assert_eq!(run_prompt(&dir, "plain", &["--disable", "k8s"]), (Some(0), instant));
+}
+
+#[test]
+fn the_full_prompt_gets_the_pre_exec_record_after_an_instant_one() {
+    let dir = make_dir("record");
+
+    let record = write_record(&dir, 15000);
+    let (code, instant) = run_prompt(&dir, "repo", &["--instant", "--combined"]);
+    assert_eq!(code, Some(2));
+    assert!(instant.contains("15s"), "{:?}", instant);
+    assert!(record.exists());
+
+    let (_, full) = run_prompt(&dir, "repo", &["--combined"]);
+    assert!(full.contains("15s"), "{:?}", full);
+    assert!(!record.exists());
+
+    // With nothing to follow it the instant prompt takes the record itself
+    let record = write_record(&dir, 15000);
+    let (code, instant) = run_prompt(&dir, "plain", &["--instant", "--combined", "--disable", "k8s"]);
+    assert_eq!(code, Some(0));
+    assert!(instant.contains("15s"), "{:?}", instant);
+    assert!(!record.exists());
+}
+
+#[test]
+fn only_zsh_gets_an_async_init_script() {
+    let (code, script) = run_prompt(&make_dir("init"), "plain", &["init", "zsh", "--async"]);
+    assert_eq!(code, Some(0));
+    assert!(script.contains("--instant") && script.contains("zle -F -w") && script.contains("zle reset-prompt"), "{}", script);
+
+    let (code, script) = run_prompt(&make_dir("init-bash"), "plain", &["init", "bash", "--async"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(script, "");
+}
@@ -0,0 +1,35 @@
+use std::env;
+use std::process::Command;
+
+// Scripts reading this rely on the field names staying put and on there being no styling mixed in
+#[test]
+fn json_output_lists_every_segment_without_escapes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--format", "json", "--exit-code", "1", "--pipestatus", "0 1", "--message", "hello", "--side", "aws-region=hidden"])
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains('\x1b'), "{:?}", stdout);
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["exit_code"], 1);
+    assert_eq!(report["pipestatus"], serde_json::json!([0, 1]));
+    assert_eq!(report["exit_state"], "exit-fail");
+    assert_eq!(report["uncommitted_changes"], serde_json::Value::Null);
+
+    let segments = report["segments"].as_array().unwrap();
+    let message = segments.iter().find(|s| s["name"] == "message").unwrap();
+    assert_eq!(message["side"], "left");
+    assert_eq!(message["text"], "hello");
+    assert_eq!(message["spans"][0]["style"]["bold"], true);
+    assert_eq!(message["error"], serde_json::Value::Null);
+
+    let region = segments.iter().find(|s| s["name"] == "aws-region").unwrap();
+    assert_eq!(region["side"], "hidden");
+    assert_eq!(region["text"], serde_json::Value::Null);
+}
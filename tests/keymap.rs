@@ -0,0 +1,87 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+// Somewhere that looks like a repository, with a git and kubectl that only note down they were run.
+// Nothing else is on the PATH so the shell does that itself
+fn make_dir(name: &str, config: &str) -> TempDir {
+    let fixture = Fixture::new(name).dir("repo/.git").file("config.toml", config);
+    let script = format!("#!/bin/sh\n: > '{}'\nexit 1\n", fixture.path().join("ran").display());
+    fixture.script("bin/git", &script).script("bin/kubectl", &script).build()
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never"])
+        .args(args)
+        .current_dir(dir.join("repo"))
+        .env("PWD", dir.join("repo"))
+        .env("PATH", dir.join("bin"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn get_chevrons(output: &str) -> &str {
+    output.lines().last().unwrap()
+}
+
+#[test]
+fn normal_mode_turns_the_last_chevron_round() {
+    let dir = make_dir("vicmd", "");
+
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "main"])), "❯❯❯ ");
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "vicmd"])), "❯❯❮ ");
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "visual", "--keymap-style", "main=M"])), "❯❯❮ ");
+}
+
+#[test]
+fn keymaps_can_have_a_colour_of_their_own() {
+    let dir = make_dir("colour", "");
+
+    let output = run_prompt(&dir, &["--keymap", "vicmd", "--keymap-style", "vicmd=N:red", "--color", "always"]);
+    assert!(get_chevrons(&output).ends_with("\u{1b}[1;31mN\u{1b}[0m "), "{:?}", output);
+}
+
+#[test]
+fn the_last_keymap_style_given_wins_over_the_config() {
+    let dir = make_dir("precedence", "[keymaps]\nvicmd = \"V\"\n");
+
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "vicmd"])), "❯❯V ");
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "vicmd", "--keymap-style", "vicmd=F"])), "❯❯F ");
+    assert_eq!(get_chevrons(&run_prompt(&dir, &["--keymap", "vicmd", "--keymap-style", "vicmd=F", "--keymap-style", "vicmd=G"])), "❯❯G ");
+}
+
+#[test]
+fn bad_keymap_styles_are_rejected() {
+    for style in ["vicmd", "vicmd=N:nope"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["--keymap-style", style]).output().unwrap();
+        assert!(!output.status.success(), "{}", style);
+    }
+}
+
+#[test]
+fn redrawing_for_a_keymap_runs_nothing() {
+    let dir = make_dir("redraw", "");
+
+    let first = run_prompt(&dir, &["--keymap", "main"]);
+    assert!(dir.join("ran").exists());
+    fs::remove_file(dir.join("ran")).unwrap();
+
+    let redrawn = run_prompt(&dir, &["--keymap", "vicmd", "--redraw"]);
+    assert!(!dir.join("ran").exists());
+    assert_eq!(redrawn, first.replace("❯❯❯ ", "❯❯❮ "));
+}
@@ -0,0 +1,91 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::lfs::{parse_lfs_pending, uses_lfs};
+
+const PENDING: &str = "On branch main
+Objects to be pushed to origin/main:
+
+\tassets/logo.psd (LFS: 4d7a214)
+\tassets/intro.mp4 (LFS: 9f86d08)
+
+Objects to be committed:
+
+
+Objects not staged for commit:
+
+\tassets/banner.psd (LFS: 2c26b46 -> File: 2c26b46)
+";
+
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name).dir("bin").git("repo", &["init", "--quiet"]).build()
+}
+
+#[test]
+fn only_repositories_with_lfs_attributes_use_it() {
+    let dir = make_repo("attributes");
+    let repo = dir.join("repo");
+    assert!(!uses_lfs(&repo));
+
+    fs::write(repo.join(".gitattributes"), "*.sh text eol=lf\n# *.psd filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+    assert!(!uses_lfs(&repo));
+
+    fs::write(repo.join(".gitattributes"), "*.sh text eol=lf\n*.psd filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+    assert!(uses_lfs(&repo));
+
+    fs::remove_file(repo.join(".gitattributes")).unwrap();
+    fs::write(repo.join(".git/info/attributes"), "*.mp4 filter=lfs\n").unwrap();
+    assert!(uses_lfs(&repo));
+}
+
+#[test]
+fn objects_waiting_to_be_pushed_are_counted() {
+    assert_eq!(parse_lfs_pending(PENDING), 2);
+    assert_eq!(parse_lfs_pending("On branch main\nObjects to be committed:\n\n\tlogo.psd (LFS: 4d7a214)\n"), 0);
+    assert_eq!(parse_lfs_pending("On branch main\nObjects to be pushed to origin/main:\n\n\nObjects to be committed:\n\n"), 0);
+    assert_eq!(parse_lfs_pending(""), 0);
+}
+
+fn run_prompt(dir: &Path, status: &str, config: &str) -> String {
+    let git_lfs = dir.join("bin").join("git-lfs");
+    fs::write(dir.join("status"), status).unwrap();
+    fs::write(&git_lfs, format!("#!/bin/sh\ntouch '{}'\ncat '{}'\n", dir.join("ran").display(), dir.join("status").display())).unwrap();
+    fs::set_permissions(&git_lfs, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--only", "git-lfs"])
+        .current_dir(dir.join("repo"))
+        .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH").unwrap()))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn unpushed_objects_are_marked_when_asked_for() {
+    let dir = make_repo("marked");
+
+    assert_eq!(run_prompt(&dir, PENDING, "git-lfs = true\n"), "");
+    assert!(!dir.join("ran").exists(), "git lfs isn't run without LFS attributes");
+
+    fs::write(dir.join("repo/.gitattributes"), "*.psd filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+    assert_eq!(run_prompt(&dir, PENDING, ""), "");
+    assert!(!dir.join("ran").exists(), "git lfs isn't run unless asked for");
+
+    assert_eq!(run_prompt(&dir, PENDING, "git-lfs = true\n"), "⇡lfs");
+    assert_eq!(run_prompt(&dir, "On branch main\nObjects to be committed:\n\n", "git-lfs = true\n"), "");
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::Fixture;
+
+fn run_prompt(dir: &Path, vars: &[(&str, &Path)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .current_dir(dir)
+        .env("PWD", dir)
+        .env("TERM", "xterm")
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env_remove("PROMPT_LOG")
+        .env_remove("PROMPT_LOG_FILE")
+        .envs(vars.iter().copied())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn commands_are_logged_to_the_file_only() {
+    let dir = Fixture::new("file").build();
+    let log = dir.join("prompt.log");
+
+    let plain = run_prompt(&dir, &[]);
+    let logged = run_prompt(&dir, &[("PROMPT_LOG", Path::new("debug")), ("PROMPT_LOG_FILE", &log)]);
+
+    assert_eq!(logged.stdout, plain.stdout);
+    assert!(logged.stderr.is_empty(), "{}", String::from_utf8_lossy(&logged.stderr));
+
+    let text = fs::read_to_string(&log).unwrap();
+    assert!(text.contains("git\\\" \\\"rev-parse\\\" \\\"--is-inside-work-tree\\\""), "{}", text);
+    assert!(text.contains("status="), "{}", text);
+}
+
+#[test]
+fn logging_is_off_unless_asked_for() {
+    let dir = Fixture::new("off").build();
+
+    run_prompt(&dir, &[]);
+    assert!(!dir.join("state").exists());
+
+    run_prompt(&dir, &[("PROMPT_LOG", Path::new("debug"))]);
+    assert!(dir.join("state").join("prompt").join("prompt.log").exists());
+}
+
+#[test]
+fn big_logs_are_moved_aside() {
+    let dir = Fixture::new("rotate").build();
+    let log = dir.join("prompt.log");
+    fs::write(&log, vec![b'x'; 2 * 1024 * 1024]).unwrap();
+
+    run_prompt(&dir, &[("PROMPT_LOG", Path::new("debug")), ("PROMPT_LOG_FILE", &log)]);
+    assert_eq!(fs::metadata(dir.join("prompt.log.old")).unwrap().len(), 2 * 1024 * 1024);
+    assert!(fs::metadata(&log).unwrap().len() < 1024 * 1024);
+}
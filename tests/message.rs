@@ -0,0 +1,94 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use common::Fixture;
+
+fn get_command(file: &Path, messages: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prompt"));
+    command.args(["--color", "always", "--only", "message"])
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("PROMPT_MESSAGE_FILE", file)
+        .env_remove("COLORTERM");
+
+    for message in messages {
+        command.args(["--message", message]);
+    }
+    command
+}
+
+fn render_with_file(file: &Path, messages: &[&str]) -> String {
+    let output = get_command(file, messages).stdin(Stdio::null()).output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+fn render_messages(messages: &[&str]) -> String {
+    render_with_file(&env::temp_dir().join("prompt-message-test-missing"), messages)
+}
+
+#[test]
+fn every_message_is_its_own_segment() {
+    assert_eq!(render_messages(&["one", "two"]), "\x1b[1;32mone\x1b[0m \x1b[1;32mtwo\x1b[0m");
+}
+
+#[test]
+fn messages_can_start_with_a_colour() {
+    assert_eq!(render_messages(&["red:PROD JUMPBOX"]), "\x1b[1;31mPROD JUMPBOX\x1b[0m");
+    assert_eq!(render_messages(&["#0000ee:deep"]), "\x1b[1;34mdeep\x1b[0m");
+    assert_eq!(render_messages(&["dim:ticket PROJ-99"]), "\x1b[1;2;32mticket PROJ-99\x1b[0m");
+}
+
+#[test]
+fn anything_else_before_a_colon_is_part_of_the_message() {
+    assert_eq!(render_messages(&["10:30 standup"]), "\x1b[1;32m10:30 standup\x1b[0m");
+    assert_eq!(render_messages(&["note: here"]), "\x1b[1;32mnote: here\x1b[0m");
+}
+
+#[test]
+fn control_characters_are_removed() {
+    assert_eq!(render_messages(&["a\x1b[31mb\x07"]), "\x1b[1;32mab\x1b[0m");
+}
+
+#[test]
+fn messages_can_be_piped_in() {
+    let mut child = get_command(&env::temp_dir().join("prompt-message-test-missing"), &["one", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"red:piped\nsecond line\n").unwrap();
+
+    let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+    assert_eq!(output.trim_end(), "\x1b[1;32mone\x1b[0m \x1b[1;31mpiped\x1b[0m");
+}
+
+#[test]
+fn the_message_file_goes_on_the_end() {
+    let dir = Fixture::new("file").build();
+    let file = dir.join("message");
+
+    assert_eq!(render_with_file(&file, &["one"]), "\x1b[1;32mone\x1b[0m");
+
+    fs::write(&file, "on-call until 18:00\nsecond line\n").unwrap();
+    assert_eq!(render_with_file(&file, &["one"]), "\x1b[1;32mone\x1b[0m \x1b[1;32mon-call until 18:00\x1b[0m");
+
+    fs::write(&file, "").unwrap();
+    assert_eq!(render_with_file(&file, &["one"]), "\x1b[1;32mone\x1b[0m");
+}
+
+#[cfg(unix)]
+#[test]
+fn message_files_that_are_not_files_are_skipped() {
+    let dir = Fixture::new("fifo").build();
+    let fifo = dir.join("message");
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+    let start = std::time::Instant::now();
+    assert_eq!(render_with_file(&fifo, &["one"]), "\x1b[1;32mone\x1b[0m");
+    assert!(start.elapsed() < std::time::Duration::from_secs(3), "{:?}", start.elapsed());
+}
@@ -0,0 +1,61 @@
+use std::net::Ipv4Addr;
+use std::process::Command;
+
+use prompt::network::{get_network, has_interface, parse_interface_marker, InterfaceMarker};
+
+fn marker(name: &str, label: &str) -> InterfaceMarker {
+    InterfaceMarker { name: name.to_owned(), label: label.to_owned() }
+}
+
+#[test]
+fn interface_markers_need_a_name_and_a_label() {
+    let wg = parse_interface_marker("wg0=vpn↑").unwrap();
+    assert_eq!((wg.name.as_str(), wg.label.as_str()), ("wg0", "vpn↑"));
+
+    assert!(parse_interface_marker("wg0").is_err());
+    assert!(parse_interface_marker("=vpn").is_err());
+    assert!(parse_interface_marker("wg0=").is_err());
+}
+
+#[test]
+fn markers_for_the_interfaces_that_are_there_come_before_the_address() {
+    let markers = [marker("tun0", "work"), marker("wg0", "vpn↑"), marker("docker0", "docker")];
+    let up = |name: &str| name != "tun0";
+    let ip = Some(Ipv4Addr::new(10, 8, 0, 2));
+
+    assert_eq!(get_network(&markers, up, ip).as_deref(), Some("vpn↑ docker 10.8.0.2"));
+    assert_eq!(get_network(&markers, up, None).as_deref(), Some("vpn↑ docker"));
+    assert_eq!(get_network(&[], up, ip).as_deref(), Some("10.8.0.2"));
+    assert_eq!(get_network(&markers, |_| false, None), None);
+}
+
+#[test]
+fn made_up_interfaces_are_not_there() {
+    assert!(!has_interface("prompt-test0"));
+    assert!(!has_interface("nul\0byte"));
+}
+
+#[test]
+fn the_network_is_only_shown_when_asked_for() {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--only", "network", "--config", "/nonexistent/config.toml"])
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n");
+}
+
+#[test]
+fn the_network_is_timed_and_explained_like_any_other_segment() {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--config", "/nonexistent/config.toml", "--interface-marker", "prompt-test0=vpn", "--timings", "--explain-missing"])
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.lines().any(|x| x.trim_start().starts_with("network ")), "{}", stderr);
+    assert!(stderr.contains("network: none of the interfaces are there"), "{}", stderr);
+}
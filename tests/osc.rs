@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use common::Fixture;
+
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("PWD", dir)
+        .env("HOME", "/nonexistent")
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+        .env("LANG", "en_GB.UTF-8")
+        .env_remove("INSIDE_EMACS")
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Anything outside of the unreserved characters has to be percent-encoded byte by byte
+#[test]
+fn cwd_is_sent_as_an_encoded_file_url() {
+    let parent = Fixture::new("url").dir("a b%ü#?").build();
+    let output = run_prompt(&parent.join("a b%ü#?"), &["--ps1", "--osc7", "--color", "never", "--no-blank-line"]);
+
+    let sequence = output.strip_prefix("\x1b]7;file://").unwrap();
+    let (url, _) = sequence.split_once('\x07').unwrap();
+    let path = &url[url.find('/').unwrap()..];
+
+    assert_eq!(path, format!("{}/a%20b%25%C3%BC%23%3F", parent.display()));
+}
+
+#[test]
+fn cwd_percent_signs_are_escaped_for_zsh() {
+    let parent = Fixture::new("zsh").dir("a b").build();
+    let output = run_prompt(&parent.join("a b"), &["--osc7", "--color", "never", "--shell", "zsh"]);
+
+    assert!(output.starts_with("%{\x1b]7;file://"));
+    assert!(output.contains(&format!("{}/a%%20b\x07%}}", parent.display())));
+}
+
+// A has to come before anything else is drawn and B only after the space following the chevrons
+#[test]
+fn semantic_prompt_markers_surround_the_prompt() {
+    let dir = env::temp_dir();
+
+    let output = run_prompt(&dir, &["--semantic-prompt", "--color", "never", "--exit-code", "0", "--shell", "bash"]);
+    assert!(output.starts_with("\\[\x1b]133;A\x07\\]\n"), "{:?}", output);
+    assert!(output.ends_with("\u{276F}\u{276F}\u{276F} \\[\x1b]133;B\x07\\]\n"), "{:?}", output);
+
+    let output = run_prompt(&dir, &["--semantic-prompt", "--color", "never", "--exit-code", "0", "--shell", "zsh", "--combined"]);
+    let (left, _) = output.split_once('\0').unwrap();
+    assert!(left.starts_with("%{\x1b]133;A\x07%}\n"), "{:?}", left);
+    assert!(left.ends_with("\u{276F}\u{276F}\u{276F} %{\x1b]133;B\x07%}"), "{:?}", left);
+}
+
+#[test]
+fn semantic_prompt_markers_surround_the_transient_prompt() {
+    let output = run_prompt(&env::temp_dir(), &["--semantic-prompt", "--transient", "--color", "never", "--exit-code", "0", "--shell", "zsh"]);
+    assert_eq!(output, "%{\x1b]133;A\x07%}\u{276F}\u{276F}\u{276F} %{\x1b]133;B\x07%}\n");
+
+    let output = run_prompt(&env::temp_dir(), &["--semantic-prompt", "--transient", "--color", "never", "--exit-code", "0", "--shell", "fish"]);
+    assert_eq!(output, "\x1b]133;A\x07\u{276F}\u{276F}\u{276F} \x1b]133;B\x07");
+}
+
+#[test]
+fn nothing_is_sent_unless_asked_for() {
+    let output = run_prompt(&env::temp_dir(), &["--color", "never", "--exit-code", "0"]);
+    assert!(!output.contains('\x1b'), "{:?}", output);
+}
@@ -0,0 +1,42 @@
+#![cfg(unix)]
+
+use std::path::Path;
+
+use prompt::path::{format_path, PathStyle};
+
+fn format(path: &str, style: PathStyle, keep: Option<usize>) -> String {
+    format_path(Path::new(path), style, keep, &[]).1
+}
+
+#[test]
+fn fish_keeps_the_last_directory_whole_by_default() {
+    assert_eq!(format("~/work/platform/services/billing", PathStyle::Fish, None), "~/w/p/s/billing");
+    assert_eq!(format("~/work/platform/services/billing", PathStyle::Fish, Some(2)), "~/w/p/services/billing");
+}
+
+#[test]
+fn fish_keeps_two_characters_of_dotfiles() {
+    assert_eq!(format("~/.config/fish", PathStyle::Fish, None), "~/.c/fish");
+}
+
+#[test]
+fn fish_shortens_to_a_whole_grapheme() {
+    assert_eq!(format("~/e\u{301}tude/notes", PathStyle::Fish, None), "~/e\u{301}/notes");
+}
+
+#[test]
+fn fish_leaves_home_alone() {
+    assert_eq!(format("~", PathStyle::Fish, None), "~");
+    assert_eq!(format("~/src", PathStyle::Fish, Some(0)), "~/s");
+}
+
+#[test]
+fn truncating_keeps_home_when_everything_fits() {
+    assert_eq!(format("~/work/billing", PathStyle::Truncate, None), "~/work/billing");
+    assert_eq!(format("~/work/platform/services/billing", PathStyle::Truncate, Some(2)), "…/services/billing");
+}
+
+#[test]
+fn truncating_to_nothing_leaves_the_ellipsis() {
+    assert_eq!(format("~/work/billing", PathStyle::Truncate, Some(0)), "…");
+}
@@ -0,0 +1,60 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use common::Fixture;
+
+// Stdout's a pipe here, just like `prompt | cat`
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--message", "hi", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("PWD", dir)
+        .env("HOME", "/nonexistent")
+        .env("LANG", "en_GB.UTF-8")
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+        .env_remove("NO_COLOR")
+        .env_remove("CLICOLOR_FORCE")
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn piped_prompts_are_plain() {
+    let dir = Fixture::new("plain").build();
+
+    let output = run_prompt(&dir, &["--osc7", "--title", "--semantic-prompt", "--iterm2", "--exit-code", "1"]);
+    assert_eq!(output, format!("{} hi\n❯!❯❯\n", dir.display()));
+}
+
+#[test]
+fn colour_can_still_be_asked_for() {
+    let dir = Fixture::new("colour").build();
+
+    let output = run_prompt(&dir, &["--color", "always"]);
+    assert!(output.starts_with("\x1b["), "{:?}", output);
+    assert!(!output.starts_with('\n'), "{:?}", output);
+}
+
+// The shell's command substitution is a pipe too but it wants the prompt just as it always was
+#[test]
+fn shells_get_the_prompt_they_always_did() {
+    let dir = Fixture::new("shells").build();
+
+    for args in [&["--shell", "bash"][..], &["--shell", "zsh"], &["--ps1"]] {
+        let output = run_prompt(&dir, args);
+        assert!(output.starts_with('\n'), "{:?}", output);
+        assert!(output.contains("\x1b["), "{:?}", output);
+        assert!(output.trim_end_matches('\n').ends_with(' '), "{:?}", output);
+    }
+
+    assert!(run_prompt(&dir, &["--ps1", "--osc7"]).starts_with("\x1b]7;"));
+}
@@ -0,0 +1,118 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use common::{Fixture, TempDir};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).dir("bin").dir("work").build()
+}
+
+fn add_plugin(dir: &Path, name: &str, script: &str) {
+    let path = dir.join("bin").join(format!("prompt-segment-{}", name));
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn run_prompt(dir: &Path, plugins: &[&str], args: &[&str]) -> String {
+    let config = format!("plugins = {:?}\n", plugins);
+    fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden"])
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH").unwrap_or_default()))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn get_top_line(output: &str) -> &str {
+    output.lines().nth(1).unwrap()
+}
+
+#[test]
+fn plugins_are_shown() {
+    let dir = make_dir("shown");
+    add_plugin(&dir, "plain", "echo 'deploy freeze'");
+    add_plugin(&dir, "multiline", "echo first; echo second");
+
+    let output = run_prompt(&dir, &["plain", "multiline"], &["--color", "never"]);
+    assert!(get_top_line(&output).ends_with(" deploy freeze first"), "{:?}", output);
+    assert!(!output.contains("second"), "{:?}", output);
+}
+
+#[test]
+fn plugins_can_pick_their_colour() {
+    let dir = make_dir("colour");
+    add_plugin(&dir, "hint", "echo 'magenta:hinted'");
+    add_plugin(&dir, "json", "echo '{\"text\": \"from json\", \"color\": \"blue\"}'");
+    add_plugin(&dir, "colon", "echo 'note: not a colour'");
+
+    let output = run_prompt(&dir, &["hint", "json", "colon"], &["--color", "always"]);
+    assert!(output.contains("\x1b[1;35mhinted\x1b[0m"), "{:?}", output);
+    assert!(output.contains("\x1b[1;34mfrom json\x1b[0m"), "{:?}", output);
+    assert!(output.contains("note: not a colour"), "{:?}", output);
+}
+
+#[test]
+fn plugins_are_given_the_context() {
+    let dir = make_dir("context");
+    fs::create_dir_all(dir.join("work").join(".git")).unwrap();
+    add_plugin(&dir, "context", "echo \"$PROMPT_EXIT_CODE $(basename \"$PROMPT_CWD\") $(basename \"$PROMPT_REPO_ROOT\")\"");
+
+    let output = run_prompt(&dir, &["context"], &["--color", "never", "--exit-code", "3"]);
+    assert!(output.contains(" 3 work work"), "{:?}", output);
+}
+
+#[test]
+fn misbehaving_plugins_are_left_out() {
+    let dir = make_dir("misbehaving");
+    add_plugin(&dir, "huge", "yes | head -c 10000000");
+    add_plugin(&dir, "endless", "yes");
+    add_plugin(&dir, "binary", "printf 'bad\\377\\376\\n'");
+    add_plugin(&dir, "escapes", "printf 'clean\\033]0;title\\007\\n'");
+    add_plugin(&dir, "hanging", "sleep 10; echo hanging");
+    add_plugin(&dir, "failing", "echo failing; exit 1");
+    add_plugin(&dir, "silent", "true");
+    add_plugin(&dir, "fine", "echo fine");
+
+    let plugins = ["huge", "endless", "binary", "escapes", "hanging", "failing", "silent", "missing", "fine"];
+
+    let start = Instant::now();
+    let output = run_prompt(&dir, &plugins, &["--color", "never"]);
+    assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+
+    assert!(get_top_line(&output).ends_with(" clean fine"), "{:?}", output);
+    for name in ["y\ny", "bad", "title", "hanging", "failing"] {
+        assert!(!output.contains(name), "{:?}", output);
+    }
+}
+
+#[test]
+fn plugins_need_valid_names() {
+    let dir = make_dir("names");
+    fs::write(dir.join("config.toml"), "plugins = [\"../../bin/sh\"]\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["config", "check"])
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid plugin name"));
+}
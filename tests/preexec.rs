@@ -0,0 +1,192 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use common::Fixture;
+use prompt::preexec::{get_command_name, peek_last_prompt, take_last_prompt, take_record, write_record, LastPrompt, Record};
+
+// There's no tty here so every run from this process shares the same state file
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("XDG_RUNTIME_DIR", dir)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+fn get_files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    fs::read_dir(dir.join("prompt")).map_or(vec![], |x| x.map(|x| x.unwrap().path()).filter(|x| x.extension().is_some_and(|e| e == extension)).collect())
+}
+
+fn get_state_files(dir: &Path) -> Vec<PathBuf> {
+    get_files(dir, "state")
+}
+
+// Moves the start of the command into the past, so there's a duration without waiting for one
+fn age_record(path: &Path, by: Duration) {
+    let mut record: Record = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    record.started_ms -= by.as_millis() as u64;
+    fs::write(path, serde_json::to_string(&record).unwrap()).unwrap();
+}
+
+#[test]
+fn the_command_name_is_the_first_word() {
+    assert_eq!(get_command_name("terrafrom plan -out x").as_deref(), Some("terrafrom"));
+    assert_eq!(get_command_name("  FOO=1 BAR=2 make test").as_deref(), Some("make"));
+    assert_eq!(get_command_name("\x1b[31mls\x1b[0m").as_deref(), Some("ls"));
+    assert_eq!(get_command_name("   "), None);
+}
+
+#[test]
+fn the_duration_comes_from_the_pre_exec_record() {
+    let dir = Fixture::new("duration").build();
+
+    assert_eq!(run_prompt(&dir, &["pre-exec", "--", "sleep", "--help"]), "");
+    let records = get_state_files(&dir);
+    assert_eq!(records.len(), 1);
+    age_record(&records[0], Duration::from_secs(75));
+
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--only", "duration"]), "");
+    assert_eq!(get_state_files(&dir).len(), 1, "--only shouldn't take the record");
+
+    assert!(run_prompt(&dir, &["--color", "never", "--right"]).contains("1m15s"));
+    assert!(get_state_files(&dir).is_empty());
+
+    // It's only used the once
+    assert!(!run_prompt(&dir, &["--color", "never", "--right"]).contains("1m"));
+}
+
+#[test]
+fn the_transient_prompt_leaves_the_record() {
+    let dir = Fixture::new("transient").build();
+
+    run_prompt(&dir, &["pre-exec", "--", "make"]);
+    run_prompt(&dir, &["--transient"]);
+    assert_eq!(get_state_files(&dir).len(), 1);
+}
+
+#[test]
+fn flags_win_over_the_record() {
+    let dir = Fixture::new("flags").build();
+
+    run_prompt(&dir, &["pre-exec", "--", "make"]);
+    age_record(&get_state_files(&dir)[0], Duration::from_secs(75));
+    assert!(run_prompt(&dir, &["--color", "never", "--right", "--duration-ms", "3000"]).contains("3.0s"));
+}
+
+#[test]
+fn stale_records_are_thrown_away() {
+    let dir = Fixture::new("stale").build();
+    let path = dir.join("record.state");
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64;
+
+    write_record(&path, "make").unwrap();
+    assert_eq!(take_record(&path).map(|(_, command)| command), Some(Some("make".to_owned())));
+    assert!(!path.exists());
+
+    // From a shell in another session that was on the same tty before
+    fs::write(&path, serde_json::to_string(&Record { started_ms: now, session: Some(u32::MAX - 1), command: None }).unwrap()).unwrap();
+    assert_eq!(take_record(&path), None);
+    assert!(!path.exists());
+
+    // From a shell that went away mid-command
+    fs::write(&path, serde_json::to_string(&Record { started_ms: now - 30 * 24 * 60 * 60 * 1000, session: None, command: None }).unwrap()).unwrap();
+    assert_eq!(take_record(&path), None);
+
+    fs::write(&path, "not json").unwrap();
+    assert_eq!(take_record(&path), None);
+    assert!(!path.exists());
+
+    assert_eq!(take_record(&path), None);
+}
+
+#[test]
+fn a_failed_command_is_named_next_to_its_exit_code() {
+    let dir = Fixture::new("failed").build();
+
+    run_prompt(&dir, &["pre-exec", "--", "FOO=1", "make", "test"]);
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "2"]), "✗ 2 make");
+
+    // The flag wins, and is cut down to fit
+    run_prompt(&dir, &["pre-exec", "--", "make"]);
+    let args = ["--color", "never", "--right", "--exit-code", "1", "--last-command", "terraform-wrapper apply", "--last-command-length", "9"];
+    assert_eq!(run_prompt(&dir, &args), "✗ 1 terrafor…");
+
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "1", "--pipestatus", "0 1", "--last-command", "cat x | grep y"]), "✗ 0|1 cat");
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "1", "--last-command", "make", "--ascii"]), "x 1 make");
+}
+
+#[test]
+fn without_a_failure_or_a_name_it_is_as_before() {
+    let dir = Fixture::new("before").build();
+
+    run_prompt(&dir, &["pre-exec", "--", "make"]);
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right"]), "");
+
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "1"]), "");
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "1", "--pipestatus", "0 1"]), "0|1");
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--right", "--exit-code", "1", "--last-command", "make", "--last-command-length", "0"]), "");
+}
+
+fn age_last_prompt(path: &Path, by: Duration) {
+    let mut last_prompt: LastPrompt = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    last_prompt.drawn_ms -= by.as_millis() as u64;
+    fs::write(path, serde_json::to_string(&last_prompt).unwrap()).unwrap();
+}
+
+#[test]
+fn a_missing_or_broken_last_prompt_is_written_over() {
+    let dir = Fixture::new("last-prompt").build();
+    let path = dir.join("prompt").join("tty.drawn");
+
+    assert_eq!(take_last_prompt(&path), None);
+    assert!(take_last_prompt(&path).is_some_and(|x| x < Duration::from_secs(60)), "the first one leaves it behind");
+
+    fs::write(&path, "{\"drawn_ms\": ").unwrap();
+    assert_eq!(peek_last_prompt(&path), None);
+    assert_eq!(take_last_prompt(&path), None);
+    assert!(peek_last_prompt(&path).is_some());
+
+    // From the future, after the clock's gone back
+    fs::write(&path, serde_json::to_string(&LastPrompt { drawn_ms: u64::MAX, session: None }).unwrap()).unwrap();
+    assert_eq!(take_last_prompt(&path), None);
+
+    // From a shell in another session that was on the same tty before
+    fs::write(&path, serde_json::to_string(&LastPrompt { drawn_ms: 0, session: Some(u32::MAX - 1) }).unwrap()).unwrap();
+    assert_eq!(take_last_prompt(&path), None);
+    assert!(take_last_prompt(&path).is_some());
+}
+
+#[test]
+fn a_terminal_left_alone_says_for_how_long() {
+    let dir = Fixture::new("idle").build();
+
+    assert!(!run_prompt(&dir, &["--color", "never"]).contains('⏾'));
+    let drawn = get_files(&dir, "drawn");
+    assert_eq!(drawn.len(), 1);
+
+    age_last_prompt(&drawn[0], Duration::from_secs(9 * 60 * 60 + 30 * 60));
+    assert_eq!(run_prompt(&dir, &["--color", "never", "--only", "idle"]), "", "--only doesn't touch it");
+    assert!(run_prompt(&dir, &["--color", "never"]).contains("⏾ 9h"));
+    assert!(!run_prompt(&dir, &["--color", "never"]).contains('⏾'), "the next prompt is fresh");
+
+    // The time spent running a command isn't time spent away
+    age_last_prompt(&drawn[0], Duration::from_secs(3 * 60 * 60));
+    run_prompt(&dir, &["pre-exec", "--", "make"]);
+    age_record(&get_state_files(&dir)[0], Duration::from_secs(3 * 60 * 60 - 60));
+    assert!(!run_prompt(&dir, &["--color", "never"]).contains('⏾'));
+
+    age_last_prompt(&drawn[0], Duration::from_secs(2 * 60 * 60));
+    assert!(!run_prompt(&dir, &["--color", "never", "--idle-threshold-mins", "180"]).contains('⏾'));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use common::Fixture;
+
+// Nothing can be found on the path so anything that tried to run git or kubectl would come up empty
+fn preview(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .arg("preview")
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("PATH", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env_remove("COLORFGBG")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn every_chevron_state_is_shown_without_running_anything() {
+    let dir = Fixture::new("states").build();
+    let output = preview(&dir, &["--color", "never", "--columns", "100"]);
+
+    for chevrons in ["❯❯❯\n", "❯!❯❯\n", "❯❯*❯↑\n", "❯❯?❯↓\n", "❯!❯=❯↕\n", "❯❯❯~\n"] {
+        assert!(output.contains(chevrons), "{} in {}", chevrons, output);
+    }
+    assert!(output.contains("main (1)\n"), "{}", output);
+    assert!(output.contains(" main prod-eu payments work eu-west-1\n"), "{}", output);
+}
+
+#[test]
+fn long_lines_are_cut_down() {
+    let dir = Fixture::new("long").build();
+    let output = preview(&dir, &["--color", "never", "--columns", "100"]);
+
+    let line = output.lines().skip_while(|x| *x != "# long path in a narrow terminal").nth(1).unwrap();
+    assert!(line.starts_with('…') && line.chars().count() <= 50, "{:?}", line);
+}
+
+#[cfg(unix)]
+#[test]
+fn the_config_and_flags_are_used() {
+    let dir = Fixture::new("config").file("config.toml", "theme = \"high-contrast\"\n[segments.git-branch]\nlabel = \"on \"\n").build();
+
+    let output = preview(&dir, &["--color", "always", "--role-color", "path=red"]);
+    assert!(output.contains("\x1b[1;31m~/src/prompt\x1b[0m \x1b[2mon \x1b[0m\x1b[1;95mmain\x1b[0m"), "{:?}", output);
+}
@@ -0,0 +1,83 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+fn get_git() -> String {
+    let output = Command::new("sh").args(["-c", "command -v git"]).output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+// A wrapper that passes everything on to the real git except the branch, which it answers itself
+fn write_shim(dir: &Path, branch: &str) {
+    fs::create_dir_all(dir).unwrap();
+    let shim = dir.join("git");
+    fs::write(&shim, format!("#!/bin/sh\nif [ \"$1\" = branch ]; then echo \"{}\"; else exec {} \"$@\"; fi\n", branch, get_git())).unwrap();
+    fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name)
+        .git("work", &["init", "--quiet", "--initial-branch", "main"])
+        .git("work", &["commit", "--quiet", "--allow-empty", "--message", "first"])
+        .build()
+}
+
+fn get_top_line(dir: &Path, path: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--disable", "k8s"])
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("TERM", "xterm")
+        .env("PATH", path)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("NVM_DIR", "/somewhere/nvm")
+        .env_remove("LC_ALL")
+        .env_remove("AWS_PROFILE")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines().nth(1).unwrap().to_owned()
+}
+
+#[test]
+fn wrappers_in_relative_path_entries_are_skipped() {
+    let dir = make_repo("relative");
+    write_shim(&dir.join("work").join("bin"), "shim");
+    let path = format!("bin:{}", env::var("PATH").unwrap_or_default());
+
+    assert!(get_top_line(&dir, &path, &[]).ends_with(" main"));
+    assert!(get_top_line(&dir, &path, &["--inherit-env"]).ends_with(" shim"));
+}
+
+#[test]
+fn wrappers_earlier_in_path_are_used() {
+    let dir = make_repo("absolute");
+    write_shim(&dir.join("shims"), "shim");
+    let path = format!("{}:{}", dir.join("shims").display(), env::var("PATH").unwrap_or_default());
+
+    assert!(get_top_line(&dir, &path, &[]).ends_with(" shim"));
+}
+
+#[test]
+fn commands_only_get_the_environment_they_need() {
+    let dir = make_repo("environment");
+    write_shim(&dir.join("shims"), "${NVM_DIR:-none}-$LC_ALL");
+    let path = format!("{}:{}", dir.join("shims").display(), env::var("PATH").unwrap_or_default());
+
+    assert!(get_top_line(&dir, &path, &[]).ends_with(" none-C"));
+
+    fs::write(dir.join("config.toml"), "inherit-env = true\n").unwrap();
+    assert!(get_top_line(&dir, &path, &[]).ends_with(" /somewhere/nvm-"));
+}
@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+// A repository in work/ with a manifest at the top and a few packages under it
+fn make_monorepo(name: &str) -> TempDir {
+    let mut fixture = Fixture::new(name).dir("work/.git").file("work/Cargo.toml", "[workspace]\nmembers = [\"services/*\"]\n");
+
+    for (package, manifest, text) in [
+        ("services/billing", "Cargo.toml", "[package]\nname = \"billing-api\"\nversion = \"0.1.0\"\n"),
+        ("services/web", "package.json", "{\"name\": \"@acme/web\", \"version\": \"1.0.0\"}"),
+        ("services/ml", "pyproject.toml", "[project]\nname = \"ml-models\"\n"),
+        ("services/legacy", "BUILD.bazel", "cc_library(name = \"legacy\")\n"),
+        ("services/broken", "package.json", "not json"),
+    ] {
+        fixture = fixture.dir(&format!("work/{}/src/deep", package)).file(&format!("work/{}/{}", package, manifest), text);
+    }
+
+    fixture.build()
+}
+
+fn get_project(dir: &Path, cwd: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "project", "--cwd"])
+        .arg(dir.join(cwd))
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("HOME", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn the_closest_manifest_names_the_project() {
+    let dir = make_monorepo("names");
+
+    assert_eq!(get_project(&dir, "work/services/billing/src/deep", &[]), "pkg:billing-api");
+    assert_eq!(get_project(&dir, "work/services/web", &[]), "pkg:@acme/web");
+    assert_eq!(get_project(&dir, "work/services/ml/src", &[]), "pkg:ml-models");
+}
+
+#[test]
+fn manifests_without_a_name_use_the_directory() {
+    let dir = make_monorepo("directories");
+
+    assert_eq!(get_project(&dir, "work/services/legacy/src", &[]), "pkg:legacy");
+    assert_eq!(get_project(&dir, "work/services/broken", &[]), "pkg:broken");
+}
+
+#[test]
+fn the_top_of_the_repository_is_not_a_project() {
+    let dir = make_monorepo("top");
+
+    assert_eq!(get_project(&dir, "work", &[]), "");
+    assert_eq!(get_project(&dir, "work/services", &[]), "");
+}
+
+#[test]
+fn nothing_is_looked_for_outside_a_repository() {
+    let dir = make_monorepo("outside");
+    fs::remove_dir(dir.join("work").join(".git")).unwrap();
+
+    assert_eq!(get_project(&dir, "work/services/billing", &[]), "");
+}
+
+#[test]
+fn the_manifests_and_label_can_be_changed() {
+    let dir = make_monorepo("changed");
+
+    assert_eq!(get_project(&dir, "work/services/billing", &["--project-manifests", "package.json"]), "");
+    assert_eq!(get_project(&dir, "work/services/web/src", &["--project-manifests", "package.json", "--label", "project=📦"]), "📦@acme/web");
+    assert_eq!(get_project(&dir, "work/services/billing", &["--label", "project="]), "billing-api");
+}
@@ -0,0 +1,260 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::{git, Fixture, TempDir, GIT_ENV};
+
+#[derive(Debug, PartialEq)]
+enum Colour {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    White
+}
+
+struct Prompt {
+    top_line: String,
+    chevrons: String,
+    colours: Vec<Colour>,
+}
+
+// A repository with a commit on main in work/, and sometimes an origin/ for it to push to. Each test
+// sets one up however it needs and says what the prompt should look like
+struct Repo {
+    root: TempDir,
+    work: PathBuf,
+}
+
+impl Repo {
+    fn new(name: &str) -> Repo {
+        let root = Fixture::new(name).git("work", &["init", "--quiet", "--initial-branch", "main"]).build();
+        let repo = Repo { work: root.join("work"), root };
+        repo.commit("file", "one");
+        repo
+    }
+
+    fn with_upstream(name: &str) -> Repo {
+        let repo = Repo::new(name);
+        git(&repo.root, &["init", "--quiet", "--bare", "--initial-branch", "main", "origin.git"]);
+        repo.git(&["remote", "add", "origin", "../origin.git"]);
+        repo.git(&["push", "--quiet", "--set-upstream", "origin", "main"]);
+        repo
+    }
+
+    // Someone else pushing a commit that this repository has fetched but not pulled
+    fn push_elsewhere(&self, file: &str, text: &str) {
+        git(&self.root, &["clone", "--quiet", "origin.git", "other"]);
+        let other = self.root.join("other");
+        fs::write(other.join(file), text).unwrap();
+        git(&other, &["add", file]);
+        git(&other, &["commit", "--quiet", "--message", file]);
+        git(&other, &["push", "--quiet"]);
+        self.git(&["fetch", "--quiet"]);
+    }
+
+    fn git(&self, args: &[&str]) {
+        git(&self.work, args);
+    }
+
+    fn write(&self, file: &str, text: &str) {
+        fs::write(self.work.join(file), text).unwrap();
+    }
+
+    fn commit(&self, file: &str, text: &str) {
+        self.write(file, text);
+        self.git(&["add", file]);
+        self.git(&["commit", "--quiet", "--message", file]);
+    }
+
+    fn get_commit(&self) -> String {
+        let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).current_dir(&self.work).envs(GIT_ENV).output().unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    // The prompt's run from the root and pointed at the repository with --cwd, as the shell would
+    // after a cd
+    fn get_output(&self, colour: &str, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", colour, "--side", "k8s-context=hidden", "--side", "k8s-namespace=hidden", "--cwd"])
+            .arg(&self.work)
+            .args(args)
+            .current_dir(&self.root)
+            .env("PWD", &self.root)
+            .env("TERM", "xterm")
+            .env("HOME", "/nonexistent")
+            .env("GIT_CEILING_DIRECTORIES", &self.root)
+            .env("PROMPT_CONFIG", self.root.join("config.toml"))
+            .envs(GIT_ENV)
+            .env_remove("AWS_PROFILE")
+            .env_remove("AWS_REGION")
+            .env_remove("AWS_DEFAULT_REGION")
+            .env_remove("AWS_PROFILE_REGION")
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // The markers are only there without colour so it's run both ways
+    fn run(&self, args: &[&str]) -> Prompt {
+        let plain = self.get_output("never", args);
+        let coloured = self.get_output("always", args);
+        let plain: Vec<_> = plain.lines().collect();
+
+        Prompt {
+            top_line: plain[1].to_owned(),
+            chevrons: plain[2].trim_end().to_owned(),
+            colours: get_colours(coloured.lines().nth(2).unwrap())
+        }
+    }
+
+    fn get_path(&self) -> String {
+        self.work.display().to_string()
+    }
+}
+
+// The foreground colour each chevron was drawn in
+fn get_colours(line: &str) -> Vec<Colour> {
+    line.split('\x1b').filter_map(|x| x.strip_prefix('[')?.split_once('m')).filter(|(_, text)| !text.trim().is_empty()).map(|(codes, _)| {
+        match codes.split(';').next_back() {
+            Some("31") => Colour::Red,
+            Some("32") => Colour::Green,
+            Some("33") => Colour::Yellow,
+            Some("34") => Colour::Blue,
+            Some("37") => Colour::White,
+            other => panic!("unexpected colour {:?} in {:?}", other, line)
+        }
+    }).collect()
+}
+
+#[test]
+fn clean_repositories() {
+    let repo = Repo::new("clean");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.top_line, format!("{} main", repo.get_path()));
+    assert_eq!(prompt.chevrons, "❯❯❯~");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Green, Colour::White]);
+}
+
+#[test]
+fn failed_commands() {
+    let repo = Repo::new("failed");
+
+    let prompt = repo.run(&["--exit-code", "1"]);
+    assert_eq!(prompt.chevrons, "❯!❯❯~");
+    assert_eq!(prompt.colours, [Colour::Red, Colour::Green, Colour::White]);
+}
+
+#[test]
+fn dirty_repositories() {
+    let repo = Repo::new("dirty");
+    repo.write("file", "two");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯*❯~");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Yellow, Colour::White]);
+}
+
+#[test]
+fn staged_changes_are_dirty() {
+    let repo = Repo::new("staged");
+    repo.write("file", "two");
+    repo.git(&["add", "file"]);
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯*❯~");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Yellow, Colour::White]);
+}
+
+#[test]
+fn untracked_files() {
+    let repo = Repo::new("untracked");
+    repo.write("new", "");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯?❯~");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Blue, Colour::White]);
+}
+
+#[test]
+fn detached_heads_show_the_commit() {
+    let repo = Repo::new("detached");
+    repo.commit("file", "two");
+    repo.git(&["checkout", "--quiet", "--detach", "HEAD~1"]);
+
+    assert_eq!(repo.run(&[]).top_line, format!("{} {}", repo.get_path(), repo.get_commit()));
+}
+
+#[test]
+fn tags_go_after_the_branch() {
+    let repo = Repo::new("tagged");
+    repo.git(&["tag", "v1.0"]);
+
+    assert_eq!(repo.run(&[]).top_line, format!("{} main [v1.0]", repo.get_path()));
+}
+
+#[test]
+fn pushed_repositories() {
+    let repo = Repo::with_upstream("pushed");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯❯");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Green, Colour::Green]);
+}
+
+#[test]
+fn unpushed_commits() {
+    let repo = Repo::with_upstream("unpushed");
+    repo.commit("file", "two");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯❯↑");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Green, Colour::Yellow]);
+}
+
+#[test]
+fn unpulled_commits() {
+    let repo = Repo::with_upstream("unpulled");
+    repo.push_elsewhere("other", "one");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯❯↓");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Green, Colour::Blue]);
+}
+
+#[test]
+fn diverged_branches() {
+    let repo = Repo::with_upstream("diverged");
+    repo.push_elsewhere("other", "one");
+    repo.commit("file", "two");
+
+    let prompt = repo.run(&[]);
+    assert_eq!(prompt.chevrons, "❯❯❯↕");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Green, Colour::Yellow]);
+}
+
+#[test]
+fn conflicts_part_way_through_a_rebase() {
+    let repo = Repo::new("rebase");
+    repo.git(&["checkout", "--quiet", "-b", "feature"]);
+    repo.commit("file", "feature");
+    repo.git(&["checkout", "--quiet", "main"]);
+    repo.commit("file", "main");
+    repo.git(&["checkout", "--quiet", "feature"]);
+
+    let status = Command::new("git").args(["rebase", "main"]).current_dir(&repo.work).envs(GIT_ENV).output().unwrap().status;
+    assert!(!status.success());
+
+    let prompt = repo.run(&[]);
+    assert!(prompt.top_line.contains("(interactive rebase)") && prompt.top_line.ends_with(" (1)"), "{:?}", prompt.top_line);
+    assert_eq!(prompt.chevrons, "❯❯=❯~");
+    assert_eq!(prompt.colours, [Colour::Green, Colour::Yellow, Colour::White]);
+}
@@ -0,0 +1,125 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use prompt::background::Background;
+use prompt::builtin::K8sContextSegment;
+use prompt::command::{Canned, CannedRunner};
+use prompt::config::Config;
+use prompt::git::{get_best_git_name, get_push_branch, get_uncommitted_changes, get_unpushed_changes, Fsmonitor, UncommittedChanges, UnpushedChanges};
+use prompt::path::WorkingDirectory;
+use prompt::segment::{Context, Reason, Segment, SegmentResult};
+use prompt::store::Store;
+use prompt::theme::{Theme, ThemeName};
+
+fn get_runner(outputs: Vec<(&'static str, Canned)>) -> CannedRunner {
+    CannedRunner { outputs }
+}
+
+#[tokio::test]
+async fn uncommitted_changes_come_from_diff_and_ls_files() {
+    let cases = [
+        (vec![("git diff --quiet", Canned::Exit(0, "")), ("git diff --cached --quiet", Canned::Exit(0, "")), ("git ls-files --other --exclude-standard --deleted", Canned::Exit(0, ""))], UncommittedChanges::None),
+        (vec![("git diff --quiet", Canned::Exit(0, "")), ("git diff --cached --quiet", Canned::Exit(0, "")), ("git ls-files --other --exclude-standard --deleted", Canned::Exit(0, "new\n"))], UncommittedChanges::FilesNotAdded),
+        (vec![("git diff --quiet", Canned::Exit(0, "")), ("git diff --cached --quiet", Canned::Exit(0, ""))], UncommittedChanges::FilesNotAdded),
+        (vec![("git diff --quiet", Canned::Exit(1, "")), ("git diff --cached --quiet", Canned::Exit(0, ""))], UncommittedChanges::FilesChanged),
+        (vec![("git diff --quiet", Canned::Exit(0, "")), ("git diff --cached --quiet", Canned::Exit(1, ""))], UncommittedChanges::FilesChanged),
+        (vec![("git diff --cached --quiet", Canned::Exit(0, ""))], UncommittedChanges::FilesChanged),
+    ];
+
+    for (i, (outputs, expected)) in cases.into_iter().enumerate() {
+        assert_eq!(get_uncommitted_changes(&get_runner(outputs), Fsmonitor::Auto).await, expected, "case {}", i);
+    }
+}
+
+#[tokio::test]
+async fn a_monitored_repository_gets_one_status_instead() {
+    let status = || ("git status --porcelain", Canned::Exit(0, "?? notes.txt\n"));
+    let monitored = || get_runner(vec![("git config --get core.fsmonitor", Canned::Exit(0, ".git/hooks/query-watchman\n")), status()]);
+
+    assert_eq!(get_uncommitted_changes(&monitored(), Fsmonitor::Auto).await, UncommittedChanges::FilesNotAdded);
+    assert_eq!(get_uncommitted_changes(&get_runner(vec![status()]), Fsmonitor::Always).await, UncommittedChanges::FilesNotAdded);
+
+    // Without the diffs to go on it's taken as changed
+    assert_eq!(get_uncommitted_changes(&monitored(), Fsmonitor::Never).await, UncommittedChanges::FilesChanged);
+}
+
+#[tokio::test]
+async fn a_slow_diff_counts_as_changes() {
+    let runner = get_runner(vec![("git diff --quiet", Canned::Hang), ("git diff --cached --quiet", Canned::Exit(0, ""))]);
+
+    let start = Instant::now();
+    assert_eq!(get_uncommitted_changes(&runner, Fsmonitor::Auto).await, UncommittedChanges::FilesChanged);
+    assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+}
+
+#[tokio::test]
+async fn unpushed_changes_compare_head_with_the_upstream() {
+    let cases = [
+        (vec![("git log @{u}..", Canned::Exit(0, "")), ("git rev-parse HEAD", Canned::Exit(0, "abc\n")), ("git rev-parse @{u}", Canned::Exit(0, "abc\n"))], UnpushedChanges::None),
+        (vec![("git log @{u}..", Canned::Exit(0, "")), ("git rev-parse HEAD", Canned::Exit(0, "abc\n")), ("git rev-parse @{u}", Canned::Exit(0, "def\n"))], UnpushedChanges::UnpulledChanges),
+        (vec![("git log @{u}..", Canned::Exit(128, "")), ("git rev-parse HEAD", Canned::Exit(0, "abc\n")), ("git rev-parse @{u}", Canned::Exit(128, ""))], UnpushedChanges::NoUpstreamBranch),
+        (vec![("git log @{u}..", Canned::Exit(0, "commit abc\n")), ("git log -1 ..@{u}", Canned::Exit(0, ""))], UnpushedChanges::UnpushedChanges),
+        (vec![("git log @{u}..", Canned::Exit(0, "commit abc\n")), ("git log -1 ..@{u}", Canned::Exit(0, "commit def\n"))], UnpushedChanges::DivergedChanges),
+        (vec![("git log @{u}..", Canned::Exit(0, "commit abc\n"))], UnpushedChanges::UnpushedChanges),
+        (vec![], UnpushedChanges::UnpushedChanges),
+    ];
+
+    for (i, (outputs, expected)) in cases.into_iter().enumerate() {
+        assert_eq!(get_unpushed_changes(&get_runner(outputs)).await, expected, "case {}", i);
+    }
+}
+
+#[tokio::test]
+async fn unpushed_changes_follow_git_push_when_it_goes_elsewhere() {
+    let get_outputs = |push: Canned| vec![
+        ("git rev-parse --symbolic-full-name @{push}", push),
+        ("git rev-parse --symbolic-full-name @{u}", Canned::Exit(0, "refs/remotes/upstream/main\n")),
+        ("git log @{u}..", Canned::Exit(0, "")),
+        ("git rev-parse HEAD", Canned::Exit(0, "abc\n")),
+        ("git rev-parse @{u}", Canned::Exit(0, "abc\n")),
+        ("git log refs/remotes/origin/main..", Canned::Exit(0, "commit abc\n")),
+        ("git log -1 ..refs/remotes/origin/main", Canned::Exit(0, "")),
+    ];
+
+    let triangular = get_runner(get_outputs(Canned::Exit(0, "refs/remotes/origin/main\n")));
+    assert_eq!(get_push_branch(&triangular).await.as_deref(), Some("refs/remotes/origin/main"));
+    assert_eq!(get_unpushed_changes(&triangular).await, UnpushedChanges::UnpushedChanges);
+
+    let same = get_runner(get_outputs(Canned::Exit(0, "refs/remotes/upstream/main\n")));
+    assert_eq!(get_push_branch(&same).await, None);
+    assert_eq!(get_unpushed_changes(&same).await, UnpushedChanges::None);
+
+    let no_push = get_runner(get_outputs(Canned::Exit(128, "")));
+    assert_eq!(get_push_branch(&no_push).await, None);
+    assert_eq!(get_unpushed_changes(&no_push).await, UnpushedChanges::None);
+}
+
+#[tokio::test]
+async fn the_git_name_prefers_the_branch() {
+    let cases = [
+        (vec![("git branch --show-current", Canned::Exit(0, "main\n")), ("git rev-parse --short HEAD", Canned::Exit(0, "abc\n"))], Some("main")),
+        (vec![("git branch --show-current", Canned::Exit(0, "")), ("git rev-parse --short HEAD", Canned::Exit(0, "abc\n"))], Some("abc")),
+        (vec![("git branch --show-current", Canned::Exit(0, "main\n")), ("git tag --points-at HEAD", Canned::Exit(0, "v1\n"))], Some("main [v1]")),
+        (vec![("git tag --points-at HEAD", Canned::Exit(0, "v1\n"))], Some(" [v1]")),
+        (vec![("git branch --show-current", Canned::Exit(128, "")), ("git rev-parse --short HEAD", Canned::Exit(128, ""))], None),
+    ];
+
+    for (i, (outputs, expected)) in cases.into_iter().enumerate() {
+        let name = get_best_git_name(&get_runner(outputs)).await;
+        assert_eq!(name.ok().as_deref(), expected, "case {}", i);
+    }
+}
+
+#[tokio::test]
+async fn commands_that_take_too_long_are_given_up_on() {
+    let runner = get_runner(vec![("kubectl config current-context", Canned::Hang)]);
+    let current_dir = WorkingDirectory { path: None, full: None, deleted: false };
+    let config = Config::default();
+    let theme = Theme { name: ThemeName::Default, background: Background::Dark, overrides: vec![] };
+    let context = Context { dir: Path::new("."), current_dir: &current_dir, config: &config, theme: &theme, exit_code: 0, timeout_ms: Some(50), runner: &runner, store: &Store::disabled() };
+
+    match K8sContextSegment.render(&context).await {
+        SegmentResult::Absent(Reason::Failed(error)) => assert_eq!(error, "kubectl: timed out after 50ms"),
+        _ => panic!("the k8s context wasn't given up on")
+    }
+}
@@ -0,0 +1,229 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use common::{git, Fixture, TempDir};
+use colored::Color;
+use prompt::background::Background;
+use prompt::builtin::{
+    get_registry, AwsProfileSegment, AwsRegionSegment, ChangesSegment, GitErrorsSegment, GitNameSegment, GitStateSegment, GitUnmergedSegment, MessageSegment, PathSegment, PushSegment,
+    UpstreamSegment
+};
+use prompt::chevron::{Position, State};
+use prompt::command::ProcessRunner;
+use prompt::config::Config;
+use prompt::git::Fsmonitor;
+use prompt::message::parse_message;
+use prompt::path::{parse_path_alias, PathStyle, WorkingDirectory};
+use prompt::segment::{Context, Reason, Rendered, Segment, SegmentKind, SegmentResult};
+use prompt::store::Store;
+use prompt::theme::{Theme, ThemeName};
+
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name)
+        .git("", &["init", "--quiet", "--initial-branch", "segments"])
+        .file("file", "one")
+        .git("", &["add", "file"])
+        .git("", &["commit", "--quiet", "--message", "first"])
+        .build()
+}
+
+fn get_working_directory(dir: &Path) -> WorkingDirectory {
+    WorkingDirectory { path: Some(dir.to_owned()), full: Some(dir.to_owned()), deleted: false }
+}
+
+async fn render(segment: &dyn Segment, current_dir: &WorkingDirectory) -> SegmentResult<Rendered> {
+    let config = Config::default();
+    let theme = Theme { name: ThemeName::Default, background: Background::Dark, overrides: vec![] };
+    let dir = current_dir.full.as_deref().unwrap_or(Path::new("."));
+    let runner = ProcessRunner { dir, inherit_env: false };
+    let context = Context { dir, current_dir, config: &config, theme: &theme, exit_code: 0, timeout_ms: None, runner: &runner, store: &Store::disabled() };
+
+    segment.render(&context).await
+}
+
+fn get_text(result: SegmentResult<Rendered>) -> Option<String> {
+    match result {
+        SegmentResult::Present(Rendered::Spans(spans)) => Some(spans.iter().map(|s| &**s).collect()),
+        _ => None
+    }
+}
+
+fn get_state(result: SegmentResult<Rendered>) -> Option<(Position, State)> {
+    match result {
+        SegmentResult::Present(Rendered::State(position, state)) => Some((position, state)),
+        _ => None
+    }
+}
+
+#[tokio::test]
+async fn the_path_segment_uses_aliases() {
+    let segment = PathSegment { style: PathStyle::Full, keep: None, aliases: vec![parse_path_alias("/srv/work=W:red").unwrap()] };
+    let current_dir = get_working_directory(Path::new("/srv/work/project"));
+
+    assert_eq!(get_text(render(&segment, &current_dir).await).as_deref(), Some("W/project"));
+
+    let spans = segment.get_spans(Path::new("/srv/work/project"), &Theme { name: ThemeName::Default, background: Background::Dark, overrides: vec![] });
+    assert_eq!(spans[0].styling.fg, Some(Color::Red));
+}
+
+#[tokio::test]
+async fn the_path_segment_is_missing_without_a_directory() {
+    let segment = PathSegment { style: PathStyle::Full, keep: None, aliases: vec![] };
+    let current_dir = WorkingDirectory { path: None, full: None, deleted: false };
+
+    assert!(matches!(render(&segment, &current_dir).await, SegmentResult::Absent(Reason::Nothing(_))));
+}
+
+#[tokio::test]
+async fn the_path_segment_says_when_the_directory_was_deleted() {
+    let segment = PathSegment { style: PathStyle::Full, keep: None, aliases: vec![] };
+    let current_dir = WorkingDirectory { path: Some("/srv/gone".into()), full: None, deleted: true };
+
+    match render(&segment, &current_dir).await {
+        SegmentResult::Present(Rendered::Aliased(spans, value)) => {
+            assert_eq!(spans.iter().map(|s| &**s).collect::<String>(), "/srv/gone (deleted)");
+            assert_eq!(value, "/srv/gone");
+        },
+        _ => panic!("expected the path with (deleted) after it")
+    }
+}
+
+#[tokio::test]
+async fn message_segments_keep_their_colour() {
+    let segment = MessageSegment { message: parse_message("blue:hello").unwrap() };
+    let current_dir = get_working_directory(Path::new("/"));
+
+    match render(&segment, &current_dir).await {
+        SegmentResult::Present(Rendered::Spans(spans)) => {
+            assert_eq!(&*spans[0], "hello");
+            assert_eq!(spans[0].styling.fg, Some(Color::Blue));
+        },
+        _ => panic!("the message wasn't shown")
+    }
+}
+
+// Everything to do with the environment is in the one test so nothing else sees it change
+#[tokio::test]
+async fn aws_segments_come_from_the_environment() {
+    let current_dir = get_working_directory(Path::new("/"));
+    for name in ["AWS_PROFILE", "AWS_REGION", "AWS_DEFAULT_REGION", "AWS_PROFILE_REGION"] {
+        env::remove_var(name);
+    }
+
+    assert!(matches!(render(&AwsProfileSegment, &current_dir).await, SegmentResult::Absent(Reason::NotSet(["AWS_PROFILE"]))));
+    assert!(matches!(render(&AwsRegionSegment, &current_dir).await, SegmentResult::Absent(Reason::NotSet(_))));
+
+    env::set_var("AWS_PROFILE", "dev");
+    env::set_var("AWS_PROFILE_REGION", "eu-west-2");
+    assert_eq!(get_text(render(&AwsProfileSegment, &current_dir).await).as_deref(), Some("dev"));
+    assert_eq!(get_text(render(&AwsRegionSegment, &current_dir).await).as_deref(), Some("eu-west-2"));
+
+    env::set_var("AWS_DEFAULT_REGION", "us-east-1");
+    assert_eq!(get_text(render(&AwsRegionSegment, &current_dir).await).as_deref(), Some("us-east-1"));
+
+    env::set_var("AWS_REGION", "ap-south-1");
+    assert_eq!(get_text(render(&AwsRegionSegment, &current_dir).await).as_deref(), Some("ap-south-1"));
+}
+
+#[tokio::test]
+async fn the_git_name_includes_any_tag() {
+    let dir = make_repo("name");
+    let current_dir = get_working_directory(&dir);
+
+    assert_eq!(get_text(render(&GitNameSegment, &current_dir).await).as_deref(), Some("segments"));
+
+    git(&dir, &["tag", "v1"]);
+    assert_eq!(get_text(render(&GitNameSegment, &current_dir).await).as_deref(), Some("segments [v1]"));
+}
+
+#[tokio::test]
+async fn the_changes_segment_follows_the_working_tree() {
+    let dir = make_repo("changes");
+    let current_dir = get_working_directory(&dir);
+
+    assert_eq!(get_state(render(&ChangesSegment { fsmonitor: Fsmonitor::Auto }, &current_dir).await), Some((Position::Changes, State::Clean)));
+
+    fs::write(dir.join("new"), "").unwrap();
+    assert_eq!(get_state(render(&ChangesSegment { fsmonitor: Fsmonitor::Auto }, &current_dir).await), Some((Position::Changes, State::Untracked)));
+
+    fs::write(dir.join("file"), "two").unwrap();
+    assert_eq!(get_state(render(&ChangesSegment { fsmonitor: Fsmonitor::Auto }, &current_dir).await), Some((Position::Changes, State::Dirty)));
+}
+
+#[tokio::test]
+async fn the_upstream_segment_needs_an_upstream() {
+    let dir = make_repo("upstream");
+    let current_dir = get_working_directory(&dir);
+
+    assert_eq!(get_state(render(&UpstreamSegment, &current_dir).await), Some((Position::Upstream, State::NoUpstream)));
+    assert_eq!(UpstreamSegment.timing_name().as_deref(), Some("git-upstream"));
+}
+
+#[tokio::test]
+async fn triangular_workflows_are_compared_with_where_push_goes() {
+    let dir = make_repo("push");
+    let remotes = Fixture::new("push-remotes").git("upstream.git", &["init", "--quiet", "--bare"]).git("fork.git", &["init", "--quiet", "--bare"]).build();
+
+    git(&dir, &["remote", "add", "upstream", remotes.join("upstream.git").to_str().unwrap()]);
+    git(&dir, &["remote", "add", "origin", remotes.join("fork.git").to_str().unwrap()]);
+    git(&dir, &["push", "--quiet", "--set-upstream", "upstream", "segments"]);
+    git(&dir, &["push", "--quiet", "origin", "segments"]);
+    fs::write(dir.join("file"), "two").unwrap();
+    git(&dir, &["commit", "--quiet", "--all", "--message", "second"]);
+    git(&dir, &["push", "--quiet", "origin", "segments"]);
+    let current_dir = get_working_directory(&dir);
+
+    // Fetched from upstream and pushed to the fork, but without saying so git push goes upstream
+    assert_eq!(get_state(render(&UpstreamSegment, &current_dir).await), Some((Position::Upstream, State::Unpushed)));
+    assert_eq!(get_text(render(&PushSegment, &current_dir).await), None);
+
+    git(&dir, &["config", "remote.pushDefault", "origin"]);
+    git(&dir, &["config", "push.default", "current"]);
+    assert_eq!(get_state(render(&UpstreamSegment, &current_dir).await), Some((Position::Upstream, State::Pushed)));
+    assert_eq!(get_text(render(&PushSegment, &current_dir).await).as_deref(), Some("△"));
+}
+
+#[tokio::test]
+async fn the_git_segments_show_what_needs_sorting_out() {
+    let dir = make_repo("trouble");
+    let current_dir = get_working_directory(&dir);
+
+    assert_eq!(get_text(render(&GitStateSegment, &current_dir).await), None);
+    assert_eq!(get_text(render(&GitErrorsSegment, &current_dir).await), None);
+    assert_eq!(get_text(render(&GitUnmergedSegment, &current_dir).await), None);
+
+    fs::write(dir.join("file"), "one \n").unwrap();
+    fs::write(dir.join(".git/MERGE_HEAD"), "").unwrap();
+    assert_eq!(get_text(render(&GitStateSegment, &current_dir).await).as_deref(), Some("(merge)"));
+    assert_eq!(get_text(render(&GitErrorsSegment, &current_dir).await).as_deref(), Some("\u{26A0}\u{FE0F}"));
+}
+
+#[test]
+fn the_registry_is_in_top_line_order() {
+    let path = PathSegment { style: PathStyle::Full, keep: None, aliases: vec![] };
+    let messages = vec![parse_message("one").unwrap(), parse_message("two").unwrap()];
+
+    let kinds: Vec<_> = get_registry(path, messages, Fsmonitor::Auto).iter().map(|x| x.kind()).collect();
+    assert_eq!(kinds, [
+        SegmentKind::Path,
+        SegmentKind::Message,
+        SegmentKind::Message,
+        SegmentKind::GitBranch,
+        SegmentKind::GitStatus,
+        SegmentKind::GitStatus,
+        SegmentKind::GitPush,
+        SegmentKind::GitState,
+        SegmentKind::GitErrors,
+        SegmentKind::GitUnmerged,
+        SegmentKind::K8sContext,
+        SegmentKind::K8sNamespace,
+        SegmentKind::HelmChart,
+        SegmentKind::AwsProfile,
+        SegmentKind::AwsRegion
+    ]);
+}
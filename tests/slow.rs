@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use common::{Fixture, TempDir};
+use prompt::slow::{get_hint, get_remedy};
+
+fn make_dir(name: &str) -> TempDir {
+    Fixture::new(name).dir("work").file("config.toml", "[custom.slow]\ncommand = \"sleep 0.8\"\ntimeout-ms = 5000\n").build()
+}
+
+// Returns whatever went to stderr, which is where the hint goes
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--disable", "k8s"])
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn a_few_slow_prompts_in_a_row_get_a_hint_once() {
+    let dir = make_dir("streak");
+
+    assert_eq!(run_prompt(&dir, &[]), "");
+    assert_eq!(run_prompt(&dir, &[]), "");
+
+    let hint = run_prompt(&dir, &[]);
+    assert!(hint.starts_with("prompt: renders are slow here (custom slow 0.8s); consider giving custom.slow a cache-ttl"), "{:?}", hint);
+    assert_eq!(hint.lines().count(), 1, "{:?}", hint);
+
+    assert_eq!(run_prompt(&dir, &[]), "");
+}
+
+#[test]
+fn fast_prompts_say_nothing_and_remember_nothing() {
+    let dir = make_dir("fast");
+    fs::write(dir.join("config.toml"), "").unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(run_prompt(&dir, &[]), "");
+    }
+    assert!(!dir.join("state").join("prompt").join("slow.json").exists());
+}
+
+#[test]
+fn the_hint_can_be_turned_off() {
+    let dir = make_dir("off");
+
+    for _ in 0..3 {
+        assert_eq!(run_prompt(&dir, &["--no-slow-warning"]), "");
+    }
+    assert!(!dir.join("state").join("prompt").join("slow.json").exists());
+}
+
+#[test]
+fn each_part_has_its_own_remedy() {
+    assert!(get_remedy("git-status").is_some_and(|x| x.contains("core.fsmonitor")));
+    assert!(get_remedy("k8s-context").is_some_and(|x| x.contains("prompt daemon")));
+    assert!(get_remedy("plugin deploy-freeze").is_some_and(|x| x.contains("deploy-freeze")));
+    assert_eq!(get_remedy("path"), None);
+
+    assert_eq!(
+        get_hint(Some(("git-errors".to_owned(), Duration::from_millis(1234)))),
+        "prompt: renders are slow here (git-errors 1.2s); consider --disable git-errors as it diffs the whole working tree or see --timings"
+    );
+    assert_eq!(get_hint(Some(("path".to_owned(), Duration::from_millis(800)))), "prompt: renders are slow here (path 0.8s); see --timings");
+    assert_eq!(get_hint(None), "prompt: renders are slow here; see --timings");
+}
@@ -0,0 +1,171 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+
+// Every mode gets the same segments, with a % in the branch for zsh and a backslash in the message
+// for bash. Run with UPDATE_SNAPSHOTS=1 to write out whatever the prompt prints now
+const ARGS: [&str; 15] = [
+    "--ps1",
+    "--exit-code", "1",
+    "--pipestatus", "0 1",
+    "--duration-ms", "65000",
+    "--jobs", "2",
+    "--message", "back\\slash",
+    "--side", "k8s-context=hidden",
+    "--side", "k8s-namespace=hidden",
+];
+
+fn make_repo(name: &str) -> TempDir {
+    Fixture::new(name).git("work", &["init", "--quiet", "--initial-branch", "fix-100%"]).build()
+}
+
+// The path's aliased so it's the same wherever the tests are run
+fn run_prompt(dir: &Path, term: &str, args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(ARGS)
+        .arg("--path-alias")
+        .arg(format!("{}=demo", dir.display()))
+        .args(args)
+        .current_dir(dir.join("work"))
+        .env("PWD", dir.join("work"))
+        .env("TERM", term)
+        .env("LC_ALL", "en_GB.UTF-8")
+        .env("HOME", "/nonexistent")
+        .env("AWS_PROFILE", "prod")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env_remove("COLORTERM")
+        .env_remove("NO_COLOR")
+        .env_remove("COLORFGBG")
+        .env_remove("CLICOLOR_FORCE")
+        .env_remove("AWS_REGION")
+        .env_remove("AWS_DEFAULT_REGION")
+        .env_remove("AWS_PROFILE_REGION")
+        .env_remove("TMUX")
+        .env_remove("STY")
+        .env_remove("CI")
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("GITLAB_CI")
+        .env_remove("BUILDKITE")
+        .env_remove("JENKINS_URL")
+        .env_remove("CIRCLECI")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+fn assert_snapshot(name: &str, output: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots").join(format!("{}.snap", name));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, output).unwrap();
+        return;
+    }
+
+    let expected = fs::read(&path).unwrap_or_default();
+    assert!(
+        output == expected,
+        "{} doesn't match its snapshot, run with UPDATE_SNAPSHOTS=1 if that's intended\n  printed: {:?}\n snapshot: {:?}",
+        name,
+        String::from_utf8_lossy(output),
+        String::from_utf8_lossy(&expected)
+    );
+}
+
+// How long things took changes every time so it's taken out
+fn remove_durations(output: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8(output.to_vec()).unwrap();
+    let mut cleaned = String::new();
+    let mut rest = text.as_str();
+
+    while let Some(i) = rest.find("\"duration_ms\":") {
+        let (before, after) = rest.split_at(i + "\"duration_ms\":".len());
+        cleaned += before;
+        let end = after.find([',', '}']).unwrap_or(after.len());
+        cleaned += if after.starts_with("null") { "null" } else { "0" };
+        rest = &after[end..];
+    }
+
+    (cleaned + rest).into_bytes()
+}
+
+#[test]
+fn ansi() {
+    let dir = make_repo("ansi");
+    assert_snapshot("ansi", &run_prompt(&dir, "xterm", &["--color", "always", "--combined"]));
+}
+
+#[test]
+fn zsh() {
+    let dir = make_repo("zsh");
+    assert_snapshot("zsh", &run_prompt(&dir, "xterm", &["--color", "always", "--combined", "--shell", "zsh"]));
+}
+
+#[test]
+fn bash() {
+    let dir = make_repo("bash");
+    assert_snapshot("bash", &run_prompt(&dir, "xterm", &["--color", "always", "--combined", "--shell", "bash"]));
+}
+
+#[test]
+fn tmux() {
+    let dir = make_repo("tmux");
+    assert_snapshot("tmux", &run_prompt(&dir, "xterm", &["--color", "always", "--format", "tmux"]));
+}
+
+#[test]
+fn plain() {
+    let dir = make_repo("plain");
+    assert_snapshot("plain", &run_prompt(&dir, "xterm", &["--color", "never", "--combined"]));
+}
+
+#[test]
+fn json() {
+    let dir = make_repo("json");
+    assert_snapshot("json", &remove_durations(&run_prompt(&dir, "xterm", &["--format", "json", "--combined"])));
+}
+
+#[test]
+fn dumb() {
+    let dir = make_repo("dumb");
+    assert_snapshot("dumb", &run_prompt(&dir, "dumb", &[]));
+}
+
+#[test]
+fn wide_characters_are_truncated_by_width() {
+    let dir = make_repo("wide");
+    assert_snapshot("wide", &run_prompt(&dir, "xterm", &["--color", "never", "--columns", "34", "--message", "日本語のとても長いメッセージです"]));
+}
+
+// Shells are fussy about the very first and last bytes, so these are pinned down for each of them
+#[test]
+fn fish_has_no_newline_at_the_end() {
+    let dir = make_repo("fish");
+    assert_snapshot("fish", &run_prompt(&dir, "xterm", &["--color", "never", "--shell", "fish"]));
+}
+
+#[test]
+fn nothing_before_or_after() {
+    let dir = make_repo("bare");
+    assert_snapshot("bare", &run_prompt(&dir, "xterm", &["--color", "never", "--shell", "zsh", "--no-blank-line", "--no-trailing-space"]));
+}
+
+#[test]
+fn trailing_text_is_escaped_like_anything_else() {
+    let dir = make_repo("trailing");
+    assert_snapshot("trailing", &run_prompt(&dir, "xterm", &["--color", "never", "--shell", "zsh", "--trailing", "\u{a0}%"]));
+}
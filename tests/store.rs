@@ -0,0 +1,112 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::Fixture;
+use prompt::store::{clear_cache, CacheKey, Store};
+
+const TTL: Duration = Duration::from_secs(60);
+
+#[test]
+fn entries_are_kept_for_their_inputs() {
+    let dir = Fixture::new("inputs").build();
+    let store = Store::new(dir.join("cache"));
+    let key = |branch: &str| CacheKey::new("test").with(("repo", branch));
+
+    assert_eq!(store.load(&key("main"), TTL), None);
+    store.save(&key("main"), "one");
+    assert_eq!(store.load(&key("main"), TTL).as_deref(), Some("one"));
+    assert_eq!(store.load(&key("feature"), TTL), None);
+
+    // Nothing's left lying around from writing it
+    let names: Vec<_> = fs::read_dir(dir.join("cache")).unwrap().map(|x| x.unwrap().file_name().into_string().unwrap()).collect();
+    assert!(names.len() == 1 && names[0].starts_with("test-"), "{:?}", names);
+}
+
+#[test]
+fn a_file_changing_means_a_different_entry() {
+    let dir = Fixture::new("files").build();
+    let store = Store::new(dir.join("cache"));
+    let source = dir.join("kubeconfig");
+    let key = || CacheKey::new("test").with_modified(&source);
+
+    store.save(&key(), "missing");
+    assert_eq!(store.load(&key(), TTL).as_deref(), Some("missing"));
+
+    fs::write(&source, "one").unwrap();
+    assert_eq!(store.load(&key(), TTL), None);
+    store.save(&key(), "there");
+    assert_eq!(store.load(&key(), TTL).as_deref(), Some("there"));
+}
+
+#[test]
+fn entries_run_out() {
+    let dir = Fixture::new("ttl").build();
+    let store = Store::new(dir.join("cache"));
+    let key = CacheKey::new("test").with_env("PROMPT_TEST_NEVER_SET");
+
+    store.save(&key, "old");
+    sleep(Duration::from_millis(20));
+    assert_eq!(store.load(&key, Duration::from_millis(10)), None);
+    assert_eq!(store.load(&key, TTL).as_deref(), Some("old"));
+
+    assert_eq!(store.get_counts(), vec![("test".to_owned(), 1, 1)]);
+}
+
+#[test]
+fn a_disabled_store_keeps_nothing() {
+    let store = Store::disabled();
+    let key = CacheKey::new("test");
+
+    store.save(&key, "text");
+    assert_eq!(store.load(&key, TTL), None);
+}
+
+#[test]
+fn clearing_removes_every_entry() {
+    let dir = Fixture::new("clear").build();
+    let store = Store::new(dir.join("cache"));
+    store.save(&CacheKey::new("one"), "1");
+    store.save(&CacheKey::new("two"), "2");
+
+    assert_eq!(clear_cache(&dir.join("cache")).unwrap(), 2);
+    assert_eq!(store.load(&CacheKey::new("one"), TTL), None);
+    assert_eq!(clear_cache(&dir.join("nowhere")).unwrap(), 0);
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(args)
+        .current_dir(dir)
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn timings_say_what_came_from_the_cache_and_it_can_be_cleared() {
+    let dir = Fixture::new("timings").build();
+    // kubectl's answer would be kept too
+    fs::write(dir.join("config.toml"), "disable = [\"k8s\"]\n\n[custom.slow]\ncommand = \"echo slow\"\ncache-ttl = 600\n").unwrap();
+
+    let first = String::from_utf8(run_prompt(&dir, &["--ps1", "--timings"]).stderr).unwrap();
+    assert!(first.lines().any(|x| x.starts_with("cache custom ") && x.ends_with(" 0 hits, 1 miss")), "{}", first);
+
+    let second = String::from_utf8(run_prompt(&dir, &["--ps1", "--timings"]).stderr).unwrap();
+    assert!(second.lines().any(|x| x.starts_with("cache custom ") && x.ends_with(" 1 hit, 0 misses")), "{}", second);
+
+    let clear = run_prompt(&dir, &["cache", "clear"]);
+    assert!(clear.status.success());
+    assert!(String::from_utf8(clear.stdout).unwrap().starts_with("removed 1 entry from "));
+
+    let third = String::from_utf8(run_prompt(&dir, &["--ps1", "--timings"]).stderr).unwrap();
+    assert!(third.lines().any(|x| x.ends_with(" 0 hits, 1 miss")), "{}", third);
+}
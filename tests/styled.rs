@@ -0,0 +1,38 @@
+use colored::Color;
+use prompt::colour::ColourDepth;
+use prompt::glyph::Glyphs;
+use prompt::render::{Format, Renderer, Shell};
+use prompt::styled::{get_width, Span, Stylize};
+
+fn get_renderer(format: Format, shell: Option<Shell>) -> Renderer {
+    Renderer { format, shell, colour: true, depth: ColourDepth::TrueColour, glyphs: Glyphs { ascii: false, icon_width: 1 } }
+}
+
+#[test]
+fn widths_come_from_the_text_alone() {
+    let line = vec!["~/src".color(Color::Cyan).bold(), " ".normal(), "日本".color(Color::Magenta)];
+
+    assert_eq!(get_width(&line), 10);
+    assert_eq!(line[2].width(), 4);
+}
+
+#[test]
+fn styles_are_only_written_out_when_rendered() {
+    let span = "main".color(Color::Magenta).bold();
+
+    assert_eq!(span.text, "main");
+    assert_eq!(get_renderer(Format::Ansi, None).span(&span), "\x1b[1;35mmain\x1b[0m");
+    assert_eq!(get_renderer(Format::Ansi, Some(Shell::Zsh)).span(&span), "%{\x1b[1;35m%}main%{\x1b[0m%}");
+    assert_eq!(get_renderer(Format::Ansi, Some(Shell::Bash)).span(&span), "\\[\x1b[1;35m\\]main\\[\x1b[0m\\]");
+    assert_eq!(get_renderer(Format::Tmux, None).span(&span), "#[bold,fg=magenta]main#[default]");
+}
+
+#[test]
+fn new_text_keeps_the_look() {
+    let span = "prod".color(Color::Red).on_color(Color::White).italic();
+    let other = span.with_text("dev");
+
+    assert_eq!(other.text, "dev");
+    assert_eq!(other.styling, span.styling);
+    assert_eq!(Span::from("plain").styling, Default::default());
+}
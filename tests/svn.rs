@@ -0,0 +1,101 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{Fixture, TempDir};
+use prompt::chevron::State;
+use prompt::svn::{parse_relative_url, parse_status};
+use prompt::vcs::{detect_vcs, Vcs};
+
+// A working copy with an svn that notes down each time it's asked for the url, and gives back
+// whatever's in the status file when asked what's changed
+fn make_dir(name: &str) -> TempDir {
+    let fixture = Fixture::new(name).dir("wc/src").file("wc/.svn/wc.db", "").file("status", "").file("config.toml", "");
+    let svn = format!(
+        "#!/bin/sh\ncase \"$1\" in\n  info) /bin/echo x >> '{0}/asked'; /bin/echo '^/branches/release-2/src' ;;\n  status) /bin/cat '{0}/status' ;;\nesac\n",
+        fixture.path().display()
+    );
+    fixture.script("bin/svn", svn).build()
+}
+
+fn run_prompt(dir: &Path, args: &[&str]) -> String {
+    let cwd = dir.join("wc").join("src");
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--disable", "k8s"])
+        .args(args)
+        .current_dir(&cwd)
+        .env("PWD", &cwd)
+        .env("PATH", dir.join("bin"))
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn branches_come_from_the_relative_url() {
+    assert_eq!(parse_relative_url("^/trunk\n").as_deref(), Some("trunk"));
+    assert_eq!(parse_relative_url("^/project/trunk/src").as_deref(), Some("trunk"));
+    assert_eq!(parse_relative_url("^/branches/release-2/src").as_deref(), Some("release-2"));
+    assert_eq!(parse_relative_url("^/tags/v1.0").as_deref(), Some("v1.0"));
+    assert_eq!(parse_relative_url("^/sandbox/alice").as_deref(), Some("sandbox/alice"));
+    assert_eq!(parse_relative_url("^/"), None);
+}
+
+#[test]
+fn status_says_whether_anything_changed() {
+    assert_eq!(parse_status(""), State::Clean);
+    assert_eq!(parse_status("M       src/main.c\n"), State::Dirty);
+    assert_eq!(parse_status("M       src/main.c\nC       src/lib.c\n"), State::Conflict);
+    assert_eq!(parse_status(" M    C src/lib.c\n"), State::Conflict);
+}
+
+#[test]
+fn the_closest_working_copy_wins() {
+    let dir = make_dir("nested");
+    fs::create_dir_all(dir.join("wc").join("vendor").join(".git")).unwrap();
+
+    assert_eq!(detect_vcs(&dir.join("wc").join("src")), Some(Vcs::Svn));
+    assert_eq!(detect_vcs(&dir.join("wc").join("vendor")), Some(Vcs::Git));
+    assert_eq!(detect_vcs(&dir), None);
+}
+
+#[test]
+fn working_copies_show_their_branch_and_changes() {
+    let dir = make_dir("prompt");
+
+    let clean = run_prompt(&dir, &["--color", "always"]);
+    assert!(clean.contains("release-2"), "{:?}", clean);
+
+    // The chevrons only change colour
+    fs::write(dir.join("status"), "M       main.c\n").unwrap();
+    let dirty = run_prompt(&dir, &["--color", "always"]);
+    assert!(dirty.contains("release-2"), "{:?}", dirty);
+    assert_ne!(clean, dirty);
+}
+
+#[test]
+fn the_branch_is_kept_until_the_working_copy_changes() {
+    let dir = make_dir("cache");
+
+    run_prompt(&dir, &[]);
+    run_prompt(&dir, &[]);
+    assert_eq!(fs::read_to_string(dir.join("asked")).unwrap().lines().count(), 1);
+
+    // Anything svn does to the working copy goes through its database
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::write(dir.join("wc").join(".svn").join("wc.db"), "updated").unwrap();
+    run_prompt(&dir, &[]);
+    assert_eq!(fs::read_to_string(dir.join("asked")).unwrap().lines().count(), 2);
+}
@@ -0,0 +1,93 @@
+use std::process::Command;
+
+use prompt::system::{format_load, format_memory, get_core_count, is_overloaded, parse_loadavg, parse_meminfo};
+
+#[test]
+fn the_last_minute_is_read_from_loadavg() {
+    assert_eq!(parse_loadavg("12.34 8.10 4.02 9/1234 56789\n"), Some(12.34));
+    assert_eq!(parse_loadavg("0.00 0.01 0.05 1/100 1\n"), Some(0.0));
+    assert_eq!(parse_loadavg("busy"), None);
+    assert_eq!(parse_loadavg("-1.0 0 0"), None);
+    assert_eq!(parse_loadavg(""), None);
+}
+
+#[test]
+fn only_more_than_the_cores_can_keep_up_with_is_overloaded() {
+    assert!(!is_overloaded(7.9, 8, 1.0));
+    assert!(!is_overloaded(8.0, 8, 1.0));
+    assert!(is_overloaded(8.1, 8, 1.0));
+    assert!(!is_overloaded(12.3, 8, 2.0));
+    assert!(is_overloaded(0.6, 1, 0.5));
+
+    assert!(get_core_count() >= 1);
+    assert_eq!(format_load(12.345), "load 12.3");
+    assert_eq!(format_load(4.0), "load 4.0");
+}
+
+#[test]
+fn the_memory_available_is_read_from_meminfo() {
+    let meminfo = "MemTotal:       16000000 kB\nMemFree:          200000 kB\nMemAvailable:     960000 kB\nBuffers:          100000 kB\n";
+    assert_eq!(parse_meminfo(meminfo), Some(6));
+    assert_eq!(parse_meminfo("MemTotal: 1000 kB\nMemAvailable: 1000 kB\n"), Some(100));
+    assert_eq!(parse_meminfo("MemAvailable: 960000 kB\nMemTotal: 16000000 kB\n"), Some(6), "the order doesn't matter");
+
+    // Kernels older than 3.14 don't have MemAvailable, and a guess from MemFree would cry wolf
+    assert_eq!(parse_meminfo("MemTotal: 16000000 kB\nMemFree: 200000 kB\n"), None);
+    assert_eq!(parse_meminfo("MemTotal: 0 kB\nMemAvailable: 0 kB\n"), None);
+    assert_eq!(parse_meminfo(""), None);
+
+    assert_eq!(format_memory(6), "mem 6%");
+}
+
+#[test]
+fn the_load_is_only_shown_when_asked_for_and_over_the_threshold() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", "never", "--only", "load", "--config", "/nonexistent/config.toml"])
+            .args(args)
+            .env("TERM", "xterm")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&[]), "\n");
+    assert_eq!(run(&["--load", "--load-threshold", "100000"]), "\n");
+
+    // It's timed and explained like any other segment
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--config", "/nonexistent/config.toml", "--load", "--load-threshold", "100000", "--timings", "--explain-missing"])
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.lines().any(|x| x.trim_start().starts_with("load ")), "{}", stderr);
+    assert!(stderr.contains("load: the load is below the threshold"), "{}", stderr);
+}
+
+#[test]
+fn memory_is_only_shown_when_asked_for_and_below_the_threshold() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+            .args(["--ps1", "--color", "never", "--only", "memory", "--config", "/nonexistent/config.toml"])
+            .args(args)
+            .env("TERM", "xterm")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&[]), "\n");
+    assert_eq!(run(&["--memory", "--memory-threshold", "0"]), "\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--config", "/nonexistent/config.toml", "--memory", "--memory-threshold", "0", "--timings", "--explain-missing"])
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.lines().any(|x| x.trim_start().starts_with("memory ")), "{}", stderr);
+    assert!(stderr.contains("memory: "), "{}", stderr);
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::process::Command;
+
+fn run_prompt(args: &[&str]) -> (String, String, bool) {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--no-blank-line", "--exit-code", "0"])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("GIT_CEILING_DIRECTORIES", env::temp_dir())
+        .env("LANG", "en_GB.UTF-8")
+        .env_remove("INSIDE_EMACS")
+        .output()
+        .unwrap();
+
+    (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap(), output.status.success())
+}
+
+// Just the top line, which is all the template changes
+fn render_template(template: &str, args: &[&str]) -> String {
+    let (stdout, stderr, success) = run_prompt(&[&["--template", template], args].concat());
+    assert!(success, "{}", stderr);
+
+    stdout.lines().next().unwrap().to_owned()
+}
+
+fn template_error(template: &str) -> String {
+    let (_, stderr, success) = run_prompt(&["--template", template]);
+    assert!(!success);
+
+    stderr
+}
+
+#[test]
+fn placeholders_are_replaced_with_segments() {
+    assert_eq!(render_template("{message}", &["--message", "hello"]), "hello");
+    assert_eq!(render_template("[{message}] and [{message}]", &["--message", "hi"]), "[hi] and [hi]");
+    assert_eq!(render_template("{message}{duration}", &["--message", "a", "--duration-ms", "5000", "--side", "duration=left"]), "a5.0s");
+}
+
+#[test]
+fn literal_text_passes_through() {
+    assert_eq!(render_template("no segments here", &[]), "no segments here");
+    assert_eq!(render_template(" > {message} < ", &["--message", "x"]), " > x < ");
+}
+
+#[test]
+fn missing_segments_are_empty() {
+    assert_eq!(render_template("[{git-branch}]", &[]), "[]");
+    assert_eq!(render_template("[{message}]", &[]), "[]");
+}
+
+#[test]
+fn prefixes_and_suffixes_only_show_with_the_segment() {
+    assert_eq!(render_template("{message:<|>}", &["--message", "hi"]), "<hi>");
+    assert_eq!(render_template("x{message:<|>}y", &[]), "xy");
+    assert_eq!(render_template("{message:on }", &["--message", "hi"]), "on hi");
+    assert_eq!(render_template("{message:|!}", &["--message", "hi"]), "hi!");
+    assert_eq!(render_template("{message:a|b|c}", &["--message", "hi"]), "ahib|c");
+}
+
+#[test]
+fn underscores_work_in_segment_names() {
+    assert_eq!(render_template("{git_branch}{message}", &["--message", "hi"]), "hi");
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(render_template("{{message}}", &["--message", "hi"]), "{message}");
+    assert_eq!(render_template("{{{message}}}", &["--message", "hi"]), "{hi}");
+    assert_eq!(render_template("}}{message:<|>}{{", &["--message", "hi"]), "}<hi>{");
+}
+
+#[test]
+fn unknown_placeholders_are_an_error() {
+    let error = template_error("{nope}");
+    assert!(error.contains("unknown segment 'nope'"), "{}", error);
+    assert!(error.contains("git-branch"), "{}", error);
+
+    let error = template_error("{path:x}{message} {k8s}");
+    assert!(error.contains("unknown segment 'k8s'"), "{}", error);
+}
+
+#[test]
+fn unbalanced_braces_are_an_error() {
+    assert!(template_error("{message").contains("unclosed '{'"));
+    assert!(template_error("message}").contains("unmatched '}'"));
+}
+
+#[test]
+fn literal_text_is_escaped_for_the_shell() {
+    assert_eq!(render_template("100% {message}", &["--message", "hi", "--shell", "zsh"]), "100%% hi");
+    assert_eq!(render_template("$ {message:`|`}", &["--message", "hi", "--shell", "bash"]), "\\\\$ \\\\`hi\\\\`");
+}
+
+#[test]
+fn least_important_segments_are_dropped_to_fit() {
+    let args = ["--message", "hello", "--duration-ms", "5000", "--side", "duration=left"];
+    assert_eq!(render_template("{message} ({duration})", &[&args[..], &["--columns", "40"]].concat()), "hello (5.0s)");
+    assert_eq!(render_template("{message} ({duration})", &[&args[..], &["--columns", "9"]].concat()), "hello ()");
+}
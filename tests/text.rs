@@ -0,0 +1,231 @@
+use std::env;
+use std::process::Command;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use prompt::text::{display_width, format_duration, format_idle, sanitize, truncate_to_width, EllipsisPosition};
+
+#[test]
+fn plain_text_is_left_alone() {
+    assert_eq!(sanitize("feature/日本語-é 100%"), "feature/日本語-é 100%");
+}
+
+#[test]
+fn control_sequences_are_removed_whole() {
+    assert_eq!(sanitize("a\x1b[31mb"), "ab");
+    assert_eq!(sanitize("a\x1b[2J\x1b[1;1Hb"), "ab");
+    assert_eq!(sanitize("a\u{9b}31mb"), "ab");
+}
+
+#[test]
+fn titles_and_other_strings_are_removed_up_to_their_end() {
+    assert_eq!(sanitize("a\x1b]0;owned\x07b"), "ab");
+    assert_eq!(sanitize("a\x1b]8;;http://example.com\x1b\\b"), "ab");
+    assert_eq!(sanitize("a\x1bPq#0\x1b\\b"), "ab");
+    assert_eq!(sanitize("a\u{9d}0;owned\u{9c}b"), "ab");
+}
+
+#[test]
+fn short_escapes_are_removed() {
+    assert_eq!(sanitize("a\x1bcb"), "ab");
+    assert_eq!(sanitize("a\x1b(Bb"), "ab");
+    assert_eq!(sanitize("a\x1b"), "a");
+}
+
+#[test]
+fn other_control_characters_are_removed() {
+    assert_eq!(sanitize("a\x07b\nc\r\td\u{85}e\x7f"), "abcde");
+}
+
+fn render_profile(profile: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "aws-profile"])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .env("AWS_PROFILE", profile)
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn environment_variables_are_sanitized() {
+    assert_eq!(render_profile("dev\x1b]0;owned\x07\x1b[1A\nprod", &[]), "devprod");
+}
+
+#[test]
+fn zsh_expansions_are_escaped_after_sanitizing() {
+    assert_eq!(render_profile("%F{red}\x1b[31m$(id)%", &["--shell", "zsh"]), "%%F{red}$(id)%%");
+}
+
+// Family emoji, flags, combining accents, double width and plain ASCII all mixed together
+const AWKWARD: [&str; 6] = [
+    "👨\u{200d}👩\u{200d}👧 family",
+    "cafe\u{301} de\u{301}ja\u{300} vu",
+    "日本語のブランチ",
+    "🇬🇧🇯🇵🇺🇸",
+    "a👨\u{200d}👩\u{200d}👧b日e\u{301}c",
+    "plain-ascii-branch",
+];
+
+const POSITIONS: [EllipsisPosition; 3] = [EllipsisPosition::Start, EllipsisPosition::Middle, EllipsisPosition::End];
+
+#[test]
+fn widths_count_whole_graphemes() {
+    assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+    assert_eq!(display_width("e\u{301}"), 1);
+    assert_eq!(display_width("日本"), 4);
+    assert_eq!(display_width(""), 0);
+}
+
+#[test]
+fn truncated_text_always_fits() {
+    for text in AWKWARD {
+        for position in POSITIONS {
+            for max in 0..=display_width(text) + 2 {
+                let truncated = truncate_to_width(text, max, position);
+                assert!(display_width(&truncated) <= max, "{:?} cut to {} at {:?} is {:?}", text, max, position, truncated);
+            }
+        }
+    }
+}
+
+#[test]
+fn truncating_never_splits_a_grapheme() {
+    for text in AWKWARD {
+        let graphemes: Vec<_> = text.graphemes(true).collect();
+        for position in POSITIONS {
+            for max in 0..=display_width(text) {
+                let truncated = truncate_to_width(text, max, position);
+                assert!(truncated.graphemes(true).all(|g| g == "…" || graphemes.contains(&g)), "{:?} cut to {} at {:?} is {:?}", text, max, position, truncated);
+            }
+        }
+    }
+}
+
+#[test]
+fn text_that_fits_is_left_alone() {
+    for text in AWKWARD {
+        for position in POSITIONS {
+            assert_eq!(truncate_to_width(text, display_width(text), position), text);
+        }
+    }
+}
+
+#[test]
+fn the_ellipsis_goes_where_its_asked_for() {
+    assert_eq!(truncate_to_width("feature/long-name", 8, EllipsisPosition::Start), "…ng-name");
+    assert_eq!(truncate_to_width("feature/long-name", 8, EllipsisPosition::Middle), "feat…ame");
+    assert_eq!(truncate_to_width("feature/long-name", 8, EllipsisPosition::End), "feature…");
+}
+
+#[test]
+fn wide_characters_leave_a_gap_rather_than_overflowing() {
+    assert_eq!(truncate_to_width("日本語のブランチ", 6, EllipsisPosition::End), "日本…");
+    assert_eq!(truncate_to_width("日本語のブランチ", 6, EllipsisPosition::Start), "…ンチ");
+    assert_eq!(truncate_to_width("日本語のブランチ", 6, EllipsisPosition::Middle), "日…チ");
+    assert_eq!(truncate_to_width("a👨\u{200d}👩\u{200d}👧b", 3, EllipsisPosition::End), "a…");
+}
+
+#[test]
+fn too_little_room_for_an_ellipsis_leaves_nothing() {
+    assert_eq!(truncate_to_width("branch", 0, EllipsisPosition::End), "");
+    assert_eq!(truncate_to_width("branch", 1, EllipsisPosition::Middle), "…");
+}
+
+fn render_message(message: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "message", "--message", message])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn segments_can_be_given_a_maximum_width() {
+    assert_eq!(render_message("feature/long-branch", &["--max-width", "message=8"]), "feature…");
+    assert_eq!(render_message("feature/long-branch", &["--max-width", "message=8:start"]), "…-branch");
+    assert_eq!(render_message("feature/long-branch", &["--max-width", "message=8:middle"]), "feat…nch");
+    assert_eq!(render_message("short", &["--max-width", "message=8"]), "short");
+}
+
+#[test]
+fn bad_maximum_widths_are_rejected() {
+    for width in ["message", "message=wide", "message=8:left", "nothing=8"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_prompt")).args(["--max-width", width]).output().unwrap();
+        assert!(!output.status.success(), "{}", width);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn paths_lose_their_start_by_default() {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "path", "--cwd", "/usr/share/doc", "--max-width", "path=8"])
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), "…are/doc");
+}
+
+#[test]
+fn short_durations_get_a_decimal() {
+    assert_eq!(format_duration(0), "0.0s");
+    assert_eq!(format_duration(999), "0.9s");
+    assert_eq!(format_duration(1000), "1.0s");
+    assert_eq!(format_duration(4250), "4.2s");
+    assert_eq!(format_duration(9950), "9.9s");
+    assert_eq!(format_duration(9999), "9.9s");
+}
+
+#[test]
+fn longer_durations_are_whole_units() {
+    assert_eq!(format_duration(10_000), "10s");
+    assert_eq!(format_duration(59_900), "59s");
+    assert_eq!(format_duration(60_000), "1m00s");
+    assert_eq!(format_duration(67_000), "1m07s");
+    assert_eq!(format_duration(3_599_000), "59m59s");
+    assert_eq!(format_duration(3_599_999), "59m59s");
+    assert_eq!(format_duration(3_600_000), "1h00m");
+    assert_eq!(format_duration(3_720_000), "1h02m");
+    assert_eq!(format_duration(90_000_000), "25h00m");
+}
+
+#[test]
+fn idle_time_is_only_the_biggest_unit() {
+    assert_eq!(format_idle(59_999), "0m");
+    assert_eq!(format_idle(45 * 60_000), "45m");
+    assert_eq!(format_idle(60 * 60_000), "1h");
+    assert_eq!(format_idle(9 * 60 * 60_000 + 59 * 60_000), "9h");
+    assert_eq!(format_idle(47 * 60 * 60_000), "47h");
+    assert_eq!(format_idle(3 * 24 * 60 * 60_000), "3d");
+}
+
+fn render_duration(duration_ms: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "duration", "--duration-ms", duration_ms])
+        .args(args)
+        .current_dir(env::temp_dir())
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+#[test]
+fn quick_commands_have_no_duration_unless_asked() {
+    assert_eq!(render_duration("1999", &[]), "");
+    assert_eq!(render_duration("2000", &[]), "2.0s");
+    assert_eq!(render_duration("4200", &["--duration-threshold-ms", "5000"]), "");
+    assert_eq!(render_duration("300", &["--always-show-duration"]), "0.3s");
+}
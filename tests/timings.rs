@@ -0,0 +1,40 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::process::{Command, Output};
+
+use common::Fixture;
+
+fn run_prompt(name: &str, args: &[&str]) -> Output {
+    let dir = Fixture::new(name).script("kubectl", "#!/bin/sh\nsleep 1\n").build();
+
+    Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--ps1", "--color", "never", "--timeout-ms", "100", "--message", "hi"])
+        .args(args)
+        .current_dir(&dir)
+        .env("TERM", "xterm")
+        .env("PATH", format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default()))
+        .env("GIT_CEILING_DIRECTORIES", &dir)
+        .env("PROMPT_CONFIG", dir.join("config.toml"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn timings_go_to_stderr() {
+    let output = run_prompt("table", &["--timings"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(" hi\n❯❯❯ \n"));
+
+    let table = String::from_utf8(output.stderr).unwrap();
+    assert!(table.lines().any(|x| x.starts_with("k8s-context ") && x.ends_with("ms  timed out")), "{}", table);
+    assert!(table.lines().last().unwrap().starts_with("total "), "{}", table);
+}
+
+#[test]
+fn nothing_is_printed_without_the_flag() {
+    assert!(run_prompt("quiet", &[]).stderr.is_empty());
+}
@@ -0,0 +1,53 @@
+#![cfg(windows)]
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::Fixture;
+
+fn render_path(cwd: &Path, home: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_prompt"))
+        .args(["--color", "never", "--only", "path", "--path-style", "full", "--cwd"])
+        .arg(cwd)
+        .env("USERPROFILE", home)
+        .env("PROMPT_CONFIG", cwd.join("config.toml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim_end().to_owned()
+}
+
+fn get_drive_letter(path: &Path) -> char {
+    path.to_string_lossy().trim_start_matches(r"\\?\").chars().next().unwrap().to_ascii_uppercase()
+}
+
+#[test]
+fn home_is_joined_onto_a_tilde_with_a_backslash() {
+    let dir = Fixture::new("tilde").dir("home/project").build();
+
+    assert_eq!(render_path(&dir.join("home"), &dir.join("home")), "~");
+    assert_eq!(render_path(&dir.join("home").join("project"), &dir.join("home")), r"~\project");
+}
+
+#[test]
+fn the_drive_is_left_off_when_home_is_on_it_too() {
+    let dir = Fixture::new("same-drive").dir("home").dir("elsewhere").build();
+
+    let expected: PathBuf = dir.join("elsewhere").components().skip(1).collect();
+    assert_eq!(render_path(&dir.join("elsewhere"), &dir.join("home")), expected.display().to_string());
+}
+
+#[test]
+fn other_drives_keep_their_letter() {
+    let dir = Fixture::new("other-drive").dir("elsewhere").build();
+
+    // Wherever the tests are running, home's put on some other drive
+    let letter = if get_drive_letter(&dir) == 'Y' { 'Z' } else { 'Y' };
+    let output = render_path(&dir.join("elsewhere"), Path::new(&format!(r"{}:\Users\you", letter)));
+
+    assert_eq!(get_drive_letter(Path::new(&output)), get_drive_letter(&dir));
+    assert!(output.ends_with(r"\elsewhere"), "{}", output);
+}